@@ -0,0 +1,11 @@
+#!/bin/bash
+set -e
+
+# Builds the controller of the pre-versioning release used by the upgrade simulation tests
+PREVIOUS_REVISION=${1:-4809830}
+WORKTREE=$(mktemp -d)
+
+git worktree add --detach "$WORKTREE" "$PREVIOUS_REVISION"
+RUSTFLAGS='-C link-arg=-s' cargo build --manifest-path "$WORKTREE/contracts/Cargo.toml" -p controller --target wasm32-unknown-unknown --release
+cp "$WORKTREE/contracts/target/wasm32-unknown-unknown/release/controller.wasm" ./contracts/controller/tests/sim/contracts/controller_v1.wasm
+git worktree remove --force "$WORKTREE"
@@ -1,22 +1,11 @@
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, near_bindgen, require, AccountId};
+use near_sdk::{env, near_bindgen, require, AccountId, BlockHeight};
 use std::collections::HashMap;
 
 use general::ratio::Ratio;
 
 use crate::*;
 
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-pub enum MethodType {
-    Withdraw,
-    Repay,
-    Supply,
-    Liquidate,
-    Borrow,
-    Deposit,
-}
-
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 #[derive(Debug)]
@@ -24,42 +13,67 @@ pub struct Market {
     pub asset_id: AccountId,
     pub dtoken: AccountId,
     pub ticker_id: String,
+    pub total_borrows: WBalance,
+    pub borrow_cap: Option<WBalance>,
+    /// Amount which still could be borrowed before reaching the borrow cap
+    pub borrow_headroom: Option<WBalance>,
 }
 
-#[near_bindgen]
 impl Contract {
-    pub fn get_admin(&self) -> AccountId {
-        self.admin.clone()
+    /// Applies the update to the market profile
+    pub(crate) fn update_market(
+        &mut self,
+        asset_id: &AccountId,
+        update: impl FnOnce(&mut MarketProfile),
+    ) {
+        let mut market = self
+            .markets
+            .get(asset_id)
+            .expect("Asset by this key doesnt exist");
+        update(&mut market);
+
+        self.markets.insert(asset_id, &market);
     }
 
-    pub fn set_admin(&mut self, account: AccountId) {
+    /// Data reported by the market, like its borrow index, is accepted from the market itself only
+    pub(crate) fn assert_market_caller(&self, dtoken: &AccountId) {
         require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
+            self.is_dtoken_caller() && env::predecessor_account_id() == *dtoken,
+            format!(
+                "This functionality is allowed to be called by {} contract only",
+                dtoken
+            )
         );
-        self.admin = account;
     }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Grants the role to the account, could be called by the owner only
+    pub fn grant_role(&mut self, role: Role, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
 
-    pub fn get_eligible_to_borrow_uncollateralized_account(&self) -> AccountId {
-        self.eligible_to_borrow_uncollateralized.clone()
+        self.apply_risk_parameter_change(RiskParameterChange::GrantRole(role, account));
     }
 
-    pub fn set_eligible_to_borrow_uncollateralized_account(&mut self, account: AccountId) {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
-        self.eligible_to_borrow_uncollateralized = account;
+    pub fn revoke_role(&mut self, role: Role, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::RevokeRole(role, account));
     }
 
-    pub fn is_allowed_to_borrow_uncollateralized(&self, account: AccountId) -> bool {
-        account == self.eligible_to_borrow_uncollateralized
+    pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+        self.roles.has_role(role, &account)
     }
 
-    #[private]
-    pub fn is_valid_admin_call(&self) -> bool {
-        env::signer_account_id() == self.admin
-            || env::signer_account_id() == env::current_account_id()
+    pub fn get_role_accounts(&self, role: Role) -> Vec<AccountId> {
+        self.roles.get_accounts(role)
+    }
+
+    pub fn get_account_roles(&self, account: AccountId) -> Vec<Role> {
+        self.roles.get_roles(&account)
     }
 
     #[private]
@@ -75,6 +89,9 @@ impl Contract {
             .iter()
             .map(|(asset_id, market)| Market {
                 asset_id,
+                total_borrows: WBalance::from(self.get_market_total_borrows(&market.dtoken)),
+                borrow_cap: market.borrow_cap,
+                borrow_headroom: self.get_borrow_headroom(&market.dtoken).map(WBalance::from),
                 dtoken: market.dtoken,
                 ticker_id: market.ticker_id,
             })
@@ -105,27 +122,51 @@ impl Contract {
         ltv: Ratio,
         lth: Ratio,
     ) {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::AddMarket {
+            asset_id,
+            dtoken,
+            ticker_id,
+            ltv,
+            lth,
+        });
+    }
+
+    pub fn remove_market(&mut self, key: AccountId) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
 
+        self.apply_risk_parameter_change(RiskParameterChange::RemoveMarket { asset_id: key });
+    }
+
+    pub(crate) fn insert_market(
+        &mut self,
+        asset_id: AccountId,
+        dtoken: AccountId,
+        ticker_id: String,
+        ltv: Ratio,
+        lth: Ratio,
+    ) {
         let market = MarketProfile {
             dtoken,
             ticker_id,
             ltv,
             lth,
+            max_price_age: DEFAULT_MAX_PRICE_AGE,
+            close_factor: get_default_close_factor(),
+            borrow_cap: None,
+            is_collateral: true,
+            debt_ceiling: None,
+            is_borrowable_in_isolation: false,
+            e_mode_category: None,
         };
 
         self.markets.insert(&asset_id, &market);
     }
 
-    pub fn remove_market(&mut self, key: AccountId) {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
-
+    pub(crate) fn delete_market(&mut self, key: AccountId) {
         require!(
             self.markets.get(&key).is_some(),
             "Asset by this key doesnt exist"
@@ -134,6 +175,96 @@ impl Contract {
         self.markets.remove(&key);
     }
 
+    pub fn set_market_max_price_age(&mut self, asset_id: AccountId, max_price_age: BlockHeight) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMarketMaxPriceAge {
+            asset_id,
+            max_price_age,
+        });
+    }
+
+    pub fn set_market_close_factor(&mut self, asset_id: AccountId, close_factor: Ratio) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMarketCloseFactor {
+            asset_id,
+            close_factor,
+        });
+    }
+
+    pub fn set_market_borrow_cap(&mut self, asset_id: AccountId, borrow_cap: Option<WBalance>) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMarketBorrowCap {
+            asset_id,
+            borrow_cap,
+        });
+    }
+
+    pub fn set_market_is_collateral(&mut self, asset_id: AccountId, is_collateral: bool) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMarketIsCollateral {
+            asset_id,
+            is_collateral,
+        });
+    }
+
+    /// Makes the market isolated with the given debt ceiling in USD or removes isolation
+    pub fn set_market_debt_ceiling(&mut self, asset_id: AccountId, debt_ceiling: Option<WBalance>) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMarketDebtCeiling {
+            asset_id,
+            debt_ceiling,
+        });
+    }
+
+    pub fn set_market_borrowable_in_isolation(
+        &mut self,
+        asset_id: AccountId,
+        is_borrowable_in_isolation: bool,
+    ) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMarketBorrowableInIsolation {
+            asset_id,
+            is_borrowable_in_isolation,
+        });
+    }
+
+    pub fn set_market_e_mode_category(&mut self, asset_id: AccountId, category_id: Option<u8>) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMarketEModeCategory {
+            asset_id,
+            category_id,
+        });
+    }
+
+    pub fn get_oracle_accounts(&self) -> Vec<AccountId> {
+        self.roles.get_accounts(Role::Oracle)
+    }
+
+    pub fn get_max_price_deviation(&self) -> Ratio {
+        self.max_price_deviation
+    }
+
+    pub fn set_max_price_deviation(&mut self, value: Ratio) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetMaxPriceDeviation(value));
+    }
+
     pub fn get_liquidation_incentive(&self) -> Ratio {
         // TODO: Move this kind of getter that don't require admin rights somewhere else
         // incentive % + 100 %
@@ -141,48 +272,32 @@ impl Contract {
     }
 
     pub fn get_liquidation_threshold(&self) -> Ratio {
-        Ratio::one()
+        self.liquidation_health_factor_threshold
     }
 
-    pub fn set_liquidation_incentive(mut self, value: Ratio) {
-        require!(
-            self.is_valid_admin_call(),
-            "this functionality is allowed to be called by admin or contract only"
-        );
+    pub fn set_liquidation_incentive(&mut self, value: Ratio) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
 
-        self.liquidation_incentive = value;
+        self.apply_risk_parameter_change(RiskParameterChange::SetLiquidationIncentive(value));
     }
 
-    pub fn pause_method(mut self, method: MethodType) {
-        require!(
-            self.is_valid_admin_call(),
-            "this functionality is allowed to be called by admin or contract only"
-        );
+    pub fn get_max_liquidation_incentive(&self) -> Ratio {
+        self.max_liquidation_incentive
+    }
+
+    pub fn set_max_liquidation_incentive(&mut self, value: Ratio) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
 
-        match method {
-            MethodType::Withdraw => self.is_action_paused.withdraw = true,
-            MethodType::Repay => self.is_action_paused.repay = true,
-            MethodType::Supply => self.is_action_paused.supply = true,
-            MethodType::Liquidate => self.is_action_paused.liquidate = true,
-            MethodType::Borrow => self.is_action_paused.borrow = true,
-            MethodType::Deposit => self.is_action_paused.deposit = true,
-        }
+        self.apply_risk_parameter_change(RiskParameterChange::SetMaxLiquidationIncentive(value));
     }
 
-    pub fn proceed_method(mut self, method: MethodType) {
-        require!(
-            self.is_valid_admin_call(),
-            "this functionality is allowed to be called by admin or contract only"
-        );
+    pub fn set_liquidation_threshold(&mut self, value: Ratio) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
 
-        match method {
-            MethodType::Withdraw => self.is_action_paused.withdraw = false,
-            MethodType::Repay => self.is_action_paused.repay = false,
-            MethodType::Supply => self.is_action_paused.supply = false,
-            MethodType::Liquidate => self.is_action_paused.liquidate = false,
-            MethodType::Borrow => self.is_action_paused.borrow = false,
-            MethodType::Deposit => self.is_action_paused.deposit = false,
-        }
+        self.apply_risk_parameter_change(RiskParameterChange::SetLiquidationThreshold(value));
     }
 
     pub fn get_user_profile(&self, user_id: AccountId) -> UserProfile {
@@ -212,8 +327,21 @@ mod tests {
     }
 
     #[test]
-    fn get_set_admin() {
+    fn owner_roles() {
         let (near_contract, _, _) = init_();
-        assert_eq!(near_contract.admin, near_contract.get_admin());
+        let owner_account: AccountId = "contract.near".parse().unwrap();
+        let oracle_account: AccountId = "oracle.near".parse().unwrap();
+
+        assert_eq!(
+            near_contract.get_account_roles(owner_account),
+            vec![
+                Role::Owner,
+                Role::RiskManager,
+                Role::PauseGuardian,
+                Role::RewardManager,
+                Role::Executor
+            ]
+        );
+        assert_eq!(near_contract.get_oracle_accounts(), vec![oracle_account]);
     }
 }
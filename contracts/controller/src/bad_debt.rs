@@ -0,0 +1,251 @@
+use crate::*;
+use near_sdk::{serde_json, PromiseOrValue, PromiseResult};
+
+#[near_bindgen]
+impl Contract {
+    /// Account has bad debt when it has borrows but no collateral left to cover them
+    pub fn has_bad_debt(&self, account_id: AccountId) -> bool {
+        let user_profile = self.user_profiles.get(&account_id).unwrap_or_default();
+
+        user_profile
+            .account_borrows
+            .values()
+            .any(|balance| *balance > 0)
+            && user_profile
+                .account_supplies
+                .values()
+                .all(|balance| *balance == 0)
+    }
+
+    /// Pages go over all of the accounts, so a page could have less accounts with bad debt
+    /// than the limit
+    pub fn view_accounts_with_bad_debt(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.user_profiles
+            .keys()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter(|account_id| self.has_bad_debt(account_id.clone()))
+            .collect()
+    }
+
+    /// Writes off residual borrows of the fully underwater account on each market
+    pub fn write_off_bad_debt(&mut self, account_id: AccountId) -> PromiseOrValue<U128> {
+        require!(
+            self.has_bad_debt(account_id.clone()),
+            format!("Account {} doesnt have bad debt", account_id)
+        );
+        require!(
+            self.mutex.try_lock(&account_id),
+            format!(
+                "failed to acquire bad debt write-off mutex for account {}",
+                account_id
+            )
+        );
+
+        let borrows = self
+            .user_profiles
+            .get(&account_id)
+            .unwrap_or_default()
+            .account_borrows
+            .into_iter()
+            .filter(|(_, balance)| *balance > 0)
+            .map(|(dtoken, _)| {
+                let borrows =
+                    self.get_account_borrows_with_interest(account_id.clone(), dtoken.clone());
+                (dtoken, WBalance::from(borrows))
+            })
+            .collect::<Vec<(AccountId, WBalance)>>();
+        let borrows_amount = borrows.len() as u64;
+
+        borrows
+            .iter()
+            .map(|(dtoken, amount)| {
+                market::write_off_bad_debt(
+                    account_id.clone(),
+                    *amount,
+                    dtoken.clone(),
+                    NO_DEPOSIT,
+                    near_sdk::Gas::ONE_TERA * 10_u64,
+                )
+            })
+            .reduce(|write_offs, write_off| write_offs.and(write_off))
+            .unwrap()
+            .then(ext_self::write_off_bad_debt_callback(
+                account_id,
+                borrows,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                near_sdk::Gas::ONE_TERA * (5_u64 + 5_u64 * borrows_amount),
+            ))
+            .into()
+    }
+
+    #[private]
+    pub fn write_off_bad_debt_callback(
+        &mut self,
+        account_id: AccountId,
+        borrows: Vec<(AccountId, WBalance)>,
+    ) -> PromiseOrValue<U128> {
+        let mut written_off: Balance = 0;
+
+        for (index, (dtoken, amount)) in borrows.into_iter().enumerate() {
+            match env::promise_result(index as u64) {
+                PromiseResult::Successful(result) => {
+                    let amount: WBalance = serde_json::from_slice(&result).unwrap_or(amount);
+                    self.decrease_borrows(account_id.clone(), dtoken.clone(), amount);
+                    written_off += amount.0;
+                    Events::BadDebtWrittenOff {
+                        account_id: account_id.clone(),
+                        dtoken,
+                        amount,
+                    }
+                    .emit();
+                }
+                _ => Events::BadDebtWriteOffFailed {
+                    account_id: account_id.clone(),
+                    dtoken,
+                    amount,
+                }
+                .emit(),
+            }
+        }
+
+        self.mutex.unlock(&account_id);
+        PromiseOrValue::Value(U128(written_off))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+    use super::*;
+
+    fn init() -> (Contract, AccountId) {
+        let borrower = alice();
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: bob(),
+            oracle_account_id: bob(),
+        });
+
+        controller_contract.add_market(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            "wnear".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        controller_contract.add_market(
+            AccountId::new_unchecked("weth.near".to_string()),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            "weth".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        controller_contract.increase_supplies(
+            borrower.clone(),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            WBalance::from(0),
+        );
+        controller_contract.increase_borrows(
+            borrower.clone(),
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            WBalance::from(100),
+        );
+        controller_contract.increase_borrows(
+            borrower.clone(),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            WBalance::from(50),
+        );
+
+        (controller_contract, borrower)
+    }
+
+    #[test]
+    fn test_bad_debt_detection() {
+        let (mut controller_contract, borrower) = init();
+
+        assert!(controller_contract.has_bad_debt(borrower.clone()));
+        assert_eq!(
+            controller_contract.view_accounts_with_bad_debt(0, 10),
+            vec![borrower.clone()]
+        );
+        assert!(controller_contract
+            .view_accounts_with_bad_debt(1, 10)
+            .is_empty());
+
+        controller_contract.increase_supplies(
+            borrower.clone(),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            WBalance::from(1),
+        );
+
+        assert!(!controller_contract.has_bad_debt(borrower));
+        assert!(controller_contract
+            .view_accounts_with_bad_debt(0, 10)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob.near doesnt have bad debt")]
+    fn test_write_off_without_bad_debt() {
+        let (mut controller_contract, _borrower) = init();
+
+        controller_contract.write_off_bad_debt(bob());
+    }
+
+    #[test]
+    fn test_write_off_bad_debt_callback() {
+        let (mut controller_contract, borrower) = init();
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(bob())
+                .predecessor_account_id(bob())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(b"\"100\"".to_vec()),
+                PromiseResult::Failed,
+            ]
+        );
+
+        controller_contract.write_off_bad_debt_callback(
+            borrower.clone(),
+            vec![
+                (
+                    AccountId::new_unchecked("wnear_market.near".to_string()),
+                    WBalance::from(100),
+                ),
+                (
+                    AccountId::new_unchecked("weth_market.near".to_string()),
+                    WBalance::from(50),
+                ),
+            ],
+        );
+
+        let user_profile = controller_contract.get_user_profile(borrower.clone());
+        assert_eq!(
+            user_profile.get(
+                ActionType::Borrow,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            0
+        );
+        assert_eq!(
+            user_profile.get(
+                ActionType::Borrow,
+                AccountId::new_unchecked("weth_market.near".to_string())
+            ),
+            50
+        );
+        assert!(controller_contract.has_bad_debt(borrower));
+    }
+}
@@ -1,5 +1,5 @@
 use near_sdk::require;
-use near_sdk::BlockHeight;
+use std::cmp::min;
 use std::collections::HashMap;
 
 use crate::borrows_supplies::ActionType::{Borrow, Supply};
@@ -14,31 +14,82 @@ pub enum ActionType {
 
 #[near_bindgen]
 impl Contract {
+    /// Market flows return the resulting health factor of the account to be emitted
+    /// with the market events, `None` if it could not be calculated with the fresh prices
     pub fn make_borrow(
         &mut self,
         account_id: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-        borrow_block: BlockHeight,
-        borrow_rate: WRatio,
-    ) {
-        if !self.is_allowed_to_borrow_uncollateralized(account_id.clone()) {
-            assert!(
-                self.is_borrow_allowed(account_id.clone(), token_address.clone(), token_amount),
-                "Borrow operation is not allowed for account {} token_address {} token_amount {}",
-                account_id,
-                token_address,
-                Balance::from(token_amount)
-            );
-        }
+        borrow_index: WRatio,
+    ) -> Option<WRatio> {
+        self.assert_market_caller(&token_address);
+        self.set_market_borrow_index(&token_address, Ratio::from(borrow_index));
+
+        assert!(
+            self.is_borrow_allowed(account_id.clone(), token_address.clone(), token_amount),
+            "Borrow operation is not allowed for account {} token_address {} token_amount {}",
+            account_id,
+            token_address,
+            Balance::from(token_amount)
+        );
+
+        self.increase_borrows(account_id.clone(), token_address, token_amount);
 
-        self.increase_borrows(
+        self.try_get_health_factor(&account_id).map(WRatio::from)
+    }
+
+    /// Borrows with the fixed rate, `fixed_rate_debt` is the fixed rate debt of the account
+    /// including the borrowed amount
+    pub fn make_fixed_rate_borrow(
+        &mut self,
+        account_id: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+        fixed_rate_debt: FixedRateDebt,
+        borrow_index: WRatio,
+    ) -> Option<WRatio> {
+        self.assert_market_caller(&token_address);
+        assert!(
+            self.is_borrow_allowed(account_id.clone(), token_address.clone(), token_amount),
+            "Borrow operation is not allowed for account {} token_address {} token_amount {}",
             account_id,
             token_address,
-            token_amount,
-            borrow_block,
-            Ratio::from(borrow_rate.0),
+            Balance::from(token_amount)
         );
+
+        self.set_fixed_rate_borrows(
+            account_id,
+            token_address,
+            Some(fixed_rate_debt),
+            borrow_index,
+        )
+    }
+
+    /// Replaces fixed rate debt of the account with the one calculated by the market,
+    /// returns the resulting health factor of the account
+    pub fn set_fixed_rate_borrows(
+        &mut self,
+        account_id: AccountId,
+        token_address: AccountId,
+        fixed_rate_debt: Option<FixedRateDebt>,
+        borrow_index: WRatio,
+    ) -> Option<WRatio> {
+        self.assert_market_caller(&token_address);
+        self.set_market_borrow_index(&token_address, Ratio::from(borrow_index));
+
+        let variable_borrows = self
+            .get_account_borrows_with_interest(account_id.clone(), token_address.clone())
+            - self.get_fixed_rate_borrows(&account_id, &token_address);
+
+        self.set_account_borrows(
+            account_id.clone(),
+            token_address,
+            variable_borrows,
+            fixed_rate_debt,
+        );
+
+        self.try_get_health_factor(&account_id).map(WRatio::from)
     }
 
     pub fn withdraw_supplies(
@@ -46,7 +97,7 @@ impl Contract {
         account_id: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-    ) -> Balance {
+    ) -> Option<WRatio> {
         assert!(
             self.is_withdraw_allowed(account_id.clone(), token_address.clone(), token_amount),
             "Withdrawal operation is not allowed for account {} token_address {} token_amount` {}",
@@ -55,7 +106,10 @@ impl Contract {
             Balance::from(token_amount)
         );
 
-        self.decrease_supplies(account_id, token_address, token_amount)
+        self.decrease_supplies(account_id.clone(), token_address, token_amount);
+        self.refund_storage_if_empty(&account_id);
+
+        self.try_get_health_factor(&account_id).map(WRatio::from)
     }
 
     pub fn get_entity_by_token(
@@ -74,12 +128,14 @@ impl Contract {
         account: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-    ) {
+    ) -> Option<WRatio> {
         let existing_supplies =
             self.get_entity_by_token(Supply, account.clone(), token_address.clone());
         let increased_supplies: Balance = existing_supplies + Balance::from(token_amount);
 
-        self.set_entity_by_token(Supply, account, token_address, increased_supplies);
+        self.set_entity_by_token(Supply, account.clone(), token_address, increased_supplies);
+
+        self.try_get_health_factor(&account).map(WRatio::from)
     }
 
     pub fn increase_borrows(
@@ -87,31 +143,11 @@ impl Contract {
         account: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-        borrow_block: BlockHeight,
-        borrow_rate: Ratio,
     ) {
         let existing_borrows: Balance =
-            self.get_entity_by_token(Borrow, account.clone(), token_address.clone());
+            self.get_account_borrows_with_interest(account.clone(), token_address.clone());
         let increased_borrows: Balance = existing_borrows + Balance::from(token_amount);
 
-        let mut borrow_block = borrow_block;
-        if existing_borrows != 0 {
-            borrow_block = self
-                .user_profiles
-                .get(&account)
-                .unwrap_or_default()
-                .get_borrow_data(token_address.clone())
-                .borrow_block;
-        }
-        let borrow_data = BorrowData {
-            borrow_block,
-            borrow_rate,
-        };
-
-        self.user_profiles
-            .get(&account)
-            .unwrap_or_default()
-            .insert_borrow_data(token_address.clone(), borrow_data);
         self.set_entity_by_token(Borrow, account, token_address, increased_borrows);
     }
 
@@ -120,28 +156,62 @@ impl Contract {
         account: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-        borrow_block: BlockHeight,
-        borrow_rate: WRatio,
     ) -> Balance {
         let existing_borrows: Balance =
-            self.get_entity_by_token(Borrow, account.clone(), token_address.clone());
+            self.get_account_borrows_with_interest(account.clone(), token_address.clone());
+        let decreased_borrows: Balance =
+            existing_borrows.saturating_sub(Balance::from(token_amount));
 
-        let decreased_borrows: Balance = existing_borrows - Balance::from(token_amount);
+        self.set_entity_by_token(Borrow, account, token_address, decreased_borrows)
+    }
 
-        let mut borrow_rate = borrow_rate.0;
-        if decreased_borrows == 0 {
-            borrow_rate = 0;
-        }
-        let borrow_data = BorrowData {
-            borrow_block,
-            borrow_rate: Ratio::from(borrow_rate),
+    /// Account borrows with interest at the last borrow index reported by the market
+    pub fn get_account_borrows_with_interest(
+        &self,
+        account: AccountId,
+        token_address: AccountId,
+    ) -> Balance {
+        let user = self.user_profiles.get(&account).unwrap_or_default();
+        let borrow_data = user.get_borrow_data(token_address.clone());
+        let fixed_rate_debt = borrow_data.fixed_rate_debt.as_ref();
+        let variable_borrows = user
+            .get(Borrow, token_address.clone())
+            .saturating_sub(fixed_rate_debt.map_or(0, |fixed_rate_debt| fixed_rate_debt.amount.0));
+
+        let variable_borrows_with_interest = if variable_borrows == 0 {
+            0
+        } else {
+            (BigBalance::from(variable_borrows) * self.get_market_borrow_index(&token_address)
+                / borrow_data.borrow_index)
+                .round_u128()
         };
+
+        variable_borrows_with_interest
+            + fixed_rate_debt.map_or(0, |fixed_rate_debt| {
+                fixed_rate_debt.get_debt(env::block_height())
+            })
+    }
+
+    /// Fixed rate part of the account borrows with interest
+    pub fn get_fixed_rate_borrows(
+        &self,
+        account: &AccountId,
+        token_address: &AccountId,
+    ) -> Balance {
         self.user_profiles
-            .get(&account)
+            .get(account)
             .unwrap_or_default()
-            .insert_borrow_data(token_address.clone(), borrow_data);
+            .get_borrow_data(token_address.clone())
+            .fixed_rate_debt
+            .map_or(0, |fixed_rate_debt| {
+                fixed_rate_debt.get_debt(env::block_height())
+            })
+    }
 
-        self.set_entity_by_token(Borrow, account, token_address, decreased_borrows)
+    pub fn get_market_borrow_index(&self, dtoken: &AccountId) -> Ratio {
+        self.market_borrow_indexes
+            .get(dtoken)
+            .unwrap_or_else(Ratio::one)
     }
 
     pub fn decrease_supplies(
@@ -175,6 +245,36 @@ impl Contract {
         token_address: AccountId,
         token_amount: Balance,
     ) -> Balance {
+        if let Borrow = action {
+            // variable rate borrows are changed first, fixed rate debt is decreased
+            // only by the amount exceeding the variable rate borrows
+            let existing_fixed_rate_borrows = self.get_fixed_rate_borrows(&user_id, &token_address);
+            let fixed_rate_borrows = min(existing_fixed_rate_borrows, token_amount);
+            let fixed_rate_debt = self
+                .user_profiles
+                .get(&user_id)
+                .unwrap_or_default()
+                .get_borrow_data(token_address.clone())
+                .fixed_rate_debt
+                .map(|fixed_rate_debt| {
+                    if fixed_rate_borrows == existing_fixed_rate_borrows {
+                        return fixed_rate_debt;
+                    }
+                    FixedRateDebt {
+                        amount: U128(fixed_rate_borrows),
+                        rate_per_block: fixed_rate_debt.rate_per_block,
+                        block: env::block_height(),
+                    }
+                });
+
+            return self.set_account_borrows(
+                user_id,
+                token_address,
+                token_amount - fixed_rate_borrows,
+                fixed_rate_debt,
+            );
+        }
+
         let mut user = self.user_profiles.get(&user_id).unwrap_or_default();
         user.set(action, token_address, token_amount);
         self.user_profiles.insert(&user_id, &user);
@@ -182,13 +282,75 @@ impl Contract {
         token_amount
     }
 
+    /// Stores variable rate borrows as principal at the current market borrow index
+    /// along with the fixed rate debt, returns total borrows
+    fn set_account_borrows(
+        &mut self,
+        user_id: AccountId,
+        token_address: AccountId,
+        variable_borrows: Balance,
+        fixed_rate_debt: Option<FixedRateDebt>,
+    ) -> Balance {
+        let mut user = self.user_profiles.get(&user_id).unwrap_or_default();
+        let fixed_rate_debt =
+            fixed_rate_debt.filter(|fixed_rate_debt| fixed_rate_debt.amount.0 > 0);
+        let borrows = variable_borrows
+            + fixed_rate_debt
+                .as_ref()
+                .map_or(0, |fixed_rate_debt| fixed_rate_debt.amount.0);
+
+        let existing_borrows = user.get(Borrow, token_address.clone());
+        let market_borrows = (self.get_market_total_borrows(&token_address) + borrows)
+            .saturating_sub(existing_borrows);
+        self.market_borrows.insert(&token_address, &market_borrows);
+
+        if let Some(isolated_dtoken) = self.get_isolated_collateral(&user) {
+            self.update_isolated_debt(&isolated_dtoken, &token_address, existing_borrows, borrows);
+        }
+
+        user.insert_borrow_data(
+            token_address.clone(),
+            BorrowData {
+                borrow_index: self.get_market_borrow_index(&token_address),
+                fixed_rate_debt,
+            },
+        );
+        user.set(Borrow, token_address, borrows);
+        self.user_profiles.insert(&user_id, &user);
+
+        borrows
+    }
+
+    /// Stores borrow index reported by the market, index never decreases
+    /// so the one reported by delayed callbacks is ignored
+    pub fn set_market_borrow_index(&mut self, dtoken: &AccountId, borrow_index: Ratio) {
+        if borrow_index > self.get_market_borrow_index(dtoken) {
+            self.market_borrow_indexes.insert(dtoken, &borrow_index);
+        }
+    }
+
+    pub fn get_market_total_borrows(&self, dtoken: &AccountId) -> Balance {
+        self.market_borrows.get(dtoken).unwrap_or(0)
+    }
+
+    /// Amount which still could be borrowed from the market, `None` if market has no borrow cap
+    pub fn get_borrow_headroom(&self, dtoken: &AccountId) -> Option<Balance> {
+        self.get_market_by_dtoken(dtoken.clone())
+            .borrow_cap
+            .map(|borrow_cap| {
+                borrow_cap
+                    .0
+                    .saturating_sub(self.get_market_total_borrows(dtoken))
+            })
+    }
+
     fn is_withdraw_allowed(
         &self,
         account: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
     ) -> bool {
-        require!(!self.is_action_paused.withdraw, "withdrawing is paused");
+        self.assert_action_not_paused(&token_address, MethodType::Withdraw);
         let existing_supplies =
             self.get_entity_by_token(Supply, account.clone(), token_address.clone());
         assert!(
@@ -196,18 +358,54 @@ impl Contract {
             "{}",
             "Not enough existing supplies for {account}"
         );
-        self.get_potential_health_factor(account, token_address, token_amount, Supply)
-            >= self.get_liquidation_threshold()
+
+        let mut user_profile = self.get_simulated_profile(&account);
+        user_profile
+            .account_supplies
+            .insert(token_address, existing_supplies - token_amount.0);
+        self.is_profile_healthy(&user_profile)
     }
 
     #[warn(dead_code)]
-    fn is_borrow_allowed(
+    pub(crate) fn is_borrow_allowed(
         &self,
         account: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
     ) -> bool {
-        require!(!self.is_action_paused.borrow, "borrowing is paused");
+        self.assert_action_not_paused(&token_address, MethodType::Borrow);
+        require!(
+            self.get_borrow_headroom(&token_address)
+                .map_or(true, |headroom| token_amount.0 <= headroom),
+            format!("Borrow cap of the market {} is exceeded", token_address)
+        );
+
+        let user_profile = self.user_profiles.get(&account).unwrap_or_default();
+        require!(
+            self.is_borrowable_in_e_mode(&token_address, user_profile.e_mode_category),
+            format!(
+                "Market {} is outside of the e-mode category of the account",
+                token_address
+            )
+        );
+        if let Some(isolated_dtoken) = self.get_isolated_collateral(&user_profile) {
+            require!(
+                self.get_market_by_dtoken(token_address.clone())
+                    .is_borrowable_in_isolation,
+                format!(
+                    "Market {} is not borrowable in isolation mode",
+                    token_address
+                )
+            );
+            require!(
+                self.calculate_usd_amount(&self.get_fresh_price(&token_address), token_amount.0)
+                    <= self.get_isolated_debt_headroom(&isolated_dtoken),
+                format!(
+                    "Debt ceiling of the isolated market {} is exceeded",
+                    isolated_dtoken
+                )
+            );
+        }
 
         self.get_potential_health_factor(account, token_address, token_amount, Borrow)
             >= self.get_liquidation_threshold()
@@ -267,10 +465,12 @@ impl Contract {
 #[cfg(test)]
 mod tests {
     use general::ratio::Ratio;
-    use general::{Price, WRatio, ONE_TOKEN};
+    use general::{FixedRateDebt, Price, WRatio, ONE_TOKEN};
     use near_sdk::json_types::U128;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
-    use near_sdk::AccountId;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, AccountId};
+    use std::str::FromStr;
 
     use crate::borrows_supplies::ActionType::{Borrow, Supply};
     use crate::{Config, Contract};
@@ -321,19 +521,11 @@ mod tests {
     fn success_increase_n_decrease_borrows() {
         let (mut near_contract, token_address, user_account) = init_test_env();
 
-        near_contract.increase_borrows(
-            user_account.clone(),
-            token_address.clone(),
-            U128(10),
-            0,
-            Ratio::zero(),
-        );
+        near_contract.increase_borrows(user_account.clone(), token_address.clone(), U128(10));
         near_contract.increase_borrows(
             user_account.clone(),
             AccountId::new_unchecked("test.nearlend".to_string()),
             U128(100),
-            0,
-            Ratio::zero(),
         );
 
         assert_eq!(
@@ -349,19 +541,11 @@ mod tests {
             100
         );
 
-        near_contract.decrease_borrows(
-            user_account.clone(),
-            token_address.clone(),
-            U128(2),
-            0,
-            WRatio::from(0),
-        );
+        near_contract.decrease_borrows(user_account.clone(), token_address.clone(), U128(2));
         near_contract.decrease_borrows(
             user_account.clone(),
             AccountId::new_unchecked("test.nearlend".to_string()),
             U128(2),
-            0,
-            WRatio::from(0),
         );
 
         assert_eq!(
@@ -378,6 +562,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn success_decrease_borrows_with_fixed_rate_debt() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        testing_env!(VMContextBuilder::new().block_index(100).build());
+
+        near_contract.set_account_borrows(
+            user_account.clone(),
+            token_address.clone(),
+            10,
+            Some(FixedRateDebt {
+                amount: U128(1000),
+                rate_per_block: WRatio::from(Ratio::from_str("0.001").unwrap()),
+                block: 100,
+            }),
+        );
+
+        testing_env!(VMContextBuilder::new().block_index(200).build());
+        assert_eq!(
+            near_contract
+                .get_account_borrows_with_interest(user_account.clone(), token_address.clone()),
+            1110
+        );
+
+        // variable rate borrows are repaid first
+        near_contract.decrease_borrows(user_account.clone(), token_address.clone(), U128(60));
+
+        assert_eq!(
+            near_contract.get_fixed_rate_borrows(&user_account, &token_address),
+            1050
+        );
+        assert_eq!(
+            near_contract.get_entity_by_token(Borrow, user_account, token_address),
+            1050
+        );
+    }
+
     #[test]
     fn success_increase_n_decrease_supplies() {
         let (mut near_contract, token_address, user_account) = init_test_env();
@@ -424,22 +644,21 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn failed_decrease_borrows() {
+    fn decrease_borrows_above_existing_borrows() {
         /*
         Test for decrease flow behavior computation
         */
         let (mut near_contract, token_address, user_account) = init_test_env();
 
-        near_contract.increase_borrows(
-            user_account.clone(),
-            token_address.clone(),
-            U128(10),
-            0,
-            Ratio::zero(),
-        );
+        near_contract.increase_borrows(user_account.clone(), token_address.clone(), U128(10));
 
-        near_contract.decrease_borrows(user_account, token_address, U128(20), 0, WRatio::from(0));
+        near_contract.decrease_borrows(user_account.clone(), token_address.clone(), U128(20));
+
+        assert_eq!(
+            near_contract.get_entity_by_token(Borrow, user_account, token_address.clone()),
+            0
+        );
+        assert_eq!(near_contract.get_market_total_borrows(&token_address), 0);
     }
 
     #[test]
@@ -469,14 +688,123 @@ mod tests {
             fraction_digits: 4u32,
         };
         near_contract.upsert_price(token_address.clone(), &price);
-        near_contract.increase_borrows(
-            user_account.clone(),
-            token_address,
-            U128(10),
-            0,
-            Ratio::zero(),
-        );
+        near_contract.increase_borrows(user_account.clone(), token_address, U128(10));
 
         assert_eq!(near_contract.get_total_borrows(&user_account), U128(1000));
     }
+
+    #[test]
+    fn test_market_total_borrows() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+
+        near_contract.increase_borrows(user_account, token_address.clone(), U128(100));
+        near_contract.increase_borrows(alice(), token_address.clone(), U128(50));
+        near_contract.decrease_borrows(alice(), token_address.clone(), U128(20));
+
+        assert_eq!(near_contract.get_market_total_borrows(&token_address), 130);
+    }
+
+    #[test]
+    #[should_panic(expected = "Borrow cap of the market dtoken.near is exceeded")]
+    fn test_borrow_cap() {
+        let (mut near_contract, _token_address, user_account) = init_test_env();
+        let dtoken: AccountId = "dtoken.near".parse().unwrap();
+
+        testing_env!(VMContextBuilder::new().signer_account_id(alice()).build());
+        near_contract.add_market(
+            "near".parse().unwrap(),
+            dtoken.clone(),
+            "wnear".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+        near_contract.set_market_borrow_cap("near".parse().unwrap(), Some(U128(150)));
+        near_contract.increase_borrows(alice(), dtoken.clone(), U128(100));
+
+        assert_eq!(near_contract.get_borrow_headroom(&dtoken), Some(50));
+
+        near_contract.is_borrow_allowed(user_account, dtoken, U128(51));
+    }
+
+    fn init_with_markets() -> (Contract, AccountId, AccountId) {
+        let (mut near_contract, _token_address, _user_account) = init_test_env();
+        let (wnear_market, weth_market): (AccountId, AccountId) = (
+            "wnear_market.near".parse().unwrap(),
+            "weth_market.near".parse().unwrap(),
+        );
+
+        testing_env!(VMContextBuilder::new().signer_account_id(alice()).build());
+        near_contract.add_market(
+            "wnear.near".parse().unwrap(),
+            wnear_market.clone(),
+            "wnear".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+        near_contract.add_market(
+            "weth.near".parse().unwrap(),
+            weth_market.clone(),
+            "weth".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        (near_contract, wnear_market, weth_market)
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "This functionality is allowed to be called by wnear_market.near contract only"
+    )]
+    fn test_make_borrow_by_foreign_caller() {
+        let (mut near_contract, wnear_market, _weth_market) = init_with_markets();
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(bob())
+            .build());
+        near_contract.make_borrow(
+            carol(),
+            wnear_market,
+            U128(0),
+            WRatio::from(Ratio::from_str("2").unwrap()),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "This functionality is allowed to be called by wnear_market.near contract only"
+    )]
+    fn test_repay_borrows_by_other_market() {
+        let (mut near_contract, wnear_market, weth_market) = init_with_markets();
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(weth_market)
+            .build());
+        near_contract.repay_borrows(
+            carol(),
+            wnear_market,
+            U128(0),
+            WRatio::from(Ratio::from_str("2").unwrap()),
+        );
+    }
+
+    #[test]
+    fn test_repay_borrows_updates_borrow_index() {
+        let (mut near_contract, wnear_market, _weth_market) = init_with_markets();
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(wnear_market.clone())
+            .build());
+        near_contract.repay_borrows(
+            carol(),
+            wnear_market.clone(),
+            U128(0),
+            WRatio::from(Ratio::from_str("2").unwrap()),
+        );
+
+        assert_eq!(
+            near_contract.get_market_borrow_index(&wnear_market),
+            Ratio::from_str("2").unwrap()
+        );
+    }
 }
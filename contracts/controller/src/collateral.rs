@@ -0,0 +1,384 @@
+use crate::*;
+
+impl Contract {
+    /// Checks whether account's supplies of the market are counted as collateral.
+    /// In isolation mode the isolated market is the only collateral of the account
+    pub fn is_collateral(&self, user_profile: &UserProfile, dtoken: &AccountId) -> bool {
+        let market = self.get_market_by_dtoken(dtoken.clone());
+        if !market.is_collateral {
+            return false;
+        }
+
+        match self.get_isolated_collateral(user_profile) {
+            Some(isolated_dtoken) => isolated_dtoken == *dtoken,
+            None => user_profile
+                .collaterals
+                .get(dtoken)
+                .copied()
+                .unwrap_or_else(|| market.debt_ceiling.is_none()),
+        }
+    }
+
+    pub fn get_account_collaterals(
+        &self,
+        user_profile: &UserProfile,
+    ) -> HashMap<AccountId, Balance> {
+        user_profile
+            .account_supplies
+            .iter()
+            .filter(|(dtoken, _)| self.is_collateral(user_profile, dtoken))
+            .map(|(dtoken, balance)| (dtoken.clone(), *balance))
+            .collect()
+    }
+
+    /// Returns isolated market enabled as collateral by the account
+    pub fn get_isolated_collateral(&self, user_profile: &UserProfile) -> Option<AccountId> {
+        user_profile
+            .collaterals
+            .iter()
+            .filter(|(_, is_enabled)| **is_enabled)
+            .map(|(dtoken, _)| dtoken)
+            .find(|dtoken| {
+                let market = self.get_market_by_dtoken((*dtoken).clone());
+                market.is_collateral && market.debt_ceiling.is_some()
+            })
+            .cloned()
+    }
+
+    pub fn calculate_usd_amount(&self, price: &Price, amount: Balance) -> Balance {
+        (BigBalance::from(price.value) * BigBalance::from(amount)
+            / Ratio::from(10u128.pow(price.fraction_digits)))
+        .0
+        .low_u128()
+    }
+
+    /// Debt in USD against the isolated market by the current prices of the borrowed markets
+    pub fn calculate_isolated_debt(&self, isolated_dtoken: &AccountId) -> Balance {
+        self.isolated_debt
+            .get(isolated_dtoken)
+            .unwrap_or_default()
+            .iter()
+            .map(|(dtoken, borrows)| {
+                self.calculate_usd_amount(&self.get_fresh_price(dtoken), *borrows)
+            })
+            .sum()
+    }
+
+    /// Debt in USD which still could be borrowed against the isolated market
+    pub fn get_isolated_debt_headroom(&self, isolated_dtoken: &AccountId) -> Balance {
+        self.get_market_by_dtoken(isolated_dtoken.clone())
+            .debt_ceiling
+            .map_or(0, |debt_ceiling| {
+                debt_ceiling
+                    .0
+                    .saturating_sub(self.calculate_isolated_debt(isolated_dtoken))
+            })
+    }
+
+    /// Tracks debt against the isolated market in the borrowed tokens, so repaying the borrows
+    /// at another price doesn't leave any debt behind
+    pub fn update_isolated_debt(
+        &mut self,
+        isolated_dtoken: &AccountId,
+        borrowed_dtoken: &AccountId,
+        existing_borrows: Balance,
+        new_borrows: Balance,
+    ) {
+        let mut isolated_debt = self.isolated_debt.get(isolated_dtoken).unwrap_or_default();
+        let borrows = (isolated_debt.get(borrowed_dtoken).copied().unwrap_or(0) + new_borrows)
+            .saturating_sub(existing_borrows);
+
+        if borrows > 0 {
+            isolated_debt.insert(borrowed_dtoken.clone(), borrows);
+        } else {
+            isolated_debt.remove(borrowed_dtoken);
+        }
+        self.isolated_debt.insert(isolated_dtoken, &isolated_debt);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn is_collateral_enabled(&self, user_id: AccountId, dtoken_id: AccountId) -> bool {
+        self.is_collateral(
+            &self.user_profiles.get(&user_id).unwrap_or_default(),
+            &dtoken_id,
+        )
+    }
+
+    pub fn get_isolated_debt(&self, dtoken_id: AccountId) -> WBalance {
+        WBalance::from(self.calculate_isolated_debt(&dtoken_id))
+    }
+
+    pub fn enable_as_collateral(&mut self, dtoken_id: AccountId) {
+        let account_id = env::predecessor_account_id();
+        let mut user_profile = self.user_profiles.get(&account_id).unwrap_or_default();
+
+        require!(
+            self.markets
+                .values()
+                .any(|market| market.dtoken == dtoken_id && market.is_collateral),
+            format!("Market {} cannot be used as collateral", dtoken_id)
+        );
+
+        let is_isolated = self
+            .get_market_by_dtoken(dtoken_id.clone())
+            .debt_ceiling
+            .is_some();
+        if is_isolated {
+            require!(
+                user_profile
+                    .account_borrows
+                    .values()
+                    .all(|balance| *balance == 0),
+                "Account cannot enter isolation mode with existing borrows"
+            );
+        } else if let Some(isolated_dtoken) = self.get_isolated_collateral(&user_profile) {
+            env::panic_str(&format!(
+                "Account is in isolation mode with collateral {}",
+                isolated_dtoken
+            ));
+        }
+
+        user_profile.collaterals.insert(dtoken_id, true);
+        self.user_profiles.insert(&account_id, &user_profile);
+        self.assert_storage_covered(&account_id);
+    }
+
+    pub fn disable_as_collateral(&mut self, dtoken_id: AccountId) {
+        let account_id = env::predecessor_account_id();
+        let mut user_profile = self.user_profiles.get(&account_id).unwrap_or_default();
+        let has_borrows = user_profile
+            .account_borrows
+            .values()
+            .any(|balance| *balance > 0);
+
+        if self.get_isolated_collateral(&user_profile) == Some(dtoken_id.clone()) {
+            require!(
+                !has_borrows,
+                "Account cannot leave isolation mode with existing borrows"
+            );
+        }
+
+        user_profile.collaterals.insert(dtoken_id, false);
+        self.user_profiles.insert(&account_id, &user_profile);
+        self.assert_storage_covered(&account_id);
+
+        if has_borrows {
+            require!(
+                self.get_health_factor(account_id) >= self.get_liquidation_threshold(),
+                "Collateral cannot be disabled as health factor would be below liquidation threshold"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use general::storage::get_storage_cost;
+
+    fn init() -> (Contract, AccountId, AccountId, AccountId) {
+        let user_account = bob();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(user_account.clone())
+            .predecessor_account_id(user_account.clone())
+            .build());
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: user_account.clone(),
+            oracle_account_id: user_account.clone(),
+        });
+        controller_contract.storage_deposits.deposit(
+            &user_account,
+            get_storage_cost(MIN_ACCOUNT_STORAGE_USAGE),
+            false,
+        );
+
+        let dtoken_near = AccountId::new_unchecked("wnear_market.near".to_string());
+        let dtoken_usdt = AccountId::new_unchecked("usdt_market.near".to_string());
+
+        for (utoken, dtoken, ticker_id) in [
+            ("wnear.near", dtoken_near.clone(), "wnear"),
+            ("usdt.near", dtoken_usdt.clone(), "usdt"),
+        ] {
+            controller_contract.add_market(
+                AccountId::new_unchecked(utoken.to_string()),
+                dtoken,
+                ticker_id.to_string(),
+                Ratio::from_str("0.6").unwrap(),
+                Ratio::from_str("0.8").unwrap(),
+            );
+        }
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 0,
+            price_list: vec![
+                Price {
+                    ticker_id: "wnear".to_string(),
+                    value: U128(20000),
+                    volatility: U128(100),
+                    fraction_digits: 4,
+                },
+                Price {
+                    ticker_id: "usdt".to_string(),
+                    value: U128(10000),
+                    volatility: U128(100),
+                    fraction_digits: 4,
+                },
+            ],
+        });
+
+        (controller_contract, dtoken_near, dtoken_usdt, user_account)
+    }
+
+    #[test]
+    fn test_disabled_collateral_is_excluded_from_health_factor() {
+        let (mut controller_contract, dtoken_near, dtoken_usdt, user_account) = init();
+
+        controller_contract.increase_supplies(user_account.clone(), dtoken_near.clone(), U128(100));
+        controller_contract.increase_supplies(user_account.clone(), dtoken_usdt.clone(), U128(100));
+        controller_contract.increase_borrows(user_account.clone(), dtoken_usdt.clone(), U128(50));
+
+        let health_factor = controller_contract.get_health_factor(user_account.clone());
+
+        controller_contract.disable_as_collateral(dtoken_usdt.clone());
+        assert!(!controller_contract.is_collateral_enabled(user_account.clone(), dtoken_usdt));
+        assert!(controller_contract.get_health_factor(user_account.clone()) < health_factor);
+
+        controller_contract.enable_as_collateral(dtoken_near.clone());
+        assert!(controller_contract.is_collateral_enabled(user_account, dtoken_near));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Collateral cannot be disabled as health factor would be below liquidation threshold"
+    )]
+    fn test_disable_collateral_backing_borrows() {
+        let (mut controller_contract, dtoken_near, dtoken_usdt, user_account) = init();
+
+        controller_contract.increase_supplies(user_account.clone(), dtoken_near.clone(), U128(100));
+        controller_contract.increase_borrows(user_account, dtoken_usdt, U128(50));
+
+        controller_contract.disable_as_collateral(dtoken_near);
+    }
+
+    #[test]
+    fn test_non_collateral_market_is_excluded() {
+        let (mut controller_contract, dtoken_near, _, user_account) = init();
+
+        controller_contract.increase_supplies(user_account.clone(), dtoken_near.clone(), U128(100));
+        controller_contract
+            .set_market_is_collateral(AccountId::new_unchecked("wnear.near".to_string()), false);
+
+        assert!(!controller_contract.is_collateral_enabled(user_account.clone(), dtoken_near));
+        assert_eq!(
+            controller_contract.get_theoretical_borrows_max(user_account),
+            U128(0)
+        );
+    }
+
+    #[test]
+    fn test_isolation_mode() {
+        let (mut controller_contract, dtoken_near, dtoken_usdt, user_account) = init();
+
+        controller_contract.set_market_debt_ceiling(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            Some(U128(10000)),
+        );
+        controller_contract.increase_supplies(user_account.clone(), dtoken_near.clone(), U128(100));
+
+        // isolated market isn't used as collateral until enabled explicitly
+        assert!(
+            !controller_contract.is_collateral_enabled(user_account.clone(), dtoken_near.clone())
+        );
+
+        controller_contract.enable_as_collateral(dtoken_near.clone());
+        let user_profile = controller_contract
+            .user_profiles
+            .get(&user_account)
+            .unwrap();
+        assert_eq!(
+            controller_contract.get_isolated_collateral(&user_profile),
+            Some(dtoken_near.clone())
+        );
+        assert!(
+            !controller_contract.is_collateral_enabled(user_account.clone(), dtoken_usdt.clone())
+        );
+
+        // 60 * 1.0 USD debt
+        controller_contract.increase_borrows(user_account.clone(), dtoken_usdt.clone(), U128(60));
+        assert_eq!(
+            controller_contract.get_isolated_debt(dtoken_near.clone()),
+            U128(60)
+        );
+
+        controller_contract.decrease_borrows(user_account, dtoken_usdt, U128(20));
+        assert_eq!(controller_contract.get_isolated_debt(dtoken_near), U128(40));
+    }
+
+    #[test]
+    fn test_isolated_debt_follows_price() {
+        let (mut controller_contract, dtoken_near, dtoken_usdt, user_account) = init();
+
+        controller_contract.set_market_debt_ceiling(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            Some(U128(10000)),
+        );
+        controller_contract.increase_supplies(user_account.clone(), dtoken_near.clone(), U128(100));
+        controller_contract.enable_as_collateral(dtoken_near.clone());
+        controller_contract.increase_borrows(user_account.clone(), dtoken_usdt.clone(), U128(60));
+
+        let mut price = controller_contract.get_price(&dtoken_usdt).unwrap();
+        price.value = U128(20000);
+        controller_contract.set_price(dtoken_usdt.clone(), &price, 0);
+
+        // 60 * 2.0 USD debt
+        assert_eq!(
+            controller_contract.get_isolated_debt(dtoken_near.clone()),
+            U128(120)
+        );
+
+        controller_contract.decrease_borrows(user_account, dtoken_usdt, U128(60));
+        assert_eq!(controller_contract.get_isolated_debt(dtoken_near), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Market usdt_market.near is not borrowable in isolation mode")]
+    fn test_isolation_mode_borrow_restriction() {
+        let (mut controller_contract, dtoken_near, dtoken_usdt, user_account) = init();
+
+        controller_contract.set_market_debt_ceiling(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            Some(U128(10000)),
+        );
+        controller_contract.increase_supplies(user_account.clone(), dtoken_near.clone(), U128(100));
+        controller_contract.enable_as_collateral(dtoken_near);
+
+        controller_contract.is_borrow_allowed(user_account, dtoken_usdt, U128(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Debt ceiling of the isolated market wnear_market.near is exceeded")]
+    fn test_isolation_mode_debt_ceiling() {
+        let (mut controller_contract, dtoken_near, dtoken_usdt, user_account) = init();
+
+        controller_contract.set_market_debt_ceiling(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            Some(U128(50)),
+        );
+        controller_contract.set_market_borrowable_in_isolation(
+            AccountId::new_unchecked("usdt.near".to_string()),
+            true,
+        );
+        controller_contract.increase_supplies(user_account.clone(), dtoken_near.clone(), U128(100));
+        controller_contract.enable_as_collateral(dtoken_near);
+
+        controller_contract.is_borrow_allowed(user_account, dtoken_usdt, U128(60));
+    }
+}
@@ -0,0 +1,381 @@
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreditDelegation {
+    /// Amount the delegatee is still allowed to borrow on behalf of the delegator
+    pub limit: WBalance,
+
+    /// Block after which the delegation could not be used
+    pub expiration_block: BlockHeight,
+}
+
+impl Contract {
+    pub fn get_credit_delegation(
+        &self,
+        delegator: &AccountId,
+        delegatee: &AccountId,
+        dtoken: &AccountId,
+    ) -> Option<CreditDelegation> {
+        self.user_profiles
+            .get(delegator)
+            .unwrap_or_default()
+            .credit_delegations
+            .get(delegatee)
+            .and_then(|delegations| delegations.get(dtoken).cloned())
+    }
+
+    fn set_credit_delegation(
+        &mut self,
+        delegator: AccountId,
+        delegatee: AccountId,
+        dtoken: AccountId,
+        delegation: Option<CreditDelegation>,
+    ) {
+        let mut user = self.user_profiles.get(&delegator).unwrap_or_default();
+        let delegations = user
+            .credit_delegations
+            .entry(delegatee.clone())
+            .or_default();
+
+        match delegation {
+            Some(delegation) => delegations.insert(dtoken, delegation),
+            None => delegations.remove(&dtoken),
+        };
+        if delegations.is_empty() {
+            user.credit_delegations.remove(&delegatee);
+        }
+
+        self.user_profiles.insert(&delegator, &user);
+    }
+
+    /// Decreases the delegated limit by the borrowed amount
+    fn use_credit_delegation(
+        &mut self,
+        delegator: AccountId,
+        delegatee: AccountId,
+        dtoken: AccountId,
+        token_amount: WBalance,
+    ) {
+        let mut delegation = self
+            .get_credit_delegation(&delegator, &delegatee, &dtoken)
+            .unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "Account {} has no credit delegation from {} in market {}",
+                    delegatee, delegator, dtoken
+                ))
+            });
+        require!(
+            env::block_height() <= delegation.expiration_block,
+            "Credit delegation is expired"
+        );
+        require!(
+            token_amount.0 <= delegation.limit.0,
+            "Amount exceeds the credit delegation limit"
+        );
+
+        delegation.limit = U128(delegation.limit.0 - token_amount.0);
+        self.set_credit_delegation(delegator, delegatee, dtoken, Some(delegation));
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Allows the delegatee to borrow up to the limit from the market against
+    /// the caller's collateral, the debt is recorded against the caller
+    pub fn approve_credit_delegation(
+        &mut self,
+        delegatee: AccountId,
+        dtoken: AccountId,
+        limit: WBalance,
+        expiration_block: BlockHeight,
+    ) {
+        let delegator = env::predecessor_account_id();
+        require!(
+            delegatee != delegator,
+            "Credit could not be delegated to the account itself"
+        );
+        require!(
+            self.is_market_registered(dtoken.clone()),
+            format!("Market {} is not registered", dtoken)
+        );
+        require!(
+            expiration_block > env::block_height(),
+            "Expiration block of the credit delegation is in the past"
+        );
+
+        let delegation = Some(CreditDelegation {
+            limit,
+            expiration_block,
+        })
+        .filter(|delegation| delegation.limit.0 > 0);
+        self.set_credit_delegation(delegator.clone(), delegatee, dtoken, delegation);
+        self.assert_storage_covered(&delegator);
+    }
+
+    pub fn revoke_credit_delegation(&mut self, delegatee: AccountId, dtoken: AccountId) {
+        let delegator = env::predecessor_account_id();
+        self.set_credit_delegation(delegator.clone(), delegatee, dtoken, None);
+        self.refund_storage_if_empty(&delegator);
+    }
+
+    /// Borrows on behalf of the delegator within the credit delegated to the borrowing account
+    pub fn make_delegated_borrow(
+        &mut self,
+        delegator: AccountId,
+        delegatee: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+        borrow_index: WRatio,
+    ) -> Option<WRatio> {
+        self.assert_market_caller(&token_address);
+        self.set_market_borrow_index(&token_address, Ratio::from(borrow_index));
+        self.use_credit_delegation(
+            delegator.clone(),
+            delegatee,
+            token_address.clone(),
+            token_amount,
+        );
+        assert!(
+            self.is_borrow_allowed(delegator.clone(), token_address.clone(), token_amount),
+            "Borrow operation is not allowed for account {} token_address {} token_amount {}",
+            delegator,
+            token_address,
+            Balance::from(token_amount)
+        );
+
+        self.increase_borrows(delegator.clone(), token_address, token_amount);
+
+        self.try_get_health_factor(&delegator).map(WRatio::from)
+    }
+
+    /// Reverts the delegated borrow which tokens were not transferred to the delegatee
+    pub fn revert_delegated_borrow(
+        &mut self,
+        delegator: AccountId,
+        delegatee: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+    ) {
+        require!(
+            self.is_dtoken_caller(),
+            "This functionality is allowed to be called by dtoken's contract only"
+        );
+        if let Some(mut delegation) =
+            self.get_credit_delegation(&delegator, &delegatee, &token_address)
+        {
+            delegation.limit = U128(delegation.limit.0 + token_amount.0);
+            self.set_credit_delegation(
+                delegator.clone(),
+                delegatee,
+                token_address.clone(),
+                Some(delegation),
+            );
+        }
+
+        self.decrease_borrows(delegator, token_address, token_amount);
+    }
+
+    /// Delegatee -> Dtoken address -> Credit delegated by the account
+    pub fn view_credit_delegations(
+        &self,
+        delegator: AccountId,
+    ) -> HashMap<AccountId, HashMap<AccountId, CreditDelegation>> {
+        self.user_profiles
+            .get(&delegator)
+            .unwrap_or_default()
+            .credit_delegations
+    }
+
+    pub fn view_credit_delegation(
+        &self,
+        delegator: AccountId,
+        delegatee: AccountId,
+        dtoken: AccountId,
+    ) -> Option<CreditDelegation> {
+        self.get_credit_delegation(&delegator, &delegatee, &dtoken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::borrows_supplies::ActionType::Borrow;
+    use general::storage::get_storage_cost;
+
+    fn init() -> (Contract, AccountId, AccountId, AccountId) {
+        let (delegator, delegatee) = (bob(), carol());
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(delegator.clone())
+            .predecessor_account_id(delegator.clone())
+            .build());
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: delegator.clone(),
+            oracle_account_id: delegator.clone(),
+        });
+        controller_contract.storage_deposits.deposit(
+            &delegator,
+            get_storage_cost(MIN_ACCOUNT_STORAGE_USAGE),
+            false,
+        );
+
+        let dtoken = AccountId::new_unchecked("usdt_market.near".to_string());
+        controller_contract.add_market(
+            AccountId::new_unchecked("usdt.near".to_string()),
+            dtoken.clone(),
+            "usdt".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 0,
+            price_list: vec![Price {
+                ticker_id: "usdt".to_string(),
+                value: U128(10000),
+                volatility: U128(100),
+                fraction_digits: 4,
+            }],
+        });
+
+        controller_contract.increase_supplies(delegator.clone(), dtoken.clone(), U128(100));
+        controller_contract.approve_credit_delegation(
+            delegatee.clone(),
+            dtoken.clone(),
+            U128(50),
+            100,
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(delegatee.clone())
+            .predecessor_account_id(dtoken.clone())
+            .block_index(10)
+            .build());
+
+        (controller_contract, dtoken, delegator, delegatee)
+    }
+
+    #[test]
+    fn test_delegated_borrow() {
+        let (mut controller_contract, dtoken, delegator, delegatee) = init();
+
+        controller_contract.make_delegated_borrow(
+            delegator.clone(),
+            delegatee.clone(),
+            dtoken.clone(),
+            U128(30),
+            WRatio::from(Ratio::one()),
+        );
+
+        assert_eq!(
+            controller_contract.get_entity_by_token(Borrow, delegator.clone(), dtoken.clone()),
+            30
+        );
+        assert_eq!(
+            controller_contract.get_entity_by_token(Borrow, delegatee.clone(), dtoken.clone()),
+            0
+        );
+        assert_eq!(
+            controller_contract
+                .view_credit_delegation(delegator.clone(), delegatee.clone(), dtoken.clone())
+                .unwrap()
+                .limit,
+            U128(20)
+        );
+
+        controller_contract.revert_delegated_borrow(
+            delegator.clone(),
+            delegatee.clone(),
+            dtoken.clone(),
+            U128(30),
+        );
+
+        assert_eq!(
+            controller_contract.get_entity_by_token(Borrow, delegator.clone(), dtoken.clone()),
+            0
+        );
+        assert_eq!(
+            controller_contract
+                .view_credit_delegation(delegator, delegatee, dtoken)
+                .unwrap()
+                .limit,
+            U128(50)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds the credit delegation limit")]
+    fn test_delegated_borrow_over_limit() {
+        let (mut controller_contract, dtoken, delegator, delegatee) = init();
+
+        controller_contract.make_delegated_borrow(
+            delegator,
+            delegatee,
+            dtoken,
+            U128(60),
+            WRatio::from(Ratio::one()),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Credit delegation is expired")]
+    fn test_expired_delegated_borrow() {
+        let (mut controller_contract, dtoken, delegator, delegatee) = init();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(delegatee.clone())
+            .predecessor_account_id(dtoken.clone())
+            .block_index(101)
+            .build());
+
+        controller_contract.make_delegated_borrow(
+            delegator,
+            delegatee,
+            dtoken,
+            U128(30),
+            WRatio::from(Ratio::one()),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "This functionality is allowed to be called by usdt_market.near contract only"
+    )]
+    fn test_delegated_borrow_by_other_market() {
+        let (mut controller_contract, dtoken, delegator, delegatee) = init();
+        let other_dtoken = AccountId::new_unchecked("weth_market.near".to_string());
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(delegator.clone())
+            .predecessor_account_id(delegator.clone())
+            .build());
+        controller_contract.add_market(
+            AccountId::new_unchecked("weth.near".to_string()),
+            other_dtoken.clone(),
+            "weth".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(delegatee.clone())
+            .predecessor_account_id(other_dtoken)
+            .block_index(10)
+            .build());
+
+        controller_contract.make_delegated_borrow(
+            delegator,
+            delegatee,
+            dtoken,
+            U128(30),
+            WRatio::from(Ratio::one()),
+        );
+    }
+}
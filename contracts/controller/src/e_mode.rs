@@ -0,0 +1,265 @@
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EModeCategory {
+    /// Category name, e.g. "stablecoins"
+    pub label: String,
+
+    /// Loan to value for the markets of the category
+    pub ltv: Ratio,
+
+    /// Liquidation threshold for the markets of the category
+    pub lth: Ratio,
+
+    /// Liquidation incentive for the accounts opted into the category
+    pub liquidation_bonus: Ratio,
+}
+
+impl Contract {
+    /// Returns category parameters applied to the market for the account opted into the category
+    pub fn get_e_mode_category_of_market(
+        &self,
+        market: &MarketProfile,
+        e_mode_category: Option<u8>,
+    ) -> Option<EModeCategory> {
+        e_mode_category
+            .filter(|category_id| market.e_mode_category == Some(*category_id))
+            .and_then(|category_id| self.e_mode_categories.get(&category_id))
+    }
+
+    pub fn get_market_ltv(&self, market: &MarketProfile, e_mode_category: Option<u8>) -> Ratio {
+        self.get_e_mode_category_of_market(market, e_mode_category)
+            .map_or(market.ltv, |category| category.ltv)
+    }
+
+    pub fn get_market_lth(&self, market: &MarketProfile, e_mode_category: Option<u8>) -> Ratio {
+        self.get_e_mode_category_of_market(market, e_mode_category)
+            .map_or(market.lth, |category| category.lth)
+    }
+
+    pub fn is_borrowable_in_e_mode(&self, dtoken: &AccountId, e_mode_category: Option<u8>) -> bool {
+        e_mode_category.map_or(true, |category_id| {
+            self.get_market_by_dtoken(dtoken.clone()).e_mode_category == Some(category_id)
+        })
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_e_mode_categories(&self) -> HashMap<u8, EModeCategory> {
+        self.e_mode_categories.iter().collect()
+    }
+
+    pub fn get_user_e_mode_category(&self, user_id: AccountId) -> Option<u8> {
+        self.user_profiles
+            .get(&user_id)
+            .unwrap_or_default()
+            .e_mode_category
+    }
+
+    pub fn set_e_mode_category(&mut self, category_id: u8, category: EModeCategory) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetEModeCategory {
+            category_id,
+            category,
+        });
+    }
+
+    pub fn remove_e_mode_category(&mut self, category_id: u8) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::RemoveEModeCategory { category_id });
+    }
+
+    /// Opts the account into the e-mode category or out of e-mode if category is not set
+    pub fn set_user_e_mode_category(&mut self, category_id: Option<u8>) {
+        let account_id = env::predecessor_account_id();
+        let mut user_profile = self.user_profiles.get(&account_id).unwrap_or_default();
+
+        if let Some(category_id) = category_id {
+            require!(
+                self.e_mode_categories.get(&category_id).is_some(),
+                "E-mode category doesnt exist"
+            );
+            for (dtoken, balance) in user_profile.account_borrows.iter() {
+                require!(
+                    *balance == 0 || self.is_borrowable_in_e_mode(dtoken, Some(category_id)),
+                    format!("Borrow of {} is outside of the e-mode category", dtoken)
+                );
+            }
+        }
+
+        let has_borrows = user_profile
+            .account_borrows
+            .values()
+            .any(|balance| *balance > 0);
+
+        user_profile.e_mode_category = category_id;
+        self.user_profiles.insert(&account_id, &user_profile);
+        self.assert_storage_covered(&account_id);
+
+        if has_borrows {
+            require!(
+                self.get_health_factor(account_id) >= self.get_liquidation_threshold(),
+                "E-mode category cannot be changed as health factor would be below liquidation threshold"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use general::storage::get_storage_cost;
+
+    const STABLECOINS: u8 = 1;
+
+    fn init() -> (Contract, AccountId, AccountId, AccountId) {
+        let user_account = bob();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(user_account.clone())
+            .predecessor_account_id(user_account.clone())
+            .build());
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: user_account.clone(),
+            oracle_account_id: user_account.clone(),
+        });
+        controller_contract.storage_deposits.deposit(
+            &user_account,
+            get_storage_cost(MIN_ACCOUNT_STORAGE_USAGE),
+            false,
+        );
+
+        let dtoken_usdt = AccountId::new_unchecked("usdt_market.near".to_string());
+        let dtoken_usdc = AccountId::new_unchecked("usdc_market.near".to_string());
+
+        controller_contract.set_e_mode_category(
+            STABLECOINS,
+            EModeCategory {
+                label: "stablecoins".to_string(),
+                ltv: Ratio::from_str("0.9").unwrap(),
+                lth: Ratio::from_str("0.95").unwrap(),
+                liquidation_bonus: Ratio::from_str("0.02").unwrap(),
+            },
+        );
+
+        for (utoken, dtoken, ticker_id) in [
+            ("usdt.near", dtoken_usdt.clone(), "usdt"),
+            ("usdc.near", dtoken_usdc.clone(), "usdc"),
+            (
+                "wnear.near",
+                AccountId::new_unchecked("wnear_market.near".to_string()),
+                "wnear",
+            ),
+        ] {
+            controller_contract.add_market(
+                AccountId::new_unchecked(utoken.to_string()),
+                dtoken,
+                ticker_id.to_string(),
+                Ratio::from_str("0.6").unwrap(),
+                Ratio::from_str("0.8").unwrap(),
+            );
+        }
+        for utoken in ["usdt.near", "usdc.near"] {
+            controller_contract.set_market_e_mode_category(
+                AccountId::new_unchecked(utoken.to_string()),
+                Some(STABLECOINS),
+            );
+        }
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 0,
+            price_list: ["usdt", "usdc", "wnear"]
+                .iter()
+                .map(|ticker_id| Price {
+                    ticker_id: ticker_id.to_string(),
+                    value: U128(10000),
+                    volatility: U128(100),
+                    fraction_digits: 4,
+                })
+                .collect(),
+        });
+
+        (controller_contract, dtoken_usdt, dtoken_usdc, user_account)
+    }
+
+    #[test]
+    fn test_e_mode_boosts_health_factor() {
+        let (mut controller_contract, dtoken_usdt, dtoken_usdc, user_account) = init();
+
+        controller_contract.increase_supplies(user_account.clone(), dtoken_usdt, U128(1000));
+        controller_contract.increase_borrows(user_account.clone(), dtoken_usdc, U128(900));
+
+        // 1000 * 0.8 / 900
+        assert!(controller_contract.get_health_factor(user_account.clone()) < Ratio::one());
+
+        controller_contract.set_user_e_mode_category(Some(STABLECOINS));
+
+        // 1000 * 0.95 / 900
+        assert!(controller_contract.get_health_factor(user_account.clone()) > Ratio::one());
+        assert_eq!(
+            controller_contract.get_theoretical_borrows_max(user_account.clone()),
+            U128(900)
+        );
+        assert_eq!(
+            controller_contract.get_liquidation_bonus(&user_account, Ratio::one()),
+            Ratio::from_str("0.02").unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Market wnear_market.near is outside of the e-mode category of the account"
+    )]
+    fn test_e_mode_blocks_borrows_outside_category() {
+        let (mut controller_contract, dtoken_usdt, _, user_account) = init();
+
+        controller_contract.increase_supplies(user_account.clone(), dtoken_usdt, U128(1000));
+        controller_contract.set_user_e_mode_category(Some(STABLECOINS));
+
+        controller_contract.is_borrow_allowed(
+            user_account,
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            U128(10),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Borrow of wnear_market.near is outside of the e-mode category")]
+    fn test_e_mode_opt_in_with_borrows_outside_category() {
+        let (mut controller_contract, dtoken_usdt, _, user_account) = init();
+
+        controller_contract.increase_supplies(user_account.clone(), dtoken_usdt, U128(1000));
+        controller_contract.increase_borrows(
+            user_account,
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            U128(100),
+        );
+
+        controller_contract.set_user_e_mode_category(Some(STABLECOINS));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "E-mode category cannot be changed as health factor would be below liquidation threshold"
+    )]
+    fn test_e_mode_opt_out_below_liquidation_threshold() {
+        let (mut controller_contract, dtoken_usdt, dtoken_usdc, user_account) = init();
+
+        controller_contract.increase_supplies(user_account.clone(), dtoken_usdt, U128(1000));
+        controller_contract.set_user_e_mode_category(Some(STABLECOINS));
+        controller_contract.increase_borrows(user_account, dtoken_usdc, U128(900));
+
+        controller_contract.set_user_e_mode_category(None);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::*;
+use general::events::emit_event;
+use near_sdk::serde_json;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum Events {
+    PriceUpdate {
+        dtoken: AccountId,
+        price: Price,
+        block_height: BlockHeight,
+    },
+    PriceRejected {
+        oracle_id: AccountId,
+        dtoken: AccountId,
+        price: Price,
+        reason: String,
+    },
+    /// `asset_id` is set for the changes of a single market parameters
+    ParameterChange {
+        parameter: String,
+        asset_id: Option<AccountId>,
+        value: serde_json::Value,
+    },
+    BadDebtWrittenOff {
+        account_id: AccountId,
+        dtoken: AccountId,
+        amount: WBalance,
+    },
+    BadDebtWriteOffFailed {
+        account_id: AccountId,
+        dtoken: AccountId,
+        amount: WBalance,
+    },
+    BatchLiquidationSuccess {
+        borrower_account_id: AccountId,
+        liquidator_account_id: AccountId,
+        health_factor: Option<WRatio>,
+    },
+    BatchLiquidationFailed {
+        borrower_account_id: AccountId,
+        liquidator_account_id: AccountId,
+    },
+    BatchLiquidationRevertFailed {
+        borrower_account_id: AccountId,
+        liquidator_account_id: AccountId,
+    },
+}
+
+impl Events {
+    pub fn parameter_change<T: Serialize>(
+        parameter: &str,
+        asset_id: Option<AccountId>,
+        value: T,
+    ) -> Self {
+        Events::ParameterChange {
+            parameter: parameter.to_string(),
+            asset_id,
+            value: serde_json::json!(value),
+        }
+    }
+
+    pub fn emit(&self) {
+        emit_event(self);
+    }
+}
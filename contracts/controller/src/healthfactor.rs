@@ -1,27 +1,31 @@
 use crate::*;
 
 use general::ratio::Ratio;
-use near_sdk::env::block_height;
 use std::collections::HashMap;
 use std::ops::Add;
 
 impl Contract {
     pub fn calculate_supplies_weighted_price_and_lth(&self, user_id: AccountId) -> Balance {
-        let supplies = self
-            .user_profiles
-            .get(&user_id)
-            .unwrap_or_default()
-            .account_supplies;
+        let user_profile = self.user_profiles.get(&user_id).unwrap_or_default();
+        let collaterals = self.get_account_collaterals(&user_profile);
 
+        self.calculate_weighted_lth_by_supplies(&collaterals, user_profile.e_mode_category)
+    }
+
+    pub fn calculate_weighted_lth_by_supplies(
+        &self,
+        supplies: &HashMap<AccountId, Balance>,
+        e_mode_category: Option<u8>,
+    ) -> Balance {
         supplies
             .iter()
             .map(|(dtoken, balance)| {
-                let price = self.get_price(dtoken).unwrap();
+                let price = self.get_fresh_price(dtoken);
                 let market = self.get_market_by_dtoken(dtoken.clone());
 
                 ((BigBalance::from(price.value)
                     * BigBalance::from(balance.to_owned())
-                    * market.lth)
+                    * self.get_market_lth(&market, e_mode_category))
                     / Ratio::from(10u128.pow(price.fraction_digits)))
                 .0
                 .low_u128()
@@ -30,23 +34,20 @@ impl Contract {
     }
 
     pub fn get_collaterals_by_borrows(&self, user_id: AccountId) -> USD {
-        let borrows = self
-            .user_profiles
-            .get(&user_id)
-            .unwrap_or_default()
-            .account_borrows;
+        let user_profile = self.user_profiles.get(&user_id).unwrap_or_default();
 
-        let collaterals: Balance = borrows
+        let collaterals: Balance = user_profile
+            .account_borrows
             .iter()
             .map(|(dtoken, balance)| {
-                let price = self.get_price(dtoken).unwrap();
+                let price = self.get_fresh_price(dtoken);
                 let market = self.get_market_by_dtoken(dtoken.clone());
 
                 ((BigBalance::from(price.value) * BigBalance::from(balance.to_owned())
                     / Ratio::from(10u128.pow(price.fraction_digits)))
-                    / market.ltv)
-                    .0
-                    .low_u128()
+                    / self.get_market_ltv(&market, user_profile.e_mode_category))
+                .0
+                .low_u128()
             })
             .sum();
 
@@ -75,34 +76,39 @@ impl Contract {
     }
 
     pub fn get_theoretical_borrows_max(&self, user_id: AccountId) -> USD {
-        let supplies = self
-            .user_profiles
-            .get(&user_id)
-            .unwrap_or_default()
-            .account_supplies;
+        let user_profile = self.user_profiles.get(&user_id).unwrap_or_default();
+        let collaterals = self.get_account_collaterals(&user_profile);
+
+        USD::from(
+            self.calculate_weighted_ltv_by_supplies(&collaterals, user_profile.e_mode_category),
+        )
+    }
 
-        let borrow_max: Balance = supplies
+    pub fn calculate_weighted_ltv_by_supplies(
+        &self,
+        supplies: &HashMap<AccountId, Balance>,
+        e_mode_category: Option<u8>,
+    ) -> Balance {
+        supplies
             .iter()
             .map(|(dtoken, balance)| {
-                let price = self.get_price(dtoken).unwrap();
+                let price = self.get_fresh_price(dtoken);
                 let market = self.get_market_by_dtoken(dtoken.clone());
 
                 ((BigBalance::from(price.value)
                     * BigBalance::from(balance.to_owned())
-                    * market.ltv)
+                    * self.get_market_ltv(&market, e_mode_category))
                     / Ratio::from(10u128.pow(price.fraction_digits)))
                 .0
                 .low_u128()
             })
-            .sum();
-
-        USD::from(borrow_max)
+            .sum()
     }
 
     pub fn calculate_assets_weighted_price(&self, map: &HashMap<AccountId, Balance>) -> Balance {
         map.iter()
             .map(|(asset, balance)| {
-                let price = self.get_price(asset).unwrap();
+                let price = self.get_fresh_price(asset);
 
                 Percentage::from(price.volatility.0).apply_to(
                     ((BigBalance::from(balance.to_owned()) * BigBalance::from(price.value))
@@ -134,26 +140,84 @@ impl Contract {
     }
 
     pub fn calculate_accrued_borrow_interest(&self, account_id: AccountId) -> Balance {
-        let mut total_accrued_interest = 0;
         let user_profile = self.user_profiles.get(&account_id).unwrap_or_default();
-        let total_borrows: Balance = user_profile.account_borrows.values().sum();
 
-        for (token_address, borrow_data) in user_profile.borrow_data.iter() {
-            let accrued_interest = Ratio::from(total_borrows)
-                * borrow_data.borrow_rate
-                * Ratio::from(block_height() - borrow_data.borrow_block);
+        user_profile
+            .account_borrows
+            .iter()
+            .map(|(token_address, borrows)| {
+                let accrued_interest = self
+                    .get_account_borrows_with_interest(account_id.clone(), token_address.clone())
+                    .saturating_sub(*borrows);
 
-            let price = self.get_price(token_address).unwrap();
-            let accrued_interest_amount = Percentage::from(price.volatility.0).apply_to(
-                (BigBalance::from(price.value) * accrued_interest
-                    / Ratio::from(10u128.pow(price.fraction_digits)))
-                .0
-                .low_u128(),
-            );
+                let price = self.get_fresh_price(token_address);
+                Percentage::from(price.volatility.0).apply_to(
+                    (BigBalance::from(price.value) * BigBalance::from(accrued_interest)
+                        / Ratio::from(10u128.pow(price.fraction_digits)))
+                    .0
+                    .low_u128(),
+                )
+            })
+            .sum()
+    }
+
+    /// Checks that liquidation with the given amounts doesn't decrease borrower's health factor
+    pub fn is_health_factor_not_decreased_by_liquidation(
+        &self,
+        borrower: AccountId,
+        repaid_borrows: &HashMap<AccountId, Balance>,
+        seized_supplies: &HashMap<AccountId, Balance>,
+    ) -> bool {
+        let user_profile = self.user_profiles.get(&borrower).unwrap_or_default();
+        let accrued_interest = self.calculate_accrued_borrow_interest(borrower);
+
+        let borrows_before =
+            self.calculate_assets_weighted_price(&user_profile.account_borrows) + accrued_interest;
+        let e_mode_category = user_profile.e_mode_category;
+        let mut account_collaterals = self.get_account_collaterals(&user_profile);
+        let collaterals_before =
+            self.calculate_weighted_lth_by_supplies(&account_collaterals, e_mode_category);
+
+        let mut account_borrows = user_profile.account_borrows;
+        for (dtoken, amount) in repaid_borrows {
+            if let Some(balance) = account_borrows.get_mut(dtoken) {
+                *balance = balance.saturating_sub(*amount);
+            }
+        }
+        for (dtoken, amount) in seized_supplies {
+            if let Some(balance) = account_collaterals.get_mut(dtoken) {
+                *balance = balance.saturating_sub(*amount);
+            }
+        }
 
-            total_accrued_interest += accrued_interest_amount;
+        let borrows_after =
+            self.calculate_assets_weighted_price(&account_borrows) + accrued_interest;
+        let collaterals_after =
+            self.calculate_weighted_lth_by_supplies(&account_collaterals, e_mode_category);
+
+        if borrows_after == 0 || borrows_before == 0 {
+            return true;
         }
-        total_accrued_interest
+
+        Ratio::from(collaterals_after) / Ratio::from(borrows_after)
+            >= Ratio::from(collaterals_before) / Ratio::from(borrows_before)
+    }
+
+    /// Health factor of the account, `None` if prices of the account markets are not fresh
+    /// or some of its markets were removed
+    pub fn try_get_health_factor(&self, account_id: &AccountId) -> Option<Ratio> {
+        let user_profile = self.user_profiles.get(account_id).unwrap_or_default();
+        let is_priced = user_profile
+            .account_supplies
+            .keys()
+            .chain(user_profile.account_borrows.keys())
+            .all(|dtoken| {
+                self.markets.values().any(|market| market.dtoken == *dtoken)
+                    && self.get_price(dtoken).is_some()
+                    && self.is_price_fresh(dtoken)
+            });
+
+        is_priced.then(|| self.get_health_factor(account_id.clone()))
     }
 
     pub fn get_hf_with_supply_and_no_borrow(&self, user_account: AccountId) -> Ratio {
@@ -163,6 +227,109 @@ impl Contract {
 
         Ratio::from(supplies_weighted_lth) / Ratio::from(max_borrows.0)
     }
+
+    /// Health factor by the balances of the profile, its borrows should already include the
+    /// accrued interest
+    pub fn calculate_profile_health_factor(&self, user_profile: &UserProfile) -> Ratio {
+        let collaterals = self.get_account_collaterals(user_profile);
+        let supplies_weighted_lth =
+            self.calculate_weighted_lth_by_supplies(&collaterals, user_profile.e_mode_category);
+        let borrows = self.calculate_assets_weighted_price(&user_profile.account_borrows);
+
+        if borrows != 0 {
+            Ratio::from(supplies_weighted_lth) / Ratio::from(borrows)
+        } else if supplies_weighted_lth > 0 {
+            Ratio::from(supplies_weighted_lth)
+                / Ratio::from(
+                    self.calculate_weighted_ltv_by_supplies(
+                        &collaterals,
+                        user_profile.e_mode_category,
+                    ),
+                )
+        } else {
+            self.get_average_hf_by_markets_ltv()
+        }
+    }
+
+    /// Prices of the profile collaterals at which its health factor reaches the liquidation
+    /// threshold while other prices stay the same. Prices have the fraction digits of the oracle
+    /// price, `None` if the collateral price alone cannot make the account liquidatable
+    pub fn calculate_liquidation_prices(
+        &self,
+        user_profile: &UserProfile,
+    ) -> HashMap<AccountId, Option<WBalance>> {
+        let e_mode_category = user_profile.e_mode_category;
+        let threshold = self.get_liquidation_threshold();
+        let collaterals = self.get_account_collaterals(user_profile);
+        let total_collaterals =
+            self.calculate_weighted_lth_by_supplies(&collaterals, e_mode_category);
+        let total_borrows = self.calculate_assets_weighted_price(&user_profile.account_borrows);
+
+        collaterals
+            .iter()
+            .filter(|(_, balance)| **balance > 0)
+            .map(|(dtoken, balance)| {
+                let price = self.get_fresh_price(dtoken);
+                let market = self.get_market_by_dtoken(dtoken.clone());
+                let borrows = user_profile
+                    .account_borrows
+                    .get(dtoken)
+                    .copied()
+                    .unwrap_or(0);
+
+                let other_collaterals = Ratio::from(
+                    total_collaterals
+                        - self.calculate_weighted_lth_by_supplies(
+                            &HashMap::from([(dtoken.clone(), *balance)]),
+                            e_mode_category,
+                        ),
+                );
+                let other_borrows = threshold
+                    * Ratio::from(
+                        total_borrows
+                            - self.calculate_assets_weighted_price(&HashMap::from([(
+                                dtoken.clone(),
+                                borrows,
+                            )])),
+                    );
+
+                // Both sides of `collaterals = threshold * borrows` are linear by the price
+                let collaterals_per_price =
+                    Ratio::from(*balance) * self.get_market_lth(&market, e_mode_category);
+                let borrows_per_price =
+                    threshold * Ratio::from(borrows) * Ratio::from(price.volatility.0)
+                        / Ratio::from(100u128);
+
+                let liquidation_price = if collaterals_per_price > borrows_per_price
+                    && other_borrows > other_collaterals
+                {
+                    Some(
+                        (other_borrows - other_collaterals)
+                            / (collaterals_per_price - borrows_per_price),
+                    )
+                } else if collaterals_per_price < borrows_per_price
+                    && other_borrows < other_collaterals
+                {
+                    Some(
+                        (other_collaterals - other_borrows)
+                            / (borrows_per_price - collaterals_per_price),
+                    )
+                } else {
+                    None
+                };
+
+                (
+                    dtoken.clone(),
+                    liquidation_price.map(|liquidation_price| {
+                        WBalance::from(
+                            (liquidation_price * Ratio::from(10u128.pow(price.fraction_digits)))
+                                .round_u128(),
+                        )
+                    }),
+                )
+            })
+            .collect()
+    }
 }
 
 #[near_bindgen]
@@ -183,6 +350,8 @@ impl Contract {
         }
     }
 
+    /// Health factor of the account after supplying to or borrowing from the market,
+    /// use `simulate_actions` to model withdrawals, repays or several actions
     pub fn get_potential_health_factor(
         &self,
         user_account: AccountId,
@@ -191,32 +360,33 @@ impl Contract {
         action: ActionType,
     ) -> Ratio {
         let mut collaterals = self.calculate_supplies_weighted_price_and_lth(user_account.clone());
-        let max_borrows = self.get_theoretical_borrows_max(user_account.clone());
+        let mut max_borrows = self.get_theoretical_borrows_max(user_account.clone()).0;
         let mut borrows = self.get_account_sum_per_action(user_account.clone(), ActionType::Borrow);
-        borrows += self.calculate_accrued_borrow_interest(user_account);
+        borrows += self.calculate_accrued_borrow_interest(user_account.clone());
+        let user_profile = self.user_profiles.get(&user_account).unwrap_or_default();
+        let is_collateral = self.is_collateral(&user_profile, &token_address);
+        let e_mode_category = user_profile.e_mode_category;
 
-        let price = self.get_price(&token_address).unwrap();
-        let usd_amount = Percentage::from(price.volatility.0).apply_to(
-            (BigBalance::from(price.value) * BigBalance::from(amount.0)
-                / Ratio::from(10u128.pow(price.fraction_digits)))
-            .0
-            .low_u128(),
-        );
         match action {
             ActionType::Supply => {
-                if borrows != 0 {
-                    collaterals -= usd_amount
+                if is_collateral {
+                    let supplies = HashMap::from([(token_address, amount.0)]);
+                    collaterals +=
+                        self.calculate_weighted_lth_by_supplies(&supplies, e_mode_category);
+                    max_borrows +=
+                        self.calculate_weighted_ltv_by_supplies(&supplies, e_mode_category);
                 }
             }
             ActionType::Borrow => {
-                borrows += usd_amount;
+                borrows += self
+                    .calculate_assets_weighted_price(&HashMap::from([(token_address, amount.0)]));
             }
         }
 
         if borrows != 0 {
             Ratio::from(collaterals) / Ratio::from(borrows)
         } else {
-            Ratio::from(collaterals) / Ratio::from(max_borrows.0)
+            Ratio::from(collaterals) / Ratio::from(max_borrows)
         }
     }
 }
@@ -282,7 +452,7 @@ mod tests {
         ];
 
         controller_contract.oracle_on_data(PriceJsonList {
-            block_height: 83452949,
+            block_height: 0,
             price_list: prices,
         });
 
@@ -339,7 +509,7 @@ mod tests {
         ];
 
         controller_contract.oracle_on_data(PriceJsonList {
-            block_height: 83452949,
+            block_height: 0,
             price_list: prices,
         });
 
@@ -399,8 +569,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("weth_market.near".to_string()),
             WBalance::from(0),
-            0,
-            Ratio::zero(),
         );
 
         assert_eq!(
@@ -425,8 +593,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("weth_market.near".to_string()),
             WBalance::from(0),
-            0,
-            Ratio::zero(),
         );
 
         assert_eq!(
@@ -451,8 +617,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("weth_market.near".to_string()),
             WBalance::from(0),
-            0,
-            Ratio::zero(),
         );
 
         // Ratio that represents standart_hf_with_supply_and_no_borrow
@@ -477,8 +641,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("weth_market.near".to_string()),
             WBalance::from(70),
-            0,
-            Ratio::zero(),
         );
 
         // Ratio that represents (100 * 1 * LTH(0.8) / 70)  = 1.142857142857142857142857%
@@ -503,8 +665,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("wnear_market.near".to_string()),
             WBalance::from(100),
-            0,
-            Ratio::zero(),
         );
 
         // Ratio that represents (100 * 1 * LTH(0.8) / 100) 80%
@@ -541,8 +701,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("wnear_market.near".to_string()),
             WBalance::from(100),
-            0,
-            Ratio::zero(),
         );
 
         // Ratio that represents 160% = (200 * LTH(80%) / 100)
@@ -551,8 +709,11 @@ mod tests {
             Ratio::from_str("1.6").unwrap()
         );
 
+        // allow prices to move twice at once
+        controller_contract.set_max_price_deviation(Ratio::one());
+
         controller_contract.oracle_on_data(PriceJsonList {
-            block_height: 83452949,
+            block_height: 0,
             price_list: vec![
                 Price {
                     ticker_id: "wnear".to_string(),
@@ -591,8 +752,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("wnear_market.near".to_string()),
             WBalance::from(100),
-            0,
-            Ratio::zero(),
         );
 
         // Ratio that represents (200 * 1 * LTH(0.8) / 100) = 160%
@@ -602,7 +761,7 @@ mod tests {
         );
 
         controller_contract.oracle_on_data(PriceJsonList {
-            block_height: 83452949,
+            block_height: 0,
             price_list: vec![
                 Price {
                     ticker_id: "wnear".to_string(),
@@ -641,8 +800,6 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("weth_market.near".to_string()),
             WBalance::from(50),
-            0,
-            Ratio::zero(),
         );
 
         let result = controller_contract.get_potential_health_factor(
@@ -670,16 +827,12 @@ mod tests {
             user_account.clone(),
             AccountId::new_unchecked("weth_market.near".to_string()),
             WBalance::from(50),
-            0,
-            Ratio::zero(),
         );
 
         controller_contract.increase_borrows(
             user_account.clone(),
             AccountId::new_unchecked("wnear_market.near".to_string()),
             WBalance::from(100),
-            0,
-            Ratio::zero(),
         );
 
         // Ratio that represents (200 * 1.1 * LTH(0.8) / 50 * 1.1 * 0.9 + 100 * 1 * 0.8)
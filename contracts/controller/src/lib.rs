@@ -1,6 +1,8 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap};
-use near_sdk::{env, ext_contract, near_bindgen, require, AccountId, Balance, BorshStorageKey};
+use near_sdk::{
+    env, ext_contract, near_bindgen, require, AccountId, Balance, BlockHeight, BorshStorageKey,
+};
 
 #[allow(unused_imports)]
 use near_sdk::json_types::U128;
@@ -8,29 +10,55 @@ use near_sdk::serde::{Deserialize, Serialize};
 use percentage::Percentage;
 
 use general::ratio::{BigBalance, Ratio};
+use general::roles::{Role, Roles};
+use general::storage::StorageDeposits;
+use general::timelock::Timelock;
 use general::*;
+use std::collections::HashMap;
 use std::str::FromStr;
 
+pub use crate::bad_debt::*;
 pub use crate::borrows_supplies::*;
+pub use crate::collateral::*;
 pub use crate::config::*;
+pub use crate::credit_delegation::*;
+pub use crate::e_mode::*;
+pub use crate::events::*;
 pub use crate::healthfactor::*;
 pub use crate::liquidation::*;
+pub use crate::migration::*;
 pub use crate::oraclehook::*;
+pub use crate::pause::*;
 pub use crate::prices::*;
 pub use crate::repay::*;
+pub use crate::rewards::*;
+pub use crate::simulation::*;
+pub use crate::storage::*;
+pub use crate::timelock::*;
 pub use crate::user_flow_protection::*;
 pub use crate::user_profile::*;
 pub use crate::views::*;
 
 mod admin;
+mod bad_debt;
 pub mod borrows_supplies;
+mod collateral;
 #[allow(unused_imports)]
 mod config;
+mod credit_delegation;
+mod e_mode;
+mod events;
 mod healthfactor;
 mod liquidation;
+mod migration;
 mod oraclehook;
+mod pause;
 mod prices;
 pub mod repay;
+mod rewards;
+mod simulation;
+mod storage;
+mod timelock;
 mod upgrade;
 pub mod user_flow_protection;
 pub mod user_profile;
@@ -40,10 +68,29 @@ pub fn get_default_liquidation_incentive() -> Ratio {
     Ratio::from_str("0.05").unwrap()
 }
 
+pub fn get_default_max_liquidation_incentive() -> Ratio {
+    Ratio::from_str("0.15").unwrap()
+}
+
+pub fn get_default_close_factor() -> Ratio {
+    Ratio::from_str("0.5").unwrap()
+}
+
 pub fn get_default_liquidation_health_factor_threshold() -> Ratio {
     Ratio::from_str("1.0").unwrap()
 }
 
+pub fn get_default_max_price_deviation() -> Ratio {
+    Ratio::from_str("0.2").unwrap()
+}
+
+/// Default amount of blocks after which price reported by oracle is considered stale
+pub const DEFAULT_MAX_PRICE_AGE: BlockHeight = 600;
+
+/// Amount of consecutive oracle rounds after which the price outside of the allowed deviation
+/// is accepted as a real market move
+pub const MAX_PRICE_DEVIATION_ROUNDS: u32 = 3;
+
 #[derive(BorshSerialize, BorshStorageKey)]
 pub enum StorageKeys {
     Markets,
@@ -51,7 +98,20 @@ pub enum StorageKeys {
     Prices,
     Config,
     Borrows,
+    /// Profiles of the V1 layout, the migrated ones are kept under `AccountProfiles`
     UserProfiles,
+    OracleReports,
+    PricesUpdatedAt,
+    MarketBorrows,
+    IsolatedDebt,
+    EModeCategories,
+    MarketBorrowIndexes,
+    RiskProposals,
+    PausedActions,
+    StateVersion,
+    StorageDeposits,
+    PriceDeviationRounds,
+    AccountProfiles,
 }
 
 #[near_bindgen]
@@ -70,27 +130,60 @@ pub struct Contract {
     /// Dtoken ID -> Price
     pub prices: LookupMap<AccountId, Price>,
 
+    /// Dtoken ID -> Total borrows of the market
+    market_borrows: LookupMap<AccountId, Balance>,
+
+    /// Dtoken ID -> Last borrow index reported by the market
+    market_borrow_indexes: LookupMap<AccountId, Ratio>,
+
+    /// Isolated market dtoken ID -> Borrowed dtoken ID -> Total borrows of accounts using
+    /// the isolated market as collateral
+    isolated_debt: LookupMap<AccountId, HashMap<AccountId, Balance>>,
+
+    /// E-mode category ID -> Category parameters
+    e_mode_categories: UnorderedMap<u8, EModeCategory>,
+
+    /// Dtoken ID -> Block height of the data the accepted price is based on
+    prices_updated_at: LookupMap<AccountId, BlockHeight>,
+
+    /// Dtoken ID -> Oracle account ID -> Last reported price
+    oracle_reports: LookupMap<AccountId, HashMap<AccountId, PriceReport>>,
+
+    /// Max allowed move of the price relatively to the last accepted one
+    pub max_price_deviation: Ratio,
+
+    /// Dtoken ID -> Price move which has been rejected by the deviation in consecutive rounds
+    price_deviation_rounds: LookupMap<AccountId, PriceDeviation>,
+
     /// Contract configuration object
     pub config: LazyOption<Config>,
 
-    /// Contract admin account (controller itself by default)
-    pub admin: AccountId,
-
-    /// Contracts that are allowed to do uncollateralized borrow from market
-    /// contract itself by default, can be set to different account
-    eligible_to_borrow_uncollateralized: AccountId,
+    /// Accounts granted with the roles, oracle accounts are allowed to push price feeds
+    roles: Roles,
 
-    /// Configuration for pausing/proceeding controller processes (false by default)
-    pub is_action_paused: ActionStatus,
+    /// Dtoken ID -> Paused action -> Block after which the action is resumed automatically
+    paused_actions: UnorderedMap<AccountId, HashMap<MethodType, Option<BlockHeight>>>,
 
     /// Liquidation Incentive
     pub liquidation_incentive: Ratio,
 
+    /// Upper bound of the liquidation incentive growing with the borrower's health factor drop
+    pub max_liquidation_incentive: Ratio,
+
     /// Liquidation Health Factor
     pub liquidation_health_factor_threshold: Ratio,
 
     ///User action protection
     mutex: ActionMutex,
+
+    /// Queue of the risk parameter changes
+    timelock: Timelock<RiskParameterChange>,
+
+    /// NEAR deposited by the accounts to cover their user profiles storage
+    storage_deposits: StorageDeposits,
+
+    /// V1 user profiles which are not migrated yet, market actions are paused until they are
+    profiles_migration: Option<ProfilesMigration>,
 }
 
 impl Default for Contract {
@@ -123,17 +216,28 @@ pub struct MarketProfile {
 
     /// Liquidation threshold for the market
     pub lth: Ratio,
-}
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-pub struct ActionStatus {
-    supply: bool,
-    withdraw: bool,
-    borrow: bool,
-    repay: bool,
-    liquidate: bool,
-    deposit: bool,
+    /// Amount of blocks after which market price is considered stale
+    pub max_price_age: BlockHeight,
+
+    /// Max part of the borrow which could be repaid by single liquidation
+    pub close_factor: Ratio,
+
+    /// Max total borrows of the market, unlimited if not set
+    pub borrow_cap: Option<WBalance>,
+
+    /// Whether supplies of the market could be used as collateral
+    pub is_collateral: bool,
+
+    /// Max total debt in USD of accounts using the market as isolated collateral, valued by the
+    /// current prices as `price * amount / 10^fraction_digits`, market isn't isolated if not set
+    pub debt_ceiling: Option<WBalance>,
+
+    /// Whether the market could be borrowed against isolated collateral
+    pub is_borrowable_in_isolation: bool,
+
+    /// E-mode category of the market
+    pub e_mode_category: Option<u8>,
 }
 
 pub trait OraclePriceHandlerHook {
@@ -147,9 +251,49 @@ trait MarketInterface {
         borrower: AccountId,
         liquidator: AccountId,
         liquidation_revenue_amount: WBalance,
+        health_factor: Option<WRatio>,
     ) -> PromiseOrValue<U128>;
 
     fn increase_borrows(&mut self, account: AccountId, token_amount: WBalance) -> Balance;
+
+    fn liquidate_batch_repay(
+        &mut self,
+        borrower: AccountId,
+        liquidator: AccountId,
+        liquidation_amount: WBalance,
+    ) -> WRatio;
+
+    fn revert_liquidate_batch_repay(
+        &mut self,
+        borrower: AccountId,
+        liquidator: AccountId,
+        liquidation_amount: WBalance,
+    );
+
+    fn write_off_bad_debt(&mut self, account_id: AccountId, amount: WBalance) -> WBalance;
+
+    fn claim_all_rewards(&mut self) -> HashMap<AccountId, WBalance>;
+}
+
+#[ext_contract(ext_self)]
+trait ControllerCallbacksInterface {
+    fn liquidate_batch_callback(
+        &mut self,
+        borrower: AccountId,
+        liquidator: AccountId,
+        repay_legs: Vec<LiquidationLeg>,
+        seize_legs: Vec<LiquidationLeg>,
+    ) -> PromiseOrValue<U128>;
+
+    fn liquidate_batch_compensation_callback(&mut self, borrower: AccountId, liquidator: AccountId);
+
+    fn write_off_bad_debt_callback(
+        &mut self,
+        account_id: AccountId,
+        borrows: Vec<(AccountId, WBalance)>,
+    ) -> PromiseOrValue<U128>;
+
+    fn claim_all_rewards_callback(&mut self, markets: Vec<AccountId>) -> Vec<MarketRewardClaim>;
 }
 
 #[near_bindgen]
@@ -167,25 +311,36 @@ impl Contract {
     #[init]
     pub fn new(config: Config) -> Self {
         require!(!env::state_exists(), "Already initialized");
+        CURRENT_STATE_VERSION.write();
+
+        let mut roles = Roles::new(config.owner_id.clone());
+        roles.grant(Role::Oracle, config.oracle_account_id.clone());
 
         Self {
             markets: UnorderedMap::new(StorageKeys::Markets),
-            user_profiles: UnorderedMap::new(StorageKeys::UserProfiles),
+            user_profiles: UnorderedMap::new(StorageKeys::AccountProfiles),
             prices: LookupMap::new(StorageKeys::Prices),
+            market_borrows: LookupMap::new(StorageKeys::MarketBorrows),
+            market_borrow_indexes: LookupMap::new(StorageKeys::MarketBorrowIndexes),
+            isolated_debt: LookupMap::new(StorageKeys::IsolatedDebt),
+            e_mode_categories: UnorderedMap::new(StorageKeys::EModeCategories),
+            prices_updated_at: LookupMap::new(StorageKeys::PricesUpdatedAt),
+            oracle_reports: LookupMap::new(StorageKeys::OracleReports),
+            max_price_deviation: get_default_max_price_deviation(),
+            price_deviation_rounds: LookupMap::new(StorageKeys::PriceDeviationRounds),
             config: LazyOption::new(StorageKeys::Config, Some(&config)),
-            admin: config.owner_id.clone(),
-            eligible_to_borrow_uncollateralized: config.owner_id.clone(),
-            is_action_paused: ActionStatus {
-                withdraw: false,
-                repay: false,
-                supply: false,
-                liquidate: false,
-                borrow: false,
-                deposit: false,
-            },
+            roles,
+            paused_actions: UnorderedMap::new(StorageKeys::PausedActions),
             liquidation_incentive: get_default_liquidation_incentive(),
+            max_liquidation_incentive: get_default_max_liquidation_incentive(),
             liquidation_health_factor_threshold: get_default_liquidation_health_factor_threshold(),
             mutex: ActionMutex::default(),
+            timelock: Timelock::new(StorageKeys::RiskProposals, config.owner_id.clone(), 0),
+            storage_deposits: StorageDeposits::new(
+                StorageKeys::StorageDeposits,
+                MIN_ACCOUNT_STORAGE_USAGE,
+            ),
+            profiles_migration: None,
         }
     }
 }
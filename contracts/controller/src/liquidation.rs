@@ -1,5 +1,30 @@
+use crate::borrows_supplies::ActionType::{Borrow, Supply};
 use crate::*;
-use near_sdk::{env::block_height, PromiseOrValue};
+use near_sdk::{env::block_height, serde_json, PromiseOrValue, PromiseResult};
+
+/// Max amount of repay and seize legs within one batch liquidation
+const MAX_LIQUIDATION_LEGS: usize = 8;
+
+/// Gas of the batch liquidation itself
+const LIQUIDATE_BATCH_GAS: u64 = 10;
+
+/// Gas of the repay leg on the market and of its revert
+const LIQUIDATE_BATCH_REPAY_GAS: u64 = 10;
+
+/// Gas of the seize leg on the market and of its revert
+const LIQUIDATE_BATCH_SEIZE_GAS: u64 = 8;
+
+/// Base gas of the batch liquidation callback, the callback takes `LIQUIDATE_BATCH_CALLBACK_LEG_GAS`
+/// more for each of the legs to revert them in case of failure
+const LIQUIDATE_BATCH_CALLBACK_GAS: u64 = 20;
+const LIQUIDATE_BATCH_CALLBACK_LEG_GAS: u64 = 12;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LiquidationLeg {
+    pub dtoken: AccountId,
+    pub amount: WBalance,
+}
 
 #[near_bindgen]
 impl Contract {
@@ -15,29 +40,24 @@ impl Contract {
             self.is_dtoken_caller(),
             "This functionality is allowed to be called by admin, contract or dtoken's contract only"
         );
+        self.assert_action_not_paused(&borrowing_dtoken, MethodType::Liquidate);
+        self.assert_action_not_paused(&collateral_dtoken, MethodType::Liquidate);
 
-        let half_of_borrower_total_supplies =
-            Percentage::from(50).apply_to(self.get_total_supplies(&borrower).0);
+        let borrowing_market = self.get_market_by_dtoken(borrowing_dtoken.clone());
+        let borrower_borrows =
+            self.get_entity_by_token(Borrow, borrower.clone(), borrowing_dtoken.clone());
 
         require!(
-            liquidation_amount.0 <= half_of_borrower_total_supplies,
-            "Liquidation amount must be less than half of the borrower`s total supplies"
-        );
-
-        let total_borrows_by_dtoken = self
-            .get_total_borrows_by_dtoken(&borrower, &borrowing_dtoken)
-            .0;
-
-        require!(
-            liquidation_amount.0 <= total_borrows_by_dtoken,
-            "Liquidation amount must be less than half of the borrower`s total borrows of dtoken"
+            Ratio::from(liquidation_amount.0)
+                <= Ratio::from(borrower_borrows) * borrowing_market.close_factor,
+            "Liquidation amount exceeds close factor of the borrower`s borrows of dtoken"
         );
 
         let res = self.calculate_liquidation_revenue(
-            borrower,
-            borrowing_dtoken,
+            borrower.clone(),
+            borrowing_dtoken.clone(),
             liquidator,
-            collateral_dtoken,
+            collateral_dtoken.clone(),
             liquidation_amount,
         );
 
@@ -46,6 +66,21 @@ impl Contract {
         }
 
         let liquidation_revenue_amount = res.unwrap();
+
+        require!(
+            liquidation_revenue_amount.0
+                <= self.get_entity_by_token(Supply, borrower.clone(), collateral_dtoken.clone()),
+            "Borrower doesnt have enough collateral for liquidation"
+        );
+        require!(
+            self.is_health_factor_not_decreased_by_liquidation(
+                borrower,
+                &HashMap::from([(borrowing_dtoken, liquidation_amount.0)]),
+                &HashMap::from([(collateral_dtoken, liquidation_revenue_amount.0)]),
+            ),
+            "Liquidation should not decrease the borrower`s health factor"
+        );
+
         PromiseOrValue::Value(liquidation_revenue_amount)
     }
 
@@ -57,7 +92,7 @@ impl Contract {
         liquidator: AccountId,
         liquidation_amount: WBalance,
         liquidation_revenue_amount: WBalance,
-        borrow_rate: WRatio,
+        borrow_index: WRatio,
     ) -> PromiseOrValue<U128> {
         require!(
             self.is_dtoken_caller(),
@@ -67,8 +102,7 @@ impl Contract {
             borrower.clone(),
             borrowing_dtoken,
             liquidation_amount,
-            block_height(),
-            borrow_rate,
+            borrow_index,
         );
         self.decrease_supplies(
             borrower.clone(),
@@ -80,31 +114,358 @@ impl Contract {
             collateral_dtoken.clone(),
             liquidation_revenue_amount,
         );
+        let health_factor = self.try_get_health_factor(&borrower).map(WRatio::from);
 
         market::swap_supplies(
             borrower,
             liquidator,
             liquidation_revenue_amount,
+            health_factor,
             collateral_dtoken,
             NO_DEPOSIT,
             near_sdk::Gas::ONE_TERA * 8_u64,
         )
         .into()
     }
+
+    /// Repays borrows of several markets from the liquidator's deposits in these markets
+    /// and seizes borrower's supplies of several markets at once
+    pub fn liquidate_batch(
+        &mut self,
+        borrower: AccountId,
+        repay_legs: Vec<LiquidationLeg>,
+        seize_legs: Vec<LiquidationLeg>,
+    ) -> PromiseOrValue<U128> {
+        let liquidator = env::predecessor_account_id();
+        self.validate_batch_liquidation(&borrower, &liquidator, &repay_legs, &seize_legs);
+
+        let legs_amount = (repay_legs.len() + seize_legs.len()) as u64;
+        require!(
+            env::prepaid_gas()
+                >= near_sdk::Gas::ONE_TERA
+                    * (LIQUIDATE_BATCH_GAS
+                        + repay_legs.len() as u64 * LIQUIDATE_BATCH_REPAY_GAS
+                        + seize_legs.len() as u64 * LIQUIDATE_BATCH_SEIZE_GAS
+                        + LIQUIDATE_BATCH_CALLBACK_GAS
+                        + legs_amount * LIQUIDATE_BATCH_CALLBACK_LEG_GAS),
+            "Prepaid gas is not enough for batch liquidation flow"
+        );
+
+        require!(
+            self.mutex.try_lock(&borrower),
+            format!(
+                "failed to acquire batch liquidation mutex for account {}",
+                borrower
+            )
+        );
+
+        repay_legs
+            .iter()
+            .map(|leg| {
+                market::liquidate_batch_repay(
+                    borrower.clone(),
+                    liquidator.clone(),
+                    leg.amount,
+                    leg.dtoken.clone(),
+                    NO_DEPOSIT,
+                    near_sdk::Gas::ONE_TERA * LIQUIDATE_BATCH_REPAY_GAS,
+                )
+            })
+            .chain(seize_legs.iter().map(|leg| {
+                // health factor after the batch is emitted with the batch liquidation event
+                market::swap_supplies(
+                    borrower.clone(),
+                    liquidator.clone(),
+                    leg.amount,
+                    None,
+                    leg.dtoken.clone(),
+                    NO_DEPOSIT,
+                    near_sdk::Gas::ONE_TERA * LIQUIDATE_BATCH_SEIZE_GAS,
+                )
+            }))
+            .reduce(|legs, leg| legs.and(leg))
+            .unwrap()
+            .then(ext_self::liquidate_batch_callback(
+                borrower,
+                liquidator,
+                repay_legs,
+                seize_legs,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                near_sdk::Gas::ONE_TERA
+                    * (LIQUIDATE_BATCH_CALLBACK_GAS
+                        + legs_amount * LIQUIDATE_BATCH_CALLBACK_LEG_GAS),
+            ))
+            .into()
+    }
+
+    #[private]
+    pub fn liquidate_batch_callback(
+        &mut self,
+        borrower: AccountId,
+        liquidator: AccountId,
+        repay_legs: Vec<LiquidationLeg>,
+        seize_legs: Vec<LiquidationLeg>,
+    ) -> PromiseOrValue<U128> {
+        let results = (0..env::promise_results_count())
+            .map(|index| match env::promise_result(index) {
+                PromiseResult::Successful(result) => Some(result),
+                _ => None,
+            })
+            .collect::<Vec<Option<Vec<u8>>>>();
+        let (repay_results, seize_results) = results.split_at(repay_legs.len());
+
+        // repay leg with the malformed borrow index is treated as failed, so the batch is reverted
+        let borrow_indexes = repay_results
+            .iter()
+            .map(|result| {
+                result
+                    .as_ref()
+                    .and_then(|result| serde_json::from_slice::<WRatio>(result).ok())
+            })
+            .collect::<Option<Vec<WRatio>>>()
+            .filter(|_| seize_results.iter().all(Option::is_some));
+
+        if let Some(borrow_indexes) = borrow_indexes {
+            for (leg, borrow_index) in repay_legs.iter().zip(borrow_indexes) {
+                self.set_market_borrow_index(&leg.dtoken, Ratio::from(borrow_index));
+                self.decrease_borrows(borrower.clone(), leg.dtoken.clone(), leg.amount);
+            }
+            for leg in seize_legs.iter() {
+                self.decrease_supplies(borrower.clone(), leg.dtoken.clone(), leg.amount);
+                self.increase_supplies(liquidator.clone(), leg.dtoken.clone(), leg.amount);
+            }
+            self.mutex.unlock(&borrower);
+
+            Events::BatchLiquidationSuccess {
+                health_factor: self.try_get_health_factor(&borrower).map(WRatio::from),
+                borrower_account_id: borrower,
+                liquidator_account_id: liquidator,
+            }
+            .emit();
+            return PromiseOrValue::Value(U128(0));
+        }
+
+        Events::BatchLiquidationFailed {
+            borrower_account_id: borrower.clone(),
+            liquidator_account_id: liquidator.clone(),
+        }
+        .emit();
+
+        let compensation = repay_legs
+            .iter()
+            .zip(repay_results)
+            .filter(|(_, result)| result.is_some())
+            .map(|(leg, _)| {
+                market::revert_liquidate_batch_repay(
+                    borrower.clone(),
+                    liquidator.clone(),
+                    leg.amount,
+                    leg.dtoken.clone(),
+                    NO_DEPOSIT,
+                    near_sdk::Gas::ONE_TERA * LIQUIDATE_BATCH_REPAY_GAS,
+                )
+            })
+            .chain(
+                seize_legs
+                    .iter()
+                    .zip(seize_results)
+                    .filter(|(_, result)| result.is_some())
+                    // swap seized supplies back to the borrower
+                    .map(|(leg, _)| {
+                        market::swap_supplies(
+                            liquidator.clone(),
+                            borrower.clone(),
+                            leg.amount,
+                            None,
+                            leg.dtoken.clone(),
+                            NO_DEPOSIT,
+                            near_sdk::Gas::ONE_TERA * LIQUIDATE_BATCH_SEIZE_GAS,
+                        )
+                    }),
+            )
+            .reduce(|legs, leg| legs.and(leg));
+
+        match compensation {
+            Some(compensation) => compensation
+                .then(ext_self::liquidate_batch_compensation_callback(
+                    borrower,
+                    liquidator,
+                    env::current_account_id(),
+                    NO_DEPOSIT,
+                    near_sdk::Gas::ONE_TERA * 5_u64,
+                ))
+                .into(),
+            None => {
+                self.mutex.unlock(&borrower);
+                PromiseOrValue::Value(U128(0))
+            }
+        }
+    }
+
+    #[private]
+    pub fn liquidate_batch_compensation_callback(
+        &mut self,
+        borrower: AccountId,
+        liquidator: AccountId,
+    ) {
+        let is_compensated = (0..env::promise_results_count())
+            .all(|index| matches!(env::promise_result(index), PromiseResult::Successful(_)));
+
+        if !is_compensated {
+            Events::BatchLiquidationRevertFailed {
+                borrower_account_id: borrower.clone(),
+                liquidator_account_id: liquidator.clone(),
+            }
+            .emit();
+            for account in [borrower.clone(), liquidator] {
+                let mut user_profile = self.user_profiles.get(&account).unwrap_or_default();
+                user_profile.set_consistency(false, block_height());
+                self.user_profiles.insert(&account, &user_profile);
+            }
+        }
+
+        self.mutex.unlock(&borrower);
+    }
 }
 
 impl Contract {
+    fn get_liquidation_legs_map(&self, legs: &[LiquidationLeg]) -> HashMap<AccountId, Balance> {
+        let mut legs_map: HashMap<AccountId, Balance> = HashMap::new();
+        for leg in legs {
+            require!(
+                self.markets
+                    .values()
+                    .any(|market| market.dtoken == leg.dtoken),
+                format!("Market {} is not registered", leg.dtoken)
+            );
+            require!(leg.amount.0 > 0, "Liquidation amount cannot be zero");
+            require!(
+                legs_map.insert(leg.dtoken.clone(), leg.amount.0).is_none(),
+                format!("Market {} is used in several legs", leg.dtoken)
+            );
+        }
+        legs_map
+    }
+
+    fn get_liquidation_legs_value(&self, legs: &HashMap<AccountId, Balance>) -> Ratio {
+        legs.iter()
+            .map(|(dtoken, amount)| {
+                let price = self.get_fresh_price(dtoken);
+                Ratio::from(*amount) * Ratio::from(price.value.0)
+                    / Ratio::from(10u128.pow(price.fraction_digits))
+            })
+            .fold(Ratio::zero(), |sum, value| sum + value)
+    }
+
+    pub fn validate_batch_liquidation(
+        &self,
+        borrower: &AccountId,
+        liquidator: &AccountId,
+        repay_legs: &[LiquidationLeg],
+        seize_legs: &[LiquidationLeg],
+    ) {
+        for leg in repay_legs.iter().chain(seize_legs) {
+            self.assert_action_not_paused(&leg.dtoken, MethodType::Liquidate);
+        }
+        require!(
+            liquidator != borrower,
+            "Liquidator cannot liquidate themselves"
+        );
+        require!(
+            !repay_legs.is_empty() && !seize_legs.is_empty(),
+            "Batch liquidation should contain both repay and seize legs"
+        );
+        require!(
+            repay_legs.len() + seize_legs.len() <= MAX_LIQUIDATION_LEGS,
+            format!(
+                "Batch liquidation cannot contain more than {} legs",
+                MAX_LIQUIDATION_LEGS
+            )
+        );
+
+        let health_factor = self.get_health_factor(borrower.clone());
+        require!(
+            health_factor <= self.get_liquidation_threshold(),
+            "Health factor is above liquidation threshold"
+        );
+
+        let repaid_borrows = self.get_liquidation_legs_map(repay_legs);
+        let seized_supplies = self.get_liquidation_legs_map(seize_legs);
+
+        for (dtoken, amount) in repaid_borrows.iter() {
+            let market = self.get_market_by_dtoken(dtoken.clone());
+            let borrows = self.get_entity_by_token(Borrow, borrower.clone(), dtoken.clone());
+            require!(
+                Ratio::from(*amount) <= Ratio::from(borrows) * market.close_factor,
+                format!(
+                    "Liquidation amount exceeds close factor of the borrower`s borrows of {}",
+                    dtoken
+                )
+            );
+        }
+
+        for (dtoken, amount) in seized_supplies.iter() {
+            require!(
+                *amount <= self.get_entity_by_token(Supply, borrower.clone(), dtoken.clone()),
+                format!("Borrower doesnt have enough collateral in {}", dtoken)
+            );
+        }
+
+        require!(
+            self.get_liquidation_legs_value(&seized_supplies)
+                <= self.get_liquidation_legs_value(&repaid_borrows)
+                    * (Ratio::one() + self.get_liquidation_bonus(&borrower, health_factor)),
+            "Seized value exceeds repaid value with liquidation bonus"
+        );
+        require!(
+            self.is_health_factor_not_decreased_by_liquidation(
+                borrower.clone(),
+                &repaid_borrows,
+                &seized_supplies,
+            ),
+            "Liquidation should not decrease the borrower`s health factor"
+        );
+    }
+
+    /// Liquidation incentive grows by half of the borrower's health factor drop
+    /// below the liquidation threshold and is capped by `max_liquidation_incentive`.
+    /// Borrowers opted into e-mode use the incentive of their category as the base one
+    pub fn get_liquidation_bonus(&self, borrower: &AccountId, health_factor: Ratio) -> Ratio {
+        let liquidation_incentive = self
+            .user_profiles
+            .get(borrower)
+            .unwrap_or_default()
+            .e_mode_category
+            .and_then(|category_id| self.e_mode_categories.get(&category_id))
+            .map_or(self.liquidation_incentive, |category| {
+                category.liquidation_bonus
+            });
+        let threshold = self.get_liquidation_threshold();
+        let health_factor_drop = if health_factor < threshold {
+            threshold - health_factor
+        } else {
+            Ratio::zero()
+        };
+
+        std::cmp::min(
+            liquidation_incentive + health_factor_drop / Ratio::from(2u128),
+            self.max_liquidation_incentive,
+        )
+    }
+
     pub fn get_liquidation_revenue(
         &self,
         borrowing_dtoken: AccountId,
         collateral_dtoken: AccountId,
         liquidation_amount: WBalance,
+        liquidation_bonus: Ratio,
     ) -> WBalance {
         WBalance::from(
-            (self.get_liquidation_incentive()
+            ((Ratio::one() + liquidation_bonus)
                 * Ratio::from(liquidation_amount.0)
-                * Ratio::from(self.prices.get(&borrowing_dtoken).unwrap().value.0)
-                / (Ratio::from(self.prices.get(&collateral_dtoken).unwrap().value.0)))
+                * Ratio::from(self.get_fresh_price(&borrowing_dtoken).value.0)
+                / (Ratio::from(self.get_fresh_price(&collateral_dtoken).value.0)))
             .round_u128(),
         )
     }
@@ -121,12 +482,466 @@ impl Contract {
             return Err(String::from("cannot liquidate themselves"));
         }
 
-        if self.get_health_factor(borrower) > self.get_liquidation_threshold() {
+        let health_factor = self.get_health_factor(borrower.clone());
+        if health_factor > self.get_liquidation_threshold() {
             return Err(String::from("health factor is above liquidation threshold"));
         }
 
-        let revenue_amount =
-            self.get_liquidation_revenue(borrowing_dtoken, collateral_dtoken, liquidation_amount);
+        let revenue_amount = self.get_liquidation_revenue(
+            borrowing_dtoken,
+            collateral_dtoken,
+            liquidation_amount,
+            self.get_liquidation_bonus(&borrower, health_factor),
+        );
         Ok(revenue_amount)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+    use super::*;
+
+    fn init(borrower_supplies: Balance, borrower_borrows: Balance) -> (Contract, AccountId) {
+        let borrower = alice();
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: bob(),
+            oracle_account_id: bob(),
+        });
+
+        controller_contract.add_market(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            "wnear".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        controller_contract.add_market(
+            AccountId::new_unchecked("weth.near".to_string()),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            "weth".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 0,
+            price_list: vec![
+                Price {
+                    ticker_id: "wnear".to_string(),
+                    value: U128(10000),
+                    volatility: U128(100),
+                    fraction_digits: 4,
+                },
+                Price {
+                    ticker_id: "weth".to_string(),
+                    value: U128(10000),
+                    volatility: U128(100),
+                    fraction_digits: 4,
+                },
+            ],
+        });
+
+        controller_contract.increase_supplies(
+            borrower.clone(),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            WBalance::from(borrower_supplies),
+        );
+
+        controller_contract.increase_borrows(
+            borrower.clone(),
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            WBalance::from(borrower_borrows),
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(AccountId::new_unchecked("wnear_market.near".to_string()))
+            .build());
+
+        (controller_contract, borrower)
+    }
+
+    fn init_batch() -> (Contract, AccountId) {
+        // HF = (600 + 400) * 0.8 / (500 + 350) = 0.941176470588235294117647
+        let (mut controller_contract, borrower) = init(600, 500);
+
+        controller_contract.increase_supplies(
+            borrower.clone(),
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            WBalance::from(400),
+        );
+        controller_contract.increase_borrows(
+            borrower.clone(),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            WBalance::from(350),
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(carol())
+            .build());
+
+        (controller_contract, borrower)
+    }
+
+    fn leg(dtoken: &str, amount: Balance) -> LiquidationLeg {
+        LiquidationLeg {
+            dtoken: AccountId::new_unchecked(dtoken.to_string()),
+            amount: WBalance::from(amount),
+        }
+    }
+
+    fn liquidate(
+        controller_contract: &mut Contract,
+        borrower: AccountId,
+        liquidation_amount: Balance,
+    ) -> Balance {
+        match controller_contract.liquidation(
+            borrower,
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            carol(),
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            WBalance::from(liquidation_amount),
+        ) {
+            PromiseOrValue::Value(revenue) => revenue.0,
+            PromiseOrValue::Promise(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_liquidation_bonus_scales_with_health_factor() {
+        let (controller_contract, borrower) = init(0, 0);
+
+        // HF is above the threshold, base incentive is used
+        assert_eq!(
+            controller_contract.get_liquidation_bonus(&borrower, Ratio::from_str("1.2").unwrap()),
+            Ratio::from_str("0.05").unwrap()
+        );
+
+        // 0.05 + (1 - 0.9) / 2
+        assert_eq!(
+            controller_contract.get_liquidation_bonus(&borrower, Ratio::from_str("0.9").unwrap()),
+            Ratio::from_str("0.1").unwrap()
+        );
+
+        // 0.05 + (1 - 0.5) / 2 is capped by max incentive
+        assert_eq!(
+            controller_contract.get_liquidation_bonus(&borrower, Ratio::from_str("0.5").unwrap()),
+            Ratio::from_str("0.15").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_liquidation_bonus_respects_max_incentive() {
+        let (mut controller_contract, borrower) = init(0, 0);
+
+        testing_env!(VMContextBuilder::new().signer_account_id(bob()).build());
+        controller_contract.set_max_liquidation_incentive(Ratio::from_str("0.08").unwrap());
+
+        assert_eq!(
+            controller_contract.get_liquidation_bonus(&borrower, Ratio::from_str("0.9").unwrap()),
+            Ratio::from_str("0.08").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_liquidation_with_dynamic_bonus() {
+        // HF = 1000 * 0.8 / 850 = 0.941176470588235294117647
+        let (mut controller_contract, borrower) = init(1000, 850);
+
+        // 400 * (1 + 0.05 + (1 - 0.941176470588235294117647) / 2)
+        assert_eq!(liquidate(&mut controller_contract, borrower, 400), 432);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Liquidation amount exceeds close factor of the borrower`s borrows of dtoken"
+    )]
+    fn test_liquidation_above_close_factor() {
+        let (mut controller_contract, borrower) = init(1000, 850);
+
+        liquidate(&mut controller_contract, borrower, 426);
+    }
+
+    #[test]
+    fn test_liquidation_with_configured_close_factor() {
+        let (mut controller_contract, borrower) = init(1000, 850);
+
+        testing_env!(VMContextBuilder::new().signer_account_id(bob()).build());
+        controller_contract.set_market_close_factor(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            Ratio::one(),
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(AccountId::new_unchecked("wnear_market.near".to_string()))
+            .build());
+        assert_eq!(liquidate(&mut controller_contract, borrower, 850), 917);
+    }
+
+    #[test]
+    #[should_panic(expected = "Liquidation should not decrease the borrower`s health factor")]
+    fn test_liquidation_decreasing_health_factor() {
+        // HF = 1000 * 0.8 / 1000 = 0.8, bonus is 0.15 and seizing 115% of the repaid
+        // value with LTH 0.8 makes the position even worse
+        let (mut controller_contract, borrower) = init(1000, 1000);
+
+        liquidate(&mut controller_contract, borrower, 500);
+    }
+
+    #[test]
+    fn test_batch_liquidation_validation() {
+        let (controller_contract, borrower) = init_batch();
+
+        // repaid value is 350, seized value 370 is below 350 * (1 + 0.079411764705882352941177)
+        controller_contract.validate_batch_liquidation(
+            &borrower,
+            &carol(),
+            &[leg("wnear_market.near", 200), leg("weth_market.near", 150)],
+            &[leg("weth_market.near", 200), leg("wnear_market.near", 170)],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Seized value exceeds repaid value with liquidation bonus")]
+    fn test_batch_liquidation_seizing_too_much() {
+        let (controller_contract, borrower) = init_batch();
+
+        controller_contract.validate_batch_liquidation(
+            &borrower,
+            &carol(),
+            &[leg("wnear_market.near", 200), leg("weth_market.near", 150)],
+            &[leg("weth_market.near", 250), leg("wnear_market.near", 170)],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Market wnear_market.near is used in several legs")]
+    fn test_batch_liquidation_duplicated_legs() {
+        let (controller_contract, borrower) = init_batch();
+
+        controller_contract.validate_batch_liquidation(
+            &borrower,
+            &carol(),
+            &[leg("wnear_market.near", 100), leg("wnear_market.near", 100)],
+            &[leg("weth_market.near", 200)],
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Liquidation amount exceeds close factor of the borrower`s borrows of weth_market.near"
+    )]
+    fn test_batch_liquidation_above_close_factor() {
+        let (controller_contract, borrower) = init_batch();
+
+        controller_contract.validate_batch_liquidation(
+            &borrower,
+            &carol(),
+            &[leg("wnear_market.near", 200), leg("weth_market.near", 176)],
+            &[leg("weth_market.near", 200)],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to acquire batch liquidation mutex for account alice")]
+    fn test_batch_liquidation_locks_borrower() {
+        let (mut controller_contract, borrower) = init_batch();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(carol())
+            .block_index(100)
+            .build());
+
+        controller_contract.liquidate_batch(
+            borrower.clone(),
+            vec![leg("wnear_market.near", 200)],
+            vec![leg("weth_market.near", 200)],
+        );
+        controller_contract.liquidate_batch(
+            borrower,
+            vec![leg("wnear_market.near", 200)],
+            vec![leg("weth_market.near", 200)],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Prepaid gas is not enough for batch liquidation flow")]
+    fn test_batch_liquidation_not_enough_gas() {
+        let (mut controller_contract, borrower) = init_batch();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(carol())
+            .prepaid_gas(near_sdk::Gas::ONE_TERA * 100_u64)
+            .build());
+
+        controller_contract.liquidate_batch(
+            borrower,
+            vec![leg("wnear_market.near", 200), leg("weth_market.near", 150)],
+            vec![leg("weth_market.near", 200), leg("wnear_market.near", 170)],
+        );
+    }
+
+    fn batch_callback(
+        controller_contract: &mut Contract,
+        borrower: AccountId,
+        promise_results: Vec<PromiseResult>,
+    ) {
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(bob())
+                .predecessor_account_id(bob())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            promise_results
+        );
+
+        controller_contract.liquidate_batch_callback(
+            borrower,
+            carol(),
+            vec![leg("wnear_market.near", 200), leg("weth_market.near", 150)],
+            vec![leg("weth_market.near", 200), leg("wnear_market.near", 170)],
+        );
+    }
+
+    #[test]
+    fn test_batch_liquidation_callback_applies_all_legs() {
+        let (mut controller_contract, borrower) = init_batch();
+
+        batch_callback(
+            &mut controller_contract,
+            borrower.clone(),
+            (0..4)
+                .map(|_| PromiseResult::Successful(b"\"0\"".to_vec()))
+                .collect(),
+        );
+
+        let profile = controller_contract.get_user_profile(borrower);
+        assert_eq!(
+            profile.get(
+                Borrow,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            300
+        );
+        assert_eq!(
+            profile.get(
+                Borrow,
+                AccountId::new_unchecked("weth_market.near".to_string())
+            ),
+            200
+        );
+        assert_eq!(
+            profile.get(
+                Supply,
+                AccountId::new_unchecked("weth_market.near".to_string())
+            ),
+            400
+        );
+        assert_eq!(
+            profile.get(
+                Supply,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            230
+        );
+
+        let liquidator_profile = controller_contract.get_user_profile(carol());
+        assert_eq!(
+            liquidator_profile.get(
+                Supply,
+                AccountId::new_unchecked("weth_market.near".to_string())
+            ),
+            200
+        );
+        assert_eq!(
+            liquidator_profile.get(
+                Supply,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            170
+        );
+    }
+
+    #[test]
+    fn test_batch_liquidation_callback_with_failed_leg() {
+        let (mut controller_contract, borrower) = init_batch();
+
+        batch_callback(
+            &mut controller_contract,
+            borrower.clone(),
+            vec![
+                PromiseResult::Successful(b"\"0\"".to_vec()),
+                PromiseResult::Successful(b"\"0\"".to_vec()),
+                PromiseResult::Failed,
+                PromiseResult::Successful(b"\"0\"".to_vec()),
+            ],
+        );
+
+        let profile = controller_contract.get_user_profile(borrower);
+        assert_eq!(
+            profile.get(
+                Borrow,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            500
+        );
+        assert_eq!(
+            profile.get(
+                Supply,
+                AccountId::new_unchecked("weth_market.near".to_string())
+            ),
+            600
+        );
+        assert_eq!(
+            controller_contract.get_user_profile(carol()).get(
+                Supply,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_batch_liquidation_callback_with_malformed_leg() {
+        let (mut controller_contract, borrower) = init_batch();
+
+        batch_callback(
+            &mut controller_contract,
+            borrower.clone(),
+            vec![
+                PromiseResult::Successful(b"\"0\"".to_vec()),
+                PromiseResult::Successful(b"not a borrow index".to_vec()),
+                PromiseResult::Successful(b"\"0\"".to_vec()),
+                PromiseResult::Successful(b"\"0\"".to_vec()),
+            ],
+        );
+
+        let profile = controller_contract.get_user_profile(borrower);
+        assert_eq!(
+            profile.get(
+                Borrow,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            500
+        );
+        assert_eq!(
+            profile.get(
+                Supply,
+                AccountId::new_unchecked("weth_market.near".to_string())
+            ),
+            600
+        );
+        assert_eq!(
+            controller_contract.get_user_profile(carol()).get(
+                Supply,
+                AccountId::new_unchecked("wnear_market.near".to_string())
+            ),
+            0
+        );
+    }
+}
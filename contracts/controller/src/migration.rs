@@ -0,0 +1,406 @@
+use crate::*;
+use near_sdk::IntoStorageKey;
+
+/// Layout of the stored contract state.
+/// Whenever the layout of a released version changes, the previous `Contract` schema
+/// should be copied below, a new version added and `migrate_state` extended with the step
+/// converting the previous version into the next one.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    PartialOrd,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum StateVersion {
+    /// State written before the versioning was introduced, the version isn't stored for it
+    V1,
+    V2,
+}
+
+pub const CURRENT_STATE_VERSION: StateVersion = StateVersion::V2;
+
+impl StateVersion {
+    pub fn read() -> Self {
+        env::storage_read(&StorageKeys::StateVersion.into_storage_key())
+            .map(|version| {
+                StateVersion::try_from_slice(&version).expect("State version is corrupted")
+            })
+            .unwrap_or(StateVersion::V1)
+    }
+
+    pub fn write(&self) {
+        env::storage_write(
+            &StorageKeys::StateVersion.into_storage_key(),
+            &self.try_to_vec().unwrap(),
+        );
+    }
+}
+
+/// Default number of the V1 user profiles migrated by a single call
+pub const PROFILES_MIGRATION_LIMIT: u64 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct MarketProfileV1 {
+    dtoken: AccountId,
+    ticker_id: String,
+    ltv: Ratio,
+    lth: Ratio,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct BorrowDataV1 {
+    borrow_block: BlockHeight,
+    borrow_rate: Ratio,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct UserProfileV1 {
+    account_supplies: HashMap<AccountId, Balance>,
+    account_borrows: HashMap<AccountId, Balance>,
+    consistency: Consistency,
+    borrow_data: HashMap<AccountId, BorrowDataV1>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ActionStatusV1 {
+    supply: bool,
+    withdraw: bool,
+    borrow: bool,
+    repay: bool,
+    liquidate: bool,
+    deposit: bool,
+}
+
+impl ActionStatusV1 {
+    fn get_paused_methods(&self) -> HashMap<MethodType, Option<BlockHeight>> {
+        [
+            (MethodType::Supply, self.supply),
+            (MethodType::Withdraw, self.withdraw),
+            (MethodType::Borrow, self.borrow),
+            (MethodType::Repay, self.repay),
+            (MethodType::Liquidate, self.liquidate),
+            (MethodType::Deposit, self.deposit),
+        ]
+        .iter()
+        .filter(|(_, is_paused)| *is_paused)
+        .map(|(method, _)| (*method, None))
+        .collect()
+    }
+}
+
+/// User profiles of the V1 layout are migrated in batches after the upgrade
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ProfilesMigration {
+    /// Profiles which are not migrated yet, the profile is removed once it is migrated
+    profiles: UnorderedMap<AccountId, UserProfileV1>,
+
+    /// Number of the profiles migrated so far, profiles are migrated starting from the last one
+    cursor: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ContractV1 {
+    markets: UnorderedMap<AccountId, MarketProfileV1>,
+    user_profiles: UnorderedMap<AccountId, UserProfileV1>,
+    prices: LookupMap<AccountId, Price>,
+    config: LazyOption<Config>,
+    admin: AccountId,
+    eligible_to_borrow_uncollateralized: AccountId,
+    is_action_paused: ActionStatusV1,
+    liquidation_incentive: Ratio,
+    liquidation_health_factor_threshold: Ratio,
+    mutex: ActionMutex,
+}
+
+impl ContractV1 {
+    /// Markets get the default risk parameters, global V1 pauses are applied to every market.
+    /// Prices are kept but aren't considered fresh until the oracles report them again.
+    /// User profiles are left to `migrate_user_profiles`
+    fn migrate(mut self) -> Contract {
+        let markets = self.markets.to_vec();
+        self.markets.clear();
+
+        let mut contract = Contract {
+            markets: UnorderedMap::new(StorageKeys::Markets),
+            user_profiles: UnorderedMap::new(StorageKeys::AccountProfiles),
+            prices: self.prices,
+            market_borrows: LookupMap::new(StorageKeys::MarketBorrows),
+            market_borrow_indexes: LookupMap::new(StorageKeys::MarketBorrowIndexes),
+            isolated_debt: LookupMap::new(StorageKeys::IsolatedDebt),
+            e_mode_categories: UnorderedMap::new(StorageKeys::EModeCategories),
+            prices_updated_at: LookupMap::new(StorageKeys::PricesUpdatedAt),
+            oracle_reports: LookupMap::new(StorageKeys::OracleReports),
+            max_price_deviation: get_default_max_price_deviation(),
+            price_deviation_rounds: LookupMap::new(StorageKeys::PriceDeviationRounds),
+            config: self.config,
+            roles: Roles::new(self.admin.clone()),
+            paused_actions: UnorderedMap::new(StorageKeys::PausedActions),
+            liquidation_incentive: self.liquidation_incentive,
+            max_liquidation_incentive: get_default_max_liquidation_incentive(),
+            liquidation_health_factor_threshold: self.liquidation_health_factor_threshold,
+            mutex: self.mutex,
+            timelock: Timelock::new(StorageKeys::RiskProposals, self.admin, 0),
+            storage_deposits: StorageDeposits::new(
+                StorageKeys::StorageDeposits,
+                MIN_ACCOUNT_STORAGE_USAGE,
+            ),
+            profiles_migration: Some(ProfilesMigration {
+                profiles: self.user_profiles,
+                cursor: 0,
+            })
+            .filter(|migration| !migration.profiles.is_empty()),
+        };
+
+        let oracle_account_id = contract.config.get().unwrap().oracle_account_id;
+        contract.roles.grant(Role::Oracle, oracle_account_id);
+
+        let paused_methods = self.is_action_paused.get_paused_methods();
+        for (asset_id, market) in markets {
+            if !paused_methods.is_empty() {
+                contract
+                    .paused_actions
+                    .insert(&market.dtoken, &paused_methods);
+            }
+            contract.insert_market(
+                asset_id,
+                market.dtoken,
+                market.ticker_id,
+                market.ltv,
+                market.lth,
+            );
+        }
+
+        contract
+    }
+}
+
+impl Contract {
+    /// Total borrows of the markets are restored from the migrated profiles
+    fn migrate_user_profile(&mut self, account_id: AccountId, profile: UserProfileV1) {
+        for (dtoken, borrows) in profile.account_borrows.iter() {
+            let market_borrows = self.get_market_total_borrows(dtoken) + borrows;
+            self.market_borrows.insert(dtoken, &market_borrows);
+        }
+
+        let user = UserProfile {
+            borrow_data: profile
+                .account_borrows
+                .keys()
+                .map(|dtoken| (dtoken.clone(), BorrowData::default()))
+                .collect(),
+            account_supplies: profile.account_supplies,
+            account_borrows: profile.account_borrows,
+            consistency: profile.consistency,
+            ..UserProfile::default()
+        };
+        self.user_profiles.insert(&account_id, &user);
+    }
+
+    /// Reads the state stored in the given version and migrates it up to the current one
+    pub(crate) fn migrate_state(version: StateVersion) -> Self {
+        require!(
+            version <= CURRENT_STATE_VERSION,
+            "State version is newer than the contract one"
+        );
+
+        let contract = match version {
+            StateVersion::V1 => env::state_read::<ContractV1>()
+                .expect("Contract is not initialized")
+                .migrate(),
+            StateVersion::V2 => env::state_read().expect("Contract is not initialized"),
+        };
+
+        CURRENT_STATE_VERSION.write();
+        contract
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_state_version(&self) -> StateVersion {
+        StateVersion::read()
+    }
+
+    /// Migrates up to `limit` of the V1 user profiles, should be called again until no profiles
+    /// remain, market actions are resumed then. Returns the number of the remaining profiles.
+    pub fn migrate_user_profiles(&mut self, limit: Option<u64>) -> u64 {
+        self.roles.assert_role(Role::Owner);
+        let mut migration = self
+            .profiles_migration
+            .take()
+            .expect("There are no user profiles to migrate");
+
+        for _ in 0..limit.unwrap_or(PROFILES_MIGRATION_LIMIT) {
+            let account_id = match migration.profiles.len().checked_sub(1) {
+                Some(last_index) => migration.profiles.keys_as_vector().get(last_index).unwrap(),
+                None => break,
+            };
+            let profile = migration.profiles.remove(&account_id).unwrap();
+            self.migrate_user_profile(account_id, profile);
+            migration.cursor += 1;
+        }
+
+        let remaining_profiles = migration.profiles.len();
+        if remaining_profiles > 0 {
+            self.profiles_migration = Some(migration);
+        }
+        remaining_profiles
+    }
+
+    /// Progress of the V1 user profiles migration, `None` once all of them are migrated
+    pub fn get_profiles_migration(&self) -> Option<MigrationProgress> {
+        self.profiles_migration
+            .as_ref()
+            .map(|migration| MigrationProgress {
+                migrated: migration.cursor,
+                remaining: migration.profiles.len(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    #[test]
+    fn test_migrate_from_v1() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .predecessor_account_id(alice())
+            .block_index(100)
+            .build());
+
+        let dtoken = AccountId::new_unchecked("weth_market.near".to_string());
+        let mut contract = ContractV1 {
+            markets: UnorderedMap::new(StorageKeys::Markets),
+            user_profiles: UnorderedMap::new(StorageKeys::UserProfiles),
+            prices: LookupMap::new(StorageKeys::Prices),
+            config: LazyOption::new(
+                StorageKeys::Config,
+                Some(&Config {
+                    owner_id: alice(),
+                    oracle_account_id: carol(),
+                }),
+            ),
+            admin: alice(),
+            eligible_to_borrow_uncollateralized: alice(),
+            is_action_paused: ActionStatusV1 {
+                supply: false,
+                withdraw: false,
+                borrow: true,
+                repay: false,
+                liquidate: false,
+                deposit: false,
+            },
+            liquidation_incentive: Ratio::from_str("0.07").unwrap(),
+            liquidation_health_factor_threshold: Ratio::one(),
+            mutex: ActionMutex::default(),
+        };
+        contract.markets.insert(
+            &AccountId::new_unchecked("weth.near".to_string()),
+            &MarketProfileV1 {
+                dtoken: dtoken.clone(),
+                ticker_id: "weth".to_string(),
+                ltv: Ratio::from_str("0.6").unwrap(),
+                lth: Ratio::from_str("0.8").unwrap(),
+            },
+        );
+        contract.user_profiles.insert(
+            &bob(),
+            &UserProfileV1 {
+                account_supplies: HashMap::from([(dtoken.clone(), 1000)]),
+                account_borrows: HashMap::from([(dtoken.clone(), 300)]),
+                consistency: Consistency::default(),
+                borrow_data: HashMap::from([(
+                    dtoken.clone(),
+                    BorrowDataV1 {
+                        borrow_block: 90,
+                        borrow_rate: Ratio::zero(),
+                    },
+                )]),
+            },
+        );
+        contract.user_profiles.insert(
+            &carol(),
+            &UserProfileV1 {
+                account_supplies: HashMap::from([(dtoken.clone(), 500)]),
+                account_borrows: HashMap::from([(dtoken.clone(), 100)]),
+                consistency: Consistency::default(),
+                borrow_data: HashMap::new(),
+            },
+        );
+        env::state_write(&contract);
+
+        let mut contract = Contract::migrate_state(StateVersion::read());
+        assert_eq!(contract.get_state_version(), CURRENT_STATE_VERSION);
+
+        let market = contract.get_market_by_dtoken(dtoken.clone());
+        assert_eq!(market.ltv, Ratio::from_str("0.6").unwrap());
+        assert_eq!(market.close_factor, get_default_close_factor());
+
+        // market actions are paused until the user profiles are migrated
+        assert_eq!(
+            contract.get_profiles_migration(),
+            Some(MigrationProgress {
+                migrated: 0,
+                remaining: 2
+            })
+        );
+        assert!(contract.is_action_paused(&dtoken, MethodType::Supply));
+
+        assert_eq!(contract.migrate_user_profiles(Some(1)), 1);
+        assert_eq!(
+            contract.get_profiles_migration(),
+            Some(MigrationProgress {
+                migrated: 1,
+                remaining: 1
+            })
+        );
+        assert!(contract.is_action_paused(&dtoken, MethodType::Supply));
+
+        assert_eq!(contract.migrate_user_profiles(None), 0);
+        assert_eq!(contract.get_profiles_migration(), None);
+        assert!(contract.is_action_paused(&dtoken, MethodType::Borrow));
+        assert!(!contract.is_action_paused(&dtoken, MethodType::Supply));
+
+        let user = contract.get_user_profile(bob());
+        assert_eq!(user.get(ActionType::Supply, dtoken.clone()), 1000);
+        assert_eq!(user.get(ActionType::Borrow, dtoken.clone()), 300);
+        let user = contract.get_user_profile(carol());
+        assert_eq!(user.get(ActionType::Supply, dtoken.clone()), 500);
+        assert_eq!(contract.get_market_total_borrows(&dtoken), 400);
+        assert_eq!(
+            contract.get_liquidation_incentive(),
+            Ratio::from_str("1.07").unwrap()
+        );
+        assert!(contract.roles.has_role(Role::Owner, &alice()));
+        assert!(contract.roles.has_role(Role::Oracle, &carol()));
+    }
+
+    #[test]
+    #[should_panic(expected = "There are no user profiles to migrate")]
+    fn test_migrate_user_profiles_of_current_version() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .predecessor_account_id(alice())
+            .build());
+        let mut contract = Contract::new(Config {
+            owner_id: alice(),
+            oracle_account_id: carol(),
+        });
+
+        contract.migrate_user_profiles(None);
+    }
+}
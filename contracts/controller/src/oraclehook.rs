@@ -3,21 +3,390 @@ use crate::*;
 #[near_bindgen]
 impl OraclePriceHandlerHook for Contract {
     fn oracle_on_data(&mut self, price_data: PriceJsonList) {
-        let config: Config = self.get_contract_config();
+        let oracle_id = env::predecessor_account_id();
 
-        assert_eq!(
-            env::predecessor_account_id(),
-            config.oracle_account_id,
-            "Oracle account {} doesn't match to the signer {}",
-            config.oracle_account_id,
-            env::predecessor_account_id()
+        require!(
+            self.is_oracle_account(&oracle_id),
+            format!("Oracle account {oracle_id} is not allowed to provide prices")
         );
 
         let tickers_map = self.get_tickers_dtoken_hash();
         for price in price_data.price_list {
             if let Some(dtoken) = tickers_map.get(&price.ticker_id) {
-                self.upsert_price(dtoken.clone(), &price);
+                let report = PriceReport {
+                    price,
+                    block_height: price_data.block_height,
+                };
+
+                let median = match self.submit_price_report(
+                    dtoken.clone(),
+                    oracle_id.clone(),
+                    report.clone(),
+                ) {
+                    Some(median) => median,
+                    None => {
+                        Events::PriceRejected {
+                            oracle_id: oracle_id.clone(),
+                            dtoken: dtoken.clone(),
+                            price: report.price,
+                            reason: "price report is stale".to_string(),
+                        }
+                        .emit();
+                        continue;
+                    }
+                };
+
+                if !self.is_price_deviation_allowed(dtoken, &median.price) {
+                    // Price which stays outside of the band for several rounds is a real move,
+                    // rejecting it forever would leave the market without a fresh price
+                    if self.count_price_deviation_round(dtoken, &median)
+                        < MAX_PRICE_DEVIATION_ROUNDS
+                    {
+                        Events::PriceRejected {
+                            oracle_id: oracle_id.clone(),
+                            dtoken: dtoken.clone(),
+                            price: median.price,
+                            reason: "price deviates too much from the last accepted one"
+                                .to_string(),
+                        }
+                        .emit();
+                        continue;
+                    }
+                }
+
+                self.price_deviation_rounds.remove(dtoken);
+                self.set_price(dtoken.clone(), &median.price, median.block_height);
+                Events::PriceUpdate {
+                    dtoken: dtoken.clone(),
+                    price: median.price,
+                    block_height: median.block_height,
+                }
+                .emit();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use general::events::{parse_event, EventMessage};
+    use near_sdk::serde_json::json;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    fn set_context(predecessor: AccountId, block_index: BlockHeight) {
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(alice())
+            .predecessor_account_id(predecessor)
+            .block_index(block_index)
+            .build());
+    }
+
+    fn weth_price(value: u128) -> Price {
+        Price {
+            ticker_id: "weth".to_string(),
+            value: U128(value),
+            volatility: U128(100),
+            fraction_digits: 4,
+        }
+    }
+
+    fn init() -> (Contract, AccountId) {
+        set_context(alice(), 100);
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: alice(),
+            oracle_account_id: alice(),
+        });
+
+        let dtoken_address_eth = AccountId::new_unchecked("weth_market.near".to_string());
+        controller_contract.add_market(
+            AccountId::new_unchecked("weth.near".to_string()),
+            dtoken_address_eth.clone(),
+            "weth".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+        controller_contract.grant_role(Role::Oracle, bob());
+        controller_contract.grant_role(Role::Oracle, carol());
+
+        (controller_contract, dtoken_address_eth)
+    }
+
+    #[test]
+    fn test_median_of_oracle_reports() {
+        let (mut controller_contract, dtoken) = init();
+
+        for (oracle, value) in [(alice(), 10000), (bob(), 10500), (carol(), 19000)] {
+            set_context(oracle, 100);
+            controller_contract.oracle_on_data(PriceJsonList {
+                block_height: 100,
+                price_list: vec![weth_price(value)],
+            });
+        }
+
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(10500),
+            "Price should be the median of fresh reports"
+        );
+    }
+
+    #[test]
+    fn test_median_of_even_oracle_reports() {
+        let (mut controller_contract, dtoken) = init();
+
+        for (oracle, value) in [(alice(), 10000), (bob(), 10500)] {
+            set_context(oracle, 100);
+            controller_contract.oracle_on_data(PriceJsonList {
+                block_height: 100,
+                price_list: vec![weth_price(value)],
+            });
+        }
+
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(10250),
+            "Price should be the mean of two middle reports"
+        );
+    }
+
+    #[test]
+    fn test_stale_report_is_rejected() {
+        let (mut controller_contract, dtoken) = init();
+
+        set_context(alice(), 100 + DEFAULT_MAX_PRICE_AGE + 1);
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+
+        assert!(
+            controller_contract.get_price(&dtoken).is_none(),
+            "Stale price shouldn't be accepted"
+        );
+    }
+
+    #[test]
+    fn test_price_deviation_is_rejected() {
+        let (mut controller_contract, dtoken) = init();
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+        set_context(alice(), 102);
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 101,
+            price_list: vec![weth_price(15000)],
+        });
+
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(10000),
+            "Price which moved more than allowed deviation shouldn't be accepted"
+        );
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 102,
+            price_list: vec![weth_price(11000)],
+        });
+
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(11000),
+            "Price within allowed deviation should be accepted"
+        );
+    }
+
+    #[test]
+    fn test_sustained_price_move_is_accepted() {
+        let (mut controller_contract, dtoken) = init();
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+
+        for round in 1..MAX_PRICE_DEVIATION_ROUNDS as u64 {
+            set_context(alice(), 100 + round);
+            controller_contract.oracle_on_data(PriceJsonList {
+                block_height: 100 + round,
+                price_list: vec![weth_price(7000)],
+            });
+            assert_eq!(
+                controller_contract.get_price(&dtoken).unwrap().value,
+                U128(10000),
+                "Price should be rejected until the move is sustained"
+            );
+        }
+
+        set_context(alice(), 100 + MAX_PRICE_DEVIATION_ROUNDS as u64);
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100 + MAX_PRICE_DEVIATION_ROUNDS as u64,
+            price_list: vec![weth_price(7000)],
+        });
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(7000),
+            "Price which stayed outside of the band should be accepted"
+        );
+        assert_eq!(
+            controller_contract.get_price_updated_at(&dtoken),
+            Some(100 + MAX_PRICE_DEVIATION_ROUNDS as u64)
+        );
+    }
+
+    #[test]
+    fn test_price_deviation_rounds_are_reset() {
+        let (mut controller_contract, dtoken) = init();
+
+        for (block_height, value) in [(100, 10000), (101, 13000), (102, 10500), (103, 13000)] {
+            set_context(alice(), block_height);
+            controller_contract.oracle_on_data(PriceJsonList {
+                block_height,
+                price_list: vec![weth_price(value)],
+            });
+        }
+
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(10500),
+            "Accepted price should reset the rejected rounds"
+        );
+    }
+
+    #[test]
+    fn test_price_deviation_rounds_of_same_block() {
+        let (mut controller_contract, dtoken) = init();
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+
+        set_context(alice(), 101);
+        for _ in 0..MAX_PRICE_DEVIATION_ROUNDS {
+            controller_contract.oracle_on_data(PriceJsonList {
+                block_height: 101,
+                price_list: vec![weth_price(7000)],
+            });
+        }
+
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(10000),
+            "Reports of the same block should be counted as a single round"
+        );
+    }
+
+    #[test]
+    fn test_price_deviation_rounds_in_opposite_directions() {
+        let (mut controller_contract, dtoken) = init();
+
+        set_context(alice(), 100);
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+
+        for (block_height, value) in [(101, 7000), (102, 13000), (103, 7000)] {
+            set_context(alice(), block_height);
+            controller_contract.oracle_on_data(PriceJsonList {
+                block_height,
+                price_list: vec![weth_price(value)],
+            });
+        }
+
+        assert_eq!(
+            controller_contract.get_price(&dtoken).unwrap().value,
+            U128(10000),
+            "Move in the opposite direction should start counting the rounds over"
+        );
+    }
+
+    #[test]
+    fn test_price_events() {
+        let (mut controller_contract, dtoken) = init();
+
+        set_context(alice(), 101);
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 101,
+            price_list: vec![weth_price(15000)],
+        });
+
+        let events = get_logs()
+            .iter()
+            .filter_map(|log| parse_event(log))
+            .collect::<Vec<EventMessage>>();
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0].standard, "nep297");
+        assert_eq!(events[0].event, "price_update");
+        assert_eq!(
+            events[0].data,
+            json!({
+                "dtoken": dtoken,
+                "price": {
+                    "ticker_id": "weth",
+                    "value": "10000",
+                    "volatility": "100",
+                    "fraction_digits": 4
+                },
+                "block_height": 100
+            })
+        );
+
+        assert_eq!(events[1].event, "price_rejected");
+        assert_eq!(events[1].data["oracle_id"], json!(alice()));
+        assert_eq!(events[1].data["price"]["value"], "15000");
+        assert_eq!(
+            events[1].data["reason"],
+            "price deviates too much from the last accepted one"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is not allowed to provide prices")]
+    fn test_unknown_oracle_is_rejected() {
+        let (mut controller_contract, _) = init();
+
+        set_context(AccountId::new_unchecked("mallory.near".to_string()), 100);
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Price report block height 101 is ahead of the current block 100")]
+    fn test_report_from_future_is_rejected() {
+        let (mut controller_contract, _) = init();
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 101,
+            price_list: vec![weth_price(10000)],
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "is stale")]
+    fn test_stale_price_is_not_used() {
+        let (mut controller_contract, dtoken) = init();
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 100,
+            price_list: vec![weth_price(10000)],
+        });
+
+        set_context(alice(), 100 + DEFAULT_MAX_PRICE_AGE + 1);
+        controller_contract.get_fresh_price(&dtoken);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::*;
+
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum MethodType {
+    Withdraw,
+    Repay,
+    Supply,
+    Liquidate,
+    Borrow,
+    Deposit,
+}
+
+impl MethodType {
+    /// Market flow of the action, `None` for the actions which could not be paused
+    pub fn from_action(action: &Actions) -> Option<Self> {
+        match action {
+            Actions::Supply => Some(MethodType::Supply),
+            Actions::Withdraw => Some(MethodType::Withdraw),
+            Actions::Borrow { .. }
+            | Actions::FixedRateBorrow { .. }
+            | Actions::DelegatedBorrow { .. } => Some(MethodType::Borrow),
+            Actions::Repay | Actions::FixedRateRepay => Some(MethodType::Repay),
+            Actions::Liquidate { .. } | Actions::LiquidationDeposit => Some(MethodType::Liquidate),
+            Actions::Deposit => Some(MethodType::Deposit),
+            Actions::Reserve | Actions::FlashLoanRepay | Actions::FundCampaign { .. } => None,
+        }
+    }
+}
+
+impl Contract {
+    /// Actions of all the markets are paused while the V1 user profiles are being migrated
+    pub fn is_action_paused(&self, dtoken: &AccountId, method: MethodType) -> bool {
+        self.profiles_migration.is_some()
+            || self
+                .paused_actions
+                .get(dtoken)
+                .and_then(|paused_actions| paused_actions.get(&method).copied())
+                .map_or(false, |expiration_block| {
+                    expiration_block.map_or(true, |block| env::block_height() < block)
+                })
+    }
+
+    pub fn assert_action_not_paused(&self, dtoken: &AccountId, method: MethodType) {
+        require!(
+            !self.is_action_paused(dtoken, method),
+            format!("{:?} is paused in the market {}", method, dtoken)
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pauses the action in the market, the action is resumed automatically
+    /// after the expiration block if it is set
+    pub fn pause_method(
+        &mut self,
+        dtoken: AccountId,
+        method: MethodType,
+        expiration_block: Option<BlockHeight>,
+    ) {
+        self.roles.assert_role(Role::PauseGuardian);
+        require!(
+            self.is_market_registered(dtoken.clone()),
+            format!("Market {} is not registered", dtoken)
+        );
+        require!(
+            expiration_block.map_or(true, |block| block > env::block_height()),
+            "Expiration block of the pause is in the past"
+        );
+
+        let mut paused_actions = self.paused_actions.get(&dtoken).unwrap_or_default();
+        paused_actions.insert(method, expiration_block);
+        self.paused_actions.insert(&dtoken, &paused_actions);
+    }
+
+    pub fn proceed_method(&mut self, dtoken: AccountId, method: MethodType) {
+        self.roles.assert_role(Role::PauseGuardian);
+
+        let mut paused_actions = self.paused_actions.get(&dtoken).unwrap_or_default();
+        paused_actions.remove(&method);
+        if paused_actions.is_empty() {
+            self.paused_actions.remove(&dtoken);
+        } else {
+            self.paused_actions.insert(&dtoken, &paused_actions);
+        }
+    }
+
+    /// Paused action -> Expiration block of the pause
+    pub fn get_paused_actions(
+        &self,
+        dtoken: AccountId,
+    ) -> HashMap<MethodType, Option<BlockHeight>> {
+        self.paused_actions
+            .get(&dtoken)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(method, _)| self.is_action_paused(&dtoken, *method))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    fn set_context(signer: AccountId, block: BlockHeight) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(signer.clone())
+            .predecessor_account_id(signer)
+            .block_index(block)
+            .build());
+    }
+
+    fn init() -> (Contract, AccountId, AccountId) {
+        set_context(bob(), 10);
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: bob(),
+            oracle_account_id: bob(),
+        });
+
+        let (weth_market, usdt_market) = (
+            AccountId::new_unchecked("weth_market.near".to_string()),
+            AccountId::new_unchecked("usdt_market.near".to_string()),
+        );
+        for (asset_id, dtoken, ticker_id) in [
+            ("weth.near", weth_market.clone(), "weth"),
+            ("usdt.near", usdt_market.clone(), "usdt"),
+        ] {
+            controller_contract.add_market(
+                AccountId::new_unchecked(asset_id.to_string()),
+                dtoken,
+                ticker_id.to_string(),
+                Ratio::from_str("0.6").unwrap(),
+                Ratio::from_str("0.8").unwrap(),
+            );
+        }
+
+        // operations account which could only pause markets
+        controller_contract.grant_role(Role::PauseGuardian, carol());
+        set_context(carol(), 10);
+
+        (controller_contract, weth_market, usdt_market)
+    }
+
+    #[test]
+    fn test_pause_method_per_market() {
+        let (mut controller_contract, weth_market, usdt_market) = init();
+
+        controller_contract.pause_method(weth_market.clone(), MethodType::Borrow, None);
+
+        assert!(controller_contract.is_action_paused(&weth_market, MethodType::Borrow));
+        assert!(!controller_contract.is_action_paused(&weth_market, MethodType::Supply));
+        assert!(!controller_contract.is_action_paused(&usdt_market, MethodType::Borrow));
+
+        controller_contract.proceed_method(weth_market.clone(), MethodType::Borrow);
+        assert!(controller_contract
+            .get_paused_actions(weth_market)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_pause_method_with_expiration() {
+        let (mut controller_contract, weth_market, _) = init();
+
+        controller_contract.pause_method(weth_market.clone(), MethodType::Withdraw, Some(20));
+        assert_eq!(
+            controller_contract.get_paused_actions(weth_market.clone()),
+            HashMap::from([(MethodType::Withdraw, Some(20))])
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .predecessor_account_id(weth_market.clone())
+            .block_index(20)
+            .build());
+        assert!(!controller_contract.is_action_paused(&weth_market, MethodType::Withdraw));
+        controller_contract.mutex_lock(Actions::Withdraw);
+    }
+
+    #[test]
+    #[should_panic(expected = "Borrow is paused in the market weth_market.near")]
+    fn test_paused_market_flow() {
+        let (mut controller_contract, weth_market, _) = init();
+        controller_contract.pause_method(weth_market.clone(), MethodType::Borrow, None);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .predecessor_account_id(weth_market)
+            .build());
+        controller_contract.mutex_lock(Actions::FixedRateBorrow {
+            account_to_borrow: bob(),
+            term: 100,
+        });
+    }
+}
@@ -1,7 +1,32 @@
 use crate::*;
 
+use near_sdk::env::block_height;
 use std::collections::HashMap;
 
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[derive(Debug)]
+pub struct PriceReport {
+    /// Reported price
+    pub price: Price,
+
+    /// Block height the price has been observed at by oracle
+    pub block_height: BlockHeight,
+}
+
+/// Move of the price outside of the allowed deviation which hasn't been accepted yet
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+pub struct PriceDeviation {
+    /// Amount of distinct oracle rounds the move has been observed in
+    pub rounds: u32,
+
+    /// Block height of the last round the move has been observed in
+    pub block_height: BlockHeight,
+
+    /// Whether the price moved up or down from the last accepted one
+    pub is_increase: bool,
+}
+
 impl Contract {
     pub fn is_market_registered(&self, dtoken: AccountId) -> bool {
         self.get_markets_list()
@@ -22,6 +47,151 @@ impl Contract {
     pub fn get_price(&self, dtoken_id: &AccountId) -> Option<Price> {
         self.prices.get(dtoken_id)
     }
+
+    pub fn set_price(&mut self, dtoken_id: AccountId, price: &Price, updated_at: BlockHeight) {
+        self.prices.insert(&dtoken_id, price);
+        self.prices_updated_at.insert(&dtoken_id, &updated_at);
+    }
+
+    pub fn get_price_updated_at(&self, dtoken_id: &AccountId) -> Option<BlockHeight> {
+        self.prices_updated_at.get(dtoken_id)
+    }
+
+    pub fn is_price_fresh(&self, dtoken_id: &AccountId) -> bool {
+        let market = self.get_market_by_dtoken(dtoken_id.clone());
+        match self.get_price_updated_at(dtoken_id) {
+            Some(updated_at) => {
+                updated_at <= block_height() && block_height() - updated_at <= market.max_price_age
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the price of dtoken and panics in case it is absent or stale
+    pub fn get_fresh_price(&self, dtoken_id: &AccountId) -> Price {
+        let price = self
+            .get_price(dtoken_id)
+            .unwrap_or_else(|| panic!("Price for {} is not available", dtoken_id));
+
+        require!(
+            self.is_price_fresh(dtoken_id),
+            format!("Price for {dtoken_id} is stale")
+        );
+
+        price
+    }
+
+    pub fn is_oracle_account(&self, account_id: &AccountId) -> bool {
+        self.roles.has_role(Role::Oracle, account_id)
+    }
+
+    /// Saves oracle report and returns the median of all fresh reports for the dtoken
+    pub fn submit_price_report(
+        &mut self,
+        dtoken_id: AccountId,
+        oracle_id: AccountId,
+        report: PriceReport,
+    ) -> Option<PriceReport> {
+        let max_price_age = self.get_market_by_dtoken(dtoken_id.clone()).max_price_age;
+        let current_block = block_height();
+        // price observed in the future would never become stale
+        require!(
+            report.block_height <= current_block,
+            format!(
+                "Price report block height {} is ahead of the current block {}",
+                report.block_height, current_block
+            )
+        );
+
+        let mut reports = self.oracle_reports.get(&dtoken_id).unwrap_or_default();
+        reports.insert(oracle_id, report);
+        reports.retain(|oracle_id, report| {
+            self.is_oracle_account(oracle_id)
+                && current_block.saturating_sub(report.block_height) <= max_price_age
+        });
+        self.oracle_reports.insert(&dtoken_id, &reports);
+
+        get_median_price_report(reports.into_values().collect())
+    }
+
+    pub fn is_price_deviation_allowed(&self, dtoken_id: &AccountId, price: &Price) -> bool {
+        let last_price = match self.get_price(dtoken_id) {
+            Some(last_price) if last_price.value.0 != 0 => last_price,
+            _ => return true,
+        };
+
+        let difference = if price.value.0 > last_price.value.0 {
+            price.value.0 - last_price.value.0
+        } else {
+            last_price.value.0 - price.value.0
+        };
+
+        Ratio::from(difference) / Ratio::from(last_price.value.0) <= self.max_price_deviation
+    }
+
+    /// Counts the round in which the reported price is outside of the allowed deviation and
+    /// returns the amount of rounds the move has been observed in.
+    /// Reports of the same block height are a single round, move in the opposite direction starts over
+    pub fn count_price_deviation_round(
+        &mut self,
+        dtoken_id: &AccountId,
+        report: &PriceReport,
+    ) -> u32 {
+        let is_increase = match self.get_price(dtoken_id) {
+            Some(last_price) => report.price.value.0 > last_price.value.0,
+            None => true,
+        };
+
+        let deviation = match self.price_deviation_rounds.get(dtoken_id) {
+            Some(deviation)
+                if deviation.is_increase == is_increase
+                    && deviation.block_height >= report.block_height =>
+            {
+                deviation
+            }
+            Some(deviation) if deviation.is_increase == is_increase => PriceDeviation {
+                rounds: deviation.rounds + 1,
+                block_height: report.block_height,
+                is_increase,
+            },
+            _ => PriceDeviation {
+                rounds: 1,
+                block_height: report.block_height,
+                is_increase,
+            },
+        };
+
+        self.price_deviation_rounds.insert(dtoken_id, &deviation);
+        deviation.rounds
+    }
+}
+
+/// Median of reported prices, in case of even amount of reports the mean of two middle ones is used
+pub fn get_median_price_report(mut reports: Vec<PriceReport>) -> Option<PriceReport> {
+    if reports.is_empty() {
+        return None;
+    }
+
+    reports.sort_by(|a, b| a.price.value.0.cmp(&b.price.value.0));
+    let middle = reports.len() / 2;
+    let oldest_block = reports
+        .iter()
+        .map(|report| report.block_height)
+        .min()
+        .unwrap();
+
+    let mut median = reports[middle].clone();
+    if reports.len() % 2 == 0 {
+        let lower = &reports[middle - 1].price;
+        median.price.value = U128(
+            lower.value.0 / 2
+                + median.price.value.0 / 2
+                + (lower.value.0 % 2 + median.price.value.0 % 2) / 2,
+        );
+    }
+    median.block_height = oldest_block;
+
+    Some(median)
 }
 
 #[near_bindgen]
@@ -29,7 +199,7 @@ impl Contract {
     // TODO Do we really need to expose this via near_bindgen
     #[private]
     pub fn upsert_price(&mut self, dtoken_id: AccountId, price: &Price) {
-        self.prices.insert(&dtoken_id, price);
+        self.set_price(dtoken_id, price, block_height());
     }
 }
 
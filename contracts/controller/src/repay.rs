@@ -1,18 +1,14 @@
 use crate::*;
-use near_sdk::BlockHeight;
 
 #[near_bindgen]
 impl Contract {
     fn is_repay_allowed(
         &self,
         _account: AccountId,
-        _token_address: AccountId,
+        token_address: AccountId,
         _token_amount: WBalance,
     ) -> bool {
-        assert!(
-            !self.is_action_paused.repay,
-            "Withdraw is paused, cant perform action"
-        );
+        self.assert_action_not_paused(&token_address, MethodType::Repay);
 
         true
     }
@@ -22,9 +18,9 @@ impl Contract {
         account_id: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-        borrow_block: BlockHeight,
-        borrow_rate: WRatio,
-    ) -> Balance {
+        borrow_index: WRatio,
+    ) -> Option<WRatio> {
+        self.assert_market_caller(&token_address);
         assert!(
             self.is_repay_allowed(account_id.clone(), token_address.clone(), token_amount),
             "repay operation is not allowed for account {} on market {}, repay amount {}",
@@ -33,12 +29,10 @@ impl Contract {
             Balance::from(token_amount)
         );
 
-        self.decrease_borrows(
-            account_id,
-            token_address,
-            token_amount,
-            borrow_block,
-            borrow_rate,
-        )
+        self.set_market_borrow_index(&token_address, Ratio::from(borrow_index));
+        self.decrease_borrows(account_id.clone(), token_address, token_amount);
+        self.refund_storage_if_empty(&account_id);
+
+        self.try_get_health_factor(&account_id).map(WRatio::from)
     }
 }
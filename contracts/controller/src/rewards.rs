@@ -0,0 +1,203 @@
+use crate::*;
+use near_sdk::{serde_json, PromiseOrValue, PromiseResult};
+
+/// Min gas of the rewards claim on the market, it is enough to transfer a single reward token.
+/// The prepaid gas left after the claim and its callback is split between the markets, so
+/// the markets paying several reward tokens get more
+const MARKET_CLAIM_ALL_REWARDS_GAS: u64 = 30;
+
+/// Gas of the claim itself and the base gas of its callback, the callback takes 1 TGas more
+/// for each of the claimed markets
+const CLAIM_ALL_REWARDS_GAS: u64 = 15;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketRewardClaim {
+    pub dtoken: AccountId,
+    /// Claimed amounts by the reward tokens, `None` if the claim on the market failed.
+    /// Amounts are reported once the market has scheduled its reward transfers, before they are
+    /// resolved, rewards of a failed transfer stay on the market to be claimed again
+    pub claimed: Option<HashMap<AccountId, WBalance>>,
+}
+
+impl Contract {
+    /// Prepaid gas left after the claim and its callback split between the claimed markets
+    fn get_market_claim_gas(markets_amount: u64) -> near_sdk::Gas {
+        let reserved_gas = near_sdk::Gas::ONE_TERA * (CLAIM_ALL_REWARDS_GAS + markets_amount);
+        near_sdk::Gas((env::prepaid_gas().0 - reserved_gas.0) / markets_amount)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Claims the vested rewards of the caller on the given markets, on all of the markets if
+    /// not set. Prepaid gas grows with the amount of markets, so the claim could be split by them
+    pub fn claim_all_rewards(
+        &mut self,
+        dtokens: Option<Vec<AccountId>>,
+    ) -> PromiseOrValue<Vec<MarketRewardClaim>> {
+        let all_markets = self
+            .markets
+            .values()
+            .map(|market| market.dtoken)
+            .collect::<Vec<AccountId>>();
+        let markets = match dtokens {
+            Some(dtokens) => {
+                for dtoken in dtokens.iter() {
+                    require!(
+                        all_markets.contains(dtoken),
+                        format!("Market {} is not supported", dtoken)
+                    );
+                }
+                dtokens
+            }
+            None => all_markets,
+        };
+        require!(!markets.is_empty(), "There are no markets to claim rewards");
+        let markets_amount = markets.len() as u64;
+        require!(
+            env::prepaid_gas()
+                >= near_sdk::Gas::ONE_TERA
+                    * (CLAIM_ALL_REWARDS_GAS + markets_amount * (MARKET_CLAIM_ALL_REWARDS_GAS + 1)),
+            "Prepaid gas is not enough for rewards claim flow"
+        );
+
+        let market_claim_gas = Self::get_market_claim_gas(markets_amount);
+
+        markets
+            .iter()
+            .map(|dtoken| market::claim_all_rewards(dtoken.clone(), NO_DEPOSIT, market_claim_gas))
+            .reduce(|claims, claim| claims.and(claim))
+            .unwrap()
+            .then(ext_self::claim_all_rewards_callback(
+                markets,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                near_sdk::Gas::ONE_TERA * (5 + markets_amount),
+            ))
+            .into()
+    }
+
+    #[private]
+    pub fn claim_all_rewards_callback(
+        &mut self,
+        markets: Vec<AccountId>,
+    ) -> Vec<MarketRewardClaim> {
+        markets
+            .into_iter()
+            .enumerate()
+            .map(|(index, dtoken)| {
+                let claimed = match env::promise_result(index as u64) {
+                    PromiseResult::Successful(result) => serde_json::from_slice(&result).ok(),
+                    _ => None,
+                };
+                MarketRewardClaim { dtoken, claimed }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::bob;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+    use super::*;
+
+    fn init_with_market() -> Contract {
+        let mut controller_contract = Contract::new(Config {
+            owner_id: bob(),
+            oracle_account_id: bob(),
+        });
+        controller_contract.add_market(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            AccountId::new_unchecked("wnear_market.near".to_string()),
+            "wnear".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+        controller_contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Market weth_market.near is not supported")]
+    fn test_claim_all_rewards_unsupported_market() {
+        let mut controller_contract = init_with_market();
+
+        controller_contract.claim_all_rewards(Some(vec![AccountId::new_unchecked(
+            "weth_market.near".to_string(),
+        )]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Prepaid gas is not enough for rewards claim flow")]
+    fn test_claim_all_rewards_not_enough_gas() {
+        let mut controller_contract = init_with_market();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(bob())
+            .predecessor_account_id(bob())
+            .prepaid_gas(near_sdk::Gas::ONE_TERA * 45)
+            .build());
+        controller_contract.claim_all_rewards(None);
+    }
+
+    #[test]
+    fn test_market_claim_gas_for_two_reward_tokens() {
+        testing_env!(VMContextBuilder::new()
+            .prepaid_gas(near_sdk::Gas::ONE_TERA * 300)
+            .build());
+
+        // the market paying two reward tokens needs 10 TGas and 20 TGas per token
+        assert!(Contract::get_market_claim_gas(2) >= near_sdk::Gas::ONE_TERA * 50);
+        assert!(Contract::get_market_claim_gas(5) >= near_sdk::Gas::ONE_TERA * 50);
+        assert_eq!(
+            Contract::get_market_claim_gas(1),
+            near_sdk::Gas::ONE_TERA * 284
+        );
+    }
+
+    #[test]
+    fn test_claim_all_rewards_callback() {
+        let mut controller_contract = Contract::new(Config {
+            owner_id: bob(),
+            oracle_account_id: bob(),
+        });
+        let wnear_market = AccountId::new_unchecked("wnear_market.near".to_string());
+        let weth_market = AccountId::new_unchecked("weth_market.near".to_string());
+        let claimed = HashMap::from([(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            WBalance::from(100),
+        )]);
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(bob())
+                .predecessor_account_id(bob())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(serde_json::to_vec(&claimed).unwrap()),
+                PromiseResult::Failed,
+            ]
+        );
+
+        assert_eq!(
+            controller_contract
+                .claim_all_rewards_callback(vec![wnear_market.clone(), weth_market.clone()]),
+            vec![
+                MarketRewardClaim {
+                    dtoken: wnear_market,
+                    claimed: Some(claimed),
+                },
+                MarketRewardClaim {
+                    dtoken: weth_market,
+                    claimed: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use crate::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SimulatedAction {
+    Supply,
+    Withdraw,
+    Borrow,
+    Repay,
+}
+
+impl SimulatedAction {
+    fn get_method_type(&self) -> MethodType {
+        match self {
+            SimulatedAction::Supply => MethodType::Supply,
+            SimulatedAction::Withdraw => MethodType::Withdraw,
+            SimulatedAction::Borrow => MethodType::Borrow,
+            SimulatedAction::Repay => MethodType::Repay,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RejectedStep {
+    /// Index of the step in the simulated actions
+    pub index: u32,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SimulationResult {
+    pub health_factor: WRatio,
+    /// Max borrows in USD by the loan to value of the collaterals
+    pub borrow_limit: USD,
+    /// Collateral dtoken -> Price at which the account becomes liquidatable
+    pub liquidation_prices: HashMap<AccountId, Option<WBalance>>,
+    /// First step which would be rejected, the steps after it are not applied
+    pub rejected_step: Option<RejectedStep>,
+}
+
+/// Changes of the market totals made by the previous steps of the simulation
+#[derive(Default)]
+struct SimulatedTotals {
+    borrows: HashMap<AccountId, Balance>,
+    isolated_debt: Balance,
+}
+
+impl Contract {
+    /// Copy of the account balances to apply actions on, borrows include the accrued interest
+    pub fn get_simulated_profile(&self, account_id: &AccountId) -> UserProfile {
+        let user_profile = self.user_profiles.get(account_id).unwrap_or_default();
+        let account_borrows = user_profile
+            .account_borrows
+            .keys()
+            .map(|dtoken| {
+                (
+                    dtoken.clone(),
+                    self.get_account_borrows_with_interest(account_id.clone(), dtoken.clone()),
+                )
+            })
+            .collect();
+
+        UserProfile {
+            account_supplies: user_profile.account_supplies,
+            account_borrows,
+            collaterals: user_profile.collaterals,
+            e_mode_category: user_profile.e_mode_category,
+            ..UserProfile::default()
+        }
+    }
+
+    /// Accounts without borrows could not be liquidated whatever their health factor is
+    pub fn is_profile_healthy(&self, user_profile: &UserProfile) -> bool {
+        self.calculate_assets_weighted_price(&user_profile.account_borrows) == 0
+            || self.calculate_profile_health_factor(user_profile)
+                >= self.get_liquidation_threshold()
+    }
+
+    fn apply_simulated_action(
+        &self,
+        user_profile: &mut UserProfile,
+        totals: &mut SimulatedTotals,
+        dtoken: &AccountId,
+        action: SimulatedAction,
+        amount: Balance,
+    ) -> Result<(), String> {
+        if !self.markets.values().any(|market| market.dtoken == *dtoken) {
+            return Err(format!("Market {} doesn't exist", dtoken));
+        }
+        if amount == 0 {
+            return Err("Amount should be positive".to_string());
+        }
+        let method = action.get_method_type();
+        if self.is_action_paused(dtoken, method) {
+            return Err(format!("{:?} is paused in the market {}", method, dtoken));
+        }
+
+        match action {
+            SimulatedAction::Supply => {
+                *user_profile
+                    .account_supplies
+                    .entry(dtoken.clone())
+                    .or_default() += amount;
+            }
+            SimulatedAction::Withdraw => {
+                let supplies = user_profile
+                    .account_supplies
+                    .get_mut(dtoken)
+                    .filter(|supplies| **supplies >= amount)
+                    .ok_or(format!(
+                        "Not enough existing supplies in the market {}",
+                        dtoken
+                    ))?;
+                *supplies -= amount;
+            }
+            SimulatedAction::Borrow => {
+                let borrowed = totals.borrows.get(dtoken).copied().unwrap_or(0) + amount;
+                if self
+                    .get_borrow_headroom(dtoken)
+                    .map_or(false, |headroom| borrowed > headroom)
+                {
+                    return Err(format!("Borrow cap of the market {} is exceeded", dtoken));
+                }
+                if !self.is_borrowable_in_e_mode(dtoken, user_profile.e_mode_category) {
+                    return Err(format!(
+                        "Market {} is outside of the e-mode category of the account",
+                        dtoken
+                    ));
+                }
+                if let Some(isolated_dtoken) = self.get_isolated_collateral(user_profile) {
+                    if !self
+                        .get_market_by_dtoken(dtoken.clone())
+                        .is_borrowable_in_isolation
+                    {
+                        return Err(format!(
+                            "Market {} is not borrowable in isolation mode",
+                            dtoken
+                        ));
+                    }
+                    let isolated_debt = totals.isolated_debt
+                        + self.calculate_usd_amount(&self.get_fresh_price(dtoken), amount);
+                    if isolated_debt > self.get_isolated_debt_headroom(&isolated_dtoken) {
+                        return Err(format!(
+                            "Debt ceiling of the isolated market {} is exceeded",
+                            isolated_dtoken
+                        ));
+                    }
+                    totals.isolated_debt = isolated_debt;
+                }
+
+                totals.borrows.insert(dtoken.clone(), borrowed);
+                *user_profile
+                    .account_borrows
+                    .entry(dtoken.clone())
+                    .or_default() += amount;
+            }
+            SimulatedAction::Repay => {
+                // Market refunds the amount exceeding the borrows
+                let borrows = user_profile
+                    .account_borrows
+                    .get_mut(dtoken)
+                    .filter(|borrows| **borrows > 0)
+                    .ok_or(format!("There are no borrows in the market {}", dtoken))?;
+                *borrows = borrows.saturating_sub(amount);
+            }
+        }
+
+        if matches!(action, SimulatedAction::Withdraw | SimulatedAction::Borrow)
+            && !self.is_profile_healthy(user_profile)
+        {
+            return Err(format!(
+                "Health factor {} is below the liquidation threshold {}",
+                self.calculate_profile_health_factor(user_profile),
+                self.get_liquidation_threshold()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Applies the actions of the account in order without changing its balances,
+    /// the result reflects the steps before the first rejected one
+    pub fn simulate_actions(
+        &self,
+        account_id: AccountId,
+        actions: Vec<(AccountId, SimulatedAction, WBalance)>,
+    ) -> SimulationResult {
+        let mut user_profile = self.get_simulated_profile(&account_id);
+        let mut totals = SimulatedTotals::default();
+
+        let rejected_step =
+            actions
+                .iter()
+                .enumerate()
+                .find_map(|(index, (dtoken, action, amount))| {
+                    let mut simulated_profile = UserProfile {
+                        account_supplies: user_profile.account_supplies.clone(),
+                        account_borrows: user_profile.account_borrows.clone(),
+                        collaterals: user_profile.collaterals.clone(),
+                        e_mode_category: user_profile.e_mode_category,
+                        ..UserProfile::default()
+                    };
+
+                    match self.apply_simulated_action(
+                        &mut simulated_profile,
+                        &mut totals,
+                        dtoken,
+                        *action,
+                        amount.0,
+                    ) {
+                        Ok(()) => {
+                            user_profile = simulated_profile;
+                            None
+                        }
+                        Err(reason) => Some(RejectedStep {
+                            index: index as u32,
+                            reason,
+                        }),
+                    }
+                });
+
+        let collaterals = self.get_account_collaterals(&user_profile);
+        SimulationResult {
+            health_factor: WRatio::from(self.calculate_profile_health_factor(&user_profile)),
+            borrow_limit: USD::from(
+                self.calculate_weighted_ltv_by_supplies(&collaterals, user_profile.e_mode_category),
+            ),
+            liquidation_prices: self.calculate_liquidation_prices(&user_profile),
+            rejected_step,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::bob;
+
+    use super::*;
+
+    fn init() -> (Contract, AccountId, AccountId, AccountId) {
+        let mut controller_contract = Contract::new(Config {
+            owner_id: bob(),
+            oracle_account_id: bob(),
+        });
+
+        let wnear_market = AccountId::new_unchecked("wnear_market.near".to_string());
+        let weth_market = AccountId::new_unchecked("weth_market.near".to_string());
+        controller_contract.add_market(
+            AccountId::new_unchecked("wnear.near".to_string()),
+            wnear_market.clone(),
+            "wnear".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+        controller_contract.add_market(
+            AccountId::new_unchecked("weth.near".to_string()),
+            weth_market.clone(),
+            "weth".to_string(),
+            Ratio::from_str("0.6").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 0,
+            price_list: vec![
+                Price {
+                    ticker_id: "wnear".to_string(),
+                    value: U128(10000),
+                    volatility: U128(100),
+                    fraction_digits: 4,
+                },
+                Price {
+                    ticker_id: "weth".to_string(),
+                    value: U128(10000),
+                    volatility: U128(100),
+                    fraction_digits: 4,
+                },
+            ],
+        });
+
+        (controller_contract, bob(), wnear_market, weth_market)
+    }
+
+    #[test]
+    fn test_simulate_actions() {
+        let (controller_contract, user_account, wnear_market, weth_market) = init();
+
+        let result = controller_contract.simulate_actions(
+            user_account,
+            vec![
+                (weth_market.clone(), SimulatedAction::Supply, U128(200)),
+                (wnear_market, SimulatedAction::Borrow, U128(100)),
+            ],
+        );
+
+        assert_eq!(
+            result,
+            SimulationResult {
+                // (200 * LTH(0.8) / 100)
+                health_factor: WRatio::from(Ratio::from_str("1.6").unwrap()),
+                // (200 * LTV(0.6))
+                borrow_limit: U128(120),
+                // (200 * 0.625 * LTH(0.8) / 100) reaches the threshold of 1
+                liquidation_prices: HashMap::from([(weth_market, Some(U128(6250)))]),
+                rejected_step: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_simulate_actions_on_existing_position() {
+        let (mut controller_contract, user_account, wnear_market, weth_market) = init();
+
+        controller_contract.increase_supplies(
+            user_account.clone(),
+            weth_market.clone(),
+            WBalance::from(200),
+        );
+        controller_contract.increase_borrows(
+            user_account.clone(),
+            wnear_market.clone(),
+            WBalance::from(100),
+        );
+
+        let result = controller_contract.simulate_actions(
+            user_account,
+            vec![
+                (wnear_market.clone(), SimulatedAction::Repay, U128(50)),
+                (weth_market.clone(), SimulatedAction::Withdraw, U128(100)),
+                (wnear_market, SimulatedAction::Borrow, U128(40)),
+            ],
+        );
+
+        // (100 * LTH(0.8) / 50), it drops to (100 * LTH(0.8) / 90) after the borrow
+        assert_eq!(
+            result.health_factor,
+            WRatio::from(Ratio::from_str("1.6").unwrap())
+        );
+        assert_eq!(result.borrow_limit, U128(60));
+        assert_eq!(
+            result.liquidation_prices,
+            HashMap::from([(weth_market, Some(U128(6250)))])
+        );
+        assert_eq!(
+            result.rejected_step,
+            Some(RejectedStep {
+                index: 2,
+                reason: format!(
+                    "Health factor {} is below the liquidation threshold {}",
+                    Ratio::from(80u128) / Ratio::from(90u128),
+                    Ratio::one()
+                ),
+            })
+        );
+    }
+
+    #[test]
+    fn test_simulate_actions_rejects_missing_balances() {
+        let (mut controller_contract, user_account, wnear_market, weth_market) = init();
+
+        controller_contract.increase_supplies(
+            user_account.clone(),
+            weth_market.clone(),
+            WBalance::from(100),
+        );
+
+        let result = controller_contract.simulate_actions(
+            user_account.clone(),
+            vec![(weth_market.clone(), SimulatedAction::Withdraw, U128(101))],
+        );
+        assert_eq!(
+            result.rejected_step,
+            Some(RejectedStep {
+                index: 0,
+                reason: "Not enough existing supplies in the market weth_market.near".to_string(),
+            })
+        );
+        assert_eq!(result.borrow_limit, U128(60));
+
+        let result = controller_contract.simulate_actions(
+            user_account,
+            vec![
+                (weth_market, SimulatedAction::Withdraw, U128(100)),
+                (wnear_market, SimulatedAction::Repay, U128(10)),
+            ],
+        );
+        assert_eq!(
+            result.rejected_step,
+            Some(RejectedStep {
+                index: 1,
+                reason: "There are no borrows in the market wnear_market.near".to_string(),
+            })
+        );
+        assert_eq!(result.borrow_limit, U128(0));
+        assert!(result.liquidation_prices.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::*;
+use general::storage::get_unordered_map_entry_usage;
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::{assert_one_yocto, Promise, StorageUsage};
+
+/// Storage reserved for every registered account: user profile with a few markets
+pub const MIN_ACCOUNT_STORAGE_USAGE: StorageUsage = 1000;
+
+impl Contract {
+    /// Storage taken by the user profile of the account
+    pub fn get_account_storage_usage(&self, account_id: &AccountId) -> StorageUsage {
+        self.user_profiles.get(account_id).map_or(0, |profile| {
+            get_unordered_map_entry_usage(account_id, &profile)
+        })
+    }
+
+    /// Should be checked before the user profile is written on behalf of the account
+    pub fn assert_storage_covered(&self, account_id: &AccountId) {
+        self.storage_deposits
+            .assert_storage_covered(account_id, self.get_account_storage_usage(account_id));
+    }
+
+    /// Whether the account has no supplies, borrows and delegated credit.
+    /// Collateral and e-mode settings of the empty account are reset.
+    pub fn is_account_empty(&self, account_id: &AccountId) -> bool {
+        self.user_profiles.get(account_id).map_or(true, |profile| {
+            profile.is_consistent()
+                && profile
+                    .account_supplies
+                    .values()
+                    .all(|balance| *balance == 0)
+                && profile
+                    .account_borrows
+                    .values()
+                    .all(|balance| *balance == 0)
+                && profile.credit_delegations.is_empty()
+        })
+    }
+
+    /// Removes the user profile and refunds the storage deposit once the account is empty
+    pub fn refund_storage_if_empty(&mut self, account_id: &AccountId) {
+        if !self.storage_deposits.is_registered(account_id) || !self.is_account_empty(account_id) {
+            return;
+        }
+
+        self.user_profiles.remove(account_id);
+        if let Some(deposit) = self.storage_deposits.unregister(account_id) {
+            Promise::new(account_id.clone()).transfer(deposit);
+        }
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let refund = self.storage_deposits.deposit(
+            &account_id,
+            env::attached_deposit(),
+            registration_only.unwrap_or(false),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let amount = self.storage_deposits.withdraw(
+            &account_id,
+            amount.map(|amount| amount.0),
+            self.get_account_storage_usage(&account_id),
+        );
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Force unregistration isn't supported as it would remove supplies and borrows of the account
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        if !self.storage_deposits.is_registered(&account_id) {
+            return false;
+        }
+        require!(
+            !force.unwrap_or(false),
+            "Force unregistration is not supported"
+        );
+        require!(
+            self.is_account_empty(&account_id),
+            "Account has supplies, borrows or delegated credit"
+        );
+
+        self.refund_storage_if_empty(&account_id);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        self.storage_deposits.get_bounds()
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get_balance(&account_id, self.get_account_storage_usage(&account_id))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn view_storage_usage(&self, account_id: AccountId) -> StorageUsage {
+        self.get_account_storage_usage(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use general::storage::get_storage_cost;
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    #[test]
+    fn test_storage_refunded_for_empty_account() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .predecessor_account_id(bob())
+            .attached_deposit(get_storage_cost(MIN_ACCOUNT_STORAGE_USAGE))
+            .build());
+        let mut controller_contract = Contract::new(Config {
+            owner_id: alice(),
+            oracle_account_id: alice(),
+        });
+        let dtoken = AccountId::new_unchecked("weth_market.near".to_string());
+
+        controller_contract.storage_deposit(None, None);
+        controller_contract.increase_supplies(bob(), dtoken.clone(), U128(100));
+        controller_contract.assert_storage_covered(&bob());
+        assert!(controller_contract.view_storage_usage(bob()) > 0);
+
+        controller_contract.decrease_supplies(bob(), dtoken, U128(100));
+        controller_contract.refund_storage_if_empty(&bob());
+        assert!(controller_contract.storage_balance_of(bob()).is_none());
+        assert_eq!(controller_contract.view_storage_usage(bob()), 0);
+    }
+}
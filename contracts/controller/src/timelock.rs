@@ -0,0 +1,474 @@
+use crate::*;
+use general::timelock::Proposal;
+use near_sdk::serde_json::json;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum RiskParameterChange {
+    AddMarket {
+        asset_id: AccountId,
+        dtoken: AccountId,
+        ticker_id: String,
+        ltv: Ratio,
+        lth: Ratio,
+    },
+    RemoveMarket {
+        asset_id: AccountId,
+    },
+    SetLiquidationIncentive(Ratio),
+    SetMaxLiquidationIncentive(Ratio),
+    GrantRole(Role, AccountId),
+    RevokeRole(Role, AccountId),
+    SetGuardian(AccountId),
+    SetTimelockDelay(BlockHeight),
+    SetMarketCloseFactor {
+        asset_id: AccountId,
+        close_factor: Ratio,
+    },
+    SetMarketIsCollateral {
+        asset_id: AccountId,
+        is_collateral: bool,
+    },
+    SetMarketDebtCeiling {
+        asset_id: AccountId,
+        debt_ceiling: Option<WBalance>,
+    },
+    SetMarketBorrowableInIsolation {
+        asset_id: AccountId,
+        is_borrowable_in_isolation: bool,
+    },
+    SetMarketEModeCategory {
+        asset_id: AccountId,
+        category_id: Option<u8>,
+    },
+    SetMaxPriceDeviation(Ratio),
+    SetLiquidationThreshold(Ratio),
+    SetEModeCategory {
+        category_id: u8,
+        category: EModeCategory,
+    },
+    RemoveEModeCategory {
+        category_id: u8,
+    },
+    SetMarketMaxPriceAge {
+        asset_id: AccountId,
+        max_price_age: BlockHeight,
+    },
+    SetMarketBorrowCap {
+        asset_id: AccountId,
+        borrow_cap: Option<WBalance>,
+    },
+}
+
+impl RiskParameterChange {
+    /// Role which is allowed to schedule the change
+    fn get_role(&self) -> Role {
+        match self {
+            RiskParameterChange::GrantRole(..)
+            | RiskParameterChange::RevokeRole(..)
+            | RiskParameterChange::SetGuardian(_)
+            | RiskParameterChange::SetTimelockDelay(_) => Role::Owner,
+            _ => Role::RiskManager,
+        }
+    }
+
+    fn get_event(&self) -> Events {
+        match self {
+            RiskParameterChange::AddMarket {
+                asset_id,
+                dtoken,
+                ticker_id,
+                ltv,
+                lth,
+            } => Events::parameter_change(
+                "add_market",
+                Some(asset_id.clone()),
+                json!({ "dtoken": dtoken, "ticker_id": ticker_id, "ltv": ltv, "lth": lth }),
+            ),
+            RiskParameterChange::RemoveMarket { asset_id } => {
+                Events::parameter_change("remove_market", Some(asset_id.clone()), ())
+            }
+            RiskParameterChange::SetLiquidationIncentive(value) => {
+                Events::parameter_change("liquidation_incentive", None, value)
+            }
+            RiskParameterChange::SetMaxLiquidationIncentive(value) => {
+                Events::parameter_change("max_liquidation_incentive", None, value)
+            }
+            RiskParameterChange::GrantRole(role, account) => Events::parameter_change(
+                "grant_role",
+                None,
+                json!({ "role": role, "account_id": account }),
+            ),
+            RiskParameterChange::RevokeRole(role, account) => Events::parameter_change(
+                "revoke_role",
+                None,
+                json!({ "role": role, "account_id": account }),
+            ),
+            RiskParameterChange::SetGuardian(account) => {
+                Events::parameter_change("guardian", None, account)
+            }
+            RiskParameterChange::SetTimelockDelay(delay) => {
+                Events::parameter_change("timelock_delay", None, delay)
+            }
+            RiskParameterChange::SetMarketCloseFactor {
+                asset_id,
+                close_factor,
+            } => Events::parameter_change("close_factor", Some(asset_id.clone()), close_factor),
+            RiskParameterChange::SetMarketIsCollateral {
+                asset_id,
+                is_collateral,
+            } => Events::parameter_change("is_collateral", Some(asset_id.clone()), is_collateral),
+            RiskParameterChange::SetMarketDebtCeiling {
+                asset_id,
+                debt_ceiling,
+            } => Events::parameter_change("debt_ceiling", Some(asset_id.clone()), debt_ceiling),
+            RiskParameterChange::SetMarketBorrowableInIsolation {
+                asset_id,
+                is_borrowable_in_isolation,
+            } => Events::parameter_change(
+                "is_borrowable_in_isolation",
+                Some(asset_id.clone()),
+                is_borrowable_in_isolation,
+            ),
+            RiskParameterChange::SetMarketEModeCategory {
+                asset_id,
+                category_id,
+            } => Events::parameter_change("e_mode_category", Some(asset_id.clone()), category_id),
+            RiskParameterChange::SetMaxPriceDeviation(value) => {
+                Events::parameter_change("max_price_deviation", None, value)
+            }
+            RiskParameterChange::SetLiquidationThreshold(value) => {
+                Events::parameter_change("liquidation_threshold", None, value)
+            }
+            RiskParameterChange::SetEModeCategory {
+                category_id,
+                category,
+            } => Events::parameter_change(
+                "e_mode_categories",
+                None,
+                json!({ "category_id": category_id, "category": category }),
+            ),
+            RiskParameterChange::RemoveEModeCategory { category_id } => Events::parameter_change(
+                "e_mode_categories",
+                None,
+                json!({ "category_id": category_id, "category": null }),
+            ),
+            RiskParameterChange::SetMarketMaxPriceAge {
+                asset_id,
+                max_price_age,
+            } => Events::parameter_change("max_price_age", Some(asset_id.clone()), max_price_age),
+            RiskParameterChange::SetMarketBorrowCap {
+                asset_id,
+                borrow_cap,
+            } => Events::parameter_change("borrow_cap", Some(asset_id.clone()), borrow_cap),
+        }
+    }
+}
+
+impl Contract {
+    /// Applies the change scheduled through the timelock or made instantly
+    pub(crate) fn apply_risk_parameter_change(&mut self, change: RiskParameterChange) {
+        let event = change.get_event();
+        match change {
+            RiskParameterChange::AddMarket {
+                asset_id,
+                dtoken,
+                ticker_id,
+                ltv,
+                lth,
+            } => self.insert_market(asset_id, dtoken, ticker_id, ltv, lth),
+            RiskParameterChange::RemoveMarket { asset_id } => self.delete_market(asset_id),
+            RiskParameterChange::SetLiquidationIncentive(value) => {
+                self.liquidation_incentive = value
+            }
+            RiskParameterChange::SetMaxLiquidationIncentive(value) => {
+                self.max_liquidation_incentive = value
+            }
+            RiskParameterChange::GrantRole(role, account) => self.roles.grant(role, account),
+            RiskParameterChange::RevokeRole(role, account) => self.roles.revoke(role, &account),
+            RiskParameterChange::SetGuardian(account) => self.timelock.guardian = account,
+            RiskParameterChange::SetTimelockDelay(delay) => self.timelock.delay = delay,
+            RiskParameterChange::SetMarketCloseFactor {
+                asset_id,
+                close_factor,
+            } => {
+                require!(
+                    close_factor > Ratio::zero() && close_factor <= Ratio::one(),
+                    "Close factor should be in range (0, 1]"
+                );
+                self.update_market(&asset_id, |market| market.close_factor = close_factor)
+            }
+            RiskParameterChange::SetMarketIsCollateral {
+                asset_id,
+                is_collateral,
+            } => self.update_market(&asset_id, |market| market.is_collateral = is_collateral),
+            RiskParameterChange::SetMarketDebtCeiling {
+                asset_id,
+                debt_ceiling,
+            } => self.update_market(&asset_id, |market| market.debt_ceiling = debt_ceiling),
+            RiskParameterChange::SetMarketBorrowableInIsolation {
+                asset_id,
+                is_borrowable_in_isolation,
+            } => self.update_market(&asset_id, |market| {
+                market.is_borrowable_in_isolation = is_borrowable_in_isolation
+            }),
+            RiskParameterChange::SetMarketEModeCategory {
+                asset_id,
+                category_id,
+            } => {
+                require!(
+                    category_id.map_or(true, |category_id| self
+                        .e_mode_categories
+                        .get(&category_id)
+                        .is_some()),
+                    "E-mode category doesnt exist"
+                );
+                self.update_market(&asset_id, |market| market.e_mode_category = category_id)
+            }
+            RiskParameterChange::SetMaxPriceDeviation(value) => self.max_price_deviation = value,
+            RiskParameterChange::SetLiquidationThreshold(value) => {
+                self.liquidation_health_factor_threshold = value
+            }
+            RiskParameterChange::SetEModeCategory {
+                category_id,
+                category,
+            } => {
+                require!(
+                    category.ltv <= category.lth && category.lth <= Ratio::one(),
+                    "E-mode category LTV should not exceed its liquidation threshold"
+                );
+                self.e_mode_categories.insert(&category_id, &category);
+            }
+            RiskParameterChange::RemoveEModeCategory { category_id } => {
+                require!(
+                    self.markets
+                        .values()
+                        .all(|market| market.e_mode_category != Some(category_id)),
+                    "E-mode category is assigned to markets"
+                );
+                self.e_mode_categories.remove(&category_id);
+            }
+            RiskParameterChange::SetMarketMaxPriceAge {
+                asset_id,
+                max_price_age,
+            } => self.update_market(&asset_id, |market| market.max_price_age = max_price_age),
+            RiskParameterChange::SetMarketBorrowCap {
+                asset_id,
+                borrow_cap,
+            } => self.update_market(&asset_id, |market| market.borrow_cap = borrow_cap),
+        }
+        event.emit();
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Queues the change which could be executed after the ETA, returns the proposal id
+    pub fn schedule_risk_parameter_change(
+        &mut self,
+        change: RiskParameterChange,
+        eta: BlockHeight,
+    ) -> u64 {
+        self.roles.assert_role(change.get_role());
+
+        self.timelock.schedule(change, eta)
+    }
+
+    pub fn execute_risk_parameter_change(&mut self, proposal_id: u64) {
+        self.roles.assert_role(Role::Executor);
+
+        let change = self.timelock.take_executable(proposal_id);
+        self.apply_risk_parameter_change(change);
+    }
+
+    /// Cancels the queued change, could be called by the guardian only
+    pub fn cancel_risk_parameter_change(
+        &mut self,
+        proposal_id: u64,
+    ) -> Proposal<RiskParameterChange> {
+        self.timelock.cancel(proposal_id)
+    }
+
+    /// Changes are applied instantly while the timelock delay is zero
+    pub fn set_timelock_delay(&mut self, delay: BlockHeight) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetTimelockDelay(delay));
+    }
+
+    pub fn set_guardian(&mut self, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetGuardian(account));
+    }
+
+    pub fn get_timelock_delay(&self) -> BlockHeight {
+        self.timelock.delay
+    }
+
+    pub fn get_guardian(&self) -> AccountId {
+        self.timelock.guardian.clone()
+    }
+
+    pub fn view_risk_parameter_changes(&self) -> Vec<Proposal<RiskParameterChange>> {
+        self.timelock.get_proposals()
+    }
+
+    pub fn view_risk_parameter_change(
+        &self,
+        proposal_id: u64,
+    ) -> Option<Proposal<RiskParameterChange>> {
+        self.timelock.get_proposal(proposal_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use general::events::parse_event;
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    fn init() -> Contract {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .build());
+
+        let mut controller_contract = Contract::new(Config {
+            owner_id: bob(),
+            oracle_account_id: bob(),
+        });
+        controller_contract.set_timelock_delay(100);
+
+        controller_contract
+    }
+
+    #[test]
+    fn test_scheduled_risk_parameter_change() {
+        let mut controller_contract = init();
+        let incentive = Ratio::from_str("0.1").unwrap();
+
+        let proposal_id = controller_contract.schedule_risk_parameter_change(
+            RiskParameterChange::SetLiquidationIncentive(incentive),
+            100,
+        );
+        assert_eq!(controller_contract.view_risk_parameter_changes().len(), 1);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .block_index(100)
+            .build());
+        controller_contract.execute_risk_parameter_change(proposal_id);
+
+        assert_eq!(
+            controller_contract.get_liquidation_incentive(),
+            incentive + Ratio::one()
+        );
+        let event = parse_event(get_logs().last().unwrap()).unwrap();
+        assert_eq!(event.event, "parameter_change");
+        assert_eq!(
+            event.data,
+            json!({
+                "parameter": "liquidation_incentive",
+                "asset_id": null,
+                "value": incentive
+            })
+        );
+        assert!(controller_contract
+            .view_risk_parameter_change(proposal_id)
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "The change should be scheduled through the timelock")]
+    fn test_instant_risk_parameter_change_with_timelock() {
+        let mut controller_contract = init();
+
+        controller_contract.set_liquidation_incentive(Ratio::from_str("0.1").unwrap());
+    }
+
+    #[test]
+    fn test_scheduled_market_parameter_change() {
+        let mut controller_contract = init();
+        controller_contract.insert_market(
+            AccountId::new_unchecked("weth.near".to_string()),
+            AccountId::new_unchecked("dweth.near".to_string()),
+            "weth".to_string(),
+            Ratio::from_str("0.4").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        let proposal_id = controller_contract.schedule_risk_parameter_change(
+            RiskParameterChange::SetMarketCloseFactor {
+                asset_id: AccountId::new_unchecked("weth.near".to_string()),
+                close_factor: Ratio::one(),
+            },
+            100,
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .block_index(100)
+            .build());
+        controller_contract.execute_risk_parameter_change(proposal_id);
+
+        assert_eq!(
+            controller_contract
+                .get_market_by_dtoken(AccountId::new_unchecked("dweth.near".to_string()))
+                .close_factor,
+            Ratio::one()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The change should be scheduled through the timelock")]
+    fn test_instant_market_parameter_change_with_timelock() {
+        let mut controller_contract = init();
+
+        controller_contract.set_max_price_deviation(Ratio::from_str("0.5").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "The change should be scheduled through the timelock")]
+    fn test_instant_borrow_cap_change_with_timelock() {
+        let mut controller_contract = init();
+        controller_contract.insert_market(
+            AccountId::new_unchecked("weth.near".to_string()),
+            AccountId::new_unchecked("dweth.near".to_string()),
+            "weth".to_string(),
+            Ratio::from_str("0.4").unwrap(),
+            Ratio::from_str("0.8").unwrap(),
+        );
+
+        controller_contract.set_market_borrow_cap(
+            AccountId::new_unchecked("weth.near".to_string()),
+            Some(U128(100)),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal doesn't exist")]
+    fn test_cancelled_risk_parameter_change() {
+        let mut controller_contract = init();
+
+        let proposal_id = controller_contract.schedule_risk_parameter_change(
+            RiskParameterChange::GrantRole(Role::Executor, alice()),
+            100,
+        );
+        controller_contract.cancel_risk_parameter_change(proposal_id);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .block_index(100)
+            .build());
+        controller_contract.execute_risk_parameter_change(proposal_id);
+    }
+}
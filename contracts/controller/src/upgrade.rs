@@ -19,8 +19,7 @@ impl Upgradable for Contract {
     #[init(ignore_state)]
     #[private]
     fn migrate() -> Self {
-        let contract: Contract = env::state_read().expect("Contract is not initialized");
-        contract
+        Contract::migrate_state(StateVersion::read())
     }
 
     fn get_version(&self) -> String {
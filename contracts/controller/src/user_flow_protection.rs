@@ -25,12 +25,23 @@ impl Contract {
             self.is_market_registered(env::predecessor_account_id()),
             format!("Market {} is not registered", env::predecessor_account_id())
         );
+        if let Some(method) = MethodType::from_action(&action) {
+            self.assert_action_not_paused(&env::predecessor_account_id(), method);
+        }
 
         require!(
             user.is_consistent(),
             format!("Account {} is inconsistent", env::signer_account_id())
         );
 
+        match &action {
+            Actions::Supply | Actions::Borrow { .. } | Actions::FixedRateBorrow { .. } => {
+                self.assert_storage_covered(&env::signer_account_id())
+            }
+            Actions::DelegatedBorrow { delegator, .. } => self.assert_storage_covered(delegator),
+            _ => {}
+        }
+
         if !self.mutex.try_lock(&env::signer_account_id()) {
             panic!(
                 "failed to acquire {} action mutex for account {account}",
@@ -17,6 +17,15 @@ pub struct UserProfile {
 
     /// Borrow data with block height and APY
     pub borrow_data: HashMap<AccountId, BorrowData>,
+
+    /// Dtoken address -> Whether supplies are used as collateral, market default if not set
+    pub collaterals: HashMap<AccountId, bool>,
+
+    /// E-mode category the account is opted into
+    pub e_mode_category: Option<u8>,
+
+    /// Delegatee -> Dtoken address -> Borrowing power delegated by the account
+    pub credit_delegations: HashMap<AccountId, HashMap<AccountId, CreditDelegation>>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -43,13 +52,22 @@ pub struct Consistency {
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
-#[derive(Default)]
 pub struct BorrowData {
-    /// Borrow block height
-    pub borrow_block: BlockHeight,
+    /// Market borrow index at the moment of the last borrows update
+    pub borrow_index: Ratio,
 
-    /// Borrow APY
-    pub borrow_rate: Ratio,
+    /// Fixed rate part of the borrows, the rest of them is variable rate
+    pub fixed_rate_debt: Option<FixedRateDebt>,
+}
+
+// Cannot derive Default as borrow index by default should be one
+impl Default for BorrowData {
+    fn default() -> Self {
+        BorrowData {
+            borrow_index: Ratio::one(),
+            fixed_rate_debt: None,
+        }
+    }
 }
 
 impl UserProfile {
@@ -96,7 +114,7 @@ impl UserProfile {
         self.borrow_data.insert(token_address, borrow_data);
     }
 
-    pub fn get_borrow_data(&mut self, token_address: AccountId) -> BorrowData {
+    pub fn get_borrow_data(&self, token_address: AccountId) -> BorrowData {
         self.borrow_data
             .get(&token_address)
             .unwrap_or(&BorrowData::default())
@@ -106,7 +124,7 @@ impl UserProfile {
 
 #[near_bindgen]
 impl Contract {
-    /// The method can be called only by Admin, Controller, Dtoken contracts
+    /// The method can be called only by Executor, Controller, Dtoken contracts
     pub fn set_account_consistency(
         &mut self,
         account: AccountId,
@@ -114,8 +132,8 @@ impl Contract {
         block: BlockHeight,
     ) {
         require!(
-            self.is_valid_admin_call() || self.is_dtoken_caller(),
-            "This functionality is allowed to be called by admin, contract or dtoken's contract only"
+            self.roles.is_role_call(Role::Executor) || self.is_dtoken_caller(),
+            "This functionality is allowed to be called by executor, contract or dtoken's contract only"
         );
 
         self.user_profiles
@@ -20,6 +20,13 @@ pub struct AccountData {
     pub user_profile: WrappedUserProfile,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountHealth {
+    pub account_id: AccountId,
+    pub health_factor_ratio: WRatio,
+}
+
 impl Default for AccountData {
     fn default() -> Self {
         AccountData {
@@ -52,7 +59,12 @@ impl Contract {
         let users = self
             .user_profiles
             .iter()
-            .filter(|(_, user_profile)| !user_profile.account_borrows.is_empty())
+            .filter(|(_, user_profile)| {
+                user_profile
+                    .account_borrows
+                    .values()
+                    .any(|balance| *balance > 0)
+            })
             .map(|(account_id, _)| account_id)
             .collect::<Vec<AccountId>>();
 
@@ -84,29 +96,93 @@ impl Contract {
             .collect::<Vec<AccountData>>();
     }
 
+    /// Accounts with borrows whose health factor is below the liquidation threshold increased by
+    /// the margin, liquidatable ones included. Pages go over all of the accounts, so a page could
+    /// have less matching accounts than the limit
+    pub fn view_accounts_near_liquidation(
+        &self,
+        margin: Ratio,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<AccountHealth> {
+        let max_health_factor = self.get_liquidation_threshold() * (Ratio::one() + margin);
+
+        self.user_profiles
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter(|(_, user_profile)| {
+                user_profile
+                    .account_borrows
+                    .values()
+                    .any(|balance| *balance > 0)
+            })
+            .filter_map(|(account_id, _)| {
+                self.try_get_health_factor(&account_id)
+                    .filter(|health_factor| *health_factor <= max_health_factor)
+                    .map(|health_factor| AccountHealth {
+                        account_id,
+                        health_factor_ratio: WRatio::from(health_factor),
+                    })
+            })
+            .collect()
+    }
+
+    /// Collateral dtoken -> Price at which the account becomes liquidatable while other prices
+    /// stay the same, `None` if the collateral price alone cannot make it liquidatable
+    pub fn view_liquidation_prices(
+        &self,
+        user_id: AccountId,
+    ) -> HashMap<AccountId, Option<WBalance>> {
+        self.calculate_liquidation_prices(&self.get_simulated_profile(&user_id))
+    }
+
     pub fn view_prices(&self, dtokens: Vec<AccountId>) -> HashMap<AccountId, Price> {
         self.get_prices_for_dtokens(dtokens)
     }
 
     pub fn view_borrow_max(&self, user_id: AccountId, dtoken_id: AccountId) -> WBalance {
+        let user_profile = self.user_profiles.get(&user_id).unwrap_or_default();
+        let isolated_collateral = self.get_isolated_collateral(&user_profile);
+        if isolated_collateral.is_some()
+            && !self
+                .get_market_by_dtoken(dtoken_id.clone())
+                .is_borrowable_in_isolation
+        {
+            return WBalance::from(0);
+        }
+
         let borrows = self.get_total_borrows(&user_id).0;
         let accrued_interest = self.calculate_accrued_borrow_interest(user_id.clone());
         let theoretical_max_borrow = self.get_theoretical_borrows_max(user_id);
 
-        let max_borrow = if theoretical_max_borrow.0 > (borrows + accrued_interest) {
+        let mut max_borrow = if theoretical_max_borrow.0 > (borrows + accrued_interest) {
             BigDecimal::from(theoretical_max_borrow.0 - (borrows + accrued_interest))
         } else {
             BigBalance::zero()
         };
+        if let Some(isolated_dtoken) = isolated_collateral {
+            max_borrow = min(
+                max_borrow,
+                BigDecimal::from(self.get_isolated_debt_headroom(&isolated_dtoken)),
+            );
+        }
 
-        let price = Ratio::from(self.get_price(&dtoken_id).unwrap().value.0);
+        let price = Ratio::from(self.get_fresh_price(&dtoken_id).value.0);
         let max_borrow_in_token = max_borrow / price;
 
         max_borrow_in_token.into()
     }
 
     pub fn view_withdraw_max(&self, user_id: AccountId, dtoken_id: AccountId) -> WBalance {
-        let supplies = self.get_total_supplies(&user_id);
+        let user_profile = self.user_profiles.get(&user_id).unwrap_or_default();
+        if !self.is_collateral(&user_profile, &dtoken_id) {
+            return self.get_entity_by_token(Supply, user_id, dtoken_id).into();
+        }
+
+        let supplies: USD = self
+            .calculate_assets_price(&self.get_account_collaterals(&user_profile))
+            .into();
         let collaterals = self.get_collaterals_by_borrows(user_id.clone());
         let accrued_interest = self.calculate_accrued_borrow_interest(user_id.clone());
 
@@ -116,7 +192,7 @@ impl Contract {
             BigBalance::zero()
         };
 
-        let price = self.get_price(&dtoken_id).unwrap();
+        let price = self.get_fresh_price(&dtoken_id);
         let max_withdraw_in_token = max_withdraw_limit / BigBalance::from(price.value.0);
         let supply_by_token = self.get_entity_by_token(Supply, user_id, dtoken_id);
 
@@ -127,13 +203,14 @@ impl Contract {
 #[cfg(test)]
 mod tests {
     use crate::ActionType::{Borrow, Supply};
-    use crate::{Config, Contract, OraclePriceHandlerHook, PriceJsonList};
+    use crate::{AccountHealth, Config, Contract, OraclePriceHandlerHook, PriceJsonList};
     use general::ratio::Ratio;
     use general::{Price, WRatio, ONE_TOKEN};
     use near_sdk::json_types::U128;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
     use near_sdk::test_utils::VMContextBuilder;
     use near_sdk::{testing_env, AccountId};
+    use std::collections::HashMap;
     use std::str::FromStr;
 
     pub fn init_test_env() -> (Contract, AccountId, AccountId, AccountId) {
@@ -190,7 +267,7 @@ mod tests {
         ];
 
         controller_contract.oracle_on_data(PriceJsonList {
-            block_height: 83456999,
+            block_height: 0,
             price_list: prices,
         });
 
@@ -284,6 +361,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_view_accounts_near_liquidation() {
+        let (mut near_contract, wnear_market, weth_market, user) = init_test_env();
+
+        // Health factor is (100 * LTH(0.8) / 50 * Volatility(0.8)) = 200%
+        near_contract.set_entity_by_token(Supply, alice(), weth_market.clone(), 100 * ONE_TOKEN);
+        near_contract.set_entity_by_token(Borrow, alice(), wnear_market.clone(), 50 * ONE_TOKEN);
+
+        // Health factor is (100 * LTH(0.8) / 95 * Volatility(0.8)) = 105.26%
+        near_contract.set_entity_by_token(Supply, bob(), weth_market.clone(), 100 * ONE_TOKEN);
+        near_contract.set_entity_by_token(Borrow, bob(), wnear_market, 95 * ONE_TOKEN);
+
+        near_contract.set_entity_by_token(Supply, user, weth_market, 100 * ONE_TOKEN);
+
+        let margin = Ratio::from_str("0.1").unwrap();
+        assert_eq!(
+            near_contract.view_accounts_near_liquidation(margin, 0, 3),
+            vec![AccountHealth {
+                account_id: bob(),
+                health_factor_ratio: WRatio::from(Ratio::from(100u128) / Ratio::from(95u128)),
+            }]
+        );
+        assert!(near_contract
+            .view_accounts_near_liquidation(margin, 0, 1)
+            .is_empty());
+        assert!(near_contract
+            .view_accounts_near_liquidation(Ratio::zero(), 0, 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_view_liquidation_prices() {
+        let (mut near_contract, wnear_market, weth_market, user) = init_test_env();
+
+        near_contract.set_entity_by_token(
+            Supply,
+            user.clone(),
+            weth_market.clone(),
+            100 * ONE_TOKEN,
+        );
+        near_contract.set_entity_by_token(
+            Borrow,
+            user.clone(),
+            wnear_market.clone(),
+            50 * ONE_TOKEN,
+        );
+
+        // (100 * 1.0 * LTH(0.8) / 50 * 2.0 * Volatility(0.8)) reaches the threshold of 100%
+        assert_eq!(
+            near_contract.view_liquidation_prices(user),
+            HashMap::from([(weth_market, Some(U128(10000)))])
+        );
+
+        // Collateral and borrows of the same market fall in price together
+        near_contract.set_entity_by_token(Supply, alice(), wnear_market.clone(), 100 * ONE_TOKEN);
+        near_contract.set_entity_by_token(Borrow, alice(), wnear_market.clone(), 50 * ONE_TOKEN);
+        assert_eq!(
+            near_contract.view_liquidation_prices(alice()),
+            HashMap::from([(wnear_market, None)])
+        );
+    }
+
     #[test]
     fn test_view_withdraw_max_without_borrows() {
         let (mut near_contract, wnear_market, weth_market, user) = init_test_env();
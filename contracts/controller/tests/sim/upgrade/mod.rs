@@ -0,0 +1 @@
+mod upgrade_migrate_state;
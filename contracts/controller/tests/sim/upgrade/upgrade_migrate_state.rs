@@ -0,0 +1,228 @@
+// IMPORTANT! The previous wasm should keep the state layout of the oldest supported state version,
+// it is built from the pre-versioning sources with `build_previous.sh`
+near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
+    CONTROLLER_CURRENT_WASM_BYTES => "../target/wasm32-unknown-unknown/release/controller.wasm",
+    CONTROLLER_PREVIOUS_WASM_BYTES => "tests/sim/contracts/controller_v1.wasm",
+}
+
+use controller::ContractContract as Controller;
+use controller::StateVersion;
+use general::roles::Role;
+use general::MigrationProgress;
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::{json, Value};
+use near_sdk::{AccountId, Balance, Gas};
+use near_sdk_sim::{
+    call, deploy, init_simulator, to_yocto, view, ContractAccount, ExecutionResult,
+};
+
+const SUPPLY_AMOUNTS: [Balance; 3] = [100, 200, 300];
+const BORROW_AMOUNTS: [Balance; 3] = [10, 0, 30];
+
+fn dtoken_id() -> AccountId {
+    AccountId::new_unchecked("dweth.near".to_string())
+}
+
+fn user_id(index: usize) -> AccountId {
+    AccountId::new_unchecked(format!("user{}.near", index))
+}
+
+fn call_previous(
+    controller: &ContractAccount<Controller>,
+    method: &str,
+    args: Value,
+) -> ExecutionResult {
+    controller.user_account.call(
+        controller.account_id(),
+        method,
+        args.to_string().as_bytes(),
+        near_sdk_sim::DEFAULT_GAS,
+        0,
+    )
+}
+
+fn upgrade_controller(controller: &ContractAccount<Controller>) -> ExecutionResult {
+    const MAX_GAS: Gas = Gas(Gas::ONE_TERA.0 * 300);
+
+    controller
+        .user_account
+        .create_transaction(controller.account_id())
+        .function_call(
+            "upgrade".to_string(),
+            CONTROLLER_CURRENT_WASM_BYTES.to_vec(),
+            MAX_GAS.0,
+            0,
+        )
+        .submit()
+}
+
+fn upgrade_fixture() -> ContractAccount<Controller> {
+    let root = init_simulator(None);
+    let croot = root.create_user("controller".parse().unwrap(), to_yocto("1200000"));
+
+    let controller = deploy!(
+        contract: Controller,
+        contract_id: AccountId::new_unchecked("controller_contract".to_string()),
+        bytes: &CONTROLLER_PREVIOUS_WASM_BYTES,
+        signer_account: croot
+    );
+
+    call_previous(
+        &controller,
+        "new",
+        json!({
+            "config": {
+                "owner_id": controller.account_id(),
+                "oracle_account_id": "oracle"
+            }
+        }),
+    )
+    .assert_success();
+
+    call_previous(
+        &controller,
+        "add_market",
+        json!({
+            "asset_id": "weth.near",
+            "dtoken": dtoken_id(),
+            "ticker_id": "weth",
+            "ltv": "0.6",
+            "lth": "0.8"
+        }),
+    )
+    .assert_success();
+
+    call_previous(
+        &controller,
+        "upsert_price",
+        json!({
+            "dtoken_id": dtoken_id(),
+            "price": {
+                "ticker_id": "weth",
+                "value": "10000",
+                "volatility": "100",
+                "fraction_digits": 4
+            }
+        }),
+    )
+    .assert_success();
+
+    // The previous version doesn't check the caller of the balance changes
+    for (index, (supply, borrow)) in SUPPLY_AMOUNTS.iter().zip(BORROW_AMOUNTS).enumerate() {
+        call_previous(
+            &controller,
+            "increase_supplies",
+            json!({
+                "account": user_id(index),
+                "token_address": dtoken_id(),
+                "token_amount": U128(*supply)
+            }),
+        )
+        .assert_success();
+
+        if borrow > 0 {
+            call_previous(
+                &controller,
+                "increase_borrows",
+                json!({
+                    "account": user_id(index),
+                    "token_address": dtoken_id(),
+                    "token_amount": U128(borrow),
+                    "borrow_block": 0,
+                    "borrow_rate": "0"
+                }),
+            )
+            .assert_success();
+        }
+    }
+
+    controller
+}
+
+fn view_entity(controller: &ContractAccount<Controller>, action: &str, index: usize) -> Balance {
+    controller
+        .user_account
+        .view(
+            controller.account_id(),
+            "get_entity_by_token",
+            json!({
+                "action": action,
+                "user_id": user_id(index),
+                "token_address": dtoken_id()
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap_json()
+}
+
+#[test]
+fn test_upgrade_migrate_state() {
+    let controller = upgrade_fixture();
+
+    let old_prices = view!(controller.view_prices(vec![dtoken_id()])).unwrap_json_value();
+    let old_markets = view!(controller.view_markets()).unwrap_json_value();
+
+    upgrade_controller(&controller).assert_success();
+
+    assert_eq!(
+        view!(controller.get_state_version()).unwrap_json::<StateVersion>(),
+        controller::CURRENT_STATE_VERSION
+    );
+
+    // user profiles are migrated in batches, market actions are paused until then
+    assert_eq!(
+        view!(controller.get_profiles_migration()).unwrap_json::<Option<MigrationProgress>>(),
+        Some(MigrationProgress {
+            migrated: 0,
+            remaining: SUPPLY_AMOUNTS.len() as u64
+        })
+    );
+    let remaining_profiles: u64 = call!(
+        controller.user_account,
+        controller.migrate_user_profiles(Some(2))
+    )
+    .unwrap_json();
+    assert_eq!(remaining_profiles, 1);
+    let remaining_profiles: u64 = call!(
+        controller.user_account,
+        controller.migrate_user_profiles(None)
+    )
+    .unwrap_json();
+    assert_eq!(remaining_profiles, 0);
+    assert_eq!(
+        view!(controller.get_profiles_migration()).unwrap_json::<Option<MigrationProgress>>(),
+        None
+    );
+
+    // markets keep their parameters and get the default risk parameters
+    let markets = view!(controller.view_markets()).unwrap_json_value();
+    assert_eq!(markets.as_array().unwrap().len(), 1);
+    for field in ["asset_id", "dtoken", "ticker_id"] {
+        assert_eq!(old_markets[0][field], markets[0][field]);
+    }
+    // total borrows of the market are restored from the user profiles
+    assert_eq!(
+        markets[0]["total_borrows"],
+        json!(U128(BORROW_AMOUNTS.iter().sum()))
+    );
+    assert_eq!(
+        old_prices,
+        view!(controller.view_prices(vec![dtoken_id()])).unwrap_json_value()
+    );
+
+    for (index, (supply, borrow)) in SUPPLY_AMOUNTS.iter().zip(BORROW_AMOUNTS).enumerate() {
+        assert_eq!(view_entity(&controller, "Supply", index), *supply);
+        assert_eq!(view_entity(&controller, "Borrow", index), borrow);
+    }
+
+    // the admin gets all the roles, the oracle account is kept
+    assert!(view!(controller.has_role(Role::Owner, controller.account_id())).unwrap_json::<bool>());
+    assert!(
+        view!(controller.has_role(Role::RiskManager, controller.account_id()))
+            .unwrap_json::<bool>()
+    );
+    assert!(
+        view!(controller.has_role(Role::Oracle, "oracle".parse().unwrap())).unwrap_json::<bool>()
+    );
+}
@@ -0,0 +1,67 @@
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::json;
+use near_sdk::{log, serde_json};
+
+pub const STANDARD: &str = "nep297";
+pub const VERSION: &str = "1.0.0";
+pub const EVENT_JSON_STR: &str = "EVENT_JSON:";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventMessage {
+    pub standard: String,
+    pub version: String,
+    pub event: serde_json::Value,
+    pub data: serde_json::Value,
+}
+
+/// Logs NEP-297 event of the enum serialized with `#[serde(tag = "event", content = "data")]`
+pub fn emit_event<T: ?Sized + Serialize>(data: &T) {
+    let result = json!(data);
+    let event_json = json!(EventMessage {
+        standard: STANDARD.to_string(),
+        version: VERSION.to_string(),
+        event: result["event"].clone(),
+        data: result["data"].clone()
+    })
+    .to_string();
+    log!("{}{}", EVENT_JSON_STR, event_json);
+}
+
+/// Parses NEP-297 event logged by `emit_event`
+pub fn parse_event(log: &str) -> Option<EventMessage> {
+    log.strip_prefix(EVENT_JSON_STR)
+        .and_then(|event_json| serde_json::from_str(event_json).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    #[serde(tag = "event", content = "data")]
+    #[serde(rename_all = "snake_case")]
+    enum TestEvent {
+        PriceUpdate { ticker_id: String },
+    }
+
+    #[test]
+    fn test_emit_event() {
+        emit_event(&TestEvent::PriceUpdate {
+            ticker_id: "weth".to_string(),
+        });
+
+        let logs = get_logs();
+        assert_eq!(
+            logs,
+            vec![
+                r#"EVENT_JSON:{"data":{"ticker_id":"weth"},"event":"price_update","standard":"nep297","version":"1.0.0"}"#
+            ]
+        );
+        let event = parse_event(&logs[0]).unwrap();
+        assert_eq!(event.event, "price_update");
+        assert_eq!(event.data["ticker_id"], "weth");
+    }
+}
@@ -1,13 +1,18 @@
+pub mod events;
 pub mod percent;
 pub mod ratio;
+pub mod roles;
+pub mod storage;
+pub mod timelock;
 
 use crate::percent::WPercent;
+use crate::ratio::{BigBalance, Ratio};
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{near_bindgen, AccountId};
-use near_sdk::{Balance, Gas};
+use near_sdk::{Balance, BlockHeight, Gas};
 use std::fmt;
 
 pub const NO_DEPOSIT: Balance = 0;
@@ -24,7 +29,7 @@ pub type WRatio = U128;
 pub type Digits = u32;
 
 #[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 #[derive(Debug)]
 pub struct Price {
@@ -52,6 +57,48 @@ impl Default for Price {
     }
 }
 
+/// Fixed rate debt snapshot, grows with simple interest at the fixed rate
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[derive(Debug)]
+pub struct FixedRateDebt {
+    /// Debt amount at the snapshot block
+    pub amount: WBalance,
+
+    /// Fixed borrow rate per block
+    pub rate_per_block: WRatio,
+
+    /// Block of the snapshot
+    pub block: BlockHeight,
+}
+
+impl FixedRateDebt {
+    /// Debt with interest accrued from the snapshot up to the given block
+    pub fn get_debt(&self, block: BlockHeight) -> Balance {
+        let blocks = block.saturating_sub(self.block);
+
+        (BigBalance::from(self.amount.0)
+            * (Ratio::one() + Ratio::from(self.rate_per_block) * BigBalance::from(blocks)))
+        .round_u128()
+    }
+
+    /// Interest accrued by the debt per block
+    pub fn get_interest_per_block(&self) -> Ratio {
+        BigBalance::from(self.amount.0) * Ratio::from(self.rate_per_block)
+    }
+}
+
+/// Progress of the state migration which is done in batches after the upgrade
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MigrationProgress {
+    /// Number of the migrated entries
+    pub migrated: u64,
+
+    /// Number of the entries which are not migrated yet
+    pub remaining: u64,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 #[derive(Debug)]
@@ -69,6 +116,20 @@ pub enum Actions {
     },
     Reserve,
     Deposit,
+    LiquidationDeposit,
+    FixedRateBorrow {
+        account_to_borrow: AccountId,
+        term: BlockHeight,
+    },
+    FixedRateRepay,
+    FlashLoanRepay,
+    DelegatedBorrow {
+        delegator: AccountId,
+        delegatee: AccountId,
+    },
+    FundCampaign {
+        campaign_id: String,
+    },
 }
 
 impl fmt::Display for Actions {
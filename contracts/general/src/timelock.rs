@@ -0,0 +1,162 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, require, AccountId, BlockHeight, IntoStorageKey};
+
+/// Change queued in the timelock
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal<T> {
+    pub id: u64,
+
+    pub action: T,
+
+    /// Block after which the proposal could be executed
+    pub eta: BlockHeight,
+}
+
+/// Queue of changes which could be executed only after the delay and cancelled by the guardian
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Timelock<T> {
+    /// Account which is allowed to cancel queued proposals
+    pub guardian: AccountId,
+
+    /// Min amount of blocks between scheduling and execution, changes are applied instantly if zero
+    pub delay: BlockHeight,
+
+    proposals: UnorderedMap<u64, Proposal<T>>,
+
+    next_proposal_id: u64,
+}
+
+impl<T: BorshSerialize + BorshDeserialize> Timelock<T> {
+    pub fn new<S: IntoStorageKey>(prefix: S, guardian: AccountId, delay: BlockHeight) -> Self {
+        Self {
+            guardian,
+            delay,
+            proposals: UnorderedMap::new(prefix),
+            next_proposal_id: 0,
+        }
+    }
+
+    pub fn assert_instant_change(&self) {
+        require!(
+            self.delay == 0,
+            "The change should be scheduled through the timelock"
+        );
+    }
+
+    pub fn schedule(&mut self, action: T, eta: BlockHeight) -> u64 {
+        require!(
+            eta >= env::block_height() + self.delay,
+            "Proposal ETA doesn't satisfy the timelock delay"
+        );
+
+        let id = self.next_proposal_id;
+        self.proposals.insert(&id, &Proposal { id, action, eta });
+        self.next_proposal_id += 1;
+
+        id
+    }
+
+    pub fn cancel(&mut self, id: u64) -> Proposal<T> {
+        require!(
+            env::predecessor_account_id() == self.guardian,
+            "This functionality is allowed to be called by guardian only"
+        );
+
+        self.proposals.remove(&id).expect("Proposal doesn't exist")
+    }
+
+    /// Removes the proposal from the queue and returns its action if ETA is reached
+    pub fn take_executable(&mut self, id: u64) -> T {
+        let proposal = self.proposals.get(&id).expect("Proposal doesn't exist");
+        require!(
+            env::block_height() >= proposal.eta,
+            "Proposal ETA is not reached yet"
+        );
+
+        self.proposals.remove(&id);
+        proposal.action
+    }
+
+    pub fn get_proposal(&self, id: u64) -> Option<Proposal<T>> {
+        self.proposals.get(&id)
+    }
+
+    pub fn get_proposals(&self) -> Vec<Proposal<T>> {
+        self.proposals.values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn init(block: BlockHeight) -> Timelock<u128> {
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(alice())
+            .block_index(block)
+            .build());
+
+        Timelock::new(b"t".to_vec(), alice(), 100)
+    }
+
+    #[test]
+    fn test_proposal_execution_after_delay() {
+        let mut timelock = init(10);
+        let id = timelock.schedule(42, 110);
+        assert_eq!(timelock.get_proposals().len(), 1);
+
+        testing_env!(VMContextBuilder::new().block_index(110).build());
+        assert_eq!(timelock.take_executable(id), 42);
+        assert!(timelock.get_proposal(id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal ETA doesn't satisfy the timelock delay")]
+    fn test_proposal_before_delay() {
+        let mut timelock = init(10);
+        timelock.schedule(42, 109);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal ETA is not reached yet")]
+    fn test_proposal_execution_before_eta() {
+        let mut timelock = init(10);
+        let id = timelock.schedule(42, 200);
+
+        testing_env!(VMContextBuilder::new().block_index(199).build());
+        timelock.take_executable(id);
+    }
+
+    #[test]
+    fn test_proposal_cancel_by_guardian() {
+        let mut timelock = init(10);
+        let id = timelock.schedule(42, 110);
+
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(bob())
+            .predecessor_account_id(alice())
+            .build());
+        assert_eq!(timelock.cancel(id).action, 42);
+        assert!(timelock.get_proposals().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by guardian only")]
+    fn test_proposal_cancel_by_not_guardian() {
+        let mut timelock = init(10);
+        let id = timelock.schedule(42, 110);
+
+        // the guardian signing the call made through another contract isn't the caller
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(alice())
+            .predecessor_account_id(bob())
+            .build());
+        timelock.cancel(id);
+    }
+}
@@ -43,6 +43,9 @@ near deploy ${CONTRACT_ID} \
         "oracle_account_id":"'$ORACLE_ID'"
     }'
 
+# oracle frees up liquidity slots
+near call ${CONTRACT_ID} grant_role '{"role": "Executor", "account": "'$ORACLE_ID'"}' --accountId ${CONTRACT_ID}
+
 # register limit orders on tokens
 near call $NEAR_TOKEN storage_deposit '{"account_id": "'$CONTRACT_ID'"}' --accountId $CONTRACT_ID --amount 0.25 &
 near call $USDT_TOKEN storage_deposit '{"account_id": "'$CONTRACT_ID'"}' --accountId $CONTRACT_ID --amount 0.25 &
@@ -174,9 +177,3 @@ near call $USDT_TOKEN mint '{
 
 near call $CONTRACT_ID add_token_market '{"token_id": "'$USDT_TOKEN'", "market_id": "'$USDT_MARKET'"}' --accountId $CONTRACT_ID
 near call $CONTRACT_ID add_token_market '{"token_id": "'$NEAR_TOKEN'", "market_id": "'$NEAR_MARKET'"}' --accountId $CONTRACT_ID
-
-near call $USDT_MARKET set_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ID}'" }' --accountId shared_admin.testnet
-near view $USDT_MARKET get_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ID}'" }'
-
-near call controller.$ROOT_ACCOUNT set_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ID}'" }' --accountId controller.$ROOT_ACCOUNT
-near view controller.$ROOT_ACCOUNT get_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ID}'" }'
@@ -39,6 +39,9 @@ near deploy ${CONTRACT_ADDRESS} \
         "oracle_account_id":"oracle.omomo-finance.testnet.'$ROOT_ACCOUNT'"
     }'
 
+# oracle frees up liquidity slots
+near call ${CONTRACT_ADDRESS} grant_role '{"role": "Executor", "account": "oracle.omomo-finance.testnet.'$ROOT_ACCOUNT'"}' --accountId ${CONTRACT_ADDRESS}
+
 # register limit orders on tokens
 near call $NEAR_TOKEN storage_deposit '{"account_id": "'${CONTRACT_ADDRESS}'"}' --accountId ${CONTRACT_ADDRESS} --amount 0.25 &
 near call $USDT_TOKEN storage_deposit '{"account_id": "'${CONTRACT_ADDRESS}'"}' --accountId ${CONTRACT_ADDRESS} --amount 0.25 &
@@ -127,10 +130,4 @@ near call $DEX_ACCOUNT storage_deposit '{"account_id": "'${CONTRACT_ADDRESS}'"}'
 near call ${CONTRACT_ADDRESS} add_token_market '{"token_id": "'$NEAR_TOKEN'", "market_id": "wnear_market.'$ROOT_ACCOUNT'"}' --account_id ${CONTRACT_ADDRESS} &
 near call ${CONTRACT_ADDRESS} add_token_market '{"token_id": "'$USDT_TOKEN'", "market_id": "usdt_market.'$ROOT_ACCOUNT'"}' --account_id ${CONTRACT_ADDRESS} &
 
-near call usdt_market.$ROOT_ACCOUNT set_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ADDRESS}'" }' --accountId shared_admin.testnet
-near view usdt_market.$ROOT_ACCOUNT get_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ADDRESS}'" }'
-
-near call controller.$ROOT_ACCOUNT set_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ADDRESS}'" }' --accountId controller.$ROOT_ACCOUNT
-near view controller.$ROOT_ACCOUNT get_eligible_to_borrow_uncollateralized_account '{ "account": "'${CONTRACT_ADDRESS}'" }'
-
 wait
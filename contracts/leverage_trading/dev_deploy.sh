@@ -23,6 +23,9 @@ near call $CONTRACT_ID --accountId=$CONTRACT_ID new_with_config '{
        "oracle_account_id":"'$ORACLE_ACCOUNT'"
    }'
 
+# oracle frees up liquidity slots
+near call $CONTRACT_ID grant_role '{"role": "Executor", "account": "'$ORACLE_ACCOUNT'"}' --accountId $CONTRACT_ID
+
 # register limit orders on tokens
 near call $NEAR_TOKEN storage_deposit '{"account_id": "'$CONTRACT_ID'"}' --accountId $CONTRACT_ID --amount 0.25 &
 near call $USDT_TOKEN storage_deposit '{"account_id": "'$CONTRACT_ID'"}' --accountId $CONTRACT_ID --amount 0.25 &
@@ -1152,10 +1152,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -51,6 +51,7 @@ impl Contract {
         );
 
         let user = env::signer_account_id();
+        self.assert_storage_covered(&user);
         require!(
             amount.0 <= self.max_order_amount,
             "Amount more than allowed value"
@@ -247,6 +248,8 @@ impl Contract {
     }
     /// Borrow step made within batch of transaction
     /// Doesn't borrow when leverage is less or equal to 1.0
+    /// Borrows within the credit the user delegated to the contract, the debt is recorded
+    /// against the user while the tokens are transferred to the contract
     pub fn borrow(
         &mut self,
         order_type: OrderType,
@@ -294,7 +297,7 @@ impl Contract {
 
         ext_market::ext(token_market)
             .with_static_gas(GAS_FOR_BORROW)
-            .borrow(borrow_amount)
+            .delegated_borrow(signer_account_id(), borrow_amount)
             .then(
                 ext_self::ext(env::current_account_id())
                     .with_unused_gas_weight(100)
@@ -305,7 +308,16 @@ impl Contract {
 
     #[private]
     pub fn borrow_callback(&mut self, borrow_amount: U128) -> PromiseOrValue<WBalance> {
-        require!(is_promise_success(), "Contract failed to borrow assets");
+        // market returns the amount which was not borrowed
+        let not_borrowed_amount: U128 = match env::promise_result(0) {
+            PromiseResult::Successful(val) => serde_json::from_slice::<U128>(&val)
+                .unwrap_or_else(|_| panic!("Some problem with borrow result parsing")),
+            _ => borrow_amount,
+        };
+        require!(
+            not_borrowed_amount.0 == 0,
+            "Contract failed to borrow assets"
+        );
         PromiseOrValue::Value(borrow_amount)
     }
     #[private]
@@ -867,10 +879,8 @@ mod tests {
         let context = get_context(false, None);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_id = PairId {
             sell_token: "usdt.qa.v1.nearlend.testnet".parse().unwrap(),
@@ -917,10 +927,8 @@ mod tests {
     fn test_create_take_profit_order() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let market_data = get_market_data();
 
@@ -974,10 +982,8 @@ mod tests {
     fn test_create_take_profit_order_without_order() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let market_data = get_market_data();
 
@@ -1002,10 +1008,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let market_data = get_market_data();
 
@@ -1087,10 +1091,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let market_data = get_market_data();
 
@@ -27,6 +27,8 @@ impl Contract {
             "Deposit was done by token, that are not currently supported"
         );
 
+        self.assert_storage_covered(&env::signer_account_id());
+
         let token_decimals = self.view_token_decimals(&token);
         let token_amount = self.from_token_to_protocol_decimals(token_amount.0, token_decimals);
 
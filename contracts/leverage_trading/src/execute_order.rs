@@ -341,10 +341,8 @@ mod tests {
 
     #[test]
     fn test_get_account_by() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let order = "{\"status\":\"Pending\",\"order_type\":\"Buy\",\"amount\":1000000000000000000000000000,\"sell_token\":\"usdt.qa.v1.nearlend.testnet\",\"buy_token\":\"wnear.qa.v1.nearlend.testnet\",\"leverage\":\"1000000000000000000000000\",\"sell_token_price\":{\"ticker_id\":\"USDT\",\"value\":\"1010000000000000000000000\"},\"buy_token_price\":{\"ticker_id\":\"WNEAR\",\"value\":\"4220000000000000000000000\"},\"open_or_close_price\":\"2.5\",\"block\":103930916, \"timestamp_ms\":86400000,\"lpt_id\":\"usdt.qa.v1.nearlend.testnet|wnear.qa.v1.nearlend.testnet|2000#543\",\"history_data\":null}".to_string();
         contract.add_order_from_string(alice(), order);
@@ -359,10 +357,8 @@ mod tests {
     fn test_order_was_execute() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_id = PairId {
             sell_token: "usdt.qa.v1.nearlend.testnet".parse().unwrap(),
@@ -388,10 +384,8 @@ mod tests {
 
     #[test]
     fn test_get_amounts_to_remove_liquidity_for_long() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDT".to_string(),
@@ -448,10 +442,8 @@ mod tests {
 
     #[test]
     fn test_get_amounts_to_remove_liquidity_for_short() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDT".to_string(),
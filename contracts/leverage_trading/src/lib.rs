@@ -27,16 +27,22 @@ mod pnl;
 mod price;
 #[allow(clippy::too_many_arguments)]
 mod ref_finance;
+mod roles;
+mod storage;
+mod storage_management;
 mod utils;
 #[allow(clippy::too_many_arguments)]
 mod view;
 mod withdraw;
 
 pub use crate::metadata::*;
+pub use crate::roles::*;
+pub use crate::storage_management::*;
 
 use crate::big_decimal::*;
 use crate::config::Config;
 use crate::metadata::PairId;
+use crate::storage::StorageDeposits;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::{LookupMap, UnorderedMap};
 use near_sdk::json_types::U128;
@@ -93,6 +99,12 @@ pub struct Contract {
 
     /// Pending orders data - for tracking info about oldest pending order
     pending_orders_data: VecDeque<PendingOrderData>,
+
+    /// Accounts granted with the roles
+    roles: Roles,
+
+    /// NEAR deposited by the accounts to cover their orders and balances storage
+    storage_deposits: StorageDeposits,
 }
 
 impl Default for Contract {
@@ -117,6 +129,9 @@ impl Contract {
     pub fn new(config: Config) -> Self {
         require!(!env::state_exists(), "Already initialized");
 
+        let mut roles = Roles::new(config.owner_id.clone());
+        roles.grant(Role::Oracle, config.oracle_account_id.clone());
+
         Self {
             protocol_fee: 10u128.pow(23),
             prices: UnorderedMap::new(StorageKeys::Prices),
@@ -134,36 +149,47 @@ impl Contract {
             max_order_amount: 10_u128.pow(30),
             orders_per_pair_view: UnorderedMap::new(StorageKeys::OrdersPerPair),
             pending_orders_data: VecDeque::new(),
+            roles,
+            storage_deposits: StorageDeposits::new(
+                StorageKeys::StorageDeposits,
+                MIN_ACCOUNT_STORAGE_USAGE,
+            ),
         }
     }
 
-    #[private]
     pub fn set_protocol_fee(&mut self, fee: U128) {
+        self.roles.assert_role(Role::Owner);
+
         self.protocol_fee = fee.0
     }
 
-    #[private]
     pub fn add_token_market(&mut self, token_id: AccountId, market_id: AccountId) {
+        self.roles.assert_role(Role::RiskManager);
+
         self.tokens_markets.insert(&token_id, &market_id);
     }
 
-    #[private]
     pub fn set_liquidation_threshold(&mut self, threshold: U128) {
+        self.roles.assert_role(Role::RiskManager);
+
         self.liquidation_threshold = threshold.0;
     }
 
-    #[private]
     pub fn set_volatility_rate(&mut self, rate: U128) {
+        self.roles.assert_role(Role::RiskManager);
+
         self.volatility_rate = BigDecimal::from(rate)
     }
 
-    #[private]
     pub fn set_max_order_amount(&mut self, value: U128) {
+        self.roles.assert_role(Role::RiskManager);
+
         self.max_order_amount = value.0
     }
 
-    #[private]
     pub fn set_max_leverage(&mut self, pair: &PairId, leverage: U128) {
+        self.roles.assert_role(Role::RiskManager);
+
         let mut traid_pair = self.supported_markets.get(pair).unwrap_or_else(|| {
             panic!(
                 "Max leverage for pair {}|{} not found",
@@ -175,6 +201,29 @@ impl Contract {
         self.supported_markets.insert(pair, &traid_pair);
     }
 
+    /// Grants the role to the account, could be called by the owner only
+    pub fn grant_role(&mut self, role: Role, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.roles.grant(role, account);
+    }
+
+    pub fn revoke_role(&mut self, role: Role, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.roles.revoke(role, &account);
+    }
+
+    pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+        self.roles.has_role(role, &account)
+    }
+
+    pub fn get_role_accounts(&self, role: Role) -> Vec<AccountId> {
+        self.roles.get_accounts(role)
+    }
+
+    pub fn get_account_roles(&self, account: AccountId) -> Vec<Role> {
+        self.roles.get_roles(&account)
+    }
+
     pub fn get_max_leverage(&self, pair: &PairId) -> U128 {
         self.supported_markets
             .get(pair)
@@ -255,14 +304,14 @@ impl Contract {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
     use std::str::FromStr;
 
     #[test]
     fn test_get_max_leverage() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair = PairId {
             sell_token: AccountId::from_str("usdt.fakes.testnet").unwrap(),
@@ -295,10 +344,8 @@ mod tests {
 
     #[test]
     fn test_set_max_leverage() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair = PairId {
             sell_token: AccountId::from_str("usdt.fakes.testnet").unwrap(),
@@ -331,10 +378,8 @@ mod tests {
 
     #[test]
     fn test_get_swap_fee() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -384,10 +429,8 @@ mod tests {
 
     #[test]
     fn from_token_to_protocol_decimals_test() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -425,10 +468,8 @@ mod tests {
 
     #[test]
     fn from_protocol_to_token_decimals_test() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -464,4 +505,23 @@ mod tests {
             BigDecimal::from(expected_result)  // 1000.0
         );
     }
+
+    #[test]
+    #[should_panic(
+        expected = "This functionality is allowed to be called by owner or contract only"
+    )]
+    fn test_set_protocol_fee_by_risk_manager() {
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
+        let risk_manager = AccountId::from_str("risk_manager.testnet").unwrap();
+        contract.grant_role(Role::RiskManager, risk_manager.clone());
+
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(risk_manager.clone())
+            .build());
+        contract.set_max_order_amount(U128(10_u128.pow(27)));
+        assert!(contract.has_role(Role::RiskManager, risk_manager));
+
+        contract.set_protocol_fee(U128(10_u128.pow(22)));
+    }
 }
@@ -147,10 +147,8 @@ mod tests {
 
     #[test]
     fn view_non_pending_limit_orders_by_user_test() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDT".to_string(),
@@ -219,10 +217,8 @@ mod tests {
 
     #[test]
     fn view_non_pending_limit_orders_by_user_by_pair_test() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_id = PairId {
             sell_token: "usdt.qa.v1.nearlend.testnet".parse().unwrap(),
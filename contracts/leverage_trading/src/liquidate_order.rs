@@ -193,10 +193,8 @@ mod tests {
     fn test_order_was_liquidate() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_id = PairId {
             sell_token: "usdt.qa.v1.nearlend.testnet".parse().unwrap(),
@@ -1,7 +1,4 @@
-use near_sdk::{
-    env::{current_account_id, signer_account_id},
-    ext_contract, is_promise_success, Gas, PromiseResult,
-};
+use near_sdk::{env::current_account_id, ext_contract, is_promise_success, Gas, PromiseResult};
 
 use crate::{
     ref_finance::{ext_ref_finance, LptId, ShortLiquidityInfo},
@@ -27,11 +24,7 @@ trait ContractCallbackInterface {
 #[near_bindgen]
 impl Contract {
     pub fn free_up_liquidity_slot(&mut self) {
-        require!(
-            signer_account_id() == self.config.oracle_account_id
-                || signer_account_id() == current_account_id(),
-            "You do not have access to call this method."
-        );
+        self.roles.assert_role(Role::Executor);
 
         let oldest_pending_order_data = self.get_oldest_pending_order_data();
 
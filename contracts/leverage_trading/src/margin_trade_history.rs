@@ -175,10 +175,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair data for "USDT/WNEAR"
         let pair_data1 = TradePair {
@@ -264,10 +262,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_id = PairId {
             sell_token: "usdt.qa.v1.nearlend.testnet".parse().unwrap(),
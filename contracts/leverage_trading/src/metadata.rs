@@ -15,6 +15,7 @@ pub enum StorageKeys {
     TokenMarkets,
     ProtocolProfit,
     TakeProfitOrders,
+    StorageDeposits,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
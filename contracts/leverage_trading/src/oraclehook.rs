@@ -18,21 +18,18 @@ pub trait OraclePriceHandlerHook {
 #[near_bindgen]
 impl OraclePriceHandlerHook for Contract {
     fn oracle_on_data(&mut self, price_data: PriceJsonList) {
-        let config: Config = self.get_contract_config();
-
-        assert_eq!(
-            env::predecessor_account_id(),
-            config.oracle_account_id,
-            "Oracle account {} doesn't match to the signer {}",
-            config.oracle_account_id,
-            env::predecessor_account_id()
+        let oracle_id = env::predecessor_account_id();
+
+        require!(
+            self.roles.has_role(Role::Oracle, &oracle_id),
+            format!("Oracle account {oracle_id} is not allowed to provide prices")
         );
 
         let ticker_map = self.get_ticker_map();
 
         for price in price_data.price_list {
             if let Some(token) = ticker_map.get(&price.ticker_id) {
-                self.update_or_insert_price(token.clone(), price.clone())
+                self.insert_price(token.clone(), price.clone())
             }
         }
     }
@@ -145,7 +145,7 @@ mod tests {
 
     use near_sdk::test_utils::test_env::alice;
     use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::{testing_env, AccountId, VMContext};
+    use near_sdk::{env, testing_env, AccountId, VMContext};
 
     fn get_context(is_view: bool, block_timestamp: Option<u64>) -> VMContext {
         VMContextBuilder::new()
@@ -202,10 +202,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = get_pair_data();
         contract.add_pair(pair_data);
@@ -229,10 +227,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = get_pair_data();
         contract.add_pair(pair_data);
@@ -256,10 +252,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = get_pair_data();
         contract.add_pair(pair_data);
@@ -283,10 +277,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = get_pair_data();
         contract.add_pair(pair_data);
@@ -310,10 +302,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = get_pair_data();
         contract.add_pair(pair_data);
@@ -337,10 +327,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = get_pair_data();
         contract.add_pair(pair_data);
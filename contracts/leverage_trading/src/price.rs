@@ -3,14 +3,10 @@ use crate::*;
 
 #[near_bindgen]
 impl Contract {
-    #[private]
     pub fn update_or_insert_price(&mut self, token_id: AccountId, price: Price) {
-        require!(
-            BigDecimal::from(price.value) != BigDecimal::zero(),
-            "Token price cannot be zero"
-        );
+        self.roles.assert_role(Role::Oracle);
 
-        self.prices.insert(&token_id, &price);
+        self.insert_price(token_id, price);
     }
 
     pub fn get_price(&self, token_id: AccountId) -> BigDecimal {
@@ -35,3 +31,14 @@ impl Contract {
         })
     }
 }
+
+impl Contract {
+    pub fn insert_price(&mut self, token_id: AccountId, price: Price) {
+        require!(
+            BigDecimal::from(price.value) != BigDecimal::zero(),
+            "Token price cannot be zero"
+        );
+
+        self.prices.insert(&token_id, &price);
+    }
+}
@@ -0,0 +1,132 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, require, AccountId};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    /// Grants and revokes roles, manages the timelock and contract funds
+    Owner,
+    /// Changes markets and their risk parameters
+    RiskManager,
+    /// Pauses and proceeds user actions
+    PauseGuardian,
+    /// Pushes price feeds
+    Oracle,
+    /// Manages reward campaigns
+    RewardManager,
+    /// Runs maintenance operations and executes scheduled changes
+    Executor,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Role::Owner => "owner",
+            Role::RiskManager => "risk manager",
+            Role::PauseGuardian => "pause guardian",
+            Role::Oracle => "oracle",
+            Role::RewardManager => "reward manager",
+            Role::Executor => "executor",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Accounts granted with each role, mirrors `general::roles` as the contract is built
+/// with a different near-sdk version
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct Roles {
+    members: HashMap<Role, HashSet<AccountId>>,
+}
+
+impl Roles {
+    /// Owner is granted with every role but the oracle one
+    pub fn new(owner: AccountId) -> Self {
+        let mut roles = Self::default();
+        for role in [
+            Role::Owner,
+            Role::RiskManager,
+            Role::PauseGuardian,
+            Role::RewardManager,
+            Role::Executor,
+        ] {
+            roles.grant(role, owner.clone());
+        }
+
+        roles
+    }
+
+    pub fn has_role(&self, role: Role, account_id: &AccountId) -> bool {
+        self.members
+            .get(&role)
+            .map_or(false, |accounts| accounts.contains(account_id))
+    }
+
+    /// Whether the call is signed by the role member or the contract itself
+    pub fn is_role_call(&self, role: Role) -> bool {
+        self.has_role(role, &env::signer_account_id())
+            || env::signer_account_id() == env::current_account_id()
+    }
+
+    pub fn assert_role(&self, role: Role) {
+        require!(
+            self.is_role_call(role),
+            format!("This functionality is allowed to be called by {role} or contract only")
+        );
+    }
+
+    pub fn grant(&mut self, role: Role, account_id: AccountId) {
+        self.members.entry(role).or_default().insert(account_id);
+    }
+
+    pub fn revoke(&mut self, role: Role, account_id: &AccountId) {
+        require!(
+            role != Role::Owner || self.get_accounts(Role::Owner).len() > 1,
+            "The last owner could not be revoked"
+        );
+        let accounts = self.members.entry(role).or_default();
+        require!(
+            accounts.remove(account_id),
+            format!("Account {account_id} doesn't have {role} role")
+        );
+    }
+
+    pub fn get_accounts(&self, role: Role) -> Vec<AccountId> {
+        let mut accounts: Vec<AccountId> = self
+            .members
+            .get(&role)
+            .map(|accounts| accounts.iter().cloned().collect())
+            .unwrap_or_default();
+        accounts.sort();
+
+        accounts
+    }
+
+    pub fn get_roles(&self, account_id: &AccountId) -> Vec<Role> {
+        let mut roles: Vec<Role> = self
+            .members
+            .iter()
+            .filter(|(_, accounts)| accounts.contains(account_id))
+            .map(|(role, _)| *role)
+            .collect();
+        roles.sort();
+
+        roles
+    }
+}
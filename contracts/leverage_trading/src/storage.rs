@@ -0,0 +1,155 @@
+use near_contract_standards::storage_management::{StorageBalance, StorageBalanceBounds};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::U128;
+use near_sdk::{env, require, AccountId, Balance, IntoStorageKey, StorageUsage};
+
+/// Storage taken by a single record besides its key and value
+pub const STORAGE_RECORD_OVERHEAD: StorageUsage = 40;
+
+fn get_serialized_len<T: BorshSerialize + ?Sized>(value: &T) -> StorageUsage {
+    value.try_to_vec().unwrap().len() as StorageUsage
+}
+
+/// Storage taken by the `UnorderedMap` entry, the key is stored twice along with its index
+pub fn get_unordered_map_entry_usage<K: BorshSerialize, V: BorshSerialize>(
+    key: &K,
+    value: &V,
+) -> StorageUsage {
+    2 * get_serialized_len(key) + get_serialized_len(value) + 8 + 3 * STORAGE_RECORD_OVERHEAD
+}
+
+/// Storage taken by the `LookupMap` entry
+pub fn get_lookup_map_entry_usage<K: BorshSerialize, V: BorshSerialize>(
+    key: &K,
+    value: &V,
+) -> StorageUsage {
+    get_serialized_len(key) + get_serialized_len(value) + STORAGE_RECORD_OVERHEAD
+}
+
+/// Storage taken by the entry of the map serialized within the contract state
+pub fn get_serialized_entry_usage<K: BorshSerialize, V: BorshSerialize>(
+    key: &K,
+    value: &V,
+) -> StorageUsage {
+    get_serialized_len(key) + get_serialized_len(value)
+}
+
+pub fn get_storage_cost(usage: StorageUsage) -> Balance {
+    Balance::from(usage) * env::storage_byte_cost()
+}
+
+/// NEAR deposited by the accounts to cover the state stored on their behalf (NEP-145),
+/// mirrors `general::storage` as the contract is built with a different near-sdk version.
+/// Storage usage of the account is calculated by the contract and passed to the methods.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageDeposits {
+    deposits: LookupMap<AccountId, Balance>,
+
+    /// Storage reserved for every registered account
+    min_usage: StorageUsage,
+}
+
+impl StorageDeposits {
+    pub fn new<S: IntoStorageKey>(prefix: S, min_usage: StorageUsage) -> Self {
+        Self {
+            deposits: LookupMap::new(prefix),
+            min_usage,
+        }
+    }
+
+    pub fn is_registered(&self, account_id: &AccountId) -> bool {
+        self.deposits.contains_key(account_id)
+    }
+
+    pub fn get_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(get_storage_cost(self.min_usage)),
+            max: None,
+        }
+    }
+
+    pub fn get_balance(
+        &self,
+        account_id: &AccountId,
+        usage: StorageUsage,
+    ) -> Option<StorageBalance> {
+        self.deposits.get(account_id).map(|total| StorageBalance {
+            total: U128(total),
+            available: U128(total.saturating_sub(get_storage_cost(usage.max(self.min_usage)))),
+        })
+    }
+
+    /// Registers the account or tops up its deposit, returns the part of the amount to refund
+    pub fn deposit(
+        &mut self,
+        account_id: &AccountId,
+        amount: Balance,
+        registration_only: bool,
+    ) -> Balance {
+        if let Some(total) = self.deposits.get(account_id) {
+            if registration_only {
+                return amount;
+            }
+            self.deposits.insert(account_id, &(total + amount));
+            return 0;
+        }
+
+        let min_deposit = get_storage_cost(self.min_usage);
+        require!(
+            amount >= min_deposit,
+            "The attached deposit is less than the minimum storage balance"
+        );
+        let deposit = if registration_only {
+            min_deposit
+        } else {
+            amount
+        };
+        self.deposits.insert(account_id, &deposit);
+
+        amount - deposit
+    }
+
+    /// Withdraws the available part of the deposit, returns the withdrawn amount
+    pub fn withdraw(
+        &mut self,
+        account_id: &AccountId,
+        amount: Option<Balance>,
+        usage: StorageUsage,
+    ) -> Balance {
+        let balance = self
+            .get_balance(account_id, usage)
+            .unwrap_or_else(|| panic!("Account {} is not registered", account_id));
+        let amount = amount.unwrap_or(balance.available.0);
+        require!(
+            amount <= balance.available.0,
+            "The amount is greater than the available storage balance"
+        );
+        self.deposits
+            .insert(account_id, &(balance.total.0 - amount));
+
+        amount
+    }
+
+    /// Removes the account, returns its deposit to refund
+    pub fn unregister(&mut self, account_id: &AccountId) -> Option<Balance> {
+        self.deposits.remove(account_id)
+    }
+
+    /// Storage deposit of the account should cover the state stored on its behalf
+    pub fn assert_storage_covered(&self, account_id: &AccountId, usage: StorageUsage) {
+        let total = self.deposits.get(account_id).unwrap_or_else(|| {
+            panic!(
+                "Account {} is not registered, storage deposit is required",
+                account_id
+            )
+        });
+        require!(
+            total >= get_storage_cost(usage),
+            format!(
+                "Storage deposit of {} doesn't cover its storage usage",
+                account_id
+            )
+        );
+    }
+}
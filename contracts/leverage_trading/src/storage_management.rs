@@ -0,0 +1,217 @@
+use crate::storage::{
+    get_lookup_map_entry_usage, get_serialized_entry_usage, get_unordered_map_entry_usage,
+};
+use crate::*;
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::{assert_one_yocto, Promise, StorageUsage};
+
+/// Storage reserved for every registered account: token balances and a few orders
+pub const MIN_ACCOUNT_STORAGE_USAGE: StorageUsage = 2000;
+
+impl Order {
+    /// Whether the order could no longer be changed and is kept for the trade history only
+    pub fn is_final(&self) -> bool {
+        match self.status {
+            OrderStatus::Canceled | OrderStatus::Closed | OrderStatus::Liquidated => true,
+            OrderStatus::Executed => self.leverage == BigDecimal::one(),
+            _ => false,
+        }
+    }
+}
+
+impl Contract {
+    /// Storage taken by the orders of the account including their copies per pair
+    /// and take profit orders, along with the token balances
+    pub fn get_account_storage_usage(&self, account_id: &AccountId) -> StorageUsage {
+        let orders = self.orders.get(account_id).unwrap_or_default();
+        let orders_usage = if orders.is_empty() {
+            0
+        } else {
+            get_unordered_map_entry_usage(account_id, &orders)
+        };
+        let orders_per_pair_usage: StorageUsage = orders
+            .iter()
+            .map(|(order_id, order)| get_serialized_entry_usage(order_id, order))
+            .sum();
+        let take_profit_orders_usage: StorageUsage = orders
+            .keys()
+            .filter_map(|order_id| {
+                self.take_profit_orders
+                    .get(order_id)
+                    .map(|take_profit_order| {
+                        get_lookup_map_entry_usage(order_id, &take_profit_order)
+                    })
+            })
+            .sum();
+        let balances_usage = self.balances.get(account_id).map_or(0, |balances| {
+            get_unordered_map_entry_usage(account_id, &balances)
+        });
+
+        orders_usage + orders_per_pair_usage + take_profit_orders_usage + balances_usage
+    }
+
+    /// Should be checked before the orders or balances are written on behalf of the account
+    pub fn assert_storage_covered(&self, account_id: &AccountId) {
+        self.storage_deposits
+            .assert_storage_covered(account_id, self.get_account_storage_usage(account_id));
+    }
+
+    /// Whether the account has no token balances and all of its orders are final
+    pub fn is_account_empty(&self, account_id: &AccountId) -> bool {
+        self.balances.get(account_id).map_or(true, |balances| {
+            balances.values().all(|balance| *balance == 0)
+        }) && self
+            .orders
+            .get(account_id)
+            .map_or(true, |orders| orders.values().all(Order::is_final))
+    }
+
+    /// Removes the orders history and balances of the account
+    fn remove_account_state(&mut self, account_id: &AccountId) {
+        for (order_id, order) in self.orders.remove(account_id).unwrap_or_default() {
+            let pair_id = PairId {
+                sell_token: order.sell_token,
+                buy_token: order.buy_token,
+            };
+            if let Some(mut pair_orders) = self.orders_per_pair_view.get(&pair_id) {
+                pair_orders.remove(&order_id);
+                self.orders_per_pair_view.insert(&pair_id, &pair_orders);
+            }
+            self.take_profit_orders.remove(&order_id);
+        }
+        self.balances.remove(account_id);
+    }
+
+    /// Removes the state of the account and refunds its storage deposit once the account is empty
+    pub fn refund_storage_if_empty(&mut self, account_id: &AccountId) {
+        if !self.storage_deposits.is_registered(account_id) || !self.is_account_empty(account_id) {
+            return;
+        }
+
+        self.remove_account_state(account_id);
+        if let Some(deposit) = self.storage_deposits.unregister(account_id) {
+            Promise::new(account_id.clone()).transfer(deposit);
+        }
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let refund = self.storage_deposits.deposit(
+            &account_id,
+            env::attached_deposit(),
+            registration_only.unwrap_or(false),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let amount = self.storage_deposits.withdraw(
+            &account_id,
+            amount.map(|amount| amount.0),
+            self.get_account_storage_usage(&account_id),
+        );
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Force unregistration isn't supported as it would remove open orders and balances
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        if !self.storage_deposits.is_registered(&account_id) {
+            return false;
+        }
+        require!(
+            !force.unwrap_or(false),
+            "Force unregistration is not supported"
+        );
+        require!(
+            self.is_account_empty(&account_id),
+            "Account has open orders or token balances"
+        );
+
+        self.refund_storage_if_empty(&account_id);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        self.storage_deposits.get_bounds()
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get_balance(&account_id, self.get_account_storage_usage(&account_id))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn view_storage_usage(&self, account_id: AccountId) -> StorageUsage {
+        self.get_account_storage_usage(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::get_storage_cost;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_storage_refunded_for_empty_account() {
+        let user: AccountId = "user.testnet".parse().unwrap();
+        let token: AccountId = "usdt.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(user.clone())
+            .attached_deposit(get_storage_cost(MIN_ACCOUNT_STORAGE_USAGE))
+            .build());
+        let mut contract = Contract::new_with_config(
+            "owner.testnet".parse().unwrap(),
+            "oracle.testnet".parse().unwrap(),
+        );
+
+        contract.storage_deposit(None, None);
+        contract.increase_balance(&user, &token, 100);
+        contract.assert_storage_covered(&user);
+        assert!(contract.view_storage_usage(user.clone()) > 0);
+
+        contract.decrease_balance(&user, &token, 100);
+        contract.refund_storage_if_empty(&user);
+        assert!(contract.storage_balance_of(user.clone()).is_none());
+        assert_eq!(contract.view_storage_usage(user), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not registered, storage deposit is required")]
+    fn test_storage_required_for_unregistered_account() {
+        let contract = Contract::new_with_config(
+            "owner.testnet".parse().unwrap(),
+            "oracle.testnet".parse().unwrap(),
+        );
+
+        contract.assert_storage_covered(&"user.testnet".parse().unwrap());
+    }
+}
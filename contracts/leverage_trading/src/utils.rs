@@ -35,6 +35,6 @@ impl Contract {
 
 #[ext_contract(ext_market)]
 pub trait MarketInterface {
-    fn borrow(&mut self, amount: WBalance) -> PromiseOrValue<U128>;
+    fn delegated_borrow(&mut self, delegator: AccountId, amount: WBalance) -> PromiseOrValue<U128>;
     fn view_market_data(&self) -> MarketData;
 }
@@ -905,10 +905,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_id = PairId {
             sell_token: "usdt.qa.v1.nearlend.testnet".parse().unwrap(),
@@ -1003,10 +1001,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_id = PairId {
             sell_token: "usdt.qa.v1.nearlend.testnet".parse().unwrap(),
@@ -1130,10 +1126,8 @@ mod tests {
     fn view_supported_pairs_test() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
             sell_token: "usdt.fakes.testnet".parse().unwrap(),
@@ -1231,10 +1225,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -1279,10 +1271,8 @@ mod tests {
 
     #[test]
     fn test_calculate_liquidation_leverage_3() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -1317,10 +1307,8 @@ mod tests {
 
     #[test]
     fn test_calculate_liquidation_leverage_1_5() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -1357,10 +1345,8 @@ mod tests {
     fn test_view_order() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -1432,10 +1418,8 @@ mod tests {
     fn test_view_orders() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "USDt".to_string(),
@@ -1582,10 +1566,8 @@ mod tests {
     fn test_view_pair_tokens_decimals() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "usdt".to_string(),
@@ -1617,10 +1599,8 @@ mod tests {
     fn view_token_decimals_test() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "usdt".to_string(),
@@ -1648,10 +1628,8 @@ mod tests {
 
     #[test]
     fn test_view_pending_limit_orders_by_user() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair data for "USDT/WNEAR"
         let pair_data = TradePair {
@@ -1713,10 +1691,8 @@ mod tests {
 
     #[test]
     fn test_view_pending_limit_orders_by_user_by_pair() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair id "USDT/WNEAR"
         let pair_id = PairId {
@@ -1818,10 +1794,8 @@ mod tests {
 
     #[test]
     fn test_view_pending_limit_orders_when_user_has_no_pending_orders() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         for count in 0..3 {
             if count < 1 {
@@ -1847,10 +1821,8 @@ mod tests {
 
     #[test]
     fn test_view_pending_limit_orders_when_user_has_no_pending_orders_by_pair() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair id "WNEAR/USDT"
         let pair_id = PairId {
@@ -1923,10 +1895,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair data for "USDT/WNEAR"
         let pair_data1 = TradePair {
@@ -2079,10 +2049,8 @@ mod tests {
         let context = get_context(false, current_day);
         testing_env!(context);
 
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair id "WNEAR/USDT"
         let pair_id = PairId {
@@ -2255,10 +2223,8 @@ mod tests {
 
     #[test]
     fn test_view_view_opened_leverage_positions_when_user_has_no_opened_positions() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair data for "USDT/WNEAR"
         let pair_data = TradePair {
@@ -2322,10 +2288,8 @@ mod tests {
 
     #[test]
     fn test_view_view_opened_leverage_positions_when_user_has_no_view_opened_positions_by_pair() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // pair id "WNEAR/USDT"
         let pair_id = PairId {
@@ -2404,10 +2368,8 @@ mod tests {
     fn test_take_profit_order_view() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let market_data = MarketData {
             underlying_token: AccountId::new_unchecked("usdt.qa.v1.nearlend.testnet".to_string()),
@@ -2479,10 +2441,8 @@ mod tests {
     fn test_take_profit_order_view_if_not_exist() {
         let context = get_context(false, None);
         testing_env!(context);
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         let pair_data = TradePair {
             sell_ticker_id: "usdt".to_string(),
@@ -2527,10 +2487,8 @@ mod tests {
 
     #[test]
     fn calculate_short_liquidation_price_test() {
-        let contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // 3000.00 USDT
         let sell_token_amount = U128::from(3000000000000000000000000000);
@@ -2562,10 +2520,8 @@ mod tests {
 
     #[test]
     fn calculate_long_liquidation_price_test() {
-        let contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
 
         // 2000.00 USDT
         let sell_token_amount = U128::from(2000000000000000000000000000);
@@ -2594,10 +2550,8 @@ mod tests {
 
     #[test]
     fn test_view_pairs() {
-        let mut contract = Contract::new_with_config(
-            "owner_id.testnet".parse().unwrap(),
-            "oracle_account_id.testnet".parse().unwrap(),
-        );
+        let mut contract =
+            Contract::new_with_config(env::signer_account_id(), env::signer_account_id());
         // if there are no pairs at all
         let view_pairs = contract.view_pairs();
         assert_eq!(view_pairs.len(), 0_usize);
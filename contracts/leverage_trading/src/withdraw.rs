@@ -57,6 +57,7 @@ impl Contract {
 
         self.decrease_balance(&account_id, &token, amount.0);
         Event::WithdrawEvent { token, amount }.emit();
+        self.refund_storage_if_empty(&account_id);
 
         if let Some(reward) = reward_executor {
             if reward {
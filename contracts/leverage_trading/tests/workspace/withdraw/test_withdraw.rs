@@ -42,7 +42,7 @@ async fn withdraw_fixture(
         .await?;
 
     ////////////////////////////////////////////////////////////////////////////
-    // Stage 3: Deposit the storage for contract leverage_trading,
+    // Stage 3: Deposit the storage for contract leverage_trading and user,
     // mint for user and user transfer to a contract leverage_trading
     ////////////////////////////////////////////////////////////////////////////
 
@@ -85,6 +85,14 @@ async fn withdraw_fixture(
         "user_ft_balance_of_after_mint"
     );
 
+    let _ = user
+        .call(leverage_trading.id(), "storage_deposit")
+        .args_json(json!({}))
+        .max_gas()
+        .deposit(10_u128.pow(23))
+        .transact()
+        .await?;
+
     let token: near_sdk::AccountId = mock_token.id().to_string().parse().unwrap();
     let action = Actions::Deposit { token };
 
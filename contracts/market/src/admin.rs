@@ -1,51 +1,70 @@
 use general::NO_DEPOSIT;
 use near_sdk::env::block_height;
-use near_sdk::{env, require, AccountId};
+use near_sdk::AccountId;
 
 use crate::*;
 
 #[near_bindgen]
 impl Contract {
-    pub fn get_admin(&self) -> AccountId {
-        self.admin.clone()
+    /// Grants the role to the account, could be called by the owner only
+    pub fn grant_role(&mut self, role: Role, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::GrantRole(role, account));
     }
 
-    pub fn set_admin(&mut self, account: AccountId) {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
-        self.admin = account;
+    pub fn revoke_role(&mut self, role: Role, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::RevokeRole(role, account));
     }
 
-    pub fn get_eligible_to_borrow_uncollateralized_account(&self) -> AccountId {
-        self.eligible_to_borrow_uncollateralized.clone()
+    pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+        self.roles.has_role(role, &account)
     }
 
-    pub fn set_eligible_to_borrow_uncollateralized_account(&mut self, account: AccountId) {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
-        self.eligible_to_borrow_uncollateralized = account;
+    pub fn get_role_accounts(&self, role: Role) -> Vec<AccountId> {
+        self.roles.get_accounts(role)
+    }
+
+    pub fn get_account_roles(&self, account: AccountId) -> Vec<Role> {
+        self.roles.get_roles(&account)
+    }
+
+    pub fn get_supply_cap(&self) -> Option<WBalance> {
+        self.supply_cap.map(WBalance::from)
+    }
+
+    pub fn set_supply_cap(&mut self, supply_cap: Option<WBalance>) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetSupplyCap(supply_cap));
+    }
+
+    /// Switches the interest rate model of the market, interest is accrued with the previous one
+    pub fn set_interest_rate_model(&mut self, interest_rate_model: InterestRateModel) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetInterestRateModel(
+            interest_rate_model,
+        ));
     }
 }
 
 impl Contract {
-    pub fn is_valid_admin_call(&self) -> bool {
-        env::signer_account_id() == self.admin
-            || env::signer_account_id() == env::current_account_id()
-    }
+    pub(crate) fn apply_interest_rate_model(&mut self, interest_rate_model: InterestRateModel) {
+        interest_rate_model.assert_valid();
 
-    pub fn is_allowed_to_borrow_uncollateralized(&self) -> bool {
-        env::predecessor_account_id() == self.eligible_to_borrow_uncollateralized
+        self.accrue_interest();
+        self.set_interest_rate_model_state(interest_rate_model);
     }
 
     pub fn add_inconsistent_account(&mut self, account: AccountId) {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
+        self.roles.assert_role(Role::Executor);
 
         controller::set_account_consistency(
             account,
@@ -106,22 +125,35 @@ mod tests {
     }
 
     #[test]
-    fn set_get_admin() {
-        let dtoken_contract = Contract::new(Config {
-            initial_exchange_rate: U128::from(Ratio::one()),
-            underlying_token_id: "weth".parse().unwrap(),
-            underlying_token_decimals: 24,
-            owner_id: "dtoken".parse().unwrap(),
-            controller_account_id: "controller".parse().unwrap(),
-            interest_rate_model: InterestRateModel::default(),
-            disable_transfer_token: true,
-        });
+    fn grant_and_revoke_role() {
+        let mut dtoken_contract = init(true);
+        let operator = AccountId::new_unchecked("operator.near".to_string());
 
-        assert_eq!(dtoken_contract.admin, dtoken_contract.get_admin());
+        dtoken_contract.grant_role(Role::PauseGuardian, operator.clone());
+        assert!(dtoken_contract.has_role(Role::PauseGuardian, operator.clone()));
         assert_eq!(
-            AccountId::new_unchecked("dtoken".parse().unwrap()),
-            dtoken_contract.get_admin()
+            dtoken_contract.get_role_accounts(Role::PauseGuardian),
+            vec![alice(), operator.clone()]
         );
+
+        dtoken_contract.revoke_role(Role::PauseGuardian, operator.clone());
+        assert!(dtoken_contract.get_account_roles(operator).is_empty());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "This functionality is allowed to be called by risk manager or contract only"
+    )]
+    fn set_interest_rate_model_without_role() {
+        let mut dtoken_contract = init(true);
+        let operator = AccountId::new_unchecked("operator.near".to_string());
+        dtoken_contract.grant_role(Role::PauseGuardian, operator.clone());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(operator)
+            .build());
+        dtoken_contract.set_interest_rate_model(InterestRateModel::default());
     }
 
     #[test]
@@ -0,0 +1,170 @@
+use crate::*;
+use std::cmp::min;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BadDebtWriteOff {
+    /// Account which borrows were written off
+    pub account_id: AccountId,
+
+    /// Written off borrows amount
+    pub amount: WBalance,
+
+    /// Part of the loss covered by market reserves
+    pub covered_by_reserves: WBalance,
+
+    /// Part of the loss spread across suppliers via exchange rate
+    pub socialized_loss: WBalance,
+
+    /// Block of the write-off
+    pub block_height: BlockHeight,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Writes off residual borrows of the account without collateral.
+    /// The loss is covered by reserves first, the rest lowers the exchange rate
+    pub fn write_off_bad_debt(&mut self, account_id: AccountId, amount: WBalance) -> WBalance {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.get_controller_address(),
+            "This method can be called only from controller contract"
+        );
+        self.accrue_interest();
+        require!(
+            amount.0 <= self.get_account_total_borrows(account_id.clone()),
+            "Write-off amount exceeds the account borrows"
+        );
+
+        let covered_by_reserves = min(amount.0, self.get_total_reserves());
+        self.set_total_reserves(self.get_total_reserves() - covered_by_reserves);
+        self.decrease_borrows(account_id.clone(), amount);
+
+        let write_off = BadDebtWriteOff {
+            account_id: account_id.clone(),
+            amount,
+            covered_by_reserves: WBalance::from(covered_by_reserves),
+            socialized_loss: WBalance::from(amount.0 - covered_by_reserves),
+            block_height: env::block_height(),
+        };
+        self.bad_debt_write_offs.push(&write_off);
+        self.total_bad_debt += amount.0;
+
+        Events::BadDebtWrittenOff {
+            account_id,
+            amount,
+            covered_by_reserves: U128(covered_by_reserves),
+            socialized_loss: U128(amount.0 - covered_by_reserves),
+        }
+        .emit();
+        amount
+    }
+
+    pub fn view_bad_debt_write_offs(&self, from_index: u64, limit: u64) -> Vec<BadDebtWriteOff> {
+        (from_index..std::cmp::min(from_index + limit, self.bad_debt_write_offs.len()))
+            .filter_map(|index| self.bad_debt_write_offs.get(index))
+            .collect()
+    }
+
+    pub fn view_total_bad_debt(&self) -> WBalance {
+        WBalance::from(self.total_bad_debt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Contract, InterestRateModel};
+    use general::ratio::Ratio;
+    use general::WBalance;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, AccountId};
+    use std::str::FromStr;
+
+    fn init_env(total_reserves: u128) -> (Contract, AccountId) {
+        let borrower: AccountId = AccountId::new_unchecked("borrower.near".to_string());
+        let supplier: AccountId = AccountId::new_unchecked("supplier.near".to_string());
+
+        let mut contract = Contract::new(Config {
+            initial_exchange_rate: U128::from(Ratio::one()),
+            underlying_token_id: bob(),
+            underlying_token_decimals: 24,
+            owner_id: alice(),
+            controller_account_id: carol(),
+            interest_rate_model: InterestRateModel::default(),
+            disable_transfer_token: true,
+        });
+
+        // 1000 supplied, 400 borrowed, 100 more underlying tokens belong to reserves
+        contract.mint(supplier, WBalance::from(1000));
+        contract.increase_borrows(borrower.clone(), WBalance::from(400));
+        contract.increase_reserve(WBalance::from(total_reserves));
+        contract.increase_contract_balance(WBalance::from(600 + total_reserves));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .predecessor_account_id(carol())
+            .build());
+
+        (contract, borrower)
+    }
+
+    #[test]
+    fn test_write_off_covered_by_reserves() {
+        let (mut contract, borrower) = init_env(100);
+        let exchange_rate = contract.get_exchange_rate(contract.view_contract_balance());
+
+        contract.write_off_bad_debt(borrower.clone(), WBalance::from(50));
+        contract.write_off_bad_debt(borrower.clone(), WBalance::from(20));
+
+        assert_eq!(contract.get_account_borrows(borrower), 330);
+        assert_eq!(contract.view_total_bad_debt(), WBalance::from(70));
+        assert_eq!(contract.get_total_reserves(), 30);
+        assert_eq!(
+            contract.get_exchange_rate(contract.view_contract_balance()),
+            exchange_rate
+        );
+    }
+
+    #[test]
+    fn test_write_off_socialized_across_suppliers() {
+        let (mut contract, borrower) = init_env(100);
+
+        contract.write_off_bad_debt(borrower.clone(), WBalance::from(400));
+
+        assert_eq!(contract.get_account_borrows(borrower.clone()), 0);
+        assert_eq!(contract.get_total_reserves(), 0);
+        // (700 + 0 - 0) / 1000
+        assert_eq!(
+            contract.get_exchange_rate(contract.view_contract_balance()),
+            Ratio::from_str("0.7").unwrap()
+        );
+
+        let write_offs = contract.view_bad_debt_write_offs(0, 10);
+        assert_eq!(write_offs.len(), 1);
+        assert_eq!(write_offs[0].account_id, borrower);
+        assert_eq!(write_offs[0].covered_by_reserves, WBalance::from(100));
+        assert_eq!(write_offs[0].socialized_loss, WBalance::from(300));
+        assert_eq!(contract.view_total_bad_debt(), WBalance::from(400));
+    }
+
+    #[test]
+    #[should_panic(expected = "Write-off amount exceeds the account borrows")]
+    fn test_write_off_more_than_borrows() {
+        let (mut contract, borrower) = init_env(0);
+
+        contract.write_off_bad_debt(borrower, WBalance::from(401));
+    }
+
+    #[test]
+    #[should_panic(expected = "This method can be called only from controller contract")]
+    fn test_write_off_not_from_controller() {
+        let (mut contract, borrower) = init_env(0);
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(bob())
+            .build());
+
+        contract.write_off_bad_debt(borrower, WBalance::from(100));
+    }
+}
@@ -4,11 +4,29 @@ use near_sdk::env::signer_account_id;
 const GAS_FOR_BORROW: Gas = Gas(65_000_000_000_000);
 
 impl Contract {
+    /// Decreases variable rate borrows first, the rest is repaid from the fixed rate loan
     pub fn decrease_borrows(&mut self, account: AccountId, token_amount: WBalance) -> Balance {
         let borrows = self.get_account_borrows(account.clone());
-        let new_borrows = borrows - Balance::from(token_amount);
+        if Balance::from(token_amount) <= borrows {
+            return self.set_account_borrows(account, U128(borrows - Balance::from(token_amount)));
+        }
 
-        self.set_account_borrows(account, U128(new_borrows))
+        let mut loan = self
+            .get_fixed_rate_loan(&account)
+            .expect("Amount exceeds the account borrows");
+        let fixed_rate_debt = loan.debt.get_debt(env::block_height());
+        require!(
+            Balance::from(token_amount) - borrows <= fixed_rate_debt,
+            "Amount exceeds the account borrows"
+        );
+        loan.debt = FixedRateDebt {
+            amount: U128(fixed_rate_debt - (Balance::from(token_amount) - borrows)),
+            rate_per_block: loan.debt.rate_per_block,
+            block: env::block_height(),
+        };
+        self.set_fixed_rate_loan(account.clone(), Some(loan));
+
+        self.set_account_borrows(account, U128(0))
     }
 
     pub fn increase_borrows(&mut self, account: AccountId, token_amount: WBalance) -> Balance {
@@ -30,38 +48,14 @@ impl Contract {
             return PromiseOrValue::Value(token_amount);
         }
 
-        if account_to_borrow != self.get_eligible_to_borrow_uncollateralized_account() {
-            self.adjust_rewards_by_campaign_type(CampaignType::Borrow);
-        }
-
-        let balance_of = self.view_contract_balance().into();
-
-        let borrow_rate = self.get_borrow_rate(
-            U128(balance_of),
-            U128(self.get_total_borrows()),
-            U128(self.get_total_reserves()),
-        );
-        let borrow_accrued_interest = self
-            .config
-            .get()
-            .unwrap()
-            .interest_rate_model
-            .calculate_accrued_interest(
-                borrow_rate,
-                self.get_account_borrows(account_to_borrow.clone()),
-                self.get_accrued_borrow_interest(account_to_borrow.clone()),
-            );
-        self.set_accrued_borrow_interest(
-            account_to_borrow.clone(),
-            borrow_accrued_interest.clone(),
-        );
+        self.adjust_rewards_by_campaign_type(CampaignType::Borrow);
+        self.accrue_interest();
 
         controller::make_borrow(
             account_to_borrow.clone(),
             self.get_contract_address(),
             token_amount,
-            borrow_accrued_interest.last_recalculation_block,
-            WRatio::from(borrow_rate),
+            WRatio::from(self.borrow_index),
             self.get_controller_address(),
             NO_DEPOSIT,
             self.terra_gas(15),
@@ -89,15 +83,12 @@ impl Contract {
             Balance::from(amount) > 0,
             "Amount should be a positive number"
         );
-
-        let mut account_to_borrow = env::predecessor_account_id();
-
-        if !self.is_allowed_to_borrow_uncollateralized() {
-            account_to_borrow = signer_account_id();
-        }
+        self.assert_storage_covered(&signer_account_id());
 
         self.mutex_account_lock(
-            Actions::Borrow { account_to_borrow },
+            Actions::Borrow {
+                account_to_borrow: signer_account_id(),
+            },
             amount,
             self.terra_gas(80),
         )
@@ -110,13 +101,11 @@ impl Contract {
         account_to_borrow: AccountId,
     ) -> PromiseOrValue<WBalance> {
         if !is_promise_success() {
-            log!(
-                "{}",
-                Events::BorrowFailedToIncreaseBorrowOnController(
-                    account_to_borrow,
-                    Balance::from(token_amount)
-                )
-            );
+            Events::BorrowFailedToIncreaseBorrowOnController {
+                account_id: account_to_borrow,
+                amount: token_amount,
+            }
+            .emit();
             self.mutex_account_unlock();
             return PromiseOrValue::Value(token_amount);
         }
@@ -135,6 +124,7 @@ impl Contract {
         .then(ext_self::borrow_ft_transfer_callback(
             token_amount,
             account_to_borrow,
+            self.get_health_factor_result(),
             env::current_account_id(),
             NO_DEPOSIT,
             self.terra_gas(14),
@@ -147,16 +137,24 @@ impl Contract {
         &mut self,
         token_amount: WBalance,
         account_to_borrow: AccountId,
+        health_factor: Option<WRatio>,
     ) -> PromiseOrValue<WBalance> {
         if is_promise_success() {
+            let accrued_interest = self.get_account_accrued_interest(&account_to_borrow);
             self.increase_borrows(account_to_borrow.clone(), token_amount);
-            self.update_campaigns_market_total_by_type(CampaignType::Borrow);
-            self.mutex_account_unlock();
-            log!(
-                "{}",
-                Events::BorrowSuccess(account_to_borrow, Balance::from(token_amount))
+            self.update_campaigns_by_account_balance(
+                account_to_borrow.clone(),
+                CampaignType::Borrow,
             );
+            self.mutex_account_unlock();
             self.decrease_contract_balance(token_amount);
+
+            Events::BorrowSuccess {
+                account_id: account_to_borrow,
+                amount: token_amount,
+                state: self.get_action_state(accrued_interest, health_factor),
+            }
+            .emit();
             PromiseOrValue::Value(U128(0))
         } else {
             controller::decrease_borrows(
@@ -186,29 +184,43 @@ impl Contract {
     ) {
         if !is_promise_success() {
             self.add_inconsistent_account(env::signer_account_id());
-            log!(
-                "{}",
-                Events::BorrowFailedToFallback(account_to_borrow, Balance::from(token_amount))
-            );
+            Events::BorrowFailedToFallback {
+                account_id: account_to_borrow,
+                amount: token_amount,
+            }
+            .emit();
         } else {
             self.mutex_account_unlock();
-            log!(
-                "{}",
-                Events::BorrowFallbackSuccess(account_to_borrow, Balance::from(token_amount))
-            );
+            Events::BorrowFallbackSuccess {
+                account_id: account_to_borrow,
+                amount: token_amount,
+            }
+            .emit();
         }
     }
 
+    /// Stores account borrows with interest as principal at the current borrow index
     #[private]
     pub fn set_account_borrows(&mut self, account: AccountId, token_amount: WBalance) -> Balance {
+        let existing_borrows = self.get_account_borrows(account.clone());
+        self.total_borrows =
+            (self.total_borrows + Balance::from(token_amount)).saturating_sub(existing_borrows);
+
         let mut user = self.user_profiles.get(&account).unwrap_or_default();
         user.borrows = Balance::from(token_amount);
+        user.borrow_index = self.borrow_index;
         self.user_profiles.insert(&account, &user);
 
         self.get_account_borrows(account)
     }
 
+    /// Account borrows with interest accrued up to the last market accrual
     pub fn get_account_borrows(&self, account: AccountId) -> Balance {
-        self.user_profiles.get(&account).unwrap_or_default().borrows
+        self.calculate_account_borrows(account, self.borrow_index)
+    }
+
+    /// Account variable and fixed rate borrows with interest
+    pub fn get_account_total_borrows(&self, account: AccountId) -> Balance {
+        self.get_account_borrows(account.clone()) + self.get_account_fixed_rate_borrows(&account)
     }
 }
@@ -1,35 +1,222 @@
 use crate::*;
-use general::ratio::Ratio;
-use std::fmt;
+use general::events::emit_event;
+use general::ratio::{BigBalance, Ratio};
+use near_sdk::{promise_result_as_success, serde_json};
+
+/// Market and account state after the action
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActionState {
+    pub exchange_rate: WRatio,
+    /// Interest accrued on the account variable rate borrows before the action
+    pub accrued_interest: WBalance,
+    /// Health factor of the account reported by the controller, `None` if it is not available
+    pub health_factor: Option<WRatio>,
+}
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
 pub enum Events {
-    BorrowFailedToGetUnderlyingBalance(AccountId, Balance, AccountId, AccountId),
-    BorrowFailedToIncreaseBorrowOnController(AccountId, Balance),
-    BorrowSuccess(AccountId, Balance),
-    BorrowFailedToFallback(AccountId, Balance),
-    BorrowFallbackSuccess(AccountId, Balance),
-
-    RepayFailedToUpdateUserBalance(AccountId, Balance),
-    RepaySuccess(AccountId, Balance),
-
-    SupplyFailedToGetUnderlyingBalance(AccountId, Balance, AccountId, AccountId),
-    SupplyFailedToIncreaseSupplyOnController(AccountId, Balance),
-    SupplySuccess(AccountId, Balance),
-
-    DepositFailedToGetUnderlyingBalance(AccountId, Balance, AccountId, AccountId),
-    MarginTradingFailedToIncreaseUserDeposit(AccountId, Balance),
-    MarginTradingDepositSuccess(AccountId, Balance),
-    MarginTradingFailedToDecreaseUserDeposit(AccountId, Balance),
-    MarginTradingRevertDepositSuccess(AccountId, Balance),
-
-    WithdrawFailedToGetUnderlyingBalance(AccountId, Balance, AccountId, AccountId),
-    WithdrawFailedToDecreaseSupplyOnController(AccountId, Balance, AccountId),
-    WithdrawSuccess(AccountId, Balance),
-    WithdrawFailedToFallback(AccountId, Balance),
-    WithdrawFallbackSuccess(AccountId, Balance),
-
-    LiquidationSuccess(AccountId, AccountId, Balance),
-    LiquidationFailed(AccountId, AccountId, Balance),
+    BorrowFailedToIncreaseBorrowOnController {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+    BorrowSuccess {
+        account_id: AccountId,
+        amount: WBalance,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+    BorrowFailedToFallback {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+    BorrowFallbackSuccess {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+
+    RepayFailedToUpdateUserBalance {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+    RepaySuccess {
+        account_id: AccountId,
+        amount: WBalance,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+
+    SupplyFailedToIncreaseSupplyOnController {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+    SupplySuccess {
+        account_id: AccountId,
+        amount: WBalance,
+        dtoken_amount: WBalance,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+    SupplyCapExceeded {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+
+    WithdrawFailedToDecreaseSupplyOnController {
+        account_id: AccountId,
+        dtoken_amount: WBalance,
+    },
+    WithdrawSuccess {
+        account_id: AccountId,
+        amount: WBalance,
+        dtoken_amount: WBalance,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+    WithdrawFailedToFallback {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+    WithdrawFallbackSuccess {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+
+    LiquidationSuccess {
+        liquidator_account_id: AccountId,
+        borrower_account_id: AccountId,
+        amount: WBalance,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+    LiquidationFailed {
+        liquidator_account_id: AccountId,
+        borrower_account_id: AccountId,
+        amount: WBalance,
+    },
+    LiquidationDepositSuccess {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+    LiquidationDepositWithdrawSuccess {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+    LiquidationDepositWithdrawFailed {
+        account_id: AccountId,
+        amount: WBalance,
+    },
+
+    BadDebtWrittenOff {
+        account_id: AccountId,
+        amount: WBalance,
+        covered_by_reserves: WBalance,
+        socialized_loss: WBalance,
+    },
+
+    FixedRateBorrowSuccess {
+        account_id: AccountId,
+        amount: WBalance,
+        rate_per_block: WRatio,
+        maturity_block: BlockHeight,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+    FixedRateRepaySuccess {
+        account_id: AccountId,
+        amount: WBalance,
+        early_repayment_fee: WBalance,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+    FixedRateLoanRebalanced {
+        account_id: AccountId,
+        rate_per_block: WRatio,
+    },
+
+    DelegatedBorrowSuccess {
+        account_id: AccountId,
+        delegatee_account_id: AccountId,
+        amount: WBalance,
+        #[serde(flatten)]
+        state: ActionState,
+    },
+
+    FlashLoanSuccess {
+        receiver_id: AccountId,
+        amount: WBalance,
+        fee: WBalance,
+    },
+    /// Amount which was not returned is added to the debt of the initiator
+    FlashLoanFailed {
+        initiator_id: AccountId,
+        receiver_id: AccountId,
+        amount: WBalance,
+        returned_amount: WBalance,
+    },
+    FlashLoanDebtRepay {
+        initiator_id: AccountId,
+        amount: WBalance,
+        remaining_debt: WBalance,
+    },
+
+    Reserve {
+        account_id: AccountId,
+        amount: WBalance,
+        total_reserves: WBalance,
+        exchange_rate: WRatio,
+    },
+
+    RewardClaim {
+        account_id: AccountId,
+        campaign_id: String,
+        token: AccountId,
+        amount: WBalance,
+    },
+    /// `amount` of locked rewards is unlocked, `transferred_amount` is left after the penalty
+    RewardUnlock {
+        account_id: AccountId,
+        campaign_id: String,
+        token: AccountId,
+        amount: WBalance,
+        transferred_amount: WBalance,
+    },
+
+    /// `is_funded` is set once all of the campaign tokens are funded and it starts the distribution
+    RewardCampaignFund {
+        campaign_id: String,
+        token: AccountId,
+        sender_id: AccountId,
+        amount: WBalance,
+        is_funded: bool,
+    },
+    RewardCampaignSweep {
+        campaign_id: String,
+        token: AccountId,
+        receiver_id: AccountId,
+        amount: WBalance,
+    },
+
+    ParameterChange {
+        parameter: String,
+        value: serde_json::Value,
+    },
+}
+
+impl Events {
+    pub fn parameter_change<T: Serialize>(parameter: &str, value: T) -> Self {
+        Events::ParameterChange {
+            parameter: parameter.to_string(),
+            value: serde_json::json!(value),
+        }
+    }
+
+    pub fn emit(&self) {
+        emit_event(self);
+    }
 }
 
 impl Contract {
@@ -51,7 +238,10 @@ impl Contract {
         self.underlying_token.clone()
     }
 
+    /// Underlying balance of the market is increased by the tokens owed by flash loans
     pub fn get_exchange_rate(&self, underlying_balance: WBalance) -> Ratio {
+        let underlying_balance =
+            WBalance::from(underlying_balance.0 + self.get_flash_loan_assets());
         let total_borrows = self.get_total_borrows();
         let total_reserves = self.get_total_reserves();
         let total_supplies = self.get_total_supplies();
@@ -80,6 +270,45 @@ impl Contract {
             / Ratio::from(total_supplies)
     }
 
+    /// Amount of underlying tokens supplied to the market including borrowed ones
+    pub fn get_total_underlying_supplies(&self, underlying_balance: WBalance) -> Balance {
+        (underlying_balance.0 + self.get_total_borrows()).saturating_sub(self.get_total_reserves())
+    }
+
+    /// Amount which still could be supplied to the market, `None` if market has no supply cap
+    pub fn get_supply_headroom(&self, underlying_balance: WBalance) -> Option<Balance> {
+        self.supply_cap.map(|supply_cap| {
+            supply_cap.saturating_sub(self.get_total_underlying_supplies(underlying_balance))
+        })
+    }
+
+    /// Interest accrued on the account variable rate borrows since they were last updated
+    pub fn get_account_accrued_interest(&self, account: &AccountId) -> Balance {
+        let principal = self.user_profiles.get(account).unwrap_or_default().borrows;
+
+        self.get_account_borrows(account.clone())
+            .saturating_sub(principal)
+    }
+
+    pub fn get_action_state(
+        &self,
+        accrued_interest: Balance,
+        health_factor: Option<WRatio>,
+    ) -> ActionState {
+        ActionState {
+            exchange_rate: WRatio::from(self.get_exchange_rate(self.view_contract_balance())),
+            accrued_interest: WBalance::from(accrued_interest),
+            health_factor,
+        }
+    }
+
+    /// Health factor of the account returned by the controller flow handled by the callback
+    pub fn get_health_factor_result(&self) -> Option<WRatio> {
+        promise_result_as_success()
+            .and_then(|result| serde_json::from_slice::<Option<WRatio>>(&result).ok())
+            .flatten()
+    }
+
     pub fn terra_gas(&self, gas: u64) -> Gas {
         TGAS * gas
     }
@@ -116,11 +345,8 @@ impl Contract {
     }
 
     pub fn get_total_borrows(&self) -> Balance {
-        // Tokens amount
-        self.user_profiles
-            .iter()
-            .map(|(_, value)| value.borrows)
-            .sum()
+        // Tokens amount including interest accrued up to the last market accrual
+        self.total_borrows + self.total_fixed_borrows
     }
 
     pub fn get_total_reserves(&self) -> Balance {
@@ -128,30 +354,20 @@ impl Contract {
     }
 
     pub fn get_repay_info(&self, user_id: AccountId, underlying_balance: WBalance) -> RepayInfo {
+        let accrual = self.calculate_interest_accrual();
         let borrow_rate = self.get_borrow_rate(
             underlying_balance,
-            U128(self.get_total_borrows()),
-            U128(self.get_total_reserves()),
+            U128(accrual.total_borrows + accrual.total_fixed_borrows),
+            U128(accrual.total_reserves),
         );
-        let user_borrows = self.get_account_borrows(user_id.clone());
-
-        let borrow_accrued_interest = self
-            .config
-            .get()
-            .unwrap()
-            .interest_rate_model
-            .calculate_accrued_interest(
-                borrow_rate,
-                user_borrows,
-                self.get_accrued_borrow_interest(user_id),
-            );
-        let accumulated_interest = borrow_accrued_interest.accumulated_interest;
+        let borrow_amount = self.user_profiles.get(&user_id).unwrap_or_default().borrows;
+        let total_amount = self.calculate_account_borrows(user_id, accrual.borrow_index);
 
         RepayInfo {
             accrued_interest_per_block: WBalance::from(borrow_rate),
-            total_amount: WBalance::from(accumulated_interest + user_borrows),
-            borrow_amount: WBalance::from(user_borrows),
-            accumulated_interest: WBalance::from(accumulated_interest),
+            total_amount: WBalance::from(total_amount),
+            borrow_amount: WBalance::from(borrow_amount),
+            accumulated_interest: WBalance::from(total_amount.saturating_sub(borrow_amount)),
         }
     }
 
@@ -160,28 +376,25 @@ impl Contract {
         user_id: AccountId,
         underlying_balance: WBalance,
     ) -> WithdrawInfo {
-        let exchange_rate = U128::from(self.get_exchange_rate(underlying_balance));
-        let interest_rate_model = self.config.get().unwrap().interest_rate_model;
-        let supply_rate: Ratio = self.get_supply_rate(
-            underlying_balance,
-            U128(self.get_total_borrows()),
-            U128(self.get_total_reserves()),
-            interest_rate_model.get_reserve_factor(),
-        );
-        let accrued_supply_interest = interest_rate_model.calculate_accrued_interest(
-            supply_rate,
-            self.get_account_supplies(user_id.clone()),
-            self.get_accrued_supply_interest(user_id.clone()),
-        );
-        let total_interest = U128::from(
-            self.get_accrued_supply_interest(user_id)
-                .accumulated_interest
-                + accrued_supply_interest.accumulated_interest,
-        );
+        let exchange_rate = self.get_exchange_rate(underlying_balance);
+        let supply_index = self.calculate_interest_accrual().supply_index;
+        let user_supply_index = self
+            .user_profiles
+            .get(&user_id)
+            .unwrap_or_default()
+            .supply_index;
+
+        // part of the supplied tokens value earned since the supply
+        let supplies = BigBalance::from(self.get_account_supplies(user_id)) * exchange_rate;
+        let total_interest = if supply_index > user_supply_index {
+            supplies * (supply_index - user_supply_index) / supply_index
+        } else {
+            BigBalance::zero()
+        };
 
         WithdrawInfo {
-            exchange_rate,
-            total_interest,
+            exchange_rate: U128::from(exchange_rate),
+            total_interest: U128::from(total_interest.round_u128()),
         }
     }
 
@@ -247,7 +460,15 @@ impl Contract {
             Actions::Withdraw => self.post_withdraw(amount),
             Actions::Supply => self.post_supply(amount),
             Actions::Borrow { account_to_borrow } => self.post_borrow(amount, account_to_borrow),
-            Actions::Deposit => self.post_deposit(amount),
+            Actions::FixedRateBorrow {
+                account_to_borrow,
+                term,
+            } => self.post_fixed_rate_borrow(amount, account_to_borrow, term),
+            Actions::FixedRateRepay => self.post_fixed_rate_repay(amount),
+            Actions::DelegatedBorrow {
+                delegator,
+                delegatee,
+            } => self.post_delegated_borrow(amount, delegator, delegatee),
             _ => {
                 panic!("Incorrect action at mutex lock callback")
             }
@@ -255,123 +476,6 @@ impl Contract {
     }
 }
 
-impl fmt::Display for Events {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Events::BorrowFailedToGetUnderlyingBalance(
-                account,
-                balance,
-                contract_id,
-                underlying_token_id,
-            ) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "BorrowFailedToGetUnderlyingBalance", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to get {contract_id} balance on {underlying_token_id}"}}}}"#
-            ),
-            Events::BorrowFailedToIncreaseBorrowOnController(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "BorrowFailedToInceaseBorrowOnController", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to make borrow for {account} on {balance} token amount"}}}}"#
-            ),
-            Events::BorrowSuccess(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "BorrowSuccess", "data": {{"account_id": "{account}", "amount": "{balance}"}}}}"#
-            ),
-            Events::BorrowFailedToFallback(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "BorrowFailedToMakeFallback", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to revert state for {account}"}}}}"#
-            ),
-            Events::BorrowFallbackSuccess(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "BorrowFallbackSuccess", "data": {{"account_id": "{account}", "amount": "{balance}"}}}}"#
-            ),
-            Events::RepayFailedToUpdateUserBalance(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "RepayFailedToUpdateUserBalance", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to update user {account} balance {balance}: user is not registered"}}}}"#
-            ),
-            Events::RepaySuccess(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "RepaySuccess", "data": {{"account_id": "{account}", "amount": "{balance}"}}}}"#
-            ),
-            Events::SupplyFailedToGetUnderlyingBalance(
-                account,
-                balance,
-                contract_id,
-                underlying_token_id,
-            ) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "SupplyFailedToGetUnderlyingBalance", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to get {contract_id} balance on {underlying_token_id}"}}}}"#
-            ),
-            Events::SupplyFailedToIncreaseSupplyOnController(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "SupplyFailedToInceaseSupplyOnController", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to increase {account} supply balance of {balance} on controller"}}}}"#
-            ),
-            Events::SupplySuccess(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "SupplySuccess", "data": {{"account_id": "{account}", "amount": "{balance}"}}}}"#
-            ),
-            Events::WithdrawFailedToGetUnderlyingBalance(
-                account,
-                balance,
-                contract_id,
-                underlying_token_id,
-            ) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "WithdrawFailedToGetUnderlyingBalance", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to get {contract_id} balance on {underlying_token_id}"}}}}"#
-            ),
-            Events::WithdrawFailedToDecreaseSupplyOnController(account, balance, contract_id) => {
-                write!(
-                    f,
-                    r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "WithdrawFailedToDecreaseSupplyOnController", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to decrease {account} supply balance of {contract_id} on controller"}}}}"#
-                )
-            }
-            Events::WithdrawSuccess(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "WithdrawSuccess", "data": {{"account_id": "{account}", "amount": "{balance}"}}}}"#
-            ),
-            Events::WithdrawFailedToFallback(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "WithdrawFailedToMakeFallback", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to revert state for {account}"}}}}"#
-            ),
-            Events::WithdrawFallbackSuccess(account, balance) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "WithdrawFallbackSuccess", "data": {{"account_id": "{account}", "amount": "{balance}"}}}}"#
-            ),
-            Events::LiquidationSuccess(liquidator, borrower, amount_liquidate) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "LiquidationSuccess", "data": {{"liquidator_account_id": "{liquidator}", "borrower_account_id": {borrower},"amount": "{amount_liquidate}"}}}}"#
-            ),
-            Events::LiquidationFailed(liquidator, borrower, amount_liquidate) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "LiquidationFailed", "data": {{"liquidator_account_id": "{liquidator}", "borrower_account_id": {borrower},"amount": "{amount_liquidate}"}}}}"#
-            ),
-            Events::DepositFailedToGetUnderlyingBalance(
-                account,
-                balance,
-                contract_id,
-                underlying_token_id,
-            ) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "DepositFailedToGetUnderlyingBalance", "data": {{"account_id": "{account}", "amount": "{balance}", "reason": "failed to get {contract_id} balance on {underlying_token_id}"}}}}"#
-            ),
-            Events::MarginTradingFailedToIncreaseUserDeposit(account, amount) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "MtradingFailedToIncreaseUserDeposit", "data": {{"account_id": "{account}", "amount": "{amount}", "reason": "failed to increase {account} deposit balance of {amount} on margin trading contract"}}}}"#
-            ),
-            Events::MarginTradingDepositSuccess(account, amount) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "MarginTradingDepositSuccess", "data": {{"account_id": "{account}", "amount": "{amount}"}}}}"#
-            ),
-            Events::MarginTradingFailedToDecreaseUserDeposit(account, amount) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "MarginTradingFailedToDecreaseUserDeposit", "data": {{"account_id": "{account}", "amount": "{amount}", "reason": "failed to revert state for {account}"}}}}"#
-            ),
-            Events::MarginTradingRevertDepositSuccess(account, amount) => write!(
-                f,
-                r#"EVENT_JSON:{{"standard": "nep297", "version": "1.0.0", "event": "MarginTradingRevertDepositSuccess", "data": {{"account_id": "{account}", "amount": "{amount}"}}}}"#
-            ),
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use crate::InterestRateModel;
@@ -379,8 +483,12 @@ mod tests {
     use general::ratio::Ratio;
     use near_sdk::json_types::U128;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
     use std::str::FromStr;
 
+    const ONE: u128 = 10u128.pow(24);
+
     pub fn init_env() -> Contract {
         let (dtoken_account, underlying_token_account, controller_account) =
             (alice(), bob(), carol());
@@ -511,4 +619,21 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_borrow_index_accrual() {
+        testing_env!(VMContextBuilder::new().block_index(100).build());
+        let mut contract = init_env();
+        contract.increase_contract_balance(U128(10_000 * ONE));
+        contract.increase_borrows(bob(), U128(1_000 * ONE));
+
+        testing_env!(VMContextBuilder::new().block_index(1100).build());
+        contract.accrue_interest();
+
+        let borrows = contract.get_account_borrows(bob());
+        assert!(contract.borrow_index > Ratio::one());
+        assert!(contract.supply_index > Ratio::one());
+        assert!(borrows > 1_000 * ONE);
+        assert_eq!(contract.get_total_borrows(), borrows);
+    }
 }
@@ -0,0 +1,149 @@
+use crate::*;
+
+const GAS_FOR_DELEGATED_BORROW: Gas = Gas(65_000_000_000_000);
+
+impl Contract {
+    pub fn post_delegated_borrow(
+        &mut self,
+        token_amount: WBalance,
+        delegator: AccountId,
+        delegatee: AccountId,
+    ) -> PromiseOrValue<WBalance> {
+        if !is_promise_success() {
+            return PromiseOrValue::Value(token_amount);
+        }
+        self.adjust_account_rewards_by_campaign_type(delegator.clone(), CampaignType::Borrow);
+        self.accrue_interest();
+
+        controller::make_delegated_borrow(
+            delegator.clone(),
+            delegatee.clone(),
+            self.get_contract_address(),
+            token_amount,
+            WRatio::from(self.borrow_index),
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(15),
+        )
+        .then(ext_self::make_delegated_borrow_callback(
+            token_amount,
+            delegator,
+            delegatee,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(40),
+        ))
+        .into()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Borrows within the credit delegated to the caller, tokens are transferred to the caller
+    /// while the debt is recorded against the delegator
+    pub fn delegated_borrow(
+        &mut self,
+        delegator: AccountId,
+        amount: WBalance,
+    ) -> PromiseOrValue<WBalance> {
+        require!(
+            env::prepaid_gas() >= GAS_FOR_DELEGATED_BORROW,
+            "Prepaid gas is not enough for delegated borrow flow"
+        );
+        require!(amount.0 > 0, "Amount should be a positive number");
+        self.assert_storage_covered(&delegator);
+
+        self.mutex_account_lock(
+            Actions::DelegatedBorrow {
+                delegator,
+                delegatee: env::predecessor_account_id(),
+            },
+            amount,
+            self.terra_gas(80),
+        )
+    }
+
+    #[private]
+    pub fn make_delegated_borrow_callback(
+        &mut self,
+        token_amount: WBalance,
+        delegator: AccountId,
+        delegatee: AccountId,
+    ) -> PromiseOrValue<WBalance> {
+        if !is_promise_success() {
+            Events::BorrowFailedToIncreaseBorrowOnController {
+                account_id: delegator,
+                amount: token_amount,
+            }
+            .emit();
+            self.mutex_account_unlock();
+            return PromiseOrValue::Value(token_amount);
+        }
+
+        underlying_token::ft_transfer(
+            delegatee.clone(),
+            self.to_decimals_token(token_amount),
+            Some(format!(
+                "Delegated borrow with token_amount {}",
+                Balance::from(token_amount)
+            )),
+            self.get_underlying_contract_address(),
+            ONE_YOCTO,
+            self.terra_gas(4),
+        )
+        .then(ext_self::delegated_borrow_ft_transfer_callback(
+            token_amount,
+            delegator,
+            delegatee,
+            self.get_health_factor_result(),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(14),
+        ))
+        .into()
+    }
+
+    #[private]
+    pub fn delegated_borrow_ft_transfer_callback(
+        &mut self,
+        token_amount: WBalance,
+        delegator: AccountId,
+        delegatee: AccountId,
+        health_factor: Option<WRatio>,
+    ) -> PromiseOrValue<WBalance> {
+        if is_promise_success() {
+            let accrued_interest = self.get_account_accrued_interest(&delegator);
+            self.increase_borrows(delegator.clone(), token_amount);
+            self.update_campaigns_by_account_balance(delegator.clone(), CampaignType::Borrow);
+            self.mutex_account_unlock();
+            self.decrease_contract_balance(token_amount);
+
+            Events::DelegatedBorrowSuccess {
+                account_id: delegator,
+                delegatee_account_id: delegatee,
+                amount: token_amount,
+                state: self.get_action_state(accrued_interest, health_factor),
+            }
+            .emit();
+            PromiseOrValue::Value(U128(0))
+        } else {
+            controller::revert_delegated_borrow(
+                delegator.clone(),
+                delegatee,
+                self.get_contract_address(),
+                token_amount,
+                self.get_controller_address(),
+                NO_DEPOSIT,
+                self.terra_gas(4),
+            )
+            .then(ext_self::controller_decrease_borrows_fail_callback(
+                token_amount,
+                delegator,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                self.terra_gas(2),
+            ))
+            .into()
+        }
+    }
+}
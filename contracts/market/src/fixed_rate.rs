@@ -0,0 +1,570 @@
+use crate::*;
+use general::ratio::BigBalance;
+use near_sdk::env::block_height;
+use std::cmp::min;
+
+const GAS_FOR_FIXED_RATE_BORROW: Gas = Gas(65_000_000_000_000);
+const GAS_FOR_FIXED_RATE_REPAY: Gas = Gas(120_000_000_000_000);
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FixedRateConfig {
+    /// Added to the current variable borrow rate per block to get the fixed one
+    pub premium_per_block: WRatio,
+
+    /// Max term of the fixed rate loan in blocks
+    pub max_term: BlockHeight,
+
+    /// Part of the repaid amount charged to reserves for repayment before the maturity
+    pub early_repayment_fee: WRatio,
+
+    /// Utilization after which the protocol could reset fixed rates of the loans
+    pub rebalance_utilization: WRatio,
+}
+
+/// Fixed rate borrow position of the account, tracked separately from the variable rate borrows
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FixedRateLoan {
+    pub debt: FixedRateDebt,
+
+    /// Block the rate is locked till
+    pub maturity_block: BlockHeight,
+}
+
+impl Contract {
+    pub fn post_fixed_rate_borrow(
+        &mut self,
+        token_amount: WBalance,
+        account_to_borrow: AccountId,
+        term: BlockHeight,
+    ) -> PromiseOrValue<WBalance> {
+        if !is_promise_success() {
+            return PromiseOrValue::Value(token_amount);
+        }
+        self.adjust_rewards_by_campaign_type(CampaignType::Borrow);
+        self.accrue_interest();
+
+        let existing_debt = self.get_account_fixed_rate_borrows(&account_to_borrow);
+        let maturity_block = block_height() + term;
+        let loan = FixedRateLoan {
+            debt: FixedRateDebt {
+                amount: U128(existing_debt + token_amount.0),
+                rate_per_block: WRatio::from(self.get_current_fixed_rate()),
+                block: block_height(),
+            },
+            maturity_block: self
+                .get_fixed_rate_loan(&account_to_borrow)
+                .map_or(maturity_block, |loan| {
+                    std::cmp::max(loan.maturity_block, maturity_block)
+                }),
+        };
+
+        controller::make_fixed_rate_borrow(
+            account_to_borrow.clone(),
+            self.get_contract_address(),
+            token_amount,
+            loan.debt.clone(),
+            WRatio::from(self.borrow_index),
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(15),
+        )
+        .then(ext_self::make_fixed_rate_borrow_callback(
+            token_amount,
+            account_to_borrow,
+            loan,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(40),
+        ))
+        .into()
+    }
+
+    pub fn post_fixed_rate_repay(&mut self, token_amount: WBalance) -> PromiseOrValue<WBalance> {
+        if !is_promise_success() {
+            return PromiseOrValue::Value(self.to_decimals_token(token_amount));
+        }
+        self.adjust_rewards_by_campaign_type(CampaignType::Borrow);
+        self.accrue_interest();
+
+        let account = env::signer_account_id();
+        let mut loan = self
+            .get_fixed_rate_loan(&account)
+            .expect("Account has no fixed rate loan");
+        let debt = loan.debt.get_debt(block_height());
+
+        let early_repayment_fee = if block_height() < loan.maturity_block {
+            Ratio::from(self.get_fixed_rate_config().early_repayment_fee)
+        } else {
+            Ratio::zero()
+        };
+
+        // the fee is charged on top of the repaid amount, tokens over the debt are returned back
+        let repay_amount = min(
+            debt,
+            (BigBalance::from(token_amount.0) / (Ratio::one() + early_repayment_fee)).round_u128(),
+        );
+        let fee = min(
+            (BigBalance::from(repay_amount) * early_repayment_fee).round_u128(),
+            token_amount.0 - repay_amount,
+        );
+
+        loan.debt = FixedRateDebt {
+            amount: U128(debt - repay_amount),
+            rate_per_block: loan.debt.rate_per_block,
+            block: block_height(),
+        };
+        let fixed_rate_debt = Some(loan.debt.clone()).filter(|debt| debt.amount.0 > 0);
+
+        controller::set_fixed_rate_borrows(
+            account,
+            self.get_contract_address(),
+            fixed_rate_debt,
+            WRatio::from(self.borrow_index),
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        )
+        .then(ext_self::controller_fixed_rate_repay_callback(
+            token_amount,
+            U128(repay_amount),
+            U128(fee),
+            loan,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(20),
+        ))
+        .into()
+    }
+
+    pub fn get_fixed_rate_loan(&self, account: &AccountId) -> Option<FixedRateLoan> {
+        self.user_profiles
+            .get(account)
+            .unwrap_or_default()
+            .fixed_rate_loan
+    }
+
+    /// Fixed rate debt of the account with interest
+    pub fn get_account_fixed_rate_borrows(&self, account: &AccountId) -> Balance {
+        self.get_fixed_rate_loan(account)
+            .map_or(0, |loan| loan.debt.get_debt(block_height()))
+    }
+
+    pub fn get_fixed_rate_config(&self) -> FixedRateConfig {
+        self.fixed_rate_config
+            .clone()
+            .expect("Fixed rate borrows are disabled in the market")
+    }
+
+    /// Rate per block for the fixed rate loans taken at the current block
+    pub fn get_current_fixed_rate(&self) -> Ratio {
+        self.get_borrow_rate(
+            self.view_contract_balance(),
+            U128(self.get_total_borrows()),
+            U128(self.get_total_reserves()),
+        ) + Ratio::from(self.get_fixed_rate_config().premium_per_block)
+    }
+
+    /// Stores the fixed rate loan of the account and updates market fixed rate totals,
+    /// interest should be accrued before
+    pub(crate) fn set_fixed_rate_loan(&mut self, account: AccountId, loan: Option<FixedRateLoan>) {
+        let mut user = self.user_profiles.get(&account).unwrap_or_default();
+        let loan = loan.filter(|loan| loan.debt.amount.0 > 0);
+
+        if let Some(existing_loan) = user.fixed_rate_loan.as_ref() {
+            self.total_fixed_borrows = self
+                .total_fixed_borrows
+                .saturating_sub(existing_loan.debt.get_debt(block_height()));
+            self.fixed_interest_per_block =
+                self.fixed_interest_per_block - existing_loan.debt.get_interest_per_block();
+        }
+        if let Some(loan) = loan.as_ref() {
+            self.total_fixed_borrows += loan.debt.get_debt(block_height());
+            self.fixed_interest_per_block =
+                self.fixed_interest_per_block + loan.debt.get_interest_per_block();
+        }
+
+        user.fixed_rate_loan = loan;
+        self.user_profiles.insert(&account, &user);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Borrows with the rate locked for the given term, the rate is the current variable
+    /// borrow rate plus the premium
+    pub fn fixed_rate_borrow(
+        &mut self,
+        amount: WBalance,
+        term: BlockHeight,
+    ) -> PromiseOrValue<WBalance> {
+        require!(
+            env::prepaid_gas() >= GAS_FOR_FIXED_RATE_BORROW,
+            "Prepaid gas is not enough for fixed rate borrow flow"
+        );
+        require!(amount.0 > 0, "Amount should be a positive number");
+        require!(
+            term > 0 && term <= self.get_fixed_rate_config().max_term,
+            "Term of the fixed rate loan is out of the allowed range"
+        );
+        self.assert_storage_covered(&env::signer_account_id());
+
+        self.mutex_account_lock(
+            Actions::FixedRateBorrow {
+                account_to_borrow: env::signer_account_id(),
+                term,
+            },
+            amount,
+            self.terra_gas(80),
+        )
+    }
+
+    pub fn fixed_rate_repay(&mut self, token_amount: WBalance) -> PromiseOrValue<WBalance> {
+        require!(
+            env::prepaid_gas() >= GAS_FOR_FIXED_RATE_REPAY,
+            "Prepaid gas is not enough for fixed rate repay flow"
+        );
+        self.mutex_account_lock(Actions::FixedRateRepay, token_amount, self.terra_gas(140))
+    }
+
+    #[private]
+    pub fn make_fixed_rate_borrow_callback(
+        &mut self,
+        token_amount: WBalance,
+        account_to_borrow: AccountId,
+        loan: FixedRateLoan,
+    ) -> PromiseOrValue<WBalance> {
+        if !is_promise_success() {
+            Events::BorrowFailedToIncreaseBorrowOnController {
+                account_id: account_to_borrow,
+                amount: token_amount,
+            }
+            .emit();
+            self.mutex_account_unlock();
+            return PromiseOrValue::Value(token_amount);
+        }
+
+        underlying_token::ft_transfer(
+            account_to_borrow.clone(),
+            self.to_decimals_token(token_amount),
+            Some(format!(
+                "Fixed rate borrow with token_amount {}",
+                Balance::from(token_amount)
+            )),
+            self.get_underlying_contract_address(),
+            ONE_YOCTO,
+            self.terra_gas(4),
+        )
+        .then(ext_self::fixed_rate_borrow_ft_transfer_callback(
+            token_amount,
+            account_to_borrow,
+            loan,
+            self.get_health_factor_result(),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(14),
+        ))
+        .into()
+    }
+
+    #[private]
+    pub fn fixed_rate_borrow_ft_transfer_callback(
+        &mut self,
+        token_amount: WBalance,
+        account_to_borrow: AccountId,
+        loan: FixedRateLoan,
+        health_factor: Option<WRatio>,
+    ) -> PromiseOrValue<WBalance> {
+        if is_promise_success() {
+            self.accrue_interest();
+            self.set_fixed_rate_loan(account_to_borrow.clone(), Some(loan.clone()));
+            self.update_campaigns_by_account_balance(
+                account_to_borrow.clone(),
+                CampaignType::Borrow,
+            );
+            self.decrease_contract_balance(token_amount);
+            self.mutex_account_unlock();
+
+            Events::FixedRateBorrowSuccess {
+                state: self.get_action_state(
+                    self.get_account_accrued_interest(&account_to_borrow),
+                    health_factor,
+                ),
+                account_id: account_to_borrow,
+                amount: token_amount,
+                rate_per_block: loan.debt.rate_per_block,
+                maturity_block: loan.maturity_block,
+            }
+            .emit();
+            PromiseOrValue::Value(U128(0))
+        } else {
+            // restores the fixed rate debt the account had before the borrow
+            controller::set_fixed_rate_borrows(
+                account_to_borrow.clone(),
+                self.get_contract_address(),
+                self.get_fixed_rate_loan(&account_to_borrow)
+                    .map(|loan| loan.debt),
+                WRatio::from(self.borrow_index),
+                self.get_controller_address(),
+                NO_DEPOSIT,
+                self.terra_gas(4),
+            )
+            .then(ext_self::controller_decrease_borrows_fail_callback(
+                token_amount,
+                account_to_borrow,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                self.terra_gas(2),
+            ))
+            .into()
+        }
+    }
+
+    #[private]
+    pub fn controller_fixed_rate_repay_callback(
+        &mut self,
+        token_amount: WBalance,
+        repay_amount: WBalance,
+        fee: WBalance,
+        loan: FixedRateLoan,
+    ) -> PromiseOrValue<WBalance> {
+        if !is_promise_success() {
+            Events::RepayFailedToUpdateUserBalance {
+                account_id: env::signer_account_id(),
+                amount: token_amount,
+            }
+            .emit();
+            self.mutex_account_unlock();
+            return PromiseOrValue::Value(self.to_decimals_token(token_amount));
+        }
+
+        let health_factor = self.get_health_factor_result();
+        self.accrue_interest();
+        self.set_fixed_rate_loan(env::signer_account_id(), Some(loan));
+        self.increase_contract_balance(U128(repay_amount.0 + fee.0));
+        self.increase_reserve(fee);
+
+        self.mutex_account_unlock();
+        self.update_campaigns_by_account_balance(env::signer_account_id(), CampaignType::Borrow);
+        Events::FixedRateRepaySuccess {
+            account_id: env::signer_account_id(),
+            amount: repay_amount,
+            early_repayment_fee: fee,
+            state: self.get_action_state(
+                self.get_account_accrued_interest(&env::signer_account_id()),
+                health_factor,
+            ),
+        }
+        .emit();
+        self.refund_storage_if_empty(&env::signer_account_id());
+
+        let dust_balance = U128(token_amount.0 - repay_amount.0 - fee.0);
+        PromiseOrValue::Value(self.to_decimals_token(dust_balance))
+    }
+
+    /// Resets the rate of the fixed rate loan to the current one when the market utilization
+    /// is over the rebalance threshold or the loan is matured
+    pub fn rebalance_fixed_rate_loan(&mut self, account_id: AccountId) -> PromiseOrValue<WBalance> {
+        self.roles.assert_role(Role::Executor);
+        self.accrue_interest();
+
+        let mut loan = self
+            .get_fixed_rate_loan(&account_id)
+            .expect("Account has no fixed rate loan");
+        let util = self.get_util_rate(
+            self.view_contract_balance(),
+            U128(self.get_total_borrows()),
+            U128(self.get_total_reserves()),
+        );
+        require!(
+            util >= Ratio::from(self.get_fixed_rate_config().rebalance_utilization)
+                || block_height() >= loan.maturity_block,
+            "Market utilization is below the rebalance threshold"
+        );
+
+        loan.debt = FixedRateDebt {
+            amount: U128(loan.debt.get_debt(block_height())),
+            rate_per_block: WRatio::from(self.get_current_fixed_rate()),
+            block: block_height(),
+        };
+
+        controller::set_fixed_rate_borrows(
+            account_id.clone(),
+            self.get_contract_address(),
+            Some(loan.debt.clone()),
+            WRatio::from(self.borrow_index),
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        )
+        .then(ext_self::rebalance_fixed_rate_loan_callback(
+            account_id,
+            loan,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        ))
+        .into()
+    }
+
+    #[private]
+    pub fn rebalance_fixed_rate_loan_callback(
+        &mut self,
+        account_id: AccountId,
+        loan: FixedRateLoan,
+    ) -> PromiseOrValue<WBalance> {
+        require!(
+            is_promise_success(),
+            "Failed to update fixed rate borrows on controller"
+        );
+
+        self.accrue_interest();
+        self.set_fixed_rate_loan(account_id.clone(), Some(loan.clone()));
+        Events::FixedRateLoanRebalanced {
+            account_id,
+            rate_per_block: loan.debt.rate_per_block,
+        }
+        .emit();
+        PromiseOrValue::Value(loan.debt.amount)
+    }
+
+    pub fn set_fixed_rate_config(&mut self, fixed_rate_config: Option<FixedRateConfig>) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetFixedRateConfig(
+            fixed_rate_config,
+        ));
+    }
+
+    pub fn view_fixed_rate_config(&self) -> Option<FixedRateConfig> {
+        self.fixed_rate_config.clone()
+    }
+
+    pub fn view_fixed_rate_loan(&self, account_id: AccountId) -> Option<FixedRateLoan> {
+        self.get_fixed_rate_loan(&account_id)
+    }
+
+    pub fn view_account_fixed_rate_borrows(&self, account_id: AccountId) -> WBalance {
+        WBalance::from(self.get_account_fixed_rate_borrows(&account_id))
+    }
+
+    pub fn view_fixed_borrow_rate(&self) -> WRatio {
+        WRatio::from(self.get_current_fixed_rate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Contract, FixedRateConfig, FixedRateLoan, InterestRateModel};
+    use general::ratio::Ratio;
+    use general::{FixedRateDebt, WRatio};
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use std::str::FromStr;
+
+    const ONE: u128 = 10u128.pow(24);
+
+    fn init_env() -> Contract {
+        let (dtoken_account, underlying_token_account, controller_account) =
+            (alice(), bob(), carol());
+
+        let mut contract = Contract::new(Config {
+            initial_exchange_rate: U128::from(Ratio::one()),
+            underlying_token_id: underlying_token_account,
+            underlying_token_decimals: 24,
+            owner_id: dtoken_account,
+            controller_account_id: controller_account,
+            interest_rate_model: InterestRateModel::default(),
+            disable_transfer_token: true,
+        });
+        contract.fixed_rate_config = Some(FixedRateConfig {
+            premium_per_block: ratio("0.00001"),
+            max_term: 10_000,
+            early_repayment_fee: ratio("0.01"),
+            rebalance_utilization: ratio("0.9"),
+        });
+        contract.increase_contract_balance(U128(10_000 * ONE));
+
+        contract
+    }
+
+    fn ratio(value: &str) -> WRatio {
+        WRatio::from(Ratio::from_str(value).unwrap())
+    }
+
+    fn fixed_rate_loan(amount: u128, rate_per_block: &str, block: u64) -> FixedRateLoan {
+        FixedRateLoan {
+            debt: FixedRateDebt {
+                amount: U128(amount),
+                rate_per_block: ratio(rate_per_block),
+                block,
+            },
+            maturity_block: block + 5_000,
+        }
+    }
+
+    #[test]
+    fn test_fixed_rate_loan_accrual() {
+        testing_env!(VMContextBuilder::new().block_index(100).build());
+        let mut contract = init_env();
+        contract.set_fixed_rate_loan(bob(), Some(fixed_rate_loan(1_000 * ONE, "0.0001", 100)));
+
+        testing_env!(VMContextBuilder::new().block_index(1100).build());
+        contract.accrue_interest();
+
+        assert_eq!(contract.get_account_fixed_rate_borrows(&bob()), 1_100 * ONE);
+        assert_eq!(contract.get_total_borrows(), 1_100 * ONE);
+
+        contract.set_fixed_rate_loan(bob(), None);
+
+        assert_eq!(contract.get_total_borrows(), 0);
+        assert_eq!(contract.fixed_interest_per_block, Ratio::zero());
+    }
+
+    #[test]
+    fn test_decrease_borrows_with_fixed_rate_loan() {
+        testing_env!(VMContextBuilder::new().block_index(100).build());
+        let mut contract = init_env();
+        contract.increase_borrows(bob(), U128(100 * ONE));
+        contract.set_fixed_rate_loan(bob(), Some(fixed_rate_loan(1_000 * ONE, "0", 100)));
+
+        contract.decrease_borrows(bob(), U128(50 * ONE));
+        assert_eq!(contract.get_account_borrows(bob()), 50 * ONE);
+        assert_eq!(contract.get_account_fixed_rate_borrows(&bob()), 1_000 * ONE);
+
+        // variable rate borrows are repaid first, the rest goes to the fixed rate loan
+        contract.decrease_borrows(bob(), U128(250 * ONE));
+        assert_eq!(contract.get_account_borrows(bob()), 0);
+        assert_eq!(contract.get_account_fixed_rate_borrows(&bob()), 800 * ONE);
+        assert_eq!(contract.get_total_borrows(), 800 * ONE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds the account borrows")]
+    fn test_decrease_borrows_above_total_debt() {
+        testing_env!(VMContextBuilder::new().block_index(100).build());
+        let mut contract = init_env();
+        contract.increase_borrows(bob(), U128(100 * ONE));
+        contract.set_fixed_rate_loan(bob(), Some(fixed_rate_loan(1_000 * ONE, "0", 100)));
+
+        contract.decrease_borrows(bob(), U128(1_101 * ONE));
+    }
+
+    #[test]
+    #[should_panic(expected = "Market utilization is below the rebalance threshold")]
+    fn test_rebalance_below_threshold() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .block_index(100)
+            .build());
+        let mut contract = init_env();
+        contract.set_fixed_rate_loan(bob(), Some(fixed_rate_loan(1_000 * ONE, "0.0001", 100)));
+
+        contract.rebalance_fixed_rate_loan(bob());
+    }
+}
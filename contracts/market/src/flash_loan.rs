@@ -0,0 +1,502 @@
+use crate::*;
+use general::ratio::BigBalance;
+use std::cmp::min;
+
+const GAS_FOR_FLASH_LOAN: Gas = Gas(100_000_000_000_000);
+
+/// Amount of blocks after which the flash loan stuck by a failed callback could be reset
+pub const FLASH_LOAN_EXPIRY: BlockHeight = 100;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlashLoan {
+    /// Account which requested the flash loan
+    pub initiator_id: AccountId,
+
+    /// Contract which receives the tokens and is called with `on_flash_loan`
+    pub receiver_id: AccountId,
+
+    pub amount: WBalance,
+
+    /// Fee which should be returned on top of the amount
+    pub fee: WBalance,
+
+    /// Amount returned to the market so far
+    pub returned_amount: WBalance,
+
+    /// Block at which the flash loan was taken
+    pub block_height: BlockHeight,
+}
+
+impl Contract {
+    /// Accepts tokens returned by the flash loan receiver, tokens over the loan and fee
+    /// are given back to the sender. Without a flash loan in progress the tokens repay
+    /// the flash loan debt of the sender
+    pub fn repay_flash_loan(
+        &mut self,
+        sender_id: AccountId,
+        token_amount: WBalance,
+    ) -> PromiseOrValue<WBalance> {
+        let mut flash_loan = match self.flash_loan.clone() {
+            Some(flash_loan) => flash_loan,
+            None => return self.repay_flash_loan_debt(sender_id, token_amount),
+        };
+
+        let outstanding_amount =
+            (flash_loan.amount.0 + flash_loan.fee.0).saturating_sub(flash_loan.returned_amount.0);
+        let returned_amount = min(token_amount.0, outstanding_amount);
+        flash_loan.returned_amount = U128(flash_loan.returned_amount.0 + returned_amount);
+        self.flash_loan = Some(flash_loan);
+
+        PromiseOrValue::Value(self.to_decimals_token(U128(token_amount.0 - returned_amount)))
+    }
+
+    fn repay_flash_loan_debt(
+        &mut self,
+        initiator_id: AccountId,
+        token_amount: WBalance,
+    ) -> PromiseOrValue<WBalance> {
+        let debt = self.get_flash_loan_debt(&initiator_id);
+        require!(debt > 0, "There is no flash loan in progress");
+
+        let repaid_amount = min(token_amount.0, debt);
+        self.set_flash_loan_debt(&initiator_id, debt - repaid_amount);
+        self.increase_contract_balance(U128(repaid_amount));
+
+        Events::FlashLoanDebtRepay {
+            initiator_id,
+            amount: U128(repaid_amount),
+            remaining_debt: U128(debt - repaid_amount),
+        }
+        .emit();
+        PromiseOrValue::Value(self.to_decimals_token(U128(token_amount.0 - repaid_amount)))
+    }
+
+    pub fn get_flash_loan_debt(&self, initiator_id: &AccountId) -> Balance {
+        self.flash_loan_debts.get(initiator_id).unwrap_or(0)
+    }
+
+    fn set_flash_loan_debt(&mut self, initiator_id: &AccountId, debt: Balance) {
+        self.total_flash_loan_debt =
+            self.total_flash_loan_debt - self.get_flash_loan_debt(initiator_id) + debt;
+        if debt == 0 {
+            self.flash_loan_debts.remove(initiator_id);
+        } else {
+            self.flash_loan_debts.insert(initiator_id, &debt);
+        }
+    }
+
+    /// Tokens lent by flash loans which are owed to the market, they are counted in the market
+    /// assets so the exchange rate doesn't drop while the flash loan is in progress or unpaid
+    pub fn get_flash_loan_assets(&self) -> Balance {
+        let in_progress_amount = self
+            .flash_loan
+            .as_ref()
+            .map_or(0, |flash_loan| flash_loan.amount.0);
+        in_progress_amount + self.total_flash_loan_debt
+    }
+
+    /// Returns the flash loan taken at the given block, the loan could already be closed
+    /// by the reset if its callback is late
+    fn get_flash_loan(&self, block_height: BlockHeight) -> Option<FlashLoan> {
+        self.flash_loan
+            .clone()
+            .filter(|flash_loan| flash_loan.block_height == block_height)
+    }
+
+    /// Closes the flash loan and returns the amount which was not given back to the market.
+    /// Transferred tokens could not be reverted, so the amount which was not returned is
+    /// recorded as the debt of the initiator
+    fn close_flash_loan(&mut self, flash_loan: FlashLoan) -> WBalance {
+        self.flash_loan = None;
+        self.accrue_interest();
+
+        let returned_amount = flash_loan.returned_amount.0;
+        self.increase_contract_balance(flash_loan.returned_amount);
+        self.increase_reserve(U128(returned_amount.saturating_sub(flash_loan.amount.0)));
+
+        if returned_amount >= flash_loan.amount.0 + flash_loan.fee.0 {
+            Events::FlashLoanSuccess {
+                receiver_id: flash_loan.receiver_id,
+                amount: flash_loan.amount,
+                fee: flash_loan.fee,
+            }
+            .emit();
+            return U128(0);
+        }
+
+        let loss = flash_loan.amount.0.saturating_sub(returned_amount);
+        self.set_flash_loan_debt(
+            &flash_loan.initiator_id,
+            self.get_flash_loan_debt(&flash_loan.initiator_id) + loss,
+        );
+        Events::FlashLoanFailed {
+            initiator_id: flash_loan.initiator_id,
+            receiver_id: flash_loan.receiver_id,
+            amount: flash_loan.amount,
+            returned_amount: U128(returned_amount),
+        }
+        .emit();
+        U128(loss)
+    }
+
+    pub fn get_flash_loan_fee(&self) -> Ratio {
+        self.flash_loan_fee
+            .expect("Flash loans are disabled in the market")
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Transfers underlying tokens to the allowed receiver and calls its `on_flash_loan`,
+    /// the amount with fee should be returned with `FlashLoanRepay` transfer within that call.
+    /// The initiator is liable for the amount which is not returned
+    pub fn flash_loan(
+        &mut self,
+        receiver_id: AccountId,
+        amount: WBalance,
+        msg: String,
+    ) -> PromiseOrValue<WBalance> {
+        require!(
+            env::prepaid_gas() >= GAS_FOR_FLASH_LOAN,
+            "Prepaid gas is not enough for flash loan flow"
+        );
+        require!(amount.0 > 0, "Amount should be a positive number");
+        require!(
+            self.flash_loan_receivers.contains(&receiver_id),
+            format!(
+                "Receiver {} is not allowed to take flash loans",
+                receiver_id
+            )
+        );
+        let initiator_id = env::predecessor_account_id();
+        require!(
+            self.get_flash_loan_debt(&initiator_id) == 0,
+            "Initiator has to repay the previous flash loan first"
+        );
+        require!(
+            self.flash_loan.is_none(),
+            "Another flash loan is in progress"
+        );
+        require!(
+            amount.0 < self.get_contract_balance(),
+            "Not enough liquidity in the market for the flash loan"
+        );
+
+        self.accrue_interest();
+        let fee = (BigBalance::from(amount.0) * self.get_flash_loan_fee()).round_u128();
+        let block_height = env::block_height();
+        self.flash_loan = Some(FlashLoan {
+            initiator_id,
+            receiver_id: receiver_id.clone(),
+            amount,
+            fee: U128(fee),
+            returned_amount: U128(0),
+            block_height,
+        });
+        self.decrease_contract_balance(amount);
+
+        underlying_token::ft_transfer(
+            receiver_id,
+            self.to_decimals_token(amount),
+            Some(format!("Flash loan with token_amount {}", amount.0)),
+            self.get_underlying_contract_address(),
+            ONE_YOCTO,
+            self.terra_gas(5),
+        )
+        .then(ext_self::flash_loan_ft_transfer_callback(
+            msg,
+            block_height,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(80),
+        ))
+        .into()
+    }
+
+    #[private]
+    pub fn flash_loan_ft_transfer_callback(
+        &mut self,
+        msg: String,
+        block_height: BlockHeight,
+    ) -> PromiseOrValue<WBalance> {
+        let flash_loan = match self.get_flash_loan(block_height) {
+            Some(flash_loan) => flash_loan,
+            None => return PromiseOrValue::Value(U128(0)),
+        };
+
+        if !is_promise_success() {
+            self.flash_loan = None;
+            self.increase_contract_balance(flash_loan.amount);
+            Events::FlashLoanFailed {
+                initiator_id: flash_loan.initiator_id,
+                receiver_id: flash_loan.receiver_id,
+                amount: flash_loan.amount,
+                returned_amount: U128(0),
+            }
+            .emit();
+            return PromiseOrValue::Value(flash_loan.amount);
+        }
+
+        flash_loan_receiver::on_flash_loan(
+            flash_loan.initiator_id,
+            self.get_underlying_contract_address(),
+            self.to_decimals_token(flash_loan.amount),
+            self.to_decimals_token(flash_loan.fee),
+            msg,
+            flash_loan.receiver_id,
+            NO_DEPOSIT,
+            self.terra_gas(60),
+        )
+        .then(ext_self::flash_loan_callback(
+            block_height,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        ))
+        .into()
+    }
+
+    #[private]
+    pub fn flash_loan_callback(&mut self, block_height: BlockHeight) -> WBalance {
+        match self.get_flash_loan(block_height) {
+            Some(flash_loan) => self.close_flash_loan(flash_loan),
+            None => U128(0),
+        }
+    }
+
+    /// Closes the flash loan which callback has failed, e.g. run out of gas, the amount
+    /// which was not returned is recorded as the debt of the initiator
+    pub fn reset_flash_loan(&mut self) -> WBalance {
+        self.roles.assert_role(Role::RiskManager);
+        let flash_loan = self
+            .flash_loan
+            .clone()
+            .expect("There is no flash loan in progress");
+        require!(
+            env::block_height() > flash_loan.block_height + FLASH_LOAN_EXPIRY,
+            "Flash loan is not expired yet"
+        );
+
+        self.close_flash_loan(flash_loan)
+    }
+
+    pub fn set_flash_loan_receiver(&mut self, receiver_id: AccountId, is_allowed: bool) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetFlashLoanReceiver {
+            receiver_id,
+            is_allowed,
+        });
+    }
+
+    pub fn set_flash_loan_fee(&mut self, flash_loan_fee: Option<WRatio>) {
+        self.roles.assert_role(Role::RiskManager);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetFlashLoanFee(flash_loan_fee));
+    }
+
+    pub fn view_flash_loan_fee(&self) -> Option<WRatio> {
+        self.flash_loan_fee.map(WRatio::from)
+    }
+
+    pub fn view_flash_loan(&self) -> Option<FlashLoan> {
+        self.flash_loan.clone()
+    }
+
+    pub fn view_flash_loan_receivers(&self) -> Vec<AccountId> {
+        self.flash_loan_receivers.to_vec()
+    }
+
+    pub fn view_flash_loan_debt(&self, initiator_id: AccountId) -> WBalance {
+        U128(self.get_flash_loan_debt(&initiator_id))
+    }
+
+    pub fn view_total_flash_loan_debt(&self) -> WBalance {
+        U128(self.total_flash_loan_debt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Contract, InterestRateModel, FLASH_LOAN_EXPIRY};
+    use general::ratio::Ratio;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{
+        testing_env, AccountId, PromiseOrValue, PromiseResult, RuntimeFeesConfig, VMConfig,
+    };
+    use std::str::FromStr;
+
+    const ONE: u128 = 10u128.pow(24);
+
+    fn init_env(total_reserves: u128) -> (Contract, AccountId) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .predecessor_account_id(alice())
+            .signer_account_id(alice())
+            .build());
+        let receiver: AccountId = AccountId::new_unchecked("receiver.near".to_string());
+
+        let mut contract = Contract::new(Config {
+            initial_exchange_rate: U128::from(Ratio::one()),
+            underlying_token_id: bob(),
+            underlying_token_decimals: 24,
+            owner_id: alice(),
+            controller_account_id: carol(),
+            interest_rate_model: InterestRateModel::default(),
+            disable_transfer_token: true,
+        });
+        contract.flash_loan_fee = Some(Ratio::from_str("0.01").unwrap());
+        contract.set_flash_loan_receiver(receiver.clone(), true);
+        contract.increase_reserve(U128(total_reserves));
+        contract.increase_contract_balance(U128(1_000 * ONE + total_reserves));
+        contract.mint(carol(), U128(1_000 * ONE));
+
+        contract.flash_loan(receiver.clone(), U128(100 * ONE), "".to_string());
+
+        (contract, receiver)
+    }
+
+    #[test]
+    fn test_flash_loan_returned_with_fee() {
+        let (mut contract, receiver) = init_env(0);
+
+        let flash_loan = contract.view_flash_loan().unwrap();
+        assert_eq!(flash_loan.receiver_id, receiver);
+        assert_eq!(flash_loan.fee, U128(ONE));
+        assert_eq!(contract.get_contract_balance(), 900 * ONE);
+
+        // tokens over the amount with fee are given back
+        match contract.repay_flash_loan(receiver, U128(102 * ONE)) {
+            PromiseOrValue::Value(unused_amount) => assert_eq!(unused_amount, U128(ONE)),
+            PromiseOrValue::Promise(_) => panic!("Unexpected promise"),
+        }
+
+        assert_eq!(contract.flash_loan_callback(0), U128(0));
+        assert!(contract.view_flash_loan().is_none());
+        assert_eq!(contract.get_total_reserves(), ONE);
+        assert_eq!(contract.get_contract_balance(), 1_001 * ONE);
+    }
+
+    #[test]
+    fn test_flash_loan_not_returned() {
+        let (mut contract, receiver) = init_env(10 * ONE);
+
+        contract.repay_flash_loan(receiver, U128(40 * ONE));
+
+        // the loss is not taken from the suppliers, the initiator owes it to the market
+        assert_eq!(contract.flash_loan_callback(0), U128(60 * ONE));
+        assert!(contract.view_flash_loan().is_none());
+        assert_eq!(contract.get_total_reserves(), 10 * ONE);
+        assert_eq!(contract.get_contract_balance(), 950 * ONE);
+        assert_eq!(contract.view_flash_loan_debt(alice()), U128(60 * ONE));
+        assert_eq!(contract.view_total_flash_loan_debt(), U128(60 * ONE));
+        // the debt is counted in the market assets, the suppliers' share keeps its value
+        assert_eq!(
+            contract.get_exchange_rate(contract.view_contract_balance()),
+            Ratio::one()
+        );
+
+        match contract.repay_flash_loan(alice(), U128(70 * ONE)) {
+            PromiseOrValue::Value(unused_amount) => assert_eq!(unused_amount, U128(10 * ONE)),
+            PromiseOrValue::Promise(_) => panic!("Unexpected promise"),
+        }
+        assert_eq!(contract.view_flash_loan_debt(alice()), U128(0));
+        assert_eq!(contract.view_total_flash_loan_debt(), U128(0));
+        assert_eq!(contract.get_contract_balance(), 1_010 * ONE);
+    }
+
+    #[test]
+    fn test_supply_during_flash_loan() {
+        let (mut contract, _) = init_env(0);
+        assert_eq!(contract.get_contract_balance(), 900 * ONE);
+        assert_eq!(
+            contract.get_exchange_rate(contract.view_contract_balance()),
+            Ratio::one()
+        );
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(alice())
+                .predecessor_account_id(alice())
+                .signer_account_id(bob())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.post_supply(U128(100 * ONE));
+
+        // the supplier gets dtokens by the rate the market had before the flash loan
+        assert_eq!(contract.token.internal_unwrap_balance_of(&bob()), 100 * ONE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Initiator has to repay the previous flash loan first")]
+    fn test_flash_loan_with_debt() {
+        let (mut contract, receiver) = init_env(0);
+        contract.flash_loan_callback(0);
+
+        contract.flash_loan(receiver, U128(100 * ONE), "".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver receiver.near is not allowed to take flash loans")]
+    fn test_flash_loan_receiver_not_allowed() {
+        let (mut contract, receiver) = init_env(0);
+        contract.repay_flash_loan(receiver.clone(), U128(101 * ONE));
+        contract.flash_loan_callback(0);
+        contract.set_flash_loan_receiver(receiver.clone(), false);
+
+        contract.flash_loan(receiver, U128(100 * ONE), "".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Another flash loan is in progress")]
+    fn test_flash_loan_in_progress() {
+        let (mut contract, receiver) = init_env(0);
+
+        contract.flash_loan(receiver, U128(100 * ONE), "".to_string());
+    }
+
+    #[test]
+    fn test_reset_expired_flash_loan() {
+        let (mut contract, _) = init_env(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .predecessor_account_id(alice())
+            .signer_account_id(alice())
+            .block_index(FLASH_LOAN_EXPIRY + 1)
+            .build());
+        assert_eq!(contract.reset_flash_loan(), U128(100 * ONE));
+        assert!(contract.view_flash_loan().is_none());
+        assert_eq!(contract.view_flash_loan_debt(alice()), U128(100 * ONE));
+
+        // callback of the reset flash loan doesn't change the state
+        assert_eq!(contract.flash_loan_callback(0), U128(0));
+        assert_eq!(contract.view_flash_loan_debt(alice()), U128(100 * ONE));
+    }
+
+    #[test]
+    #[should_panic(expected = "The change should be scheduled through the timelock")]
+    fn test_instant_flash_loan_fee_change_with_timelock() {
+        let (mut contract, _) = init_env(0);
+        contract.set_timelock_delay(100);
+
+        contract.set_flash_loan_fee(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Flash loan is not expired yet")]
+    fn test_reset_flash_loan_before_expiry() {
+        let (mut contract, _) = init_env(0);
+
+        contract.reset_flash_loan();
+    }
+}
@@ -12,11 +12,6 @@ impl FungibleTokenReceiver for Contract {
         amount: U128,
         msg: String,
     ) -> PromiseOrValue<U128> {
-        assert_eq!(
-            env::predecessor_account_id(),
-            self.underlying_token,
-            "The call should come from token account"
-        );
         assert!(
             Balance::from(amount) > 0,
             "Amount should be a positive number"
@@ -28,9 +23,26 @@ impl FungibleTokenReceiver for Contract {
 
         let action: Actions = serde_json::from_str(&msg).expect("Incorrect command in transfer");
 
+        // reward campaigns are funded in their reward tokens, other actions accept the underlying only
+        if let Actions::FundCampaign { campaign_id } = action {
+            return self.fund_reward_campaign(
+                campaign_id,
+                env::predecessor_account_id(),
+                sender_id,
+                converted_amount,
+            );
+        }
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.underlying_token,
+            "The call should come from token account"
+        );
+
         match action {
             Actions::Supply => self.supply(converted_amount),
             Actions::Repay => self.repay(converted_amount),
+            Actions::FixedRateRepay => self.fixed_rate_repay(converted_amount),
+            Actions::FlashLoanRepay => self.repay_flash_loan(sender_id, converted_amount),
             Actions::Liquidate {
                 borrower,
                 borrowing_dtoken,
@@ -43,7 +55,10 @@ impl FungibleTokenReceiver for Contract {
                 converted_amount,
             ),
             Actions::Reserve => self.reserve(converted_amount),
-            Actions::Deposit => self.deposit(converted_amount),
+            Actions::LiquidationDeposit => {
+                self.assert_storage_covered(&sender_id);
+                self.increase_liquidation_deposit(sender_id, converted_amount)
+            }
             _ => {
                 panic!("Incorrect action in transfer")
             }
@@ -1,6 +1,5 @@
 use crate::*;
 use general::ratio::{BigBalance, Ratio};
-use std::cmp::min;
 
 #[near_bindgen]
 impl Contract {
@@ -33,24 +32,11 @@ impl Contract {
         total_reserves: WBalance,
     ) -> Ratio {
         let util = self.get_util_rate(underlying_balance, total_borrows, total_reserves);
-        let interest_rate_model = self.config.get().unwrap().interest_rate_model;
-        let kink = interest_rate_model.get_kink();
-        let multiplier_per_block = interest_rate_model.get_multiplier_per_block();
-        let base_rate_per_block = interest_rate_model.get_base_rate_per_block();
-        let jump_multiplier_per_block = interest_rate_model.get_jump_multiplier_per_block();
-
-        let multiplier = if util > kink {
-            util - kink
-        } else {
-            Ratio::zero()
-        };
 
-        min(util, kink) * multiplier_per_block
-            + multiplier * jump_multiplier_per_block
-            + base_rate_per_block
+        self.get_interest_rate_model().get_borrow_rate(util)
     }
 
-    fn get_util_rate(
+    pub(crate) fn get_util_rate(
         &self,
         underlying_balance: WBalance,
         total_borrows: WBalance,
@@ -64,7 +50,12 @@ impl Contract {
                     - Balance::from(total_reserves),
             )
         } else {
-            self.config.get().unwrap().interest_rate_model.get_kink()
+            // all the underlying balance is reserved, so the market is fully utilized
+            return if total_borrows.0 > 0 {
+                Ratio::one()
+            } else {
+                Ratio::zero()
+            };
         };
 
         // this may happen when there is no supplies
@@ -78,11 +69,17 @@ impl Contract {
 
 #[cfg(test)]
 mod tests {
-    use crate::InterestRateModel;
+    use crate::{
+        AdaptiveModel, InterestRateModel, JumpRateModel, MultiKinkModel, RateKink,
+        RiskParameterChange,
+    };
     use general::ratio::Ratio;
     use general::WRatio;
     use near_sdk::json_types::U128;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use std::cmp::{max, min};
     use std::str::FromStr;
 
     use crate::{Config, Contract};
@@ -116,7 +113,7 @@ mod tests {
     fn test_get_borrow_rate() {
         let contract = init_test_env();
 
-        let mut interest_rate_model = contract.config.get().unwrap().interest_rate_model;
+        let mut interest_rate_model = JumpRateModel::default();
 
         interest_rate_model.set_base_rate_per_block(WRatio::from(0));
         interest_rate_model.set_multiplier_per_block(WRatio::from(500000000));
@@ -133,7 +130,7 @@ mod tests {
     fn test_get_supply_rate() {
         let contract = init_test_env();
 
-        let mut interest_rate_model = contract.config.get().unwrap().interest_rate_model;
+        let mut interest_rate_model = JumpRateModel::default();
 
         interest_rate_model.set_base_rate_per_block(WRatio::from(0));
         interest_rate_model.set_multiplier_per_block(WRatio::from(500000000));
@@ -151,4 +148,206 @@ mod tests {
             Ratio::from_str("1.709999999999998803").unwrap()
         );
     }
+
+    fn ratio(value: &str) -> WRatio {
+        WRatio::from(Ratio::from_str(value).unwrap())
+    }
+
+    /// Ratio division rounds up the last digit, so rates are compared with that precision
+    fn assert_rate_eq(actual: Ratio, expected: &str, util: &str) {
+        let expected = Ratio::from_str(expected).unwrap();
+        let precision = Ratio::from_str("0.00000000000000000000001").unwrap();
+
+        assert!(
+            max(actual, expected) - min(actual, expected) < precision,
+            "Unexpected borrow rate {} at utilization {}, expected {}",
+            actual,
+            util,
+            expected
+        );
+    }
+
+    fn jump_rate_model() -> InterestRateModel {
+        InterestRateModel::JumpRate(JumpRateModel {
+            kink: ratio("0.8"),
+            base_rate_per_block: ratio("0.01"),
+            multiplier_per_block: ratio("0.1"),
+            jump_multiplier_per_block: ratio("1"),
+            reserve_factor: ratio("0.1"),
+        })
+    }
+
+    fn multi_kink_model() -> InterestRateModel {
+        InterestRateModel::MultiKink(MultiKinkModel {
+            base_rate_per_block: ratio("0.01"),
+            kinks: vec![
+                RateKink {
+                    utilization: ratio("0.5"),
+                    rate_per_block: ratio("0.05"),
+                },
+                RateKink {
+                    utilization: ratio("0.9"),
+                    rate_per_block: ratio("0.15"),
+                },
+                RateKink {
+                    utilization: ratio("1"),
+                    rate_per_block: ratio("1.15"),
+                },
+            ],
+            reserve_factor: ratio("0.1"),
+        })
+    }
+
+    fn adaptive_model() -> AdaptiveModel {
+        AdaptiveModel {
+            optimal_utilization: ratio("0.8"),
+            rate_at_target: ratio("0.04"),
+            min_rate_at_target: ratio("0.001"),
+            max_rate_at_target: ratio("0.1"),
+            adjustment_speed: ratio("0.001"),
+            curve_steepness: ratio("4"),
+            reserve_factor: ratio("0.1"),
+        }
+    }
+
+    #[test]
+    fn test_borrow_rate_curves() {
+        let cases = vec![
+            (
+                jump_rate_model(),
+                vec![
+                    ("0", "0.01"),
+                    ("0.5", "0.06"),
+                    ("0.8", "0.09"),
+                    ("0.9", "0.19"),
+                    ("1", "0.29"),
+                ],
+            ),
+            (
+                multi_kink_model(),
+                vec![
+                    ("0", "0.01"),
+                    ("0.25", "0.03"),
+                    ("0.5", "0.05"),
+                    ("0.7", "0.1"),
+                    ("0.95", "0.65"),
+                    ("1", "1.15"),
+                ],
+            ),
+            (
+                InterestRateModel::Adaptive(adaptive_model()),
+                vec![
+                    ("0", "0.01"),
+                    ("0.4", "0.025"),
+                    ("0.8", "0.04"),
+                    ("0.9", "0.1"),
+                    ("1", "0.16"),
+                ],
+            ),
+        ];
+
+        for (model, points) in cases {
+            for (util, expected_rate) in points {
+                assert_rate_eq(
+                    model.get_borrow_rate(Ratio::from_str(util).unwrap()),
+                    expected_rate,
+                    util,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_adaptive_rate_at_target() {
+        let cases = vec![
+            // above the optimal utilization the rate at target grows
+            ("1", 100, "0.044"),
+            ("0.8", 100, "0.04"),
+            // below the optimal utilization the rate at target falls
+            ("0.4", 100, "0.038"),
+            // rate at target stays within the bounds
+            ("1", 100_000, "0.1"),
+            ("0", 100_000, "0.001"),
+        ];
+
+        for (util, blocks, expected_rate_at_target) in cases {
+            let mut model = adaptive_model();
+            model.adapt(Ratio::from_str(util).unwrap(), blocks);
+
+            assert_eq!(
+                Ratio::from(model.rate_at_target),
+                Ratio::from_str(expected_rate_at_target).unwrap(),
+                "Unexpected rate at target at utilization {}",
+                util
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_interest_rate_model() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .build());
+        let mut contract = init_test_env();
+
+        contract.set_interest_rate_model(multi_kink_model());
+
+        assert_rate_eq(
+            contract.get_borrow_rate(U128(50), U128(50), U128(0)),
+            "0.05",
+            "0.5",
+        );
+        assert_eq!(
+            contract.get_interest_rate_model().get_reserve_factor(),
+            Ratio::from_str("0.1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scheduled_interest_rate_model() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .build());
+        let mut contract = init_test_env();
+        contract.set_timelock_delay(100);
+
+        let proposal_id = contract.schedule_risk_parameter_change(
+            RiskParameterChange::SetInterestRateModel(multi_kink_model()),
+            100,
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .block_index(100)
+            .build());
+        contract.execute_risk_parameter_change(proposal_id);
+
+        assert_rate_eq(
+            contract.get_borrow_rate(U128(50), U128(50), U128(0)),
+            "0.05",
+            "0.5",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The last kink should be at the full utilization")]
+    fn test_set_interest_rate_model_with_incomplete_curve() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .build());
+        let mut contract = init_test_env();
+
+        contract.set_interest_rate_model(InterestRateModel::MultiKink(MultiKinkModel {
+            base_rate_per_block: ratio("0.01"),
+            kinks: vec![RateKink {
+                utilization: ratio("0.5"),
+                rate_per_block: ratio("0.05"),
+            }],
+            reserve_factor: ratio("0.1"),
+        }));
+    }
 }
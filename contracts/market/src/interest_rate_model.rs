@@ -1,12 +1,23 @@
 use crate::*;
 use general::ratio::{BigBalance, Ratio};
 use near_sdk::env::block_height;
+use std::cmp::{max, min};
 use std::fmt;
 use std::str::FromStr;
 
+/// Interest rate model of the market, all rates are per block
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
-pub struct InterestRateModel {
+pub enum InterestRateModel {
+    JumpRate(JumpRateModel),
+    MultiKink(MultiKinkModel),
+    Adaptive(AdaptiveModel),
+}
+
+/// Borrow rate grows with the multiplier up to the kink and with the jump multiplier after it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JumpRateModel {
     pub kink: WRatio,
     pub multiplier_per_block: WRatio,
     pub base_rate_per_block: WRatio,
@@ -14,6 +25,41 @@ pub struct InterestRateModel {
     pub reserve_factor: WRatio,
 }
 
+/// Point of the piecewise-linear borrow rate curve
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RateKink {
+    pub utilization: WRatio,
+    pub rate_per_block: WRatio,
+}
+
+/// Borrow rate is linearly interpolated between the base rate at zero utilization and the kinks,
+/// the last kink should be at the full utilization
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MultiKinkModel {
+    pub base_rate_per_block: WRatio,
+    pub kinks: Vec<RateKink>,
+    pub reserve_factor: WRatio,
+}
+
+/// Borrow rate follows the curve around the rate at target, which itself grows while utilization
+/// is above the optimal one and falls while it is below
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AdaptiveModel {
+    pub optimal_utilization: WRatio,
+    /// Borrow rate at the optimal utilization, adjusted with every interest accrual
+    pub rate_at_target: WRatio,
+    pub min_rate_at_target: WRatio,
+    pub max_rate_at_target: WRatio,
+    /// Relative change of the rate at target per block at the max utilization deviation
+    pub adjustment_speed: WRatio,
+    /// Borrow rate at the full utilization relatively to the rate at target
+    pub curve_steepness: WRatio,
+    pub reserve_factor: WRatio,
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -45,6 +91,50 @@ impl fmt::Display for WithdrawInfo {
 }
 
 impl InterestRateModel {
+    pub fn get_reserve_factor(&self) -> Ratio {
+        match self {
+            InterestRateModel::JumpRate(model) => Ratio::from(model.reserve_factor),
+            InterestRateModel::MultiKink(model) => Ratio::from(model.reserve_factor),
+            InterestRateModel::Adaptive(model) => Ratio::from(model.reserve_factor),
+        }
+    }
+
+    pub fn get_borrow_rate(&self, util: Ratio) -> Ratio {
+        match self {
+            InterestRateModel::JumpRate(model) => model.get_borrow_rate(util),
+            InterestRateModel::MultiKink(model) => model.get_borrow_rate(util),
+            InterestRateModel::Adaptive(model) => model.get_borrow_rate(util),
+        }
+    }
+
+    /// Moves the state of the model according to the utilization held for the given blocks
+    pub fn adapt(&mut self, util: Ratio, blocks: BlockHeight) {
+        if let InterestRateModel::Adaptive(model) = self {
+            model.adapt(util, blocks);
+        }
+    }
+
+    pub fn assert_valid(&self) {
+        require!(
+            self.get_reserve_factor() <= Ratio::one(),
+            "Reserve factor should be less or equal to one"
+        );
+
+        match self {
+            InterestRateModel::JumpRate(_) => {}
+            InterestRateModel::MultiKink(model) => model.assert_valid(),
+            InterestRateModel::Adaptive(model) => model.assert_valid(),
+        }
+    }
+}
+
+impl Default for InterestRateModel {
+    fn default() -> Self {
+        InterestRateModel::JumpRate(JumpRateModel::default())
+    }
+}
+
+impl JumpRateModel {
     pub fn get_kink(&self) -> Ratio {
         Ratio::from(self.kink)
     }
@@ -85,25 +175,21 @@ impl InterestRateModel {
         self.reserve_factor = value;
     }
 
-    pub fn calculate_accrued_interest(
-        &self,
-        borrow_rate: Ratio,
-        total_borrow: Balance,
-        accrued_interest: AccruedInterest,
-    ) -> AccruedInterest {
-        let current_block_height = block_height();
-        let accrued_rate = BigBalance::from(total_borrow)
-            * borrow_rate
-            * BigBalance::from(current_block_height - accrued_interest.last_recalculation_block);
-
-        AccruedInterest {
-            accumulated_interest: accrued_interest.accumulated_interest + accrued_rate.round_u128(),
-            last_recalculation_block: current_block_height,
-        }
+    pub fn get_borrow_rate(&self, util: Ratio) -> Ratio {
+        let kink = self.get_kink();
+        let multiplier = if util > kink {
+            util - kink
+        } else {
+            Ratio::zero()
+        };
+
+        min(util, kink) * self.get_multiplier_per_block()
+            + multiplier * self.get_jump_multiplier_per_block()
+            + self.get_base_rate_per_block()
     }
 }
 
-impl Default for InterestRateModel {
+impl Default for JumpRateModel {
     fn default() -> Self {
         Self {
             kink: WRatio::from(Ratio::one()),
@@ -115,41 +201,232 @@ impl Default for InterestRateModel {
     }
 }
 
+impl MultiKinkModel {
+    pub fn get_borrow_rate(&self, util: Ratio) -> Ratio {
+        let mut prev_util = Ratio::zero();
+        let mut prev_rate = Ratio::from(self.base_rate_per_block);
+
+        for kink in self.kinks.iter() {
+            let kink_util = Ratio::from(kink.utilization);
+            let kink_rate = Ratio::from(kink.rate_per_block);
+
+            if util <= kink_util {
+                return prev_rate
+                    + (kink_rate - prev_rate) * (util - prev_util) / (kink_util - prev_util);
+            }
+
+            prev_util = kink_util;
+            prev_rate = kink_rate;
+        }
+
+        prev_rate
+    }
+
+    pub fn assert_valid(&self) {
+        require!(
+            !self.kinks.is_empty(),
+            "Interest rate curve should have kinks"
+        );
+
+        let mut prev_util = Ratio::zero();
+        let mut prev_rate = Ratio::from(self.base_rate_per_block);
+        for kink in self.kinks.iter() {
+            require!(
+                Ratio::from(kink.utilization) > prev_util,
+                "Kinks utilization should be strictly increasing"
+            );
+            require!(
+                Ratio::from(kink.rate_per_block) >= prev_rate,
+                "Kinks rate should be non-decreasing"
+            );
+            prev_util = Ratio::from(kink.utilization);
+            prev_rate = Ratio::from(kink.rate_per_block);
+        }
+
+        require!(
+            prev_util == Ratio::one(),
+            "The last kink should be at the full utilization"
+        );
+    }
+}
+
+impl AdaptiveModel {
+    pub fn get_borrow_rate(&self, util: Ratio) -> Ratio {
+        let rate_at_target = Ratio::from(self.rate_at_target);
+        let optimal_utilization = Ratio::from(self.optimal_utilization);
+        let curve_steepness = Ratio::from(self.curve_steepness);
+
+        let deviation = self.get_utilization_deviation(util);
+
+        if util >= optimal_utilization {
+            // grows from the rate at target to rate at target * steepness at full utilization
+            rate_at_target + rate_at_target * (curve_steepness - Ratio::one()) * deviation
+        } else {
+            // falls from the rate at target to rate at target / steepness at zero utilization
+            rate_at_target
+                - rate_at_target * (Ratio::one() - Ratio::one() / curve_steepness) * deviation
+        }
+    }
+
+    /// Distance from the optimal utilization normalized to [0, 1] on both sides of it
+    fn get_utilization_deviation(&self, util: Ratio) -> Ratio {
+        let optimal_utilization = Ratio::from(self.optimal_utilization);
+        let util = min(util, Ratio::one());
+
+        if util >= optimal_utilization {
+            (util - optimal_utilization) / (Ratio::one() - optimal_utilization)
+        } else {
+            (optimal_utilization - util) / optimal_utilization
+        }
+    }
+
+    pub fn adapt(&mut self, util: Ratio, blocks: BlockHeight) {
+        let rate_at_target = Ratio::from(self.rate_at_target);
+        let change = Ratio::from(self.adjustment_speed)
+            * self.get_utilization_deviation(util)
+            * BigBalance::from(blocks);
+
+        let rate_at_target = if util >= Ratio::from(self.optimal_utilization) {
+            rate_at_target + rate_at_target * change
+        } else if change >= Ratio::one() {
+            Ratio::zero()
+        } else {
+            rate_at_target - rate_at_target * change
+        };
+
+        self.rate_at_target = WRatio::from(min(
+            max(rate_at_target, Ratio::from(self.min_rate_at_target)),
+            Ratio::from(self.max_rate_at_target),
+        ));
+    }
+
+    pub fn assert_valid(&self) {
+        let optimal_utilization = Ratio::from(self.optimal_utilization);
+        require!(
+            optimal_utilization > Ratio::zero() && optimal_utilization < Ratio::one(),
+            "Optimal utilization should be in range (0, 1)"
+        );
+        require!(
+            Ratio::from(self.curve_steepness) >= Ratio::one(),
+            "Curve steepness should be greater or equal to one"
+        );
+        require!(
+            Ratio::from(self.min_rate_at_target) <= Ratio::from(self.rate_at_target)
+                && Ratio::from(self.rate_at_target) <= Ratio::from(self.max_rate_at_target),
+            "Rate at target should be in range [min, max]"
+        );
+    }
+}
+
+/// Market interest state accrued up to the current block
+pub struct InterestAccrual {
+    pub borrow_index: Ratio,
+    pub supply_index: Ratio,
+    pub total_borrows: Balance,
+    pub total_fixed_borrows: Balance,
+    pub total_reserves: Balance,
+}
+
+impl Contract {
+    /// Calculates market indexes and totals with the interest accrued since the last accrual
+    pub fn calculate_interest_accrual(&self) -> InterestAccrual {
+        let blocks = block_height() - self.accrual_block;
+        if blocks == 0 {
+            return InterestAccrual {
+                borrow_index: self.borrow_index,
+                supply_index: self.supply_index,
+                total_borrows: self.total_borrows,
+                total_fixed_borrows: self.total_fixed_borrows,
+                total_reserves: self.total_reserves,
+            };
+        }
+
+        let interest_rate_model = self.get_interest_rate_model();
+        let balance_of = self.view_contract_balance();
+        let borrow_rate = self.get_borrow_rate(
+            balance_of,
+            U128(self.get_total_borrows()),
+            U128(self.total_reserves),
+        );
+        let supply_rate = self.get_supply_rate(
+            balance_of,
+            U128(self.get_total_borrows()),
+            U128(self.total_reserves),
+            interest_rate_model.get_reserve_factor(),
+        );
+
+        let borrow_interest_factor = borrow_rate * BigBalance::from(blocks);
+        let variable_interest = BigBalance::from(self.total_borrows) * borrow_interest_factor;
+        let fixed_interest = self.fixed_interest_per_block * BigBalance::from(blocks);
+
+        InterestAccrual {
+            borrow_index: self.borrow_index * (Ratio::one() + borrow_interest_factor),
+            supply_index: self.supply_index
+                * (Ratio::one() + supply_rate * BigBalance::from(blocks)),
+            total_borrows: self.total_borrows + variable_interest.round_u128(),
+            total_fixed_borrows: self.total_fixed_borrows + fixed_interest.round_u128(),
+            total_reserves: self.total_reserves
+                + ((variable_interest + fixed_interest) * interest_rate_model.get_reserve_factor())
+                    .round_u128(),
+        }
+    }
+
+    /// Accrues interest to the market indexes and totals, should be called before any change
+    /// of market borrows, supplies or reserves
+    pub fn accrue_interest(&mut self) {
+        let blocks = block_height() - self.accrual_block;
+        if blocks == 0 {
+            return;
+        }
+
+        let accrual = self.calculate_interest_accrual();
+        let util = self.get_util_rate(
+            self.view_contract_balance(),
+            U128(self.get_total_borrows()),
+            U128(self.total_reserves),
+        );
+        let mut interest_rate_model = self.get_interest_rate_model();
+        interest_rate_model.adapt(util, blocks);
+        self.set_interest_rate_model_state(interest_rate_model);
+
+        self.borrow_index = accrual.borrow_index;
+        self.supply_index = accrual.supply_index;
+        self.total_borrows = accrual.total_borrows;
+        self.total_fixed_borrows = accrual.total_fixed_borrows;
+        self.total_reserves = accrual.total_reserves;
+        self.accrual_block = block_height();
+    }
+
+    /// Stores the interest rate model, also used to save the adapted state of the model
+    pub(crate) fn set_interest_rate_model_state(&mut self, interest_rate_model: InterestRateModel) {
+        let mut config = self.get_contract_config();
+        config.interest_rate_model = interest_rate_model.clone();
+        self.config.set(&config);
+        self.model = interest_rate_model;
+    }
+
+    /// Account borrows with interest for the given market borrow index
+    pub fn calculate_account_borrows(&self, account: AccountId, borrow_index: Ratio) -> Balance {
+        let user = self.user_profiles.get(&account).unwrap_or_default();
+        if user.borrows == 0 {
+            return 0;
+        }
+
+        (BigBalance::from(user.borrows) * borrow_index / user.borrow_index).round_u128()
+    }
+}
+
 #[near_bindgen]
 impl Contract {
-    pub fn get_accrued_borrow_interest(&self, account: AccountId) -> AccruedInterest {
-        self.user_profiles
-            .get(&account)
-            .unwrap_or_default()
-            .borrow_interest
-    }
-
-    pub fn get_accrued_supply_interest(&self, account: AccountId) -> AccruedInterest {
-        self.user_profiles
-            .get(&account)
-            .unwrap_or_default()
-            .supply_interest
-    }
-
-    #[private]
-    pub fn set_accrued_supply_interest(
-        &mut self,
-        account: AccountId,
-        accrued_interest: AccruedInterest,
-    ) {
-        let mut user = self.user_profiles.get(&account).unwrap_or_default();
-        user.supply_interest = accrued_interest;
-        self.user_profiles.insert(&account, &user);
-    }
-
-    #[private]
-    pub fn set_accrued_borrow_interest(
-        &mut self,
-        account: AccountId,
-        accrued_interest: AccruedInterest,
-    ) {
-        let mut user = self.user_profiles.get(&account).unwrap_or_default();
-        user.borrow_interest = accrued_interest;
-        self.user_profiles.insert(&account, &user);
+    pub fn get_interest_rate_model(&self) -> InterestRateModel {
+        self.get_contract_config().interest_rate_model
+    }
+
+    pub fn view_borrow_index(&self) -> WRatio {
+        WRatio::from(self.calculate_interest_accrual().borrow_index)
+    }
+
+    pub fn view_supply_index(&self) -> WRatio {
+        WRatio::from(self.calculate_interest_accrual().supply_index)
     }
 }
@@ -1,46 +1,62 @@
 use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, UnorderedMap};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::U128;
 use near_sdk::require;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
     env, ext_contract, is_promise_success, log, near_bindgen, AccountId, Balance, BlockHeight,
-    BorshStorageKey, Gas, PromiseOrValue, PromiseResult,
+    BorshStorageKey, Gas, PromiseOrValue,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub use general::ratio::Ratio;
+use general::roles::{Role, Roles};
+use general::storage::StorageDeposits;
+use general::timelock::Timelock;
 #[allow(unused_imports)]
 pub use general::*;
 
+pub use crate::bad_debt::*;
 pub use crate::borrow::*;
 pub use crate::common::*;
 pub use crate::config::*;
+pub use crate::credit_delegation::*;
+pub use crate::fixed_rate::*;
+pub use crate::flash_loan::*;
 pub use crate::ft::*;
 pub use crate::interest_model::*;
 pub use crate::interest_rate_model::*;
+pub use crate::migration::*;
 pub use crate::repay::*;
 pub use crate::rewards::*;
+pub use crate::storage::*;
 pub use crate::supply::*;
+pub use crate::timelock::*;
 pub use crate::user_profile::*;
 pub use crate::utils::*;
 pub use crate::views::*;
 pub use crate::withdraw::*;
 
 mod admin;
+mod bad_debt;
 mod borrow;
 mod common;
 mod config;
-mod deposit;
+mod credit_delegation;
+mod fixed_rate;
+mod flash_loan;
 mod ft;
 mod interest_model;
 mod interest_rate_model;
 mod liquidation;
+mod migration;
 mod repay;
 mod reserve;
 mod rewards;
+mod storage;
 mod supply;
+mod timelock;
 mod upgrade;
 mod user_profile;
 mod utils;
@@ -52,6 +68,18 @@ enum StorageKeys {
     Config,
     UserProfiles,
     RewardCampaigns,
+    LiquidationDeposits,
+    BadDebtWriteOffs,
+    RiskProposals,
+    StateVersion,
+    StorageDeposits,
+    Rewards,
+    AccountRewardCampaigns,
+    CampaignRewardAccounts,
+    CampaignRewardAccountsOf { campaign_id: String },
+    VotingPowerShares,
+    FlashLoanReceivers,
+    FlashLoanDebts,
 }
 
 #[near_bindgen]
@@ -63,6 +91,18 @@ pub struct Contract {
     /// Total sum of supplied tokens
     total_reserves: Balance,
 
+    /// Total borrows of the market including accrued interest
+    total_borrows: Balance,
+
+    /// Borrow index growing with the borrow interest, one at the market creation
+    borrow_index: Ratio,
+
+    /// Supply index growing with the supply interest, one at the market creation
+    supply_index: Ratio,
+
+    /// Block of the last interest accrual
+    accrual_block: BlockHeight,
+
     /// Underlying balance of contract itself
     contract_balance: Balance,
 
@@ -81,12 +121,8 @@ pub struct Contract {
 
     model: InterestRateModel,
 
-    /// Contract admin account (dtoken itself by default)
-    pub admin: AccountId,
-
-    /// Contracts that are allowed to do uncollateralized borrow from market
-    /// contract itself by default, can be set to different account
-    eligible_to_borrow_uncollateralized: AccountId,
+    /// Accounts granted with the roles
+    roles: Roles,
 
     /// Campaign id -> Reward campaign
     reward_campaigns: UnorderedMap<String, RewardCampaign>,
@@ -94,11 +130,69 @@ pub struct Contract {
     /// Unique incremental identifier
     uid: u64,
 
-    /// User account_id -> { campaign_id -> reward }
-    rewards: HashMap<AccountId, HashMap<String, Reward>>,
+    /// (User account_id, campaign_id) -> reward
+    rewards: LookupMap<(AccountId, String), Reward>,
+
+    /// User account_id -> IDs of the campaigns the account has rewards in
+    account_reward_campaigns: LookupMap<AccountId, HashSet<String>>,
+
+    /// Campaign id -> Accounts having rewards in the campaign
+    campaign_reward_accounts: LookupMap<String, UnorderedSet<AccountId>>,
+
+    /// Governance token contract the tokens are locked in to boost the rewards,
+    /// rewards are not boosted if not set
+    boost_token: Option<AccountId>,
+
+    /// User account_id -> Share of the total voting power in the governance token contract
+    voting_power_shares: LookupMap<AccountId, Ratio>,
 
     /// Disable transfer opportunity
     disable_transfer: bool,
+
+    /// Liquidator account_id -> Tokens deposited to repay borrows within batch liquidations
+    liquidation_deposits: LookupMap<AccountId, Balance>,
+
+    /// History of written off bad debts
+    bad_debt_write_offs: Vector<BadDebtWriteOff>,
+
+    /// Sum of the written off bad debts
+    total_bad_debt: Balance,
+
+    /// Max amount of underlying tokens supplied to the market, unlimited if not set
+    supply_cap: Option<Balance>,
+
+    /// Fixed rate borrows configuration, fixed rate borrows are disabled if not set
+    fixed_rate_config: Option<FixedRateConfig>,
+
+    /// Total fixed rate borrows of the market including accrued interest
+    total_fixed_borrows: Balance,
+
+    /// Interest accrued per block by all the fixed rate loans
+    fixed_interest_per_block: Ratio,
+
+    /// Part of the flash loan amount charged to reserves, flash loans are disabled if not set
+    flash_loan_fee: Option<Ratio>,
+
+    /// Flash loan which is not returned yet, only one flash loan could be taken at a time
+    flash_loan: Option<FlashLoan>,
+
+    /// Contracts allowed to receive flash loans
+    flash_loan_receivers: UnorderedSet<AccountId>,
+
+    /// Initiator account ID -> Flash loan amount which was not returned to the market
+    flash_loan_debts: LookupMap<AccountId, Balance>,
+
+    /// Sum of the flash loan debts of all the initiators
+    total_flash_loan_debt: Balance,
+
+    /// Queue of the risk parameter changes
+    timelock: Timelock<RiskParameterChange>,
+
+    /// NEAR deposited by the accounts to cover their storage
+    storage_deposits: StorageDeposits,
+
+    /// V1 rewards which are not migrated yet, rewards are paused until they are
+    rewards_migration: Option<RewardsMigration>,
 }
 
 impl Default for Contract {
@@ -121,10 +215,21 @@ trait UnderlineTokenInterface {
     fn ft_resolve_transfer(&self, account_id: AccountId) -> U128;
 }
 
-#[ext_contract(mtrading)]
-trait MarginTradingInterface {
-    fn increase_user_deposit(&mut self, market_id: AccountId, user_id: AccountId, amount: WBalance);
-    fn decrease_user_deposit(&mut self, market_id: AccountId, user_id: AccountId, amount: WBalance);
+#[ext_contract(voting_escrow)]
+trait VotingEscrowInterface {
+    fn get_voting_power(&self, account_id: AccountId) -> VotingPower;
+}
+
+#[ext_contract(flash_loan_receiver)]
+trait FlashLoanReceiverInterface {
+    fn on_flash_loan(
+        &mut self,
+        initiator_id: AccountId,
+        token_id: AccountId,
+        amount: WBalance,
+        fee: WBalance,
+        msg: String,
+    );
 }
 
 #[ext_contract(controller)]
@@ -141,8 +246,7 @@ trait ControllerInterface {
         account_id: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-        borrow_block: BlockHeight,
-        borrow_rate: WRatio,
+        borrow_index: WRatio,
     );
     fn withdraw_supplies(
         &mut self,
@@ -155,8 +259,7 @@ trait ControllerInterface {
         account_id: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
-        borrow_block: BlockHeight,
-        borrow_rate: WRatio,
+        borrow_index: WRatio,
     );
     fn decrease_borrows(
         &mut self,
@@ -164,6 +267,36 @@ trait ControllerInterface {
         token_address: AccountId,
         token_amount: WBalance,
     );
+    fn make_fixed_rate_borrow(
+        &mut self,
+        account_id: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+        fixed_rate_debt: FixedRateDebt,
+        borrow_index: WRatio,
+    );
+    fn make_delegated_borrow(
+        &mut self,
+        delegator: AccountId,
+        delegatee: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+        borrow_index: WRatio,
+    );
+    fn revert_delegated_borrow(
+        &mut self,
+        delegator: AccountId,
+        delegatee: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+    );
+    fn set_fixed_rate_borrows(
+        &mut self,
+        account_id: AccountId,
+        token_address: AccountId,
+        fixed_rate_debt: Option<FixedRateDebt>,
+        borrow_index: WRatio,
+    );
     fn liquidation(
         &mut self,
         borrower: AccountId,
@@ -180,7 +313,7 @@ trait ControllerInterface {
         liquidator: AccountId,
         liquidation_amount: WBalance,
         liquidation_revenue_amount: WBalance,
-        borrow_rate: WRatio,
+        borrow_index: WRatio,
     );
     fn mutex_lock(&mut self, action: Actions);
     fn mutex_unlock(&mut self);
@@ -207,6 +340,7 @@ trait InternalTokenInterface {
         &mut self,
         token_amount: WBalance,
         account_to_borrow: AccountId,
+        health_factor: Option<WRatio>,
     ) -> PromiseOrValue<WBalance>;
     fn controller_repay_borrows_callback(
         &mut self,
@@ -217,18 +351,62 @@ trait InternalTokenInterface {
         token_amount: WBalance,
         account_to_borrow: AccountId,
     ) -> PromiseOrValue<WBalance>;
+    fn make_fixed_rate_borrow_callback(
+        &mut self,
+        token_amount: WBalance,
+        account_to_borrow: AccountId,
+        loan: FixedRateLoan,
+    ) -> PromiseOrValue<WBalance>;
+    fn fixed_rate_borrow_ft_transfer_callback(
+        &mut self,
+        token_amount: WBalance,
+        account_to_borrow: AccountId,
+        loan: FixedRateLoan,
+        health_factor: Option<WRatio>,
+    ) -> PromiseOrValue<WBalance>;
+    fn controller_fixed_rate_repay_callback(
+        &mut self,
+        token_amount: WBalance,
+        repay_amount: WBalance,
+        fee: WBalance,
+        loan: FixedRateLoan,
+    ) -> PromiseOrValue<WBalance>;
+    fn rebalance_fixed_rate_loan_callback(
+        &mut self,
+        account_id: AccountId,
+        loan: FixedRateLoan,
+    ) -> PromiseOrValue<WBalance>;
+    fn make_delegated_borrow_callback(
+        &mut self,
+        token_amount: WBalance,
+        delegator: AccountId,
+        delegatee: AccountId,
+    ) -> PromiseOrValue<WBalance>;
+    fn delegated_borrow_ft_transfer_callback(
+        &mut self,
+        token_amount: WBalance,
+        delegator: AccountId,
+        delegatee: AccountId,
+        health_factor: Option<WRatio>,
+    ) -> PromiseOrValue<WBalance>;
+    fn flash_loan_ft_transfer_callback(
+        &mut self,
+        msg: String,
+        block_height: BlockHeight,
+    ) -> PromiseOrValue<WBalance>;
+    fn flash_loan_callback(&mut self, block_height: BlockHeight) -> WBalance;
 
     fn withdraw_supplies_callback(
         &mut self,
         user_account: AccountId,
         token_amount: WBalance,
         dtoken_amount: WBalance,
-        whole_amount: WBalance,
     ) -> PromiseOrValue<WBalance>;
     fn withdraw_ft_transfer_call_callback(
         &mut self,
         token_amount: WBalance,
         dtoken_amount: WBalance,
+        health_factor: Option<WRatio>,
     ) -> PromiseOrValue<WBalance>;
     fn withdraw_increase_supplies_callback(
         &mut self,
@@ -251,6 +429,11 @@ trait InternalTokenInterface {
         liquidation_amount: WBalance,
         result: Option<Vec<u8>>,
     );
+    fn withdraw_liquidation_deposit_callback(
+        &mut self,
+        liquidator: AccountId,
+        amount: WBalance,
+    ) -> PromiseOrValue<WBalance>;
     fn mutex_lock_callback(
         &mut self,
         action: Actions,
@@ -258,23 +441,27 @@ trait InternalTokenInterface {
     ) -> PromiseOrValue<WBalance>;
     fn claim_reward_ft_transfer_callback(
         &mut self,
-        reward: Reward,
+        campaign_id: String,
+        token: AccountId,
         account_id: AccountId,
         amount: WBalance,
         unlocked: WBalance,
     );
-
-    fn deposit_balance_of_callback(&mut self, amount: WBalance) -> PromiseOrValue<WBalance>;
-    fn mtrading_increase_user_deposit_callback(
+    fn claim_all_rewards_ft_transfer_callback(
         &mut self,
-        market_id: AccountId,
-        user_id: AccountId,
-        amount: WBalance,
-    ) -> PromiseOrValue<WBalance>;
-    fn mtrading_decrease_user_deposit_fail_callback(
+        token: AccountId,
+        account_id: AccountId,
+        claims: Vec<(String, WBalance)>,
+    );
+    fn sweep_reward_campaign_callback(
         &mut self,
+        campaign_id: String,
+        token: AccountId,
+        receiver_id: AccountId,
         amount: WBalance,
-    ) -> PromiseOrValue<WBalance>;
+    );
+
+    fn update_boost_callback(&mut self, account_id: AccountId) -> WRatio;
 }
 
 #[near_bindgen]
@@ -304,10 +491,16 @@ impl Contract {
     #[init]
     pub fn new(config: Config) -> Self {
         require!(!env::state_exists(), "Already initialized");
+        config.interest_rate_model.assert_valid();
+        CURRENT_STATE_VERSION.write();
 
         Self {
             initial_exchange_rate: Ratio::from(config.initial_exchange_rate),
             total_reserves: 0,
+            total_borrows: 0,
+            borrow_index: Ratio::one(),
+            supply_index: Ratio::one(),
+            accrual_block: env::block_height(),
             contract_balance: 0,
             user_profiles: UnorderedMap::new(StorageKeys::UserProfiles),
             underlying_token: config.underlying_token_id.clone(),
@@ -315,12 +508,33 @@ impl Contract {
             token: FungibleToken::new(b"t".to_vec()),
             config: LazyOption::new(StorageKeys::Config, Some(&config)),
             model: config.interest_rate_model,
-            admin: config.owner_id.clone(),
-            eligible_to_borrow_uncollateralized: config.owner_id,
+            roles: Roles::new(config.owner_id.clone()),
             reward_campaigns: UnorderedMap::new(StorageKeys::RewardCampaigns),
             uid: 0,
-            rewards: HashMap::new(),
+            rewards: LookupMap::new(StorageKeys::Rewards),
+            account_reward_campaigns: LookupMap::new(StorageKeys::AccountRewardCampaigns),
+            campaign_reward_accounts: LookupMap::new(StorageKeys::CampaignRewardAccounts),
+            boost_token: None,
+            voting_power_shares: LookupMap::new(StorageKeys::VotingPowerShares),
             disable_transfer: config.disable_transfer_token,
+            liquidation_deposits: LookupMap::new(StorageKeys::LiquidationDeposits),
+            bad_debt_write_offs: Vector::new(StorageKeys::BadDebtWriteOffs),
+            total_bad_debt: 0,
+            supply_cap: None,
+            fixed_rate_config: None,
+            total_fixed_borrows: 0,
+            fixed_interest_per_block: Ratio::zero(),
+            flash_loan_fee: None,
+            flash_loan: None,
+            flash_loan_receivers: UnorderedSet::new(StorageKeys::FlashLoanReceivers),
+            flash_loan_debts: LookupMap::new(StorageKeys::FlashLoanDebts),
+            total_flash_loan_debt: 0,
+            timelock: Timelock::new(StorageKeys::RiskProposals, config.owner_id, 0),
+            storage_deposits: StorageDeposits::new(
+                StorageKeys::StorageDeposits,
+                MIN_ACCOUNT_STORAGE_USAGE,
+            ),
+            rewards_migration: None,
         }
     }
 }
@@ -39,6 +39,37 @@ impl Contract {
         ))
         .into()
     }
+
+    pub fn increase_liquidation_deposit(
+        &mut self,
+        liquidator: AccountId,
+        token_amount: WBalance,
+    ) -> PromiseOrValue<U128> {
+        let deposit = self.get_liquidation_deposit(liquidator.clone()).0 + token_amount.0;
+        self.liquidation_deposits.insert(&liquidator, &deposit);
+
+        Events::LiquidationDepositSuccess {
+            account_id: liquidator,
+            amount: token_amount,
+        }
+        .emit();
+        PromiseOrValue::Value(U128(0))
+    }
+
+    fn decrease_liquidation_deposit(&mut self, liquidator: AccountId, token_amount: WBalance) {
+        let deposit = self.get_liquidation_deposit(liquidator.clone()).0;
+        require!(
+            token_amount.0 <= deposit,
+            "Not enough liquidation deposit for the liquidator"
+        );
+
+        if deposit == token_amount.0 {
+            self.liquidation_deposits.remove(&liquidator);
+        } else {
+            self.liquidation_deposits
+                .insert(&liquidator, &(deposit - token_amount.0));
+        }
+    }
 }
 
 #[near_bindgen]
@@ -101,19 +132,7 @@ impl Contract {
         );
         require!(is_promise_success(), &err_message);
 
-        let balance_of: Balance = match env::promise_result(0) {
-            PromiseResult::NotReady => 0,
-            PromiseResult::Failed => 0,
-            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result)
-                .unwrap()
-                .into(),
-        };
-
-        let borrow_rate = self.get_borrow_rate(
-            U128(balance_of - liquidation_amount.0),
-            U128(self.get_total_borrows()),
-            U128(self.get_total_reserves()),
-        );
+        self.accrue_interest();
 
         let liquidation_revenue_amount: WBalance =
             near_sdk::serde_json::from_slice::<U128>(&result.unwrap()).unwrap();
@@ -127,7 +146,7 @@ impl Contract {
             liquidator,
             liquidation_amount,
             liquidation_revenue_amount,
-            U128::from(borrow_rate),
+            WRatio::from(self.borrow_index),
             self.get_controller_address(),
             NO_DEPOSIT,
             self.terra_gas(40),
@@ -135,11 +154,131 @@ impl Contract {
         .into()
     }
 
+    pub fn get_liquidation_deposit(&self, account_id: AccountId) -> WBalance {
+        WBalance::from(self.liquidation_deposits.get(&account_id).unwrap_or(0))
+    }
+
+    /// Returns unused tokens deposited for batch liquidations back to the liquidator
+    pub fn withdraw_liquidation_deposit(&mut self, amount: WBalance) -> PromiseOrValue<WBalance> {
+        let liquidator = env::predecessor_account_id();
+        require!(amount.0 > 0, "Amount should be a positive number");
+        self.decrease_liquidation_deposit(liquidator.clone(), amount);
+
+        underlying_token::ft_transfer(
+            liquidator.clone(),
+            self.to_decimals_token(amount),
+            Some(format!(
+                "Withdraw liquidation deposit with token_amount {}",
+                Balance::from(amount)
+            )),
+            self.get_underlying_contract_address(),
+            ONE_YOCTO,
+            self.terra_gas(10),
+        )
+        .then(ext_self::withdraw_liquidation_deposit_callback(
+            liquidator,
+            amount,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        ))
+        .into()
+    }
+
+    #[private]
+    pub fn withdraw_liquidation_deposit_callback(
+        &mut self,
+        liquidator: AccountId,
+        amount: WBalance,
+    ) -> PromiseOrValue<WBalance> {
+        if is_promise_success() {
+            Events::LiquidationDepositWithdrawSuccess {
+                account_id: liquidator.clone(),
+                amount,
+            }
+            .emit();
+            self.refund_storage_if_empty(&liquidator);
+            PromiseOrValue::Value(amount)
+        } else {
+            Events::LiquidationDepositWithdrawFailed {
+                account_id: liquidator.clone(),
+                amount,
+            }
+            .emit();
+            self.increase_liquidation_deposit(liquidator, amount);
+            PromiseOrValue::Value(U128(0))
+        }
+    }
+
+    /// Repays borrower's debt from the liquidator's deposit as a leg of batch liquidation,
+    /// returns borrow index to be stored on controller
+    pub fn liquidate_batch_repay(
+        &mut self,
+        borrower: AccountId,
+        liquidator: AccountId,
+        liquidation_amount: WBalance,
+    ) -> WRatio {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.get_controller_address(),
+            "This method can be called only from controller contract"
+        );
+        self.accrue_interest();
+        require!(
+            liquidation_amount.0 <= self.get_account_total_borrows(borrower.clone()),
+            "Liquidation amount exceeds the borrower`s borrows"
+        );
+
+        let accrued_interest = self.get_account_accrued_interest(&borrower);
+        self.decrease_liquidation_deposit(liquidator.clone(), liquidation_amount);
+        self.decrease_borrows(borrower.clone(), liquidation_amount);
+        self.increase_contract_balance(liquidation_amount);
+
+        // health factor after the whole batch is emitted by the controller
+        Events::LiquidationSuccess {
+            liquidator_account_id: liquidator,
+            borrower_account_id: borrower,
+            amount: liquidation_amount,
+            state: self.get_action_state(accrued_interest, None),
+        }
+        .emit();
+        WRatio::from(self.borrow_index)
+    }
+
+    /// Compensates the repay leg of batch liquidation which failed as a whole
+    pub fn revert_liquidate_batch_repay(
+        &mut self,
+        borrower: AccountId,
+        liquidator: AccountId,
+        liquidation_amount: WBalance,
+    ) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.get_controller_address(),
+            "This method can be called only from controller contract"
+        );
+
+        self.accrue_interest();
+        self.decrease_contract_balance(liquidation_amount);
+        self.increase_borrows(borrower.clone(), liquidation_amount);
+        self.increase_liquidation_deposit(liquidator.clone(), liquidation_amount);
+
+        Events::LiquidationFailed {
+            liquidator_account_id: liquidator,
+            borrower_account_id: borrower,
+            amount: liquidation_amount,
+        }
+        .emit();
+    }
+
+    /// Transfers seized dtokens to the liquidator, `health_factor` of the borrower
+    /// after the liquidation is reported by the controller
     pub fn swap_supplies(
         &mut self,
         borrower: AccountId,
         liquidator: AccountId,
         liquidation_revenue_amount: WBalance,
+        health_factor: Option<WRatio>,
     ) -> PromiseOrValue<U128> {
         assert_eq!(
             env::predecessor_account_id(),
@@ -155,14 +294,14 @@ impl Contract {
         self.token
             .internal_transfer(&borrower, &liquidator, amount, None);
 
-        log!(
-            "{}",
-            Events::LiquidationSuccess(
-                liquidator,
-                borrower,
-                Balance::from(liquidation_revenue_amount)
-            )
-        );
+        Events::LiquidationSuccess {
+            state: self
+                .get_action_state(self.get_account_accrued_interest(&borrower), health_factor),
+            liquidator_account_id: liquidator,
+            borrower_account_id: borrower,
+            amount: liquidation_revenue_amount,
+        }
+        .emit();
         PromiseOrValue::Value(U128(0))
     }
 }
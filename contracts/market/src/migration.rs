@@ -0,0 +1,590 @@
+use crate::*;
+use general::ratio::BigBalance;
+use near_sdk::IntoStorageKey;
+
+/// Layout of the stored contract state.
+/// Whenever the layout of a released version changes, the previous `Contract` schema
+/// should be copied below, a new version added and `migrate_state` extended with the step
+/// converting the previous version into the next one.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    PartialOrd,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum StateVersion {
+    /// State written before the versioning was introduced, the version isn't stored for it
+    V1,
+    V2,
+}
+
+pub const CURRENT_STATE_VERSION: StateVersion = StateVersion::V2;
+
+impl StateVersion {
+    pub fn read() -> Self {
+        env::storage_read(&StorageKeys::StateVersion.into_storage_key())
+            .map(|version| {
+                StateVersion::try_from_slice(&version).expect("State version is corrupted")
+            })
+            .unwrap_or(StateVersion::V1)
+    }
+
+    pub fn write(&self) {
+        env::storage_write(
+            &StorageKeys::StateVersion.into_storage_key(),
+            &self.try_to_vec().unwrap(),
+        );
+    }
+}
+
+/// Default number of the accounts which V1 rewards are migrated by a single call
+pub const REWARDS_MIGRATION_LIMIT: u64 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct InterestRateModelV1 {
+    kink: WRatio,
+    multiplier_per_block: WRatio,
+    base_rate_per_block: WRatio,
+    jump_multiplier_per_block: WRatio,
+    reserve_factor: WRatio,
+}
+
+impl From<InterestRateModelV1> for InterestRateModel {
+    fn from(model: InterestRateModelV1) -> Self {
+        InterestRateModel::JumpRate(JumpRateModel {
+            kink: model.kink,
+            multiplier_per_block: model.multiplier_per_block,
+            base_rate_per_block: model.base_rate_per_block,
+            jump_multiplier_per_block: model.jump_multiplier_per_block,
+            reserve_factor: model.reserve_factor,
+        })
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ConfigV1 {
+    initial_exchange_rate: U128,
+    underlying_token_id: AccountId,
+    underlying_token_decimals: u8,
+    owner_id: AccountId,
+    controller_account_id: AccountId,
+    interest_rate_model: InterestRateModelV1,
+    disable_transfer_token: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct AccruedInterestV1 {
+    last_recalculation_block: BlockHeight,
+    accumulated_interest: Balance,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct UserProfileV1 {
+    borrows: Balance,
+    borrow_interest: AccruedInterestV1,
+    supply_interest: AccruedInterestV1,
+    is_consistent: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct VestingV1 {
+    start_time: u64,
+    end_time: u64,
+    penalty: Ratio,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct RewardCampaignV1 {
+    campaign_type: CampaignType,
+    start_time: u64,
+    end_time: u64,
+    token: AccountId,
+    ticker_id: String,
+    reward_amount: WBalance,
+    last_update_time: u64,
+    rewards_per_token: BigBalance,
+    last_market_total: WBalance,
+    vesting: VestingV1,
+}
+
+impl From<RewardCampaignV1> for RewardCampaign {
+    /// Rewards distributed by the last update time are restored from the even distribution
+    /// of the reward amount over the campaign duration used by V1. V1 campaigns were paid from
+    /// the contract balance, so they are considered funded. V1 kept the unlock penalties on the
+    /// contract, which matches burning them
+    fn from(campaign: RewardCampaignV1) -> Self {
+        let duration = campaign.end_time.saturating_sub(campaign.start_time);
+        let elapsed = campaign
+            .last_update_time
+            .clamp(campaign.start_time, campaign.end_time)
+            - campaign.start_time;
+        let distributed_amount = match duration {
+            0 => 0,
+            _ => campaign.reward_amount.0 * u128::from(elapsed) / u128::from(duration),
+        };
+
+        RewardCampaign {
+            campaign_type: campaign.campaign_type,
+            start_time: campaign.start_time,
+            end_time: campaign.end_time,
+            tokens: vec![RewardToken {
+                token: campaign.token,
+                ticker_id: campaign.ticker_id,
+                reward_amount: campaign.reward_amount,
+                distributed_amount: WBalance::from(distributed_amount),
+                rewards_per_token: campaign.rewards_per_token,
+                penalty_per_token: BigBalance::zero(),
+                ledger: RewardLedger {
+                    funded: campaign.reward_amount,
+                    remaining: campaign.reward_amount,
+                    ..RewardLedger::default()
+                },
+            }],
+            last_update_time: campaign.last_update_time,
+            last_market_total: campaign.last_market_total,
+            vesting: Vesting {
+                start_time: campaign.vesting.start_time,
+                end_time: campaign.vesting.end_time,
+                penalty: campaign.vesting.penalty,
+                penalty_destination: PenaltyDestination::Burn,
+            },
+            boost: None,
+            boosted_shares: WBalance::from(0),
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct RewardV1 {
+    campaign_id: String,
+    amount: WBalance,
+    rewards_per_token_paid: BigBalance,
+    claimed: WBalance,
+    unlocked: WBalance,
+}
+
+/// Rewards of the V1 layout are migrated in batches after the upgrade
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RewardsMigration {
+    /// Rewards of the accounts which are not migrated yet, the account is removed once its
+    /// rewards are migrated
+    rewards: Vec<(AccountId, HashMap<String, RewardV1>)>,
+
+    /// Number of the accounts which rewards are migrated so far, accounts are migrated starting
+    /// from the last one
+    cursor: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ContractV1 {
+    initial_exchange_rate: Ratio,
+    total_reserves: Balance,
+    contract_balance: Balance,
+    user_profiles: UnorderedMap<AccountId, UserProfileV1>,
+    underlying_token: AccountId,
+    underlying_token_decimals: u8,
+    token: FungibleToken,
+    config: LazyOption<ConfigV1>,
+    model: InterestRateModelV1,
+    admin: AccountId,
+    eligible_to_borrow_uncollateralized: AccountId,
+    reward_campaigns: UnorderedMap<String, RewardCampaignV1>,
+    uid: u64,
+    rewards: HashMap<AccountId, HashMap<String, RewardV1>>,
+    disable_transfer: bool,
+}
+
+impl ContractV1 {
+    /// Borrow interest accumulated by V1 is moved into the borrows principal, so it is counted
+    /// in the total borrows and paid to suppliers through the exchange rate, which replaces
+    /// the supply interest tracked by V1 separately for every account.
+    /// Rewards of the accounts are left to `migrate_rewards`
+    fn migrate(mut self) -> Contract {
+        let profiles = self.user_profiles.to_vec();
+        self.user_profiles.clear();
+
+        let mut user_profiles = UnorderedMap::new(StorageKeys::UserProfiles);
+        let mut total_borrows = 0;
+        for (account_id, profile) in profiles {
+            let borrows = profile.borrows + profile.borrow_interest.accumulated_interest;
+            total_borrows += borrows;
+
+            user_profiles.insert(
+                &account_id,
+                &UserProfile {
+                    borrows,
+                    is_consistent: profile.is_consistent,
+                    ..UserProfile::default()
+                },
+            );
+        }
+
+        let config = self.config.get().expect("Config is not initialized");
+        let config = Config {
+            initial_exchange_rate: config.initial_exchange_rate,
+            underlying_token_id: config.underlying_token_id,
+            underlying_token_decimals: config.underlying_token_decimals,
+            owner_id: config.owner_id,
+            controller_account_id: config.controller_account_id,
+            interest_rate_model: InterestRateModel::from(config.interest_rate_model),
+            disable_transfer_token: config.disable_transfer_token,
+        };
+
+        let campaigns = self.reward_campaigns.to_vec();
+        self.reward_campaigns.clear();
+
+        let mut reward_campaigns = UnorderedMap::new(StorageKeys::RewardCampaigns);
+        for (campaign_id, campaign) in campaigns {
+            reward_campaigns.insert(&campaign_id, &RewardCampaign::from(campaign));
+        }
+
+        let mut rewards = std::mem::take(&mut self.rewards)
+            .into_iter()
+            .collect::<Vec<(AccountId, HashMap<String, RewardV1>)>>();
+        rewards.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Contract {
+            initial_exchange_rate: self.initial_exchange_rate,
+            total_reserves: self.total_reserves,
+            total_borrows,
+            borrow_index: Ratio::one(),
+            supply_index: Ratio::one(),
+            accrual_block: env::block_height(),
+            contract_balance: self.contract_balance,
+            user_profiles,
+            underlying_token: self.underlying_token,
+            underlying_token_decimals: self.underlying_token_decimals,
+            token: self.token,
+            config: LazyOption::new(StorageKeys::Config, Some(&config)),
+            model: InterestRateModel::from(self.model),
+            roles: Roles::new(self.admin.clone()),
+            reward_campaigns,
+            uid: self.uid,
+            rewards: LookupMap::new(StorageKeys::Rewards),
+            account_reward_campaigns: LookupMap::new(StorageKeys::AccountRewardCampaigns),
+            campaign_reward_accounts: LookupMap::new(StorageKeys::CampaignRewardAccounts),
+            boost_token: None,
+            voting_power_shares: LookupMap::new(StorageKeys::VotingPowerShares),
+            disable_transfer: self.disable_transfer,
+            liquidation_deposits: LookupMap::new(StorageKeys::LiquidationDeposits),
+            bad_debt_write_offs: Vector::new(StorageKeys::BadDebtWriteOffs),
+            total_bad_debt: 0,
+            supply_cap: None,
+            fixed_rate_config: None,
+            total_fixed_borrows: 0,
+            fixed_interest_per_block: Ratio::zero(),
+            flash_loan_fee: None,
+            flash_loan: None,
+            flash_loan_receivers: UnorderedSet::new(StorageKeys::FlashLoanReceivers),
+            flash_loan_debts: LookupMap::new(StorageKeys::FlashLoanDebts),
+            total_flash_loan_debt: 0,
+            timelock: Timelock::new(StorageKeys::RiskProposals, self.admin, 0),
+            storage_deposits: StorageDeposits::new(
+                StorageKeys::StorageDeposits,
+                MIN_ACCOUNT_STORAGE_USAGE,
+            ),
+            rewards_migration: Some(RewardsMigration { rewards, cursor: 0 })
+                .filter(|migration| !migration.rewards.is_empty()),
+        }
+    }
+}
+
+impl Contract {
+    /// Rewards of the campaigns removed in V1 are dropped, claims of the rest are moved
+    /// to the campaign ledgers, V1 doesn't separate the unlock penalties from the claims
+    fn migrate_account_rewards(
+        &mut self,
+        account_id: AccountId,
+        rewards: HashMap<String, RewardV1>,
+    ) {
+        for reward in rewards.into_values() {
+            if let Some(mut campaign) = self.get_reward_campaign_by_id(reward.campaign_id.clone()) {
+                let ledger = &mut campaign.tokens[0].ledger;
+                ledger.claimed = WBalance::from(ledger.claimed.0 + reward.claimed.0);
+                ledger.remaining =
+                    WBalance::from(ledger.remaining.0.saturating_sub(reward.claimed.0));
+                self.reward_campaigns.insert(&reward.campaign_id, &campaign);
+
+                let token_reward = TokenReward {
+                    amount: reward.amount,
+                    rewards_per_token_paid: reward.rewards_per_token_paid,
+                    claimed: reward.claimed,
+                    unlocked: reward.unlocked,
+                    ..TokenReward::default()
+                };
+                self.insert_reward(
+                    &account_id,
+                    &Reward {
+                        campaign_id: reward.campaign_id,
+                        tokens: HashMap::from([(campaign.tokens[0].token.clone(), token_reward)]),
+                        boosted_shares: WBalance::from(0),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Rewards are paused until the V1 rewards are migrated, as the accounts would start
+    /// earning the campaign rewards from scratch otherwise
+    pub(crate) fn assert_rewards_migrated(&self) {
+        require!(
+            self.rewards_migration.is_none(),
+            "Rewards are paused until the V1 rewards are migrated"
+        );
+    }
+
+    /// Reads the state stored in the given version and migrates it up to the current one
+    pub(crate) fn migrate_state(version: StateVersion) -> Self {
+        require!(
+            version <= CURRENT_STATE_VERSION,
+            "State version is newer than the contract one"
+        );
+
+        let contract = match version {
+            StateVersion::V1 => env::state_read::<ContractV1>()
+                .expect("Contract is not initialized")
+                .migrate(),
+            StateVersion::V2 => env::state_read().expect("Contract is not initialized"),
+        };
+
+        CURRENT_STATE_VERSION.write();
+        contract
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_state_version(&self) -> StateVersion {
+        StateVersion::read()
+    }
+
+    /// Migrates the V1 rewards of up to `limit` accounts, should be called again until no
+    /// accounts remain, rewards are resumed then. Returns the number of the remaining accounts.
+    pub fn migrate_rewards(&mut self, limit: Option<u64>) -> u64 {
+        self.roles.assert_role(Role::Owner);
+        let mut migration = self
+            .rewards_migration
+            .take()
+            .expect("There are no rewards to migrate");
+
+        for _ in 0..limit.unwrap_or(REWARDS_MIGRATION_LIMIT) {
+            match migration.rewards.pop() {
+                Some((account_id, rewards)) => {
+                    self.migrate_account_rewards(account_id, rewards);
+                    migration.cursor += 1;
+                }
+                None => break,
+            }
+        }
+
+        let remaining_accounts = migration.rewards.len() as u64;
+        if remaining_accounts > 0 {
+            self.rewards_migration = Some(migration);
+        }
+        remaining_accounts
+    }
+
+    /// Progress of the V1 rewards migration, `None` once all of them are migrated
+    pub fn get_rewards_migration(&self) -> Option<MigrationProgress> {
+        self.rewards_migration
+            .as_ref()
+            .map(|migration| MigrationProgress {
+                migrated: migration.cursor,
+                remaining: migration.rewards.len() as u64,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn init_v1() -> ContractV1 {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(alice())
+            .predecessor_account_id(alice())
+            .block_index(100)
+            .build());
+
+        let model = || InterestRateModelV1 {
+            kink: WRatio::from(Ratio::from_str("0.8").unwrap()),
+            multiplier_per_block: WRatio::from(0),
+            base_rate_per_block: WRatio::from(0),
+            jump_multiplier_per_block: WRatio::from(0),
+            reserve_factor: WRatio::from(Ratio::from_str("0.1").unwrap()),
+        };
+
+        let mut contract = ContractV1 {
+            initial_exchange_rate: Ratio::one(),
+            total_reserves: 1000,
+            contract_balance: 5000,
+            user_profiles: UnorderedMap::new(StorageKeys::UserProfiles),
+            underlying_token: bob(),
+            underlying_token_decimals: 24,
+            token: FungibleToken::new(b"t".to_vec()),
+            config: LazyOption::new(
+                StorageKeys::Config,
+                Some(&ConfigV1 {
+                    initial_exchange_rate: U128::from(Ratio::one()),
+                    underlying_token_id: bob(),
+                    underlying_token_decimals: 24,
+                    owner_id: alice(),
+                    controller_account_id: carol(),
+                    interest_rate_model: model(),
+                    disable_transfer_token: true,
+                }),
+            ),
+            model: model(),
+            admin: alice(),
+            eligible_to_borrow_uncollateralized: alice(),
+            reward_campaigns: UnorderedMap::new(StorageKeys::RewardCampaigns),
+            uid: 7,
+            rewards: HashMap::new(),
+            disable_transfer: true,
+        };
+
+        contract.token.internal_register_account(&bob());
+        contract.token.internal_deposit(&bob(), 3000);
+        contract.user_profiles.insert(
+            &bob(),
+            &UserProfileV1 {
+                borrows: 200,
+                borrow_interest: AccruedInterestV1 {
+                    last_recalculation_block: 90,
+                    accumulated_interest: 20,
+                },
+                supply_interest: AccruedInterestV1 {
+                    last_recalculation_block: 90,
+                    accumulated_interest: 5,
+                },
+                is_consistent: false,
+            },
+        );
+        contract.reward_campaigns.insert(
+            &"7".to_string(),
+            &RewardCampaignV1 {
+                campaign_type: CampaignType::Supply,
+                start_time: 0,
+                end_time: 1000,
+                token: carol(),
+                ticker_id: "carol".to_string(),
+                reward_amount: U128(10000),
+                last_update_time: 250,
+                rewards_per_token: BigBalance::zero(),
+                last_market_total: U128(0),
+                vesting: VestingV1 {
+                    start_time: 0,
+                    end_time: 1000,
+                    penalty: Ratio::zero(),
+                },
+            },
+        );
+        contract.rewards.insert(
+            bob(),
+            HashMap::from([(
+                "7".to_string(),
+                RewardV1 {
+                    campaign_id: "7".to_string(),
+                    amount: U128(100),
+                    rewards_per_token_paid: BigBalance::zero(),
+                    claimed: U128(40),
+                    unlocked: U128(0),
+                },
+            )]),
+        );
+        contract.rewards.insert(
+            dave(),
+            HashMap::from([(
+                "7".to_string(),
+                RewardV1 {
+                    campaign_id: "7".to_string(),
+                    amount: U128(50),
+                    rewards_per_token_paid: BigBalance::zero(),
+                    claimed: U128(10),
+                    unlocked: U128(0),
+                },
+            )]),
+        );
+
+        contract
+    }
+
+    fn dave() -> AccountId {
+        AccountId::new_unchecked("dave.near".to_string())
+    }
+
+    #[test]
+    fn test_migrate_from_v1() {
+        env::state_write(&init_v1());
+        assert_eq!(StateVersion::read(), StateVersion::V1);
+
+        let mut contract = Contract::migrate_state(StateVersion::read());
+        assert_eq!(contract.get_state_version(), CURRENT_STATE_VERSION);
+
+        // rewards are migrated in batches of accounts
+        assert_eq!(
+            contract.get_rewards_migration(),
+            Some(MigrationProgress {
+                migrated: 0,
+                remaining: 2
+            })
+        );
+        assert!(contract.get_account_rewards(&bob()).is_empty());
+        assert_eq!(contract.migrate_rewards(Some(1)), 1);
+        assert_eq!(contract.migrate_rewards(None), 0);
+        assert_eq!(contract.get_rewards_migration(), None);
+
+        assert_eq!(contract.get_account_supplies(bob()), 3000);
+        assert_eq!(contract.get_account_borrows(bob()), 220);
+        assert_eq!(contract.get_total_borrows(), 220);
+        assert_eq!(contract.get_total_reserves(), 1000);
+        assert_eq!(contract.view_contract_balance(), U128(5000));
+        let campaign = contract.get_reward_campaign_by_id("7".to_string()).unwrap();
+        assert_eq!(campaign.tokens[0].distributed_amount, U128(2500));
+        assert_eq!(campaign.tokens[0].ledger.claimed, U128(50));
+        assert_eq!(campaign.tokens[0].ledger.remaining, U128(9950));
+        let rewards = contract.get_account_rewards(&bob());
+        assert_eq!(rewards.len(), 1);
+        assert_eq!(rewards[0].get_token_reward(&carol()).claimed, U128(40));
+        assert_eq!(contract.get_unique_id(), "7");
+        assert!(contract.roles.has_role(Role::Owner, &alice()));
+        assert_eq!(
+            contract
+                .get_contract_config()
+                .interest_rate_model
+                .get_reserve_factor(),
+            Ratio::from_str("0.1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_migrate_current_version() {
+        let contract = init_v1().migrate();
+        env::state_write(&contract);
+        CURRENT_STATE_VERSION.write();
+
+        let contract = Contract::migrate_state(StateVersion::read());
+        assert_eq!(contract.get_account_borrows(bob()), 220);
+    }
+
+    #[test]
+    #[should_panic(expected = "Rewards are paused until the V1 rewards are migrated")]
+    fn test_rewards_paused_during_migration() {
+        let mut contract = init_v1().migrate();
+        contract.migrate_rewards(Some(1));
+
+        contract.update_reward("7".to_string(), bob());
+    }
+}
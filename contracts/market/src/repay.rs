@@ -1,4 +1,5 @@
 use crate::*;
+use std::cmp::min;
 
 const GAS_FOR_REPAY: Gas = Gas(120_000_000_000_000);
 
@@ -16,52 +17,20 @@ impl Contract {
             return PromiseOrValue::Value(self.to_decimals_token(token_amount));
         }
         self.adjust_rewards_by_campaign_type(CampaignType::Borrow);
+        self.accrue_interest();
 
-        let balance_of = self.view_contract_balance();
-
-        let borrow_rate = self.get_borrow_rate(
-            U128(Balance::from(balance_of)),
-            U128(self.get_total_borrows()),
-            U128(self.get_total_reserves()),
+        // borrows are decreased by the repaid amount including interest,
+        // tokens over the account borrows are returned back
+        let repay_amount = min(
+            token_amount.0,
+            self.get_account_borrows(env::signer_account_id()),
         );
 
-        let borrow_accrued_interest = self
-            .config
-            .get()
-            .unwrap()
-            .interest_rate_model
-            .calculate_accrued_interest(
-                borrow_rate,
-                self.get_account_borrows(env::signer_account_id()),
-                self.get_accrued_borrow_interest(env::signer_account_id()),
-            );
-
-        // let borrow_amount = self.get_account_borrows(env::signer_account_id());
-        //
-        // let borrow_with_rate_amount = borrow_amount + borrow_accrued_interest.accumulated_interest;
-        self.set_accrued_borrow_interest(env::signer_account_id(), borrow_accrued_interest.clone());
-
-        let borrow_amount = self.get_account_borrows(env::signer_account_id());
-
-        // if borrow is less then accrued interest then we do not decerease borrows on controller
-        // if borrow is up to borrows + accrued then we repay accrued and decrease remaining tokens on controller
-        // if borrow is over borrows + accrued then we decrease full borrows on controller
-        let borrow_decrease_amount = if token_amount.0
-            <= borrow_accrued_interest.accumulated_interest
-        {
-            0u128
-        } else if token_amount.0 <= borrow_amount + borrow_accrued_interest.accumulated_interest {
-            token_amount.0 - borrow_accrued_interest.accumulated_interest
-        } else {
-            borrow_amount
-        };
-
         controller::repay_borrows(
             env::signer_account_id(),
             self.get_contract_address(),
-            U128(borrow_decrease_amount),
-            borrow_accrued_interest.last_recalculation_block,
-            U128::from(borrow_rate),
+            U128(repay_amount),
+            WRatio::from(self.borrow_index),
             self.get_controller_address(),
             NO_DEPOSIT,
             self.terra_gas(5),
@@ -84,62 +53,35 @@ impl Contract {
         token_amount: WBalance,
     ) -> PromiseOrValue<U128> {
         if !is_promise_success() {
-            log!(
-                "{}",
-                Events::RepayFailedToUpdateUserBalance(
-                    env::signer_account_id(),
-                    Balance::from(token_amount)
-                )
-            );
+            Events::RepayFailedToUpdateUserBalance {
+                account_id: env::signer_account_id(),
+                amount: token_amount,
+            }
+            .emit();
             self.mutex_account_unlock();
             return PromiseOrValue::Value(self.to_decimals_token(token_amount));
         }
 
-        let mut borrow_interest = self.get_accrued_borrow_interest(env::signer_account_id());
-        // update total reserves only after successful repay
-        let new_total_reserve = self.get_total_reserves()
-            + (Ratio::from(borrow_interest.accumulated_interest) * self.model.get_reserve_factor())
-                .round_u128();
-        self.set_total_reserves(new_total_reserve);
-
-        let dust_balance = U128::from(
-            token_amount
-                .0
-                .saturating_sub(self.get_account_borrows(env::signer_account_id()))
-                .saturating_sub(borrow_interest.accumulated_interest),
+        let repay_amount = min(
+            token_amount.0,
+            self.get_account_borrows(env::signer_account_id()),
         );
+        let dust_balance = U128::from(token_amount.0 - repay_amount);
+        let health_factor = self.get_health_factor_result();
+        let accrued_interest = self.get_account_accrued_interest(&env::signer_account_id());
 
-        let borrow_accrued_interest = self.get_accrued_borrow_interest(env::signer_account_id());
-        let borrow_amount = self.get_account_borrows(env::signer_account_id());
-
-        // if borrow is less then accrued interest then we decerease only accrued interest
-        // if borrow is up to borrows + accrued then we repay accrued and decrease borrows by remaining tokens
-        // if borrow is over borrows + accrued then we decrease full borrows
-        if token_amount.0 <= borrow_accrued_interest.accumulated_interest {
-            borrow_interest.accumulated_interest -= token_amount.0;
-            self.set_accrued_borrow_interest(env::signer_account_id(), borrow_interest);
-            self.increase_contract_balance(token_amount)
-        } else if token_amount.0 <= borrow_amount + borrow_accrued_interest.accumulated_interest {
-            self.decrease_borrows(
-                env::signer_account_id(),
-                WBalance::from(token_amount.0 - borrow_interest.accumulated_interest),
-            );
-            self.set_accrued_borrow_interest(env::signer_account_id(), AccruedInterest::default());
-            self.increase_contract_balance(token_amount)
-        } else {
-            self.decrease_borrows(env::signer_account_id(), U128(borrow_amount));
-            self.set_accrued_borrow_interest(env::signer_account_id(), AccruedInterest::default());
-            self.increase_contract_balance(U128::from(
-                borrow_amount + borrow_accrued_interest.accumulated_interest,
-            ))
-        };
+        self.decrease_borrows(env::signer_account_id(), U128(repay_amount));
+        self.increase_contract_balance(U128(repay_amount));
 
         self.mutex_account_unlock();
-        self.update_campaigns_market_total_by_type(CampaignType::Borrow);
-        log!(
-            "{}",
-            Events::RepaySuccess(env::signer_account_id(), Balance::from(token_amount))
-        );
+        self.update_campaigns_by_account_balance(env::signer_account_id(), CampaignType::Borrow);
+        Events::RepaySuccess {
+            account_id: env::signer_account_id(),
+            amount: U128(repay_amount),
+            state: self.get_action_state(accrued_interest, health_factor),
+        }
+        .emit();
+        self.refund_storage_if_empty(&env::signer_account_id());
 
         PromiseOrValue::Value(self.to_decimals_token(dust_balance))
     }
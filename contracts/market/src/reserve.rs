@@ -10,14 +10,21 @@ impl Contract {
         );
 
         require!(
-            self.is_valid_admin_call(),
-            "Reserve action can be called by admin only"
+            self.roles.is_role_call(Role::Owner),
+            "Reserve action can be called by owner only"
         );
 
         self.increase_reserve(token_amount);
 
         self.increase_contract_balance(token_amount);
 
+        Events::Reserve {
+            account_id: env::signer_account_id(),
+            amount: token_amount,
+            total_reserves: U128(self.get_total_reserves()),
+            exchange_rate: WRatio::from(self.get_exchange_rate(self.view_contract_balance())),
+        }
+        .emit();
         PromiseOrValue::Value(U128(0))
     }
 
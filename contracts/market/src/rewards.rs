@@ -1,6 +1,7 @@
 use crate::*;
 use general::ratio::{BigBalance, Ratio};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{promise_result_as_success, Promise};
 use std::cmp::{max, min};
 use std::fmt;
 
@@ -15,44 +16,144 @@ pub enum CampaignType {
 #[serde(crate = "near_sdk::serde")]
 pub struct Vesting {
     /// Campaign vesting start time, seconds
-    start_time: u64,
+    pub(crate) start_time: u64,
     /// Campaign vesting end time, seconds
-    end_time: u64,
+    pub(crate) end_time: u64,
     /// Penalty amount which will be arrested in case of early withdraw
-    penalty: Ratio,
+    pub(crate) penalty: Ratio,
+    /// Where the arrested penalties go
+    pub(crate) penalty_destination: PenaltyDestination,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PenaltyDestination {
+    /// Added to the market reserves, allowed for the campaigns paid in the underlying token only
+    Reserves,
+    /// Kept on the contract forever, they aren't swept after the campaign end
+    Burn,
+    /// Paid to the rest of the campaign accounts pro rata to their shares
+    Redistribution,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardToken {
+    /// Reward token address
+    pub(crate) token: AccountId,
+    /// Token ticker id
+    pub(crate) ticker_id: String,
+    /// Reward tokens total amount including top ups
+    pub(crate) reward_amount: WBalance,
+    /// Reward tokens amount distributed by the last update time
+    pub(crate) distributed_amount: WBalance,
+    /// Represent the token rewards amount which contract should pay for 1 token putted into liquidity
+    pub(crate) rewards_per_token: BigBalance,
+    /// Part of `rewards_per_token` coming from the redistributed penalties
+    #[serde(skip_deserializing, default = "BigBalance::zero")]
+    pub(crate) penalty_per_token: BigBalance,
+    /// Balance of the token held by the contract for the campaign, maintained by the contract only
+    #[serde(skip_deserializing)]
+    pub(crate) ledger: RewardLedger,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardLedger {
+    /// Tokens transferred to the contract to pay the campaign
+    pub(crate) funded: WBalance,
+    /// Tokens transferred to the accounts by the claims and unlocks
+    pub(crate) claimed: WBalance,
+    /// Rewards forfeited by the accounts on the early unlocks and not redistributed to the others
+    pub(crate) penalty: WBalance,
+    /// Tokens of the campaign still held by the contract
+    pub(crate) remaining: WBalance,
+}
+
+impl Default for RewardLedger {
+    fn default() -> Self {
+        Self {
+            funded: WBalance::from(0),
+            claimed: WBalance::from(0),
+            penalty: WBalance::from(0),
+            remaining: WBalance::from(0),
+        }
+    }
+}
+
+impl RewardToken {
+    /// Tokens left undistributed or forfeited on the unlocks, which aren't owed to the accounts
+    pub fn get_sweepable_amount(&self) -> Balance {
+        let owed = self
+            .distributed_amount
+            .0
+            .saturating_sub(self.ledger.claimed.0 + self.ledger.penalty.0);
+        self.ledger.remaining.0.saturating_sub(owed)
+    }
+}
+
+/// Boost of the account shares by the tokens locked in the governance token contract
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Boost {
+    /// Max multiplier of the account shares, reached once the account share of the voting power
+    /// is not less than its share of the market total
+    max_multiplier: Ratio,
+    /// Max part of the campaign total shares the boosted account could hold
+    max_share: Ratio,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct RewardCampaign {
     /// Reward campaign type
-    campaign_type: CampaignType,
+    pub(crate) campaign_type: CampaignType,
     /// Campaign start time seconds
-    start_time: u64,
+    pub(crate) start_time: u64,
     /// Campaign end time seconds
-    end_time: u64,
-    /// Reward token address
-    token: AccountId,
-    /// Token ticker id
-    ticker_id: String,
-    /// Reward tokens total amount
-    reward_amount: WBalance,
+    pub(crate) end_time: u64,
+    /// Tokens paid by the campaign
+    pub(crate) tokens: Vec<RewardToken>,
     /// Last time when rewardPerToken was recomputed/updated
-    last_update_time: u64,
-    /// Represent the token rewards amount which contract should pay for 1 token putted into liquidity
-    rewards_per_token: BigBalance,
+    pub(crate) last_update_time: u64,
     /// Last market total by campaign type value
-    last_market_total: WBalance,
+    pub(crate) last_market_total: WBalance,
     /// Vesting configuration
-    vesting: Vesting,
+    pub(crate) vesting: Vesting,
+    /// Boost by the locked governance tokens, the campaign isn't boosted if not set
+    pub(crate) boost: Option<Boost>,
+    /// Extra shares of the boosted accounts added to the market total
+    pub(crate) boosted_shares: WBalance,
 }
 
+/// Max number of rewards removed by a single call of the campaign removal
+pub const REWARDS_REMOVAL_LIMIT: u64 = 100;
+
+/// Gas of the claim of all rewards itself, every claimed token takes 10 TGas more for its
+/// transfer and 10 TGas for the transfer callback
+const CLAIM_ALL_REWARDS_GAS: u64 = 10;
+
 impl fmt::Display for RewardCampaign {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{self:?}")
     }
 }
 
+impl RewardCampaign {
+    pub fn get_token(&self, token: &AccountId) -> Option<&RewardToken> {
+        self.tokens
+            .iter()
+            .find(|reward_token| reward_token.token == *token)
+    }
+
+    /// Campaign distributes the rewards only once all of its tokens are funded
+    pub fn is_funded(&self) -> bool {
+        self.tokens
+            .iter()
+            .all(|reward_token| reward_token.ledger.funded.0 >= reward_token.reward_amount.0)
+    }
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct RewardCampaignExtended {
@@ -62,8 +163,8 @@ pub struct RewardCampaignExtended {
     campaign: RewardCampaign,
     /// Market total Supply/Borrow depends on reward campaign type
     market_total: WBalance,
-    /// Rewards per day token amount
-    rewards_per_day: WBalance,
+    /// Reward token -> Rewards per day token amount
+    rewards_per_day: HashMap<AccountId, WBalance>,
 }
 
 impl fmt::Display for RewardCampaignExtended {
@@ -75,24 +176,54 @@ impl fmt::Display for RewardCampaignExtended {
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct RewardState {
+    token: AccountId,
+
     max_claim_amount: WBalance,
 
     max_unlock_amount: WBalance,
+
+    penalty_received: WBalance,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
-pub struct Reward {
-    /// Reward campaign id
-    campaign_id: String,
+pub struct TokenReward {
     /// Total rewards amount, default = 0
-    amount: WBalance,
+    pub(crate) amount: WBalance,
     /// The last rewards_per_token which used for rewards adjustment, default = 0
-    rewards_per_token_paid: BigBalance,
+    pub(crate) rewards_per_token_paid: BigBalance,
     /// Tokens total amount that has been claimed by the user
-    claimed: WBalance,
+    pub(crate) claimed: WBalance,
     /// Tokens amount which were unlocked with penalty
-    unlocked: WBalance,
+    pub(crate) unlocked: WBalance,
+    /// The last penalty_per_token which used for rewards adjustment, default = 0
+    pub(crate) penalty_per_token_paid: BigBalance,
+    /// Penalties of the other accounts included into the rewards amount
+    pub(crate) penalty_received: WBalance,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Reward {
+    /// Reward campaign id
+    pub(crate) campaign_id: String,
+    /// Reward token -> rewards of the token
+    pub(crate) tokens: HashMap<AccountId, TokenReward>,
+    /// Extra shares of the account given by the boost at the last adjustment
+    pub(crate) boosted_shares: WBalance,
+}
+
+impl Default for TokenReward {
+    fn default() -> Self {
+        TokenReward {
+            amount: U128(0),
+            rewards_per_token_paid: BigBalance::zero(),
+            claimed: U128(0),
+            unlocked: U128(0),
+            penalty_per_token_paid: BigBalance::zero(),
+            penalty_received: U128(0),
+        }
+    }
 }
 
 impl fmt::Display for Reward {
@@ -105,12 +236,29 @@ impl Reward {
     pub fn new(campaig_id: String) -> Reward {
         Reward {
             campaign_id: campaig_id,
-            amount: U128(0),
-            rewards_per_token_paid: BigBalance::zero(),
-            claimed: U128(0),
-            unlocked: U128(0),
+            tokens: HashMap::new(),
+            boosted_shares: U128(0),
         }
     }
+
+    pub fn get_token_reward(&self, token: &AccountId) -> TokenReward {
+        self.tokens.get(token).cloned().unwrap_or_default()
+    }
+
+    /// Whether the whole rewards amount is claimed
+    pub fn is_settled(&self) -> bool {
+        self.tokens
+            .values()
+            .all(|token_reward| token_reward.claimed.0 >= token_reward.amount.0)
+    }
+}
+
+/// Voting power of the account and the total one reported by the governance token contract
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VotingPower {
+    pub account: WBalance,
+    pub total: WBalance,
 }
 
 impl Contract {
@@ -123,18 +271,32 @@ impl Contract {
             .iter()
             .map(|(id, campaign)| RewardCampaignExtended {
                 campaign_id: id,
-                campaign: campaign.clone(),
-                market_total: self.get_market_total(campaign.clone()),
-                rewards_per_day: self.get_reward_tokens_per_day(campaign),
+                market_total: self.get_market_total(&campaign),
+                rewards_per_day: self.get_reward_tokens_per_day(&campaign),
+                campaign,
             })
             .collect::<Vec<RewardCampaignExtended>>()
     }
 
-    pub fn get_rewards_per_time(&self, campaign: RewardCampaign, seconds: u128) -> WBalance {
-        let divider = u128::from(campaign.end_time - campaign.start_time);
+    /// Rewards distributed within the given time, the rest of the undistributed tokens
+    /// is spread evenly till the campaign end, so top ups and extensions affect
+    /// the future rewards only
+    pub fn get_rewards_per_time(
+        &self,
+        campaign: &RewardCampaign,
+        reward_token: &RewardToken,
+        seconds: u64,
+    ) -> WBalance {
+        let divider = campaign
+            .end_time
+            .saturating_sub(max(campaign.last_update_time, campaign.start_time));
         let rewards_per_time = match divider {
             0 => 0,
-            _ => campaign.reward_amount.0 * seconds / divider,
+            _ => {
+                (reward_token.reward_amount.0 - reward_token.distributed_amount.0)
+                    * u128::from(seconds)
+                    / u128::from(divider)
+            }
         };
         WBalance::from(rewards_per_time)
     }
@@ -143,15 +305,39 @@ impl Contract {
         env::block_timestamp_ms() / 1000u64
     }
 
-    pub fn get_rewards_per_second(&self, campaign: RewardCampaign) -> WBalance {
-        self.get_rewards_per_time(campaign, 1)
+    pub fn get_rewards_per_second(
+        &self,
+        campaign: &RewardCampaign,
+    ) -> HashMap<AccountId, WBalance> {
+        campaign
+            .tokens
+            .iter()
+            .map(|reward_token| {
+                (
+                    reward_token.token.clone(),
+                    self.get_rewards_per_time(campaign, reward_token, 1),
+                )
+            })
+            .collect()
     }
 
-    pub fn get_reward_tokens_per_day(&self, campaign: RewardCampaign) -> WBalance {
-        self.get_rewards_per_time(campaign, 24 * 60 * 60)
+    pub fn get_reward_tokens_per_day(
+        &self,
+        campaign: &RewardCampaign,
+    ) -> HashMap<AccountId, WBalance> {
+        campaign
+            .tokens
+            .iter()
+            .map(|reward_token| {
+                (
+                    reward_token.token.clone(),
+                    self.get_rewards_per_time(campaign, reward_token, 24 * 60 * 60),
+                )
+            })
+            .collect()
     }
 
-    pub fn get_market_total(&self, campaign: RewardCampaign) -> WBalance {
+    pub fn get_market_total(&self, campaign: &RewardCampaign) -> WBalance {
         if self.get_timestamp_in_seconds() > campaign.end_time {
             return campaign.last_market_total;
         }
@@ -162,114 +348,234 @@ impl Contract {
         WBalance::from(total_amount)
     }
 
-    pub fn get_account_total(&self, campaign: RewardCampaign, account_id: AccountId) -> WBalance {
+    /// Market total along with the extra shares of the boosted accounts
+    pub fn get_campaign_total_shares(&self, campaign: &RewardCampaign) -> Balance {
+        self.get_market_total(campaign).0 + campaign.boosted_shares.0
+    }
+
+    pub fn get_account_total(&self, campaign: &RewardCampaign, account_id: AccountId) -> WBalance {
         let account_total = match campaign.campaign_type {
             CampaignType::Supply => self.get_account_supplies(account_id),
-            CampaignType::Borrow => self.get_account_borrows(account_id),
+            CampaignType::Borrow => self.get_account_total_borrows(account_id),
         };
         WBalance::from(account_total)
     }
 
-    fn insert_map_if_not_exists(&mut self, account_id: AccountId) {
-        let rewards_map: HashMap<String, Reward> = HashMap::new();
+    /// Extra shares of the account given by the boost. The account gets the max boost once its
+    /// share of the voting power is not less than its share of the market total, the boosted
+    /// shares of the account are capped by the max share of the campaign total.
+    pub fn get_account_boosted_shares(
+        &self,
+        campaign: &RewardCampaign,
+        account_id: &AccountId,
+        boosted_shares: Balance,
+    ) -> Balance {
+        let boost = match &campaign.boost {
+            Some(boost) => boost,
+            None => return 0,
+        };
+        let voting_power_share = self
+            .voting_power_shares
+            .get(account_id)
+            .unwrap_or_else(Ratio::zero);
+        let account_total = self.get_account_total(campaign, account_id.clone()).0;
+        if account_total == 0 || voting_power_share == Ratio::zero() {
+            return 0;
+        }
+
+        let market_total = self.get_market_total(campaign).0;
+        let boost_ratio = min(
+            Ratio::one(),
+            voting_power_share * Ratio::from(market_total) / Ratio::from(account_total),
+        );
+        let extra_shares =
+            ((boost.max_multiplier - Ratio::one()) * boost_ratio).round_mul_u128(account_total);
+
+        let other_shares = self.get_campaign_total_shares(campaign) - boosted_shares;
+        let max_extra_shares = boost
+            .max_share
+            .round_mul_u128(other_shares)
+            .saturating_sub(account_total);
+        min(extra_shares, max_extra_shares)
+    }
+
+    pub fn get_reward(&self, account_id: &AccountId, campaign_id: &str) -> Option<Reward> {
         self.rewards
-            .entry(account_id)
-            .or_insert_with(|| rewards_map);
+            .get(&(account_id.clone(), campaign_id.to_string()))
     }
 
-    pub fn get_accrued_rewards_per_token(&self, campaign_id: String) -> BigBalance {
-        if let Some(campaign) = self.get_reward_campaign_by_id(campaign_id.clone()) {
-            let total = self.get_market_total(campaign.clone());
-            let current_time = min(self.get_timestamp_in_seconds(), campaign.end_time);
-            if total.0 == 0 {
-                return BigBalance::zero();
-            };
+    /// Stored rewards of the account including the ones of the campaigns being removed
+    pub fn get_account_rewards(&self, account_id: &AccountId) -> Vec<Reward> {
+        self.account_reward_campaigns
+            .get(account_id)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|campaign_id| self.get_reward(account_id, campaign_id))
+            .collect()
+    }
 
-            let rewards_per_time = self.get_rewards_per_time(
-                campaign.clone(),
-                u128::from(current_time - max(campaign.last_update_time, campaign.start_time)),
-            );
+    fn get_campaign_reward_accounts(&self, campaign_id: &str) -> UnorderedSet<AccountId> {
+        self.campaign_reward_accounts
+            .get(&campaign_id.to_string())
+            .unwrap_or_else(|| {
+                UnorderedSet::new(StorageKeys::CampaignRewardAccountsOf {
+                    campaign_id: campaign_id.to_string(),
+                })
+            })
+    }
 
-            let result = (BigBalance::from(rewards_per_time.0) / BigBalance::from(total.0))
-                * BigBalance::from(ONE_TOKEN);
-            return result;
+    pub(crate) fn insert_reward(&mut self, account_id: &AccountId, reward: &Reward) {
+        let key = (account_id.clone(), reward.campaign_id.clone());
+        if self.rewards.insert(&key, reward).is_some() {
+            return;
         }
-        panic!(
-            "Campaign {} wasn't found on the current contract",
-            campaign_id
-        );
+
+        let mut campaigns = self
+            .account_reward_campaigns
+            .get(account_id)
+            .unwrap_or_default();
+        campaigns.insert(reward.campaign_id.clone());
+        self.account_reward_campaigns.insert(account_id, &campaigns);
+
+        let mut accounts = self.get_campaign_reward_accounts(&reward.campaign_id);
+        accounts.insert(account_id);
+        self.campaign_reward_accounts
+            .insert(&reward.campaign_id, &accounts);
+    }
+
+    pub fn remove_reward(&mut self, account_id: &AccountId, campaign_id: &str) {
+        self.rewards
+            .remove(&(account_id.clone(), campaign_id.to_string()));
+
+        if let Some(mut campaigns) = self.account_reward_campaigns.get(account_id) {
+            campaigns.remove(campaign_id);
+            if campaigns.is_empty() {
+                self.account_reward_campaigns.remove(account_id);
+            } else {
+                self.account_reward_campaigns.insert(account_id, &campaigns);
+            }
+        }
+
+        let campaign_id = campaign_id.to_string();
+        if let Some(mut accounts) = self.campaign_reward_accounts.get(&campaign_id) {
+            accounts.remove(account_id);
+            if accounts.is_empty() {
+                self.campaign_reward_accounts.remove(&campaign_id);
+            } else {
+                self.campaign_reward_accounts
+                    .insert(&campaign_id, &accounts);
+            }
+        }
+    }
+
+    /// Campaign with the rewards per token accrued since the last update time
+    pub fn get_accrued_reward_campaign(&self, campaign: &RewardCampaign) -> RewardCampaign {
+        let total = self.get_campaign_total_shares(campaign);
+        let current_time = min(self.get_timestamp_in_seconds(), campaign.end_time);
+        // rewards of the time the campaign isn't funded are spread over the rest of the campaign
+        let seconds = match campaign.is_funded() {
+            true => {
+                current_time.saturating_sub(max(campaign.last_update_time, campaign.start_time))
+            }
+            false => 0,
+        };
+
+        let mut accrued_campaign = campaign.clone();
+        for reward_token in accrued_campaign.tokens.iter_mut() {
+            let rewards_per_time = self.get_rewards_per_time(campaign, reward_token, seconds);
+            reward_token.distributed_amount =
+                WBalance::from(reward_token.distributed_amount.0 + rewards_per_time.0);
+            if total > 0 {
+                reward_token.rewards_per_token = reward_token.rewards_per_token
+                    + (BigBalance::from(rewards_per_time.0) / BigBalance::from(total))
+                        * BigBalance::from(ONE_TOKEN);
+            }
+        }
+        accrued_campaign.last_update_time = current_time;
+        accrued_campaign
     }
 
     pub fn update_reward_campaign(&mut self, campaign_id: String) -> RewardCampaign {
-        let mut campaign = self.get_reward_campaign_by_id(campaign_id.clone()).unwrap();
-        let accrued_tokens = self.get_accrued_rewards_per_token(campaign_id.clone());
-        campaign.rewards_per_token = campaign.rewards_per_token + accrued_tokens;
-        campaign.last_update_time = min(self.get_timestamp_in_seconds(), campaign.end_time);
+        let campaign = self
+            .get_reward_campaign_by_id(campaign_id.clone())
+            .unwrap_or_else(|| {
+                panic!(
+                    "Campaign {} wasn't found on the current contract",
+                    campaign_id
+                )
+            });
+        let campaign = self.get_accrued_reward_campaign(&campaign);
         self.reward_campaigns.insert(&campaign_id, &campaign);
         campaign
     }
 
     pub fn update_campaign_market_total(&mut self, campaign_id: String) -> RewardCampaign {
         let mut campaign = self.get_reward_campaign_by_id(campaign_id.clone()).unwrap();
-        campaign.last_market_total = self.get_market_total(campaign.clone());
+        campaign.last_market_total = self.get_market_total(&campaign);
         self.reward_campaigns.insert(&campaign_id, &campaign);
         campaign
     }
 
-    pub fn get_updated_reward_amount(&self, reward: &Reward, account_id: AccountId) -> WBalance {
-        self.get_updated_reward_amount_with_accrued(reward, account_id, BigBalance::zero())
-    }
-
-    pub fn get_updated_reward_amount_with_accrued(
+    /// Reward with the rewards earned by the account shares since the last adjustment,
+    /// `campaign` is expected to be accrued
+    pub fn get_updated_reward(
         &self,
+        campaign: &RewardCampaign,
         reward: &Reward,
         account_id: AccountId,
-        accrued: BigBalance,
-    ) -> WBalance {
-        let campaign_option = self.get_reward_campaign_by_id(reward.campaign_id.clone());
-
-        assert!(
-            campaign_option.is_some(),
-            "{}",
-            "Campaign for reward {reward} not exists"
-        );
-        let campaign = campaign_option.unwrap();
-        let total = self.get_account_total(campaign.clone(), account_id);
-        WBalance::from(
-            reward.amount.0
-                + (BigBalance::from(total.0)
-                    * (campaign.rewards_per_token - reward.rewards_per_token_paid + accrued)
-                    / BigBalance::from(ONE_TOKEN))
-                .round_u128(),
-        )
+    ) -> Reward {
+        let shares = self.get_account_total(campaign, account_id).0 + reward.boosted_shares.0;
+
+        let mut updated_reward = reward.clone();
+        for reward_token in campaign.tokens.iter() {
+            let token_reward = updated_reward
+                .tokens
+                .entry(reward_token.token.clone())
+                .or_default();
+            token_reward.amount = WBalance::from(
+                token_reward.amount.0
+                    + (BigBalance::from(shares)
+                        * (reward_token.rewards_per_token - token_reward.rewards_per_token_paid)
+                        / BigBalance::from(ONE_TOKEN))
+                    .round_u128(),
+            );
+            token_reward.penalty_received = WBalance::from(
+                token_reward.penalty_received.0
+                    + (BigBalance::from(shares)
+                        * (reward_token.penalty_per_token - token_reward.penalty_per_token_paid)
+                        / BigBalance::from(ONE_TOKEN))
+                    .round_u128(),
+            );
+            token_reward.rewards_per_token_paid = reward_token.rewards_per_token;
+            token_reward.penalty_per_token_paid = reward_token.penalty_per_token;
+        }
+        updated_reward
     }
 
+    /// Adjusts the reward of the account and refreshes its boosted shares
     pub fn update_reward(&mut self, campaign_id: String, account_id: AccountId) -> Reward {
-        let campaign = self.update_reward_campaign(campaign_id.clone());
-        let default_reward = Reward::new(campaign_id.clone());
-
-        self.insert_map_if_not_exists(account_id.clone());
-
-        let account_rewards = self.rewards.get(&account_id).unwrap();
-        let old_reward = account_rewards.get(&campaign_id).unwrap_or(&default_reward);
-        let mut reward = Reward::new(campaign_id.clone());
-
-        reward.amount = self.get_updated_reward_amount(old_reward, account_id.clone());
-        reward.rewards_per_token_paid = campaign.rewards_per_token;
-        reward.claimed = old_reward.claimed;
-        reward.unlocked = old_reward.unlocked;
+        self.assert_rewards_migrated();
+        let mut campaign = self.update_reward_campaign(campaign_id.clone());
+        let old_reward = self
+            .get_reward(&account_id, &campaign_id)
+            .unwrap_or_else(|| Reward::new(campaign_id.clone()));
+        let mut reward = self.get_updated_reward(&campaign, &old_reward, account_id.clone());
+
+        let boosted_shares =
+            self.get_account_boosted_shares(&campaign, &account_id, reward.boosted_shares.0);
+        if boosted_shares != reward.boosted_shares.0 {
+            campaign.boosted_shares = WBalance::from(
+                campaign.boosted_shares.0 - reward.boosted_shares.0 + boosted_shares,
+            );
+            self.reward_campaigns.insert(&campaign_id, &campaign);
+            reward.boosted_shares = WBalance::from(boosted_shares);
+        }
 
         reward
     }
 
     pub fn update_reward_in_state(&mut self, account_id: AccountId, reward: Reward) -> Reward {
-        let default_reward = Reward::new(reward.campaign_id.clone());
-        *self
-            .rewards
-            .entry(account_id)
-            .or_default()
-            .entry(reward.campaign_id.clone())
-            .or_insert(default_reward) = reward.clone();
+        self.insert_reward(&account_id, &reward);
         reward
     }
 
@@ -292,6 +598,20 @@ impl Contract {
         });
     }
 
+    /// Adjusts rewards of the given account instead of the signer one
+    pub fn adjust_account_rewards_by_campaign_type(
+        &mut self,
+        account_id: AccountId,
+        campaign_type: CampaignType,
+    ) {
+        let campaigns = self.get_campaigns_by_campaign_type(campaign_type);
+
+        campaigns.iter().for_each(|campaign_id| {
+            let reward = self.update_reward(campaign_id.clone(), account_id.clone());
+            self.update_reward_in_state(account_id.clone(), reward);
+        });
+    }
+
     pub fn update_campaigns_market_total_by_type(&mut self, campaign_type: CampaignType) {
         let campaigns = self.get_campaigns_by_campaign_type(campaign_type);
 
@@ -300,6 +620,17 @@ impl Contract {
         });
     }
 
+    /// Refreshes the market totals of the campaigns and the boosted shares of the account
+    /// once its balance is changed, so the boost follows the account balance
+    pub fn update_campaigns_by_account_balance(
+        &mut self,
+        account_id: AccountId,
+        campaign_type: CampaignType,
+    ) {
+        self.update_campaigns_market_total_by_type(campaign_type.clone());
+        self.adjust_account_rewards_by_campaign_type(account_id, campaign_type);
+    }
+
     pub fn adjust_reward(&mut self, campaign_id: String) -> Reward {
         let account_id = env::signer_account_id();
         let reward = self.update_reward(campaign_id, account_id.clone());
@@ -312,67 +643,86 @@ impl Contract {
         campaign_id: String,
         reward: Reward,
     ) -> Reward {
-        let accrued = self.get_accrued_rewards_per_token(campaign_id);
-        Reward {
-            campaign_id: reward.campaign_id.clone(),
-            amount: self.get_updated_reward_amount_with_accrued(&reward, account_id, accrued),
-            rewards_per_token_paid: reward.rewards_per_token_paid,
-            claimed: reward.claimed,
-            unlocked: reward.unlocked,
+        let campaign = self
+            .get_reward_campaign_by_id(campaign_id.clone())
+            .unwrap_or_else(|| {
+                panic!(
+                    "Campaign {} wasn't found on the current contract",
+                    campaign_id
+                )
+            });
+        let mut updated_reward = self.get_updated_reward(
+            &self.get_accrued_reward_campaign(&campaign),
+            &reward,
+            account_id,
+        );
+
+        // only amounts are updated by the view, the reward is adjusted by the next action
+        for (token, token_reward) in updated_reward.tokens.iter_mut() {
+            let paid_reward = reward.get_token_reward(token);
+            token_reward.rewards_per_token_paid = paid_reward.rewards_per_token_paid;
+            token_reward.penalty_per_token_paid = paid_reward.penalty_per_token_paid;
         }
+        updated_reward
     }
 
-    pub fn get_reward_state(&self, account_id: AccountId, campaign_id: String) -> RewardState {
-        let default_reward = Reward::new(campaign_id.clone());
+    pub fn get_reward_state(&self, account_id: AccountId, campaign_id: String) -> Vec<RewardState> {
         let reward = self
-            .rewards
-            .get(&account_id)
-            .unwrap()
-            .get(&campaign_id)
-            .unwrap_or(&default_reward);
+            .get_reward(&account_id, &campaign_id)
+            .unwrap_or_else(|| Reward::new(campaign_id.clone()));
 
-        let updated_reward =
-            self.get_view_reward_state_for_user(account_id, campaign_id, reward.clone());
+        let updated_reward = self.get_view_reward_state_for_user(account_id, campaign_id, reward);
 
-        let available_to_claim_amount = self.get_amount_available_to_claim(updated_reward.clone());
-        let available_to_unlock_amount = updated_reward.amount.0 - available_to_claim_amount;
-        RewardState {
-            max_claim_amount: WBalance::from(available_to_claim_amount),
-            max_unlock_amount: WBalance::from(available_to_unlock_amount),
-        }
+        updated_reward
+            .tokens
+            .iter()
+            .map(|(token, token_reward)| {
+                let available_to_claim_amount =
+                    self.get_amount_available_to_claim(&updated_reward, token);
+                RewardState {
+                    token: token.clone(),
+                    max_claim_amount: WBalance::from(available_to_claim_amount),
+                    max_unlock_amount: WBalance::from(
+                        token_reward.amount.0 - available_to_claim_amount,
+                    ),
+                    penalty_received: token_reward.penalty_received,
+                }
+            })
+            .collect()
     }
 
+    /// Rewards of the account, the ones of the campaigns being removed are skipped
     pub fn get_rewards_list(&self, account_id: AccountId) -> HashMap<String, Reward> {
-        let default_map: HashMap<String, Reward> = HashMap::new();
-        let account_rewards = self.rewards.get(&account_id).unwrap_or(&default_map);
-        let mut view_rewards: HashMap<String, Reward> = HashMap::new();
-
-        account_rewards.iter().for_each(|(campaign_id, reward)| {
-            view_rewards.insert(
-                campaign_id.clone(),
-                self.get_view_reward_state_for_user(
-                    account_id.clone(),
-                    campaign_id.clone(),
-                    reward.clone(),
-                ),
-            );
-        });
-        view_rewards
+        self.get_account_rewards(&account_id)
+            .into_iter()
+            .filter(|reward| self.reward_campaigns.get(&reward.campaign_id).is_some())
+            .map(|reward| {
+                (
+                    reward.campaign_id.clone(),
+                    self.get_view_reward_state_for_user(
+                        account_id.clone(),
+                        reward.campaign_id.clone(),
+                        reward,
+                    ),
+                )
+            })
+            .collect()
     }
 
-    pub fn get_amount_available_to_claim(&self, reward: Reward) -> Balance {
+    pub fn get_amount_available_to_claim(&self, reward: &Reward, token: &AccountId) -> Balance {
         let mut result: Balance = 0;
         if let Some(campaign) = self.get_reward_campaign_by_id(reward.campaign_id.clone()) {
             if campaign.vesting.start_time > self.get_timestamp_in_seconds() {
                 return result;
             };
+            let token_reward = reward.get_token_reward(token);
             let vesting_duration =
                 Balance::from(campaign.vesting.end_time - campaign.vesting.start_time);
             let current_time = min(self.get_timestamp_in_seconds(), campaign.vesting.end_time);
 
             result = match vesting_duration {
-                0 => reward.amount.0 - reward.claimed.0,
-                _ => ((BigBalance::from(reward.amount.0 - reward.claimed.0)
+                0 => token_reward.amount.0 - token_reward.claimed.0,
+                _ => ((BigBalance::from(token_reward.amount.0 - token_reward.claimed.0)
                     * BigBalance::from(current_time - campaign.vesting.start_time))
                     / BigBalance::from(vesting_duration))
                 .round_u128(),
@@ -381,6 +731,7 @@ impl Contract {
         result
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn claim_or_unlock_request(
         &mut self,
         account_id: AccountId,
@@ -389,87 +740,583 @@ impl Contract {
         token_address: AccountId,
         claimed_amount: WBalance,
         unlocked_amount: WBalance,
-        reward: Reward,
+        campaign_id: String,
     ) {
         underlying_token::ft_transfer(
             account_id.clone(),
-            self.to_decimals_token(transfer_amount),
-            Some(msg),
-            token_address,
-            ONE_YOCTO,
-            self.terra_gas(10),
+            self.to_decimals_token(transfer_amount),
+            Some(msg),
+            token_address.clone(),
+            ONE_YOCTO,
+            self.terra_gas(10),
+        )
+        .then(ext_self::claim_reward_ft_transfer_callback(
+            campaign_id,
+            token_address,
+            account_id,
+            claimed_amount,
+            unlocked_amount,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(5),
+        ));
+    }
+
+    /// Records the claim in the account reward and the campaign ledger before the transfer,
+    /// so the same rewards couldn't be claimed again while the transfer is in progress
+    fn book_reward_claim(
+        &mut self,
+        campaign_id: &str,
+        token: &AccountId,
+        account_id: &AccountId,
+        amount: Balance,
+    ) {
+        if let Some(mut campaign) = self.get_reward_campaign_by_id(campaign_id.to_string()) {
+            if let Some(reward_token) = campaign
+                .tokens
+                .iter_mut()
+                .find(|reward_token| reward_token.token == *token)
+            {
+                let ledger = &mut reward_token.ledger;
+                require!(
+                    ledger.remaining.0 >= amount,
+                    format!(
+                        "Campaign {} doesn't hold enough {} tokens to pay the claim",
+                        campaign_id, token
+                    )
+                );
+                ledger.claimed = WBalance::from(ledger.claimed.0 + amount);
+                ledger.remaining = WBalance::from(ledger.remaining.0 - amount);
+                self.reward_campaigns
+                    .insert(&campaign_id.to_string(), &campaign);
+            }
+        }
+
+        let mut reward = self
+            .get_reward(account_id, campaign_id)
+            .unwrap_or_else(|| Reward::new(campaign_id.to_string()));
+        let token_reward = reward.tokens.entry(token.clone()).or_default();
+        token_reward.claimed = WBalance::from(token_reward.claimed.0 + amount);
+        self.insert_reward(account_id, &reward);
+    }
+
+    /// Reverts the claim booked for the transfer which has failed
+    fn revert_reward_claim(
+        &mut self,
+        campaign_id: &str,
+        token: &AccountId,
+        account_id: &AccountId,
+        amount: Balance,
+    ) {
+        if let Some(mut campaign) = self.get_reward_campaign_by_id(campaign_id.to_string()) {
+            if let Some(reward_token) = campaign
+                .tokens
+                .iter_mut()
+                .find(|reward_token| reward_token.token == *token)
+            {
+                let ledger = &mut reward_token.ledger;
+                ledger.claimed = WBalance::from(ledger.claimed.0 - amount);
+                ledger.remaining = WBalance::from(ledger.remaining.0 + amount);
+                self.reward_campaigns
+                    .insert(&campaign_id.to_string(), &campaign);
+            }
+        }
+
+        if let Some(mut reward) = self.get_reward(account_id, campaign_id) {
+            let token_reward = reward.tokens.entry(token.clone()).or_default();
+            token_reward.claimed = WBalance::from(token_reward.claimed.0 - amount);
+            self.insert_reward(account_id, &reward);
+        }
+    }
+
+    /// Records the transferred claim or unlock in the account reward and the campaign ledger
+    fn apply_reward_claim(
+        &mut self,
+        campaign_id: String,
+        token: AccountId,
+        account_id: AccountId,
+        amount: WBalance,
+        unlocked: WBalance,
+    ) {
+        if unlocked.0 > 0 {
+            Events::RewardUnlock {
+                account_id: account_id.clone(),
+                campaign_id: campaign_id.clone(),
+                token: token.clone(),
+                amount,
+                transferred_amount: unlocked,
+            }
+            .emit();
+        } else {
+            Events::RewardClaim {
+                account_id: account_id.clone(),
+                campaign_id: campaign_id.clone(),
+                token: token.clone(),
+                amount,
+            }
+            .emit();
+        }
+
+        let mut reward = self
+            .get_reward(&account_id, &campaign_id)
+            .unwrap_or_else(|| Reward::new(campaign_id.clone()));
+        let transferred = if unlocked.0 > 0 { unlocked.0 } else { amount.0 };
+        let penalty = amount.0 - transferred;
+
+        if let Some(mut campaign) = self.get_reward_campaign_by_id(campaign_id.clone()) {
+            let destination = campaign.vesting.penalty_destination.clone();
+            // rewards accrued before are paid to the account prior to the redistribution
+            let mut others_shares = 0;
+            if penalty > 0 && destination == PenaltyDestination::Redistribution {
+                campaign = self.update_reward_campaign(campaign_id.clone());
+                reward = self.get_updated_reward(&campaign, &reward, account_id.clone());
+                let account_shares = self.get_account_total(&campaign, account_id.clone()).0
+                    + reward.boosted_shares.0;
+                others_shares = self
+                    .get_campaign_total_shares(&campaign)
+                    .saturating_sub(account_shares);
+            }
+
+            if let Some(reward_token) = campaign
+                .tokens
+                .iter_mut()
+                .find(|reward_token| reward_token.token == token)
+            {
+                let ledger = &mut reward_token.ledger;
+                ledger.claimed = WBalance::from(ledger.claimed.0 + transferred);
+                ledger.remaining = WBalance::from(ledger.remaining.0.saturating_sub(transferred));
+
+                if penalty > 0 {
+                    match destination {
+                        PenaltyDestination::Redistribution if others_shares > 0 => {
+                            let penalty_per_token = BigBalance::from(penalty)
+                                / BigBalance::from(others_shares)
+                                * BigBalance::from(ONE_TOKEN);
+                            reward_token.rewards_per_token =
+                                reward_token.rewards_per_token + penalty_per_token;
+                            reward_token.penalty_per_token =
+                                reward_token.penalty_per_token + penalty_per_token;
+
+                            // the account doesn't get its own penalty
+                            let token_reward = reward.tokens.entry(token.clone()).or_default();
+                            token_reward.rewards_per_token_paid = reward_token.rewards_per_token;
+                            token_reward.penalty_per_token_paid = reward_token.penalty_per_token;
+                        }
+                        // penalty is left for the sweep if there is nobody to redistribute it to
+                        PenaltyDestination::Redistribution => {
+                            ledger.penalty = WBalance::from(ledger.penalty.0 + penalty);
+                        }
+                        PenaltyDestination::Reserves | PenaltyDestination::Burn => {
+                            ledger.penalty = WBalance::from(ledger.penalty.0 + penalty);
+                            ledger.remaining =
+                                WBalance::from(ledger.remaining.0.saturating_sub(penalty));
+                            if destination == PenaltyDestination::Reserves {
+                                self.increase_reserve(WBalance::from(penalty));
+                                self.increase_contract_balance(WBalance::from(penalty));
+                            }
+                        }
+                    }
+                }
+                self.reward_campaigns.insert(&campaign_id, &campaign);
+            }
+        }
+
+        let token_reward = reward.tokens.entry(token).or_default();
+        token_reward.claimed = WBalance::from(token_reward.claimed.0 + amount.0);
+        token_reward.unlocked = WBalance::from(token_reward.unlocked.0 + unlocked.0);
+        self.update_reward_in_state(account_id, reward);
+    }
+
+    /// Accounts the reward tokens transferred with `FundCampaign`, the tokens above the campaign
+    /// reward amount could be used for the top ups or swept after the campaign end
+    pub fn fund_reward_campaign(
+        &mut self,
+        campaign_id: String,
+        token: AccountId,
+        sender_id: AccountId,
+        amount: WBalance,
+    ) -> PromiseOrValue<U128> {
+        let mut campaign = self.update_reward_campaign(campaign_id.clone());
+        require!(
+            campaign.end_time > self.get_timestamp_in_seconds(),
+            "Finished campaign can't be funded"
+        );
+
+        let reward_token = campaign
+            .tokens
+            .iter_mut()
+            .find(|reward_token| reward_token.token == token)
+            .expect("Token is not paid by the campaign");
+        reward_token.ledger.funded = WBalance::from(reward_token.ledger.funded.0 + amount.0);
+        reward_token.ledger.remaining = WBalance::from(reward_token.ledger.remaining.0 + amount.0);
+        self.reward_campaigns.insert(&campaign_id, &campaign);
+
+        Events::RewardCampaignFund {
+            campaign_id,
+            token,
+            sender_id,
+            amount,
+            is_funded: campaign.is_funded(),
+        }
+        .emit();
+        PromiseOrValue::Value(U128(0))
+    }
+
+    fn assert_valid_reward_campaign(&self, reward_campaign: &RewardCampaign) {
+        require!(
+            !reward_campaign.tokens.is_empty(),
+            "Campaign should pay at least one token"
+        );
+        let tokens: HashSet<&AccountId> = reward_campaign
+            .tokens
+            .iter()
+            .map(|reward_token| &reward_token.token)
+            .collect();
+        require!(
+            tokens.len() == reward_campaign.tokens.len(),
+            "Campaign tokens should be unique"
+        );
+        require!(
+            reward_campaign.vesting.penalty < Ratio::one(),
+            "Vesting penalty should be less than one"
+        );
+        if reward_campaign.vesting.penalty_destination == PenaltyDestination::Reserves {
+            require!(
+                reward_campaign
+                    .tokens
+                    .iter()
+                    .all(|reward_token| reward_token.token == self.underlying_token),
+                "Penalties could be sent to the reserves by the campaigns paid in the underlying token only"
+            );
+        }
+        if let Some(boost) = &reward_campaign.boost {
+            require!(
+                boost.max_multiplier >= Ratio::one() && boost.max_share <= Ratio::one(),
+                "Boost multiplier should not be less than one and max share should not exceed one"
+            );
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn add_reward_campaign(&mut self, reward_campaign: RewardCampaign) -> String {
+        self.roles.assert_role(Role::RewardManager);
+        require!(
+            reward_campaign.end_time >= self.get_timestamp_in_seconds(),
+            "Campaign end time can't be in the past"
+        );
+        self.assert_valid_reward_campaign(&reward_campaign);
+        let campaign_id = self.request_unique_id();
+        self.reward_campaigns.insert(&campaign_id, &reward_campaign);
+        campaign_id
+    }
+
+    /// Adds reward tokens to the campaign, they are distributed till the campaign end
+    /// along with the undistributed rest, rewards accrued before are kept.
+    /// Tokens of the top up should be transferred with `FundCampaign` beforehand
+    pub fn top_up_reward_campaign(
+        &mut self,
+        campaign_id: String,
+        token: AccountId,
+        amount: WBalance,
+    ) {
+        self.roles.assert_role(Role::RewardManager);
+        let mut campaign = self.update_reward_campaign(campaign_id.clone());
+        require!(
+            campaign.end_time > self.get_timestamp_in_seconds(),
+            "Finished campaign can't be changed"
+        );
+
+        let reward_token = campaign
+            .tokens
+            .iter_mut()
+            .find(|reward_token| reward_token.token == token)
+            .expect("Token is not paid by the campaign");
+        require!(
+            reward_token.ledger.funded.0 >= reward_token.reward_amount.0 + amount.0,
+            "Top up should be funded first"
+        );
+        reward_token.reward_amount = WBalance::from(reward_token.reward_amount.0 + amount.0);
+        self.reward_campaigns.insert(&campaign_id, &campaign);
+    }
+
+    /// Moves the campaign end, undistributed rewards are spread till the new end
+    pub fn extend_reward_campaign(&mut self, campaign_id: String, end_time: u64) {
+        self.roles.assert_role(Role::RewardManager);
+        let mut campaign = self.update_reward_campaign(campaign_id.clone());
+        require!(
+            campaign.end_time > self.get_timestamp_in_seconds(),
+            "Finished campaign can't be changed"
+        );
+        require!(
+            end_time > campaign.end_time,
+            "Campaign end time could be only moved forward"
+        );
+
+        campaign.end_time = end_time;
+        self.reward_campaigns.insert(&campaign_id, &campaign);
+    }
+
+    pub fn get_rewards_by_campaign_id(
+        &self,
+        campaign_id: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(AccountId, Reward)> {
+        let accounts = self.get_campaign_reward_accounts(&campaign_id);
+        accounts
+            .as_vector()
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|account_id| {
+                self.get_reward(&account_id, &campaign_id)
+                    .map(|reward| (account_id, reward))
+            })
+            .collect()
+    }
+
+    /// Removes the campaign and up to `limit` of its rewards, should be called again until
+    /// no rewards of the campaign remain. Returns the number of the remaining rewards.
+    pub fn remove_reward_campaign(&mut self, campaign_id: String, limit: Option<u64>) -> u64 {
+        self.roles.assert_role(Role::RewardManager);
+        self.assert_rewards_migrated();
+        let accounts = self.campaign_reward_accounts.get(&campaign_id);
+        require!(
+            self.reward_campaigns.remove(&campaign_id).is_some() || accounts.is_some(),
+            "Reward campaign by this key doesn't exists"
+        );
+
+        let batch = accounts.map_or(vec![], |accounts| {
+            accounts
+                .as_vector()
+                .iter()
+                .take(limit.unwrap_or(REWARDS_REMOVAL_LIMIT) as usize)
+                .collect()
+        });
+        for account_id in batch {
+            self.remove_reward(&account_id, &campaign_id);
+        }
+
+        self.campaign_reward_accounts
+            .get(&campaign_id)
+            .map_or(0, |accounts| accounts.len())
+    }
+
+    /// Transfers the tokens of the finished campaign which aren't owed to the accounts,
+    /// i.e. undistributed ones and the unlock penalties, to the receiver
+    pub fn sweep_reward_campaign(
+        &mut self,
+        campaign_id: String,
+        token: AccountId,
+        receiver_id: AccountId,
+    ) -> Promise {
+        self.roles.assert_role(Role::RewardManager);
+        self.assert_rewards_migrated();
+        let mut campaign = self.update_reward_campaign(campaign_id.clone());
+        require!(
+            campaign.end_time <= self.get_timestamp_in_seconds(),
+            "Campaign is not finished yet"
+        );
+
+        let reward_token = campaign
+            .tokens
+            .iter_mut()
+            .find(|reward_token| reward_token.token == token)
+            .expect("Token is not paid by the campaign");
+        let amount = reward_token.get_sweepable_amount();
+        require!(amount > 0, "There are no tokens to sweep");
+        reward_token.ledger.remaining = WBalance::from(reward_token.ledger.remaining.0 - amount);
+        self.reward_campaigns.insert(&campaign_id, &campaign);
+
+        underlying_token::ft_transfer(
+            receiver_id.clone(),
+            self.to_decimals_token(WBalance::from(amount)),
+            Some(format!("Sweep of the reward campaign {}", campaign_id)),
+            token.clone(),
+            ONE_YOCTO,
+            self.terra_gas(10),
+        )
+        .then(ext_self::sweep_reward_campaign_callback(
+            campaign_id,
+            token,
+            receiver_id,
+            WBalance::from(amount),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(5),
+        ))
+    }
+
+    #[private]
+    pub fn sweep_reward_campaign_callback(
+        &mut self,
+        campaign_id: String,
+        token: AccountId,
+        receiver_id: AccountId,
+        amount: WBalance,
+    ) {
+        if is_promise_success() {
+            Events::RewardCampaignSweep {
+                campaign_id,
+                token,
+                receiver_id,
+                amount,
+            }
+            .emit();
+            return;
+        }
+
+        // the swept tokens are returned to the campaign if the transfer failed
+        if let Some(mut campaign) = self.get_reward_campaign_by_id(campaign_id.clone()) {
+            if let Some(reward_token) = campaign
+                .tokens
+                .iter_mut()
+                .find(|reward_token| reward_token.token == token)
+            {
+                reward_token.ledger.remaining =
+                    WBalance::from(reward_token.ledger.remaining.0 + amount.0);
+                self.reward_campaigns.insert(&campaign_id, &campaign);
+            }
+        }
+    }
+
+    pub fn get_boost_token(&self) -> Option<AccountId> {
+        self.boost_token.clone()
+    }
+
+    /// Sets the governance token contract the tokens are locked in to boost the rewards
+    pub fn set_boost_token(&mut self, boost_token: Option<AccountId>) {
+        self.roles.assert_role(Role::RewardManager);
+        Events::parameter_change("boost_token", &boost_token).emit();
+        self.boost_token = boost_token;
+    }
+
+    pub fn get_voting_power_share(&self, account_id: AccountId) -> WRatio {
+        WRatio::from(
+            self.voting_power_shares
+                .get(&account_id)
+                .unwrap_or_else(Ratio::zero),
+        )
+    }
+
+    /// Syncs the voting power of the account with the governance token contract and refreshes
+    /// its boosted shares, could be called by anyone e.g. once the lock of the account expires
+    pub fn update_boost(&mut self, account_id: AccountId) -> Promise {
+        let boost_token = self
+            .boost_token
+            .clone()
+            .expect("Boost token is not set for the market");
+        self.assert_storage_covered(&account_id);
+
+        voting_escrow::get_voting_power(
+            account_id.clone(),
+            boost_token,
+            NO_DEPOSIT,
+            self.terra_gas(5),
         )
-        .then(ext_self::claim_reward_ft_transfer_callback(
-            reward,
+        .then(ext_self::update_boost_callback(
             account_id,
-            claimed_amount,
-            unlocked_amount,
             env::current_account_id(),
             NO_DEPOSIT,
-            self.terra_gas(5),
-        ));
+            self.terra_gas(50),
+        ))
     }
-}
 
-#[near_bindgen]
-impl Contract {
-    pub fn add_reward_campaign(&mut self, reward_campaign: RewardCampaign) -> String {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
-        require!(
-            reward_campaign.end_time >= self.get_timestamp_in_seconds(),
-            "Campaign end time can't be in the past"
-        );
-        let campaign_id = self.request_unique_id();
-        self.reward_campaigns.insert(&campaign_id, &reward_campaign);
-        campaign_id
-    }
+    #[private]
+    pub fn update_boost_callback(&mut self, account_id: AccountId) -> WRatio {
+        let voting_power: VotingPower = promise_result_as_success()
+            .and_then(|result| near_sdk::serde_json::from_slice(&result).ok())
+            .expect("Voting power is not available");
+
+        let voting_power_share = match voting_power.total.0 {
+            0 => Ratio::zero(),
+            _ => Ratio::from(voting_power.account.0) / Ratio::from(voting_power.total.0),
+        };
+        if voting_power_share == Ratio::zero() {
+            self.voting_power_shares.remove(&account_id);
+        } else {
+            self.voting_power_shares
+                .insert(&account_id, &voting_power_share);
+        }
 
-    pub fn get_all_rewards_by_campaign_id(
-        &self,
-        campaign_id: String,
-    ) -> HashMap<AccountId, Reward> {
-        let mut result: HashMap<AccountId, Reward> = HashMap::new();
-        self.rewards.iter().for_each(|(account_id, rewards)| {
-            if let Some(reward) = rewards.get(&campaign_id) {
-                result.insert(account_id.clone(), reward.clone());
-            }
-        });
-        result
+        self.adjust_account_rewards_by_campaign_type(account_id.clone(), CampaignType::Supply);
+        self.adjust_account_rewards_by_campaign_type(account_id, CampaignType::Borrow);
+        WRatio::from(voting_power_share)
     }
 
-    pub fn remove_rewards_entries_by_campaign_id(&mut self, campaign_id: String) {
-        self.get_all_rewards_by_campaign_id(campaign_id)
-            .iter()
-            .for_each(|(account_id, reward)| {
-                self.rewards
-                    .get_mut(account_id)
-                    .unwrap()
-                    .remove(&reward.campaign_id);
-            });
-    }
+    /// Claims the vested rewards of all campaigns of the caller with a single transfer per token,
+    /// returns the amounts transferred by the tokens. Prepaid gas grows with the amount of tokens
+    pub fn claim_all_rewards(&mut self) -> HashMap<AccountId, WBalance> {
+        let account_id = env::signer_account_id();
+        let campaign_ids = self
+            .account_reward_campaigns
+            .get(&account_id)
+            .unwrap_or_default();
+
+        let mut claims: HashMap<AccountId, Vec<(String, WBalance)>> = HashMap::new();
+        for campaign_id in campaign_ids {
+            let campaign = match self.get_reward_campaign_by_id(campaign_id.clone()) {
+                Some(campaign) => campaign,
+                None => continue,
+            };
+            let reward = self.adjust_reward(campaign_id.clone());
+            for reward_token in campaign.tokens {
+                let amount = self.get_amount_available_to_claim(&reward, &reward_token.token);
+                if amount > 0 {
+                    claims
+                        .entry(reward_token.token)
+                        .or_default()
+                        .push((campaign_id.clone(), WBalance::from(amount)));
+                }
+            }
+        }
 
-    pub fn remove_reward_campaign(&mut self, campaign_id: String) {
-        require!(
-            self.is_valid_admin_call(),
-            "This functionality is allowed to be called by admin or contract only"
-        );
         require!(
-            self.get_reward_campaign_by_id(campaign_id.clone())
-                .is_some(),
-            "Reward campaign by this key doesn't exists"
+            env::prepaid_gas() >= self.terra_gas(CLAIM_ALL_REWARDS_GAS + 20 * claims.len() as u64),
+            format!(
+                "Prepaid gas is not enough to claim rewards in {} tokens",
+                claims.len()
+            )
         );
-        self.remove_rewards_entries_by_campaign_id(campaign_id.clone());
-        self.reward_campaigns.remove(&campaign_id);
+
+        let mut claimed = HashMap::new();
+        for (token, token_claims) in claims {
+            let amount: Balance = token_claims.iter().map(|(_, amount)| amount.0).sum();
+            for (campaign_id, claim_amount) in token_claims.iter() {
+                self.book_reward_claim(campaign_id, &token, &account_id, claim_amount.0);
+            }
+            underlying_token::ft_transfer(
+                account_id.clone(),
+                self.to_decimals_token(WBalance::from(amount)),
+                Some(format!(
+                    "Claim rewards of {} campaigns with token_amount {}",
+                    token_claims.len(),
+                    amount
+                )),
+                token.clone(),
+                ONE_YOCTO,
+                self.terra_gas(10),
+            )
+            .then(ext_self::claim_all_rewards_ft_transfer_callback(
+                token.clone(),
+                account_id.clone(),
+                token_claims,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                self.terra_gas(10),
+            ));
+            claimed.insert(token, WBalance::from(amount));
+        }
+        claimed
     }
 
-    pub fn claim_rewards(&mut self, campaign_id: String, amount: WBalance) {
+    pub fn claim_rewards(&mut self, campaign_id: String, token: AccountId, amount: WBalance) {
         let account_id = env::signer_account_id();
         let reward = self.adjust_reward(campaign_id.clone());
-        let available_amount = self.get_amount_available_to_claim(reward.clone());
-        let campaign = self.get_reward_campaign_by_id(campaign_id).unwrap();
+        let available_amount = self.get_amount_available_to_claim(&reward, &token);
+        let campaign = self.get_reward_campaign_by_id(campaign_id.clone()).unwrap();
         assert!(
             self.get_timestamp_in_seconds() > campaign.vesting.start_time,
             "No rewards amount available to claim, because vesting is not started"
@@ -485,19 +1332,20 @@ impl Contract {
             account_id,
             amount,
             message,
-            campaign.token,
+            token,
             amount,
             WBalance::from(0),
-            reward,
+            campaign_id,
         )
     }
 
-    pub fn unlock_rewards(&mut self, campaign_id: String, amount: WBalance) {
+    pub fn unlock_rewards(&mut self, campaign_id: String, token: AccountId, amount: WBalance) {
         let account_id = env::signer_account_id();
         let reward = self.adjust_reward(campaign_id.clone());
-        let available_to_claim_amount = self.get_amount_available_to_claim(reward.clone());
-        let available_to_unlock_amount = reward.amount.0 - available_to_claim_amount;
-        let campaign = self.get_reward_campaign_by_id(campaign_id).unwrap();
+        let available_to_claim_amount = self.get_amount_available_to_claim(&reward, &token);
+        let available_to_unlock_amount =
+            reward.get_token_reward(&token).amount.0 - available_to_claim_amount;
+        let campaign = self.get_reward_campaign_by_id(campaign_id.clone()).unwrap();
         assert!(
             self.get_timestamp_in_seconds() > campaign.vesting.start_time,
             "No unlock amount available to claim, because vesting is not started"
@@ -507,8 +1355,9 @@ impl Contract {
             "{}", "There are not enough amount to unlock. Possible amount is {available_to_unlock_amount}"
         );
 
-        let amount_with_penalty =
-            WBalance::from(BigBalance::from(amount) * campaign.vesting.penalty);
+        let amount_with_penalty = WBalance::from(
+            amount.0 - (BigBalance::from(amount) * campaign.vesting.penalty).round_u128(),
+        );
         let message = format!(
             "Unlock rewards with amount {} and amount_with_penalty {}",
             amount.0, amount_with_penalty.0
@@ -517,48 +1366,70 @@ impl Contract {
             account_id,
             amount_with_penalty,
             message,
-            campaign.token,
+            token,
             amount,
             amount_with_penalty,
-            reward,
+            campaign_id,
         )
     }
 
     #[private]
     pub fn claim_reward_ft_transfer_callback(
         &mut self,
-        reward: Reward,
+        campaign_id: String,
+        token: AccountId,
         account_id: AccountId,
         amount: WBalance,
         unlocked: WBalance,
     ) {
         assert!(is_promise_success(), "Claim operation wasn't successful");
-        self.update_reward_in_state(
-            account_id,
-            Reward {
-                campaign_id: reward.campaign_id,
-                amount: reward.amount,
-                rewards_per_token_paid: reward.rewards_per_token_paid,
-                claimed: WBalance::from(reward.claimed.0 + amount.0),
-                unlocked: WBalance::from(reward.unlocked.0 + unlocked.0),
-            },
-        );
+        self.apply_reward_claim(campaign_id, token, account_id, amount, unlocked);
+    }
+
+    /// Claims are booked before the transfer, the ones of the failed transfer are reverted
+    #[private]
+    pub fn claim_all_rewards_ft_transfer_callback(
+        &mut self,
+        token: AccountId,
+        account_id: AccountId,
+        claims: Vec<(String, WBalance)>,
+    ) {
+        let is_transferred = is_promise_success();
+        for (campaign_id, amount) in claims {
+            if !is_transferred {
+                self.revert_reward_claim(&campaign_id, &token, &account_id, amount.0);
+                continue;
+            }
+            Events::RewardClaim {
+                account_id: account_id.clone(),
+                campaign_id,
+                token: token.clone(),
+                amount,
+            }
+            .emit();
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::rewards::{CampaignType, Vesting};
+    use crate::rewards::{
+        Boost, CampaignType, PenaltyDestination, RewardLedger, RewardToken, Vesting, VotingPower,
+    };
     use crate::{Config, Contract, Reward};
     use crate::{InterestRateModel, RewardCampaign};
     use general::ratio::{BigBalance, Ratio};
     use near_sdk::json_types::U128;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
     use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::{env, testing_env, AccountId, Balance, BlockHeight, VMContext};
+    use near_sdk::{
+        env, serde_json, testing_env, AccountId, Balance, BlockHeight, PromiseResult,
+        RuntimeFeesConfig, VMConfig, VMContext,
+    };
 
-    use general::{WBalance, ONE_TOKEN};
+    use general::{WBalance, WRatio, ONE_TOKEN};
     use std::convert::TryFrom;
+    use std::str::FromStr;
 
     const REWARD_AMOUNT: Balance = 100000 * 10u128.pow(0);
 
@@ -612,24 +1483,41 @@ mod tests {
         get_custom_context(is_view, 0, 100)
     }
 
+    /// Ledger of the campaign token funded in advance
+    fn get_ledger(funded: Balance) -> RewardLedger {
+        RewardLedger {
+            funded: U128(funded),
+            remaining: U128(funded),
+            ..RewardLedger::default()
+        }
+    }
+
     fn get_campaign() -> RewardCampaign {
         let vesting = Vesting {
             start_time: 1651362400,
             end_time: 1651372400,
-            penalty: Ratio::from(5000u128),
+            penalty: Ratio::from_str("0.5").unwrap(),
+            penalty_destination: PenaltyDestination::Burn,
         };
 
         RewardCampaign {
             campaign_type: CampaignType::Supply,
             start_time: 1651352400,
             end_time: 1651362400,
-            token: carol(),
-            ticker_id: "CAROL".to_string(),
-            reward_amount: U128(REWARD_AMOUNT),
+            tokens: vec![RewardToken {
+                token: carol(),
+                ticker_id: "CAROL".to_string(),
+                reward_amount: U128(REWARD_AMOUNT),
+                distributed_amount: U128(0),
+                rewards_per_token: BigBalance::zero(),
+                penalty_per_token: BigBalance::zero(),
+                ledger: get_ledger(REWARD_AMOUNT),
+            }],
             last_update_time: 0,
-            rewards_per_token: BigBalance::zero(),
             last_market_total: U128(0),
             vesting,
+            boost: None,
+            boosted_shares: U128(0),
         }
     }
 
@@ -663,9 +1551,10 @@ mod tests {
             rewards_list_on_half_way
                 .get(campaign_id1.as_str())
                 .unwrap()
+                .get_token_reward(&carol())
                 .amount
                 .0,
-            campaign1.reward_amount.0 / 2,
+            REWARD_AMOUNT / 2,
             "Rewards amount should be half of full campaign rewards"
         );
 
@@ -691,11 +1580,12 @@ mod tests {
             "Rewards list should be consist of 2 rewards"
         );
 
-        let half_reward = campaign1.reward_amount.0 / 2;
+        let half_reward = REWARD_AMOUNT / 2;
         assert_eq!(
             rewards_list_on_finish_alice
                 .get(campaign_id1.as_str())
                 .unwrap()
+                .get_token_reward(&carol())
                 .amount
                 .0,
             half_reward + half_reward / 4,
@@ -712,6 +1602,7 @@ mod tests {
             rewards_list_on_finish_bob
                 .get(campaign_id1.as_str())
                 .unwrap()
+                .get_token_reward(&carol())
                 .amount
                 .0,
             half_reward * 3 / 4,
@@ -733,6 +1624,7 @@ mod tests {
             rewards_list_on_finish_alice
                 .get(campaign_id1.as_str())
                 .unwrap()
+                .get_token_reward(&carol())
                 .amount
                 .0,
             half_reward + half_reward / 4,
@@ -743,6 +1635,7 @@ mod tests {
             rewards_list_on_finish_bob
                 .get(campaign_id1.as_str())
                 .unwrap()
+                .get_token_reward(&carol())
                 .amount
                 .0,
             half_reward * 3 / 4,
@@ -765,7 +1658,7 @@ mod tests {
         let reward = contract.adjust_reward(campaign_id);
 
         println!("{reward}");
-        let amount_available_to_claim = contract.get_amount_available_to_claim(reward.clone());
+        let amount_available_to_claim = contract.get_amount_available_to_claim(&reward, &carol());
         assert_eq!(
             amount_available_to_claim, 0,
             "Amount for claim doesn't match to expected"
@@ -774,26 +1667,28 @@ mod tests {
         let context1 = get_custom_context(false, 1651367400000000000, 1);
         testing_env!(context1);
 
-        let amount_available_to_claim1 = contract.get_amount_available_to_claim(reward.clone());
+        let amount_available_to_claim1 = contract.get_amount_available_to_claim(&reward, &carol());
         assert_eq!(
             amount_available_to_claim1,
-            reward.amount.0 / 2,
+            reward.get_token_reward(&carol()).amount.0 / 2,
             "Amount for claim doesn't match to expected"
         );
 
         let context2 = get_custom_context(false, 1651372400000000000, 1);
         testing_env!(context2);
-        let amount_available_to_claim2 = contract.get_amount_available_to_claim(reward.clone());
+        let amount_available_to_claim2 = contract.get_amount_available_to_claim(&reward, &carol());
         assert_eq!(
-            amount_available_to_claim2, reward.amount.0,
+            amount_available_to_claim2,
+            reward.get_token_reward(&carol()).amount.0,
             "Amount for claim doesn't match to expected"
         );
 
         let context3 = get_custom_context(false, 1651375400000000000, 1);
         testing_env!(context3);
-        let amount_available_to_claim3 = contract.get_amount_available_to_claim(reward.clone());
+        let amount_available_to_claim3 = contract.get_amount_available_to_claim(&reward, &carol());
         assert_eq!(
-            amount_available_to_claim3, reward.amount.0,
+            amount_available_to_claim3,
+            reward.get_token_reward(&carol()).amount.0,
             "Amount for claim doesn't match to expected"
         );
     }
@@ -828,13 +1723,29 @@ mod tests {
             "Rewards list length doesn't match to expected"
         );
         assert_ne!(
-            result1.get(&campaign_id).unwrap().amount,
-            result2.get(&campaign_id).unwrap().amount,
+            result1
+                .get(&campaign_id)
+                .unwrap()
+                .get_token_reward(&carol())
+                .amount,
+            result2
+                .get(&campaign_id)
+                .unwrap()
+                .get_token_reward(&carol())
+                .amount,
             "Amounts are shouldn't be equal"
         );
         assert_eq!(
-            result1.get(&campaign_id).unwrap().rewards_per_token_paid,
-            result2.get(&campaign_id).unwrap().rewards_per_token_paid,
+            result1
+                .get(&campaign_id)
+                .unwrap()
+                .get_token_reward(&carol())
+                .rewards_per_token_paid,
+            result2
+                .get(&campaign_id)
+                .unwrap()
+                .get_token_reward(&carol())
+                .rewards_per_token_paid,
             "Rewards per token paid are should be similar"
         )
     }
@@ -844,7 +1755,7 @@ mod tests {
         let account_id = AccountId::try_from(alice().to_string()).unwrap();
         let mut contract = init_env();
         let mut campaign = get_campaign();
-        campaign.rewards_per_token = BigBalance::from(10 * ONE_TOKEN);
+        campaign.tokens[0].rewards_per_token = BigBalance::from(10 * ONE_TOKEN);
         let context = get_context(false);
         testing_env!(context);
         campaign.campaign_type = CampaignType::Borrow;
@@ -853,10 +1764,12 @@ mod tests {
         let reward = Reward::new(campaign_id);
         contract.increase_borrows(account_id.clone(), WBalance::from(100));
 
-        let result = contract.get_updated_reward_amount(&reward, account_id);
+        let campaign = contract.get_reward_campaign_by_id(reward.campaign_id.clone());
+        let result = contract.get_updated_reward(&campaign.unwrap(), &reward, account_id);
 
         assert_eq!(
-            result.0, 1000,
+            result.get_token_reward(&carol()).amount.0,
+            1000,
             "Reward amount doesn't match to expected value"
         );
     }
@@ -868,7 +1781,9 @@ mod tests {
         let context = get_context(false);
         testing_env!(context);
         let campaign_id = contract.add_reward_campaign(campaign);
-        let campaign_result = contract.get_accrued_rewards_per_token(campaign_id);
+        let campaign = contract.get_reward_campaign_by_id(campaign_id).unwrap();
+        let campaign_result =
+            contract.get_accrued_reward_campaign(&campaign).tokens[0].rewards_per_token;
 
         assert_eq!(
             BigBalance::zero(),
@@ -886,11 +1801,13 @@ mod tests {
         testing_env!(context);
         contract.mint(contract.get_signer_address(), WBalance::from(total_supply));
 
-        let campaign_id = contract.add_reward_campaign(campaign.clone());
-        let campaign_result = contract.get_accrued_rewards_per_token(campaign_id);
+        let campaign_id = contract.add_reward_campaign(campaign);
+        let campaign = contract.get_reward_campaign_by_id(campaign_id).unwrap();
+        let campaign_result =
+            contract.get_accrued_reward_campaign(&campaign).tokens[0].rewards_per_token;
 
         assert_eq!(
-            (BigBalance::from(campaign.reward_amount.0) * BigBalance::from(ONE_TOKEN)
+            (BigBalance::from(REWARD_AMOUNT) * BigBalance::from(ONE_TOKEN)
                 / BigBalance::from(total_supply)),
             campaign_result,
             "Get accrued rewards value doesn't match"
@@ -902,7 +1819,7 @@ mod tests {
         let contract = init_env();
         let mut campaign = get_campaign();
         campaign.campaign_type = CampaignType::Supply;
-        let supply_total = contract.get_market_total(campaign.clone());
+        let supply_total = contract.get_market_total(&campaign);
         assert_eq!(
             supply_total.0,
             contract.get_total_supplies(),
@@ -910,7 +1827,7 @@ mod tests {
         );
 
         campaign.campaign_type = CampaignType::Borrow;
-        let borrow_total = contract.get_market_total(campaign);
+        let borrow_total = contract.get_market_total(&campaign);
         assert_eq!(
             borrow_total.0,
             contract.get_total_borrows(),
@@ -922,7 +1839,7 @@ mod tests {
     fn test_get_rewards_per_second() {
         let contract = init_env();
         let campaign = get_campaign();
-        let amount = contract.get_rewards_per_second(campaign.clone());
+        let amount = contract.get_rewards_per_second(&campaign)[&carol()];
 
         assert_eq!(
             REWARD_AMOUNT / Balance::from(campaign.end_time - campaign.start_time),
@@ -935,8 +1852,8 @@ mod tests {
     fn test_get_reward_tokens_per_day() {
         let contract = init_env();
         let campaign = get_campaign();
-        let amount_per_second = contract.get_rewards_per_second(campaign.clone());
-        let amount = contract.get_reward_tokens_per_day(campaign);
+        let amount_per_second = contract.get_rewards_per_second(&campaign)[&carol()];
+        let amount = contract.get_reward_tokens_per_day(&campaign)[&carol()];
         assert_eq!(
             24 * 60 * 60 * amount_per_second.0,
             amount.0,
@@ -960,12 +1877,38 @@ mod tests {
             "Campaign with id {campaign_id} wasn't added"
         );
 
-        contract.remove_reward_campaign(campaign_id.clone());
+        for account_id in [alice(), bob(), carol()] {
+            let reward = contract.update_reward(campaign_id.clone(), account_id.clone());
+            contract.update_reward_in_state(account_id, reward);
+        }
+
+        assert_eq!(
+            contract.remove_reward_campaign(campaign_id.clone(), Some(2)),
+            1
+        );
         assert!(
-            contract.get_reward_campaign_by_id(campaign_id).is_none(),
+            contract
+                .get_reward_campaign_by_id(campaign_id.clone())
+                .is_none(),
             "{}",
             "Campaign with id {campaign_id} wasn't removed"
         );
+        assert!(contract.get_rewards_list(alice()).is_empty());
+        assert_eq!(
+            contract
+                .get_rewards_by_campaign_id(campaign_id.clone(), 0, 10)
+                .len(),
+            1
+        );
+
+        assert_eq!(
+            contract.remove_reward_campaign(campaign_id.clone(), None),
+            0
+        );
+        assert!(contract
+            .get_rewards_by_campaign_id(campaign_id, 0, 10)
+            .is_empty());
+        assert!(contract.get_account_rewards(&carol()).is_empty());
     }
 
     #[test]
@@ -985,13 +1928,13 @@ mod tests {
         let gotten_campaign = campaign_list.get(0).unwrap();
 
         assert_eq!(
-            gotten_campaign.rewards_per_day.0,
-            contract.get_reward_tokens_per_day(campaign.clone()).0,
+            gotten_campaign.rewards_per_day,
+            contract.get_reward_tokens_per_day(&campaign),
             "Values rewards_per_day don't match"
         );
         assert_eq!(
             gotten_campaign.market_total.0,
-            contract.get_market_total(campaign).0,
+            contract.get_market_total(&campaign).0,
             "Values rewards_per_day don't match"
         );
     }
@@ -1024,7 +1967,7 @@ mod tests {
             "Campaigns are not similar"
         );
         assert_eq!(
-            campaign.token, received_campaign_unwrapped.token,
+            campaign.tokens[0].token, received_campaign_unwrapped.tokens[0].token,
             "Campaigns are not similar"
         );
         assert_eq!(
@@ -1032,4 +1975,494 @@ mod tests {
             "Campaigns are not similar"
         );
     }
+
+    #[test]
+    fn test_multi_token_campaign_top_up_and_extend() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.tokens.push(RewardToken {
+            token: bob(),
+            ticker_id: "BOB".to_string(),
+            reward_amount: U128(REWARD_AMOUNT / 2),
+            distributed_amount: U128(0),
+            rewards_per_token: BigBalance::zero(),
+            penalty_per_token: BigBalance::zero(),
+            ledger: get_ledger(REWARD_AMOUNT / 2),
+        });
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id = contract.add_reward_campaign(campaign);
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.mint(alice(), WBalance::from(100000));
+
+        // half of the campaign is passed, the rest of the tokens is paid till the new end
+        testing_env!(get_custom_context(false, 1651357400000000000, 1));
+        contract.fund_reward_campaign(campaign_id.clone(), carol(), bob(), U128(REWARD_AMOUNT));
+        contract.top_up_reward_campaign(campaign_id.clone(), carol(), U128(REWARD_AMOUNT));
+        contract.extend_reward_campaign(campaign_id.clone(), 1651367400);
+
+        testing_env!(get_custom_context(false, 1651362400000000000, 1));
+        let rewards = contract.get_rewards_list(alice());
+        let reward = rewards.get(&campaign_id).unwrap();
+        assert_eq!(
+            reward.get_token_reward(&carol()).amount.0,
+            REWARD_AMOUNT / 2 + (REWARD_AMOUNT / 2 + REWARD_AMOUNT) / 2
+        );
+        assert_eq!(
+            reward.get_token_reward(&bob()).amount.0,
+            REWARD_AMOUNT / 4 + REWARD_AMOUNT / 8
+        );
+
+        testing_env!(get_custom_context(false, 1651367400000000000, 1));
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        let campaign = contract.get_reward_campaign_by_id(campaign_id).unwrap();
+        assert_eq!(campaign.tokens[0].distributed_amount.0, 2 * REWARD_AMOUNT);
+        assert_eq!(campaign.tokens[1].distributed_amount.0, REWARD_AMOUNT / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Campaign end time could be only moved forward")]
+    fn test_extend_reward_campaign_backward() {
+        let mut contract = init_env();
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id = contract.add_reward_campaign(get_campaign());
+
+        contract.extend_reward_campaign(campaign_id, 1651357400);
+    }
+
+    #[test]
+    fn test_boost_capped_by_max_share() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.boost = Some(Boost {
+            max_multiplier: Ratio::from_str("2.5").unwrap(),
+            max_share: Ratio::from_str("0.6").unwrap(),
+        });
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        contract.set_boost_token(Some(carol()));
+        let campaign_id = contract.add_reward_campaign(campaign);
+        for account_id in [alice(), bob()] {
+            contract.mint(account_id.clone(), WBalance::from(100000));
+            let reward = contract.update_reward(campaign_id.clone(), account_id.clone());
+            contract.update_reward_in_state(account_id, reward);
+        }
+
+        testing_env!(
+            get_custom_context(false, 1651352400000000000, 1),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                serde_json::to_vec(&VotingPower {
+                    account: U128(100),
+                    total: U128(100),
+                })
+                .unwrap()
+            )]
+        );
+        assert_eq!(
+            contract.update_boost_callback(alice()),
+            WRatio::from(Ratio::one())
+        );
+
+        // alice would get 1.5 extra shares of her supply, but she is capped by 60% of the total
+        let campaign = contract
+            .get_reward_campaign_by_id(campaign_id.clone())
+            .unwrap();
+        assert_eq!(campaign.boosted_shares, U128(20000));
+
+        testing_env!(get_custom_context(false, 1651357400000000000, 1));
+        let alice_reward = contract
+            .get_rewards_list(alice())
+            .remove(&campaign_id)
+            .unwrap();
+        let bob_reward = contract
+            .get_rewards_list(bob())
+            .remove(&campaign_id)
+            .unwrap();
+        assert_eq!(alice_reward.get_token_reward(&carol()).amount.0, 27273);
+        assert_eq!(bob_reward.get_token_reward(&carol()).amount.0, 22727);
+    }
+
+    #[test]
+    fn test_boost_removed_with_withdrawn_supplies() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.boost = Some(Boost {
+            max_multiplier: Ratio::from_str("2.5").unwrap(),
+            max_share: Ratio::from_str("0.6").unwrap(),
+        });
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        contract.set_boost_token(Some(carol()));
+        let campaign_id = contract.add_reward_campaign(campaign);
+        for account_id in [alice(), bob()] {
+            contract.mint(account_id.clone(), WBalance::from(100000));
+            let reward = contract.update_reward(campaign_id.clone(), account_id.clone());
+            contract.update_reward_in_state(account_id, reward);
+        }
+
+        testing_env!(
+            get_custom_context(false, 1651352400000000000, 1),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                serde_json::to_vec(&VotingPower {
+                    account: U128(100),
+                    total: U128(100),
+                })
+                .unwrap()
+            )]
+        );
+        contract.update_boost_callback(alice());
+
+        // alice withdraws all of her supplies in the middle of the campaign
+        testing_env!(get_custom_context(false, 1651354900000000000, 1));
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.burn(&alice(), WBalance::from(100000));
+        contract.update_campaigns_by_account_balance(alice(), CampaignType::Supply);
+
+        let campaign = contract
+            .get_reward_campaign_by_id(campaign_id.clone())
+            .unwrap();
+        assert_eq!(campaign.boosted_shares, U128(0));
+        assert_eq!(
+            contract
+                .get_reward(&alice(), &campaign_id)
+                .unwrap()
+                .boosted_shares,
+            U128(0)
+        );
+        let alice_amount = contract
+            .get_rewards_list(alice())
+            .remove(&campaign_id)
+            .unwrap()
+            .get_token_reward(&carol())
+            .amount
+            .0;
+        let bob_amount = contract
+            .get_rewards_list(bob())
+            .remove(&campaign_id)
+            .unwrap()
+            .get_token_reward(&carol())
+            .amount
+            .0;
+
+        // the rest of the campaign rewards goes to bob only
+        testing_env!(get_custom_context(false, 1651357400000000000, 1));
+        let alice_reward = contract
+            .get_rewards_list(alice())
+            .remove(&campaign_id)
+            .unwrap();
+        let bob_reward = contract
+            .get_rewards_list(bob())
+            .remove(&campaign_id)
+            .unwrap();
+        assert_eq!(
+            alice_reward.get_token_reward(&carol()).amount.0,
+            alice_amount
+        );
+        assert_eq!(
+            bob_reward.get_token_reward(&carol()).amount.0,
+            bob_amount + 25000
+        );
+    }
+
+    #[test]
+    fn test_campaign_distributes_rewards_once_funded() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.tokens[0].ledger = RewardLedger::default();
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id = contract.add_reward_campaign(campaign);
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.mint(alice(), WBalance::from(100000));
+        contract.fund_reward_campaign(campaign_id.clone(), carol(), bob(), U128(REWARD_AMOUNT / 2));
+
+        // rewards of the time before the full funding are paid over the rest of the campaign
+        testing_env!(get_custom_context(false, 1651357400000000000, 1));
+        contract.fund_reward_campaign(campaign_id.clone(), carol(), bob(), U128(REWARD_AMOUNT / 2));
+        let campaign = contract
+            .get_reward_campaign_by_id(campaign_id.clone())
+            .unwrap();
+        assert!(campaign.is_funded());
+        assert_eq!(campaign.tokens[0].distributed_amount, U128(0));
+        assert_eq!(campaign.tokens[0].ledger.remaining, U128(REWARD_AMOUNT));
+
+        testing_env!(get_custom_context(false, 1651362400000000000, 1));
+        let rewards = contract.get_rewards_list(alice());
+        assert_eq!(
+            rewards[&campaign_id].get_token_reward(&carol()).amount,
+            U128(REWARD_AMOUNT)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Top up should be funded first")]
+    fn test_top_up_reward_campaign_not_funded() {
+        let mut contract = init_env();
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id = contract.add_reward_campaign(get_campaign());
+
+        contract.top_up_reward_campaign(campaign_id, carol(), U128(1));
+    }
+
+    #[test]
+    fn test_sweep_reward_campaign() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.tokens[0].ledger = get_ledger(2 * REWARD_AMOUNT);
+        // penalty is left for the sweep as there are no other accounts to redistribute it to
+        campaign.vesting.penalty_destination = PenaltyDestination::Redistribution;
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id = contract.add_reward_campaign(campaign);
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.mint(alice(), WBalance::from(100000));
+
+        // the campaign is finished, a quarter of its rewards is unlocked with a half penalty
+        testing_env!(
+            get_custom_context(false, 1651362400000000000, 1),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.update_reward_campaign(campaign_id.clone());
+        contract.claim_reward_ft_transfer_callback(
+            campaign_id.clone(),
+            carol(),
+            alice(),
+            U128(REWARD_AMOUNT / 4),
+            U128(REWARD_AMOUNT / 8),
+        );
+
+        let campaign = contract
+            .get_reward_campaign_by_id(campaign_id.clone())
+            .unwrap();
+        let reward_token = &campaign.tokens[0];
+        assert_eq!(reward_token.ledger.claimed, U128(REWARD_AMOUNT / 8));
+        assert_eq!(reward_token.ledger.penalty, U128(REWARD_AMOUNT / 8));
+        assert_eq!(
+            reward_token.get_sweepable_amount(),
+            REWARD_AMOUNT + REWARD_AMOUNT / 8
+        );
+
+        contract.sweep_reward_campaign(campaign_id.clone(), carol(), bob());
+        let campaign = contract.get_reward_campaign_by_id(campaign_id).unwrap();
+        assert_eq!(campaign.tokens[0].get_sweepable_amount(), 0);
+        assert_eq!(
+            campaign.tokens[0].ledger.remaining,
+            U128(REWARD_AMOUNT * 3 / 4)
+        );
+    }
+
+    #[test]
+    fn test_claim_all_rewards() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.tokens.push(RewardToken {
+            token: bob(),
+            ticker_id: "BOB".to_string(),
+            reward_amount: U128(REWARD_AMOUNT / 2),
+            distributed_amount: U128(0),
+            rewards_per_token: BigBalance::zero(),
+            penalty_per_token: BigBalance::zero(),
+            ledger: get_ledger(REWARD_AMOUNT / 2),
+        });
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id1 = contract.add_reward_campaign(get_campaign());
+        let campaign_id2 = contract.add_reward_campaign(campaign);
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.mint(alice(), WBalance::from(100000));
+        contract.update_campaigns_market_total_by_type(CampaignType::Supply);
+
+        // rewards of both campaigns are vested
+        testing_env!(
+            get_custom_context(false, 1651372400000000000, 1),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        let claimed = contract.claim_all_rewards();
+        assert_eq!(claimed.len(), 2);
+        assert_eq!(claimed[&carol()], U128(2 * REWARD_AMOUNT));
+        assert_eq!(claimed[&bob()], U128(REWARD_AMOUNT / 2));
+
+        // claims are booked before the transfers are resolved, so they aren't paid twice
+        assert!(contract.claim_all_rewards().is_empty());
+        let campaign = contract
+            .get_reward_campaign_by_id(campaign_id2.clone())
+            .unwrap();
+        assert_eq!(campaign.tokens[1].ledger.claimed, U128(REWARD_AMOUNT / 2));
+        assert_eq!(campaign.tokens[1].ledger.remaining, U128(0));
+
+        contract.claim_all_rewards_ft_transfer_callback(
+            carol(),
+            alice(),
+            vec![
+                (campaign_id1.clone(), U128(REWARD_AMOUNT)),
+                (campaign_id2.clone(), U128(REWARD_AMOUNT)),
+            ],
+        );
+
+        // the failed transfer returns the claim back to the rewards
+        testing_env!(
+            get_custom_context(false, 1651372400000000000, 1),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.claim_all_rewards_ft_transfer_callback(
+            bob(),
+            alice(),
+            vec![(campaign_id2.clone(), U128(REWARD_AMOUNT / 2))],
+        );
+
+        let rewards = contract.get_rewards_list(alice());
+        for campaign_id in [campaign_id1, campaign_id2.clone()] {
+            assert_eq!(
+                rewards[&campaign_id].get_token_reward(&carol()).claimed,
+                U128(REWARD_AMOUNT)
+            );
+        }
+        assert_eq!(
+            rewards[&campaign_id2].get_token_reward(&bob()).claimed,
+            U128(0)
+        );
+        let campaign = contract.get_reward_campaign_by_id(campaign_id2).unwrap();
+        assert_eq!(campaign.tokens[1].ledger.claimed, U128(0));
+        assert_eq!(campaign.tokens[1].ledger.remaining, U128(REWARD_AMOUNT / 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Prepaid gas is not enough to claim rewards in 2 tokens")]
+    fn test_claim_all_rewards_not_enough_gas() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.tokens.push(RewardToken {
+            token: bob(),
+            ticker_id: "BOB".to_string(),
+            reward_amount: U128(REWARD_AMOUNT / 2),
+            distributed_amount: U128(0),
+            rewards_per_token: BigBalance::zero(),
+            penalty_per_token: BigBalance::zero(),
+            ledger: get_ledger(REWARD_AMOUNT / 2),
+        });
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        contract.add_reward_campaign(campaign);
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.mint(alice(), WBalance::from(100000));
+        contract.update_campaigns_market_total_by_type(CampaignType::Supply);
+
+        // the gas is enough for a single token only
+        let mut context = get_custom_context(false, 1651372400000000000, 1);
+        context.prepaid_gas = near_sdk::Gas::ONE_TERA * 30;
+        testing_env!(context);
+        contract.claim_all_rewards();
+    }
+
+    fn unlock_callback(contract: &mut Contract, campaign_id: String, token: AccountId) {
+        testing_env!(
+            get_custom_context(false, 1651362400000000000, 1),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.claim_reward_ft_transfer_callback(
+            campaign_id,
+            token,
+            alice(),
+            U128(REWARD_AMOUNT / 2),
+            U128(REWARD_AMOUNT / 4),
+        );
+    }
+
+    #[test]
+    fn test_unlock_penalty_redistribution() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.vesting.penalty_destination = PenaltyDestination::Redistribution;
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id = contract.add_reward_campaign(campaign);
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.adjust_account_rewards_by_campaign_type(bob(), CampaignType::Supply);
+        contract.mint(alice(), WBalance::from(100000));
+        contract.mint(bob(), WBalance::from(100000));
+        contract.update_campaigns_market_total_by_type(CampaignType::Supply);
+
+        // alice unlocks all of her rewards at the campaign end with a half penalty
+        unlock_callback(&mut contract, campaign_id.clone(), carol());
+
+        let alice_reward = contract
+            .get_rewards_list(alice())
+            .remove(&campaign_id)
+            .unwrap();
+        let alice_reward = alice_reward.get_token_reward(&carol());
+        assert_eq!(alice_reward.amount, U128(REWARD_AMOUNT / 2));
+        assert_eq!(alice_reward.claimed, U128(REWARD_AMOUNT / 2));
+        assert_eq!(alice_reward.penalty_received, U128(0));
+
+        let bob_reward = contract
+            .get_rewards_list(bob())
+            .remove(&campaign_id)
+            .unwrap();
+        let bob_reward = bob_reward.get_token_reward(&carol());
+        assert_eq!(
+            bob_reward.amount,
+            U128(REWARD_AMOUNT / 2 + REWARD_AMOUNT / 4)
+        );
+        assert_eq!(bob_reward.penalty_received, U128(REWARD_AMOUNT / 4));
+
+        let campaign = contract.get_reward_campaign_by_id(campaign_id).unwrap();
+        assert_eq!(campaign.tokens[0].ledger.penalty, U128(0));
+        assert_eq!(campaign.tokens[0].get_sweepable_amount(), 0);
+    }
+
+    #[test]
+    fn test_unlock_penalty_to_reserves() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.tokens[0].token = contract.get_underlying_contract_address();
+        campaign.vesting.penalty_destination = PenaltyDestination::Reserves;
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        let campaign_id = contract.add_reward_campaign(campaign);
+        contract.adjust_rewards_by_campaign_type(CampaignType::Supply);
+        contract.mint(alice(), WBalance::from(100000));
+        contract.update_campaigns_market_total_by_type(CampaignType::Supply);
+
+        let total_reserves = contract.get_total_reserves();
+        unlock_callback(&mut contract, campaign_id.clone(), bob());
+
+        assert_eq!(
+            contract.get_total_reserves(),
+            total_reserves + REWARD_AMOUNT / 4
+        );
+        let campaign = contract.get_reward_campaign_by_id(campaign_id).unwrap();
+        assert_eq!(campaign.tokens[0].ledger.penalty, U128(REWARD_AMOUNT / 4));
+        assert_eq!(campaign.tokens[0].ledger.remaining, U128(REWARD_AMOUNT / 2));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Penalties could be sent to the reserves by the campaigns paid in the underlying token only"
+    )]
+    fn test_penalty_to_reserves_in_other_token() {
+        let mut contract = init_env();
+        let mut campaign = get_campaign();
+        campaign.vesting.penalty_destination = PenaltyDestination::Reserves;
+
+        testing_env!(get_custom_context(false, 1651352400000000000, 1));
+        contract.add_reward_campaign(campaign);
+    }
 }
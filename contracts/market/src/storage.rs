@@ -0,0 +1,235 @@
+use crate::*;
+use general::storage::{
+    get_lookup_map_entry_usage, get_unordered_map_entry_usage, get_unordered_set_entry_usage,
+};
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::{assert_one_yocto, Promise, StorageUsage};
+
+/// Storage reserved for every registered account: user profile, dtokens balance and a few rewards
+pub const MIN_ACCOUNT_STORAGE_USAGE: StorageUsage = 1000;
+
+impl Contract {
+    /// Storage taken by the user profile, dtokens balance, rewards, voting power share
+    /// and liquidation deposit of the account
+    pub fn get_account_storage_usage(&self, account_id: &AccountId) -> StorageUsage {
+        let profile_usage = self.user_profiles.get(account_id).map_or(0, |profile| {
+            get_unordered_map_entry_usage(account_id, &profile)
+        });
+        let token_usage = self.token.accounts.get(account_id).map_or(0, |balance| {
+            get_lookup_map_entry_usage(account_id, &balance)
+        });
+        let rewards_usage = self
+            .account_reward_campaigns
+            .get(account_id)
+            .map_or(0, |campaigns| {
+                get_lookup_map_entry_usage(account_id, &campaigns)
+                    + campaigns
+                        .iter()
+                        .filter_map(|campaign_id| {
+                            self.get_reward(account_id, campaign_id).map(|reward| {
+                                get_lookup_map_entry_usage(
+                                    &(account_id.clone(), campaign_id.clone()),
+                                    &reward,
+                                ) + get_unordered_set_entry_usage(account_id)
+                            })
+                        })
+                        .sum::<StorageUsage>()
+            });
+        let voting_power_usage = self
+            .voting_power_shares
+            .get(account_id)
+            .map_or(0, |share| get_lookup_map_entry_usage(account_id, &share));
+        let liquidation_deposit_usage = self
+            .liquidation_deposits
+            .get(account_id)
+            .map_or(0, |deposit| {
+                get_lookup_map_entry_usage(account_id, &deposit)
+            });
+
+        profile_usage + token_usage + rewards_usage + voting_power_usage + liquidation_deposit_usage
+    }
+
+    /// Should be checked before the state of the account is written
+    pub fn assert_storage_covered(&self, account_id: &AccountId) {
+        self.storage_deposits
+            .assert_storage_covered(account_id, self.get_account_storage_usage(account_id));
+    }
+
+    /// Whether the account has no supplies, borrows, deposits and unclaimed rewards
+    pub fn is_account_empty(&self, account_id: &AccountId) -> bool {
+        self.get_account_supplies(account_id.clone()) == 0
+            && self.get_account_total_borrows(account_id.clone()) == 0
+            && self.get_liquidation_deposit(account_id.clone()).0 == 0
+            && self
+                .account_reward_campaigns
+                .get(account_id)
+                .unwrap_or_default()
+                .iter()
+                .all(|campaign_id| {
+                    self.reward_campaigns.get(campaign_id).is_none()
+                        || self
+                            .get_reward(account_id, campaign_id)
+                            .map_or(true, |reward| reward.is_settled())
+                })
+    }
+
+    fn remove_account_state(&mut self, account_id: &AccountId) {
+        self.user_profiles.remove(account_id);
+        self.token.accounts.remove(account_id);
+        let campaigns = self
+            .account_reward_campaigns
+            .get(account_id)
+            .unwrap_or_default();
+        for campaign_id in campaigns {
+            self.remove_reward(account_id, &campaign_id);
+        }
+        self.voting_power_shares.remove(account_id);
+        self.liquidation_deposits.remove(account_id);
+    }
+
+    /// Removes the state of the account and refunds its storage deposit once the account is empty
+    pub fn refund_storage_if_empty(&mut self, account_id: &AccountId) {
+        if !self.storage_deposits.is_registered(account_id) || !self.is_account_empty(account_id) {
+            return;
+        }
+
+        self.remove_account_state(account_id);
+        if let Some(deposit) = self.storage_deposits.unregister(account_id) {
+            Promise::new(account_id.clone()).transfer(deposit);
+        }
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let refund = self.storage_deposits.deposit(
+            &account_id,
+            env::attached_deposit(),
+            registration_only.unwrap_or(false),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let amount = self.storage_deposits.withdraw(
+            &account_id,
+            amount.map(|amount| amount.0),
+            self.get_account_storage_usage(&account_id),
+        );
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Force unregistration isn't supported as it would remove supplies and borrows of the account
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        if !self.storage_deposits.is_registered(&account_id) {
+            return false;
+        }
+        require!(
+            !force.unwrap_or(false),
+            "Force unregistration is not supported"
+        );
+        require!(
+            self.is_account_empty(&account_id),
+            "Account has supplies, borrows or unclaimed rewards in the market"
+        );
+
+        self.refund_storage_if_empty(&account_id);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        self.storage_deposits.get_bounds()
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get_balance(&account_id, self.get_account_storage_usage(&account_id))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn view_storage_usage(&self, account_id: AccountId) -> StorageUsage {
+        self.get_account_storage_usage(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use general::storage::get_storage_cost;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn init() -> Contract {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .signer_account_id(bob())
+            .predecessor_account_id(bob())
+            .attached_deposit(get_storage_cost(MIN_ACCOUNT_STORAGE_USAGE))
+            .build());
+
+        Contract::new(Config {
+            initial_exchange_rate: U128::from(Ratio::one()),
+            underlying_token_id: carol(),
+            underlying_token_decimals: 24,
+            owner_id: alice(),
+            controller_account_id: carol(),
+            interest_rate_model: InterestRateModel::default(),
+            disable_transfer_token: true,
+        })
+    }
+
+    #[test]
+    fn test_storage_refunded_for_empty_account() {
+        let mut contract = init();
+
+        contract.storage_deposit(None, None);
+        contract.mint(bob(), U128(100));
+        contract.increase_borrows(bob(), U128(10));
+        contract.assert_storage_covered(&bob());
+        assert!(contract.view_storage_usage(bob()) > 0);
+
+        contract.burn(&bob(), U128(100));
+        contract.refund_storage_if_empty(&bob());
+        assert!(contract.storage_balance_of(bob()).is_some());
+
+        contract.decrease_borrows(bob(), U128(10));
+        contract.refund_storage_if_empty(&bob());
+        assert!(contract.storage_balance_of(bob()).is_none());
+        assert_eq!(contract.view_storage_usage(bob()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not registered, storage deposit is required")]
+    fn test_supply_requires_storage_deposit() {
+        let mut contract = init();
+
+        contract.supply(U128(100));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::*;
-use general::ratio::{BigBalance, Ratio};
+use general::ratio::BigBalance;
 
 const GAS_FOR_SUPPLY: Gas = Gas(120_000_000_000_000);
 
@@ -9,6 +9,7 @@ impl Contract {
             env::prepaid_gas() >= GAS_FOR_SUPPLY,
             "Prepaid gas is not enough for supply flow"
         );
+        self.assert_storage_covered(&env::signer_account_id());
         self.mutex_account_lock(Actions::Supply, token_amount, self.terra_gas(120))
     }
 }
@@ -19,29 +20,31 @@ impl Contract {
         if !is_promise_success() {
             return PromiseOrValue::Value(self.to_decimals_token(token_amount));
         }
-        self.adjust_rewards_by_campaign_type(CampaignType::Supply);
 
+        self.accrue_interest();
         let balance_of = self.view_contract_balance();
 
+        if self
+            .get_supply_headroom(balance_of)
+            .map_or(false, |headroom| token_amount.0 > headroom)
+        {
+            Events::SupplyCapExceeded {
+                account_id: env::signer_account_id(),
+                amount: token_amount,
+            }
+            .emit();
+            self.mutex_account_unlock();
+            return PromiseOrValue::Value(self.to_decimals_token(token_amount));
+        }
+
+        self.adjust_rewards_by_campaign_type(CampaignType::Supply);
+
         let exchange_rate = self.get_exchange_rate(balance_of);
         let dtoken_amount = WBalance::from(
             (BigBalance::from(Balance::from(token_amount)) / exchange_rate).round_u128(),
         );
 
-        let interest_rate_model = self.config.get().unwrap().interest_rate_model;
-        let supply_rate: Ratio = self.get_supply_rate(
-            balance_of,
-            U128(self.get_total_borrows()),
-            U128(self.get_total_reserves()),
-            interest_rate_model.get_reserve_factor(),
-        );
-        let accrued_interest = self.get_accrued_supply_interest(env::signer_account_id());
-        let accrued_supply_interest = interest_rate_model.calculate_accrued_interest(
-            supply_rate,
-            self.get_account_supplies(env::signer_account_id()),
-            accrued_interest,
-        );
-        self.set_accrued_supply_interest(env::signer_account_id(), accrued_supply_interest);
+        self.update_account_supply_index(env::signer_account_id(), dtoken_amount);
 
         // Dtokens minting and adding them to the user account
         self.mint(self.get_signer_address(), dtoken_amount);
@@ -71,6 +74,21 @@ impl Contract {
         .into()
     }
 
+    /// Averages account supply index by existing and newly supplied dtokens
+    fn update_account_supply_index(&mut self, account: AccountId, dtoken_amount: WBalance) {
+        let existing_supplies = self.get_account_supplies(account.clone());
+        if existing_supplies + dtoken_amount.0 == 0 {
+            return;
+        }
+
+        let mut user = self.user_profiles.get(&account).unwrap_or_default();
+
+        user.supply_index = (BigBalance::from(existing_supplies) * user.supply_index
+            + BigBalance::from(dtoken_amount.0) * self.supply_index)
+            / BigBalance::from(existing_supplies + dtoken_amount.0);
+        self.user_profiles.insert(&account, &user);
+    }
+
     pub fn get_account_supplies(&self, account: AccountId) -> Balance {
         self.token.accounts.get(&account).unwrap_or_default()
     }
@@ -82,26 +100,119 @@ impl Contract {
         dtoken_amount: WBalance,
     ) -> PromiseOrValue<U128> {
         if !is_promise_success() {
-            log!(
-                "{}",
-                Events::SupplyFailedToIncreaseSupplyOnController(
-                    env::signer_account_id(),
-                    Balance::from(amount)
-                )
-            );
+            Events::SupplyFailedToIncreaseSupplyOnController {
+                account_id: env::signer_account_id(),
+                amount,
+            }
+            .emit();
             self.burn(&self.get_signer_address(), dtoken_amount);
 
             self.mutex_account_unlock();
             return PromiseOrValue::Value(self.to_decimals_token(amount));
         }
-        self.update_campaigns_market_total_by_type(CampaignType::Supply);
-        log!(
-            "{}",
-            Events::SupplySuccess(env::signer_account_id(), Balance::from(amount))
-        );
+        let health_factor = self.get_health_factor_result();
+        self.update_campaigns_by_account_balance(env::signer_account_id(), CampaignType::Supply);
         self.increase_contract_balance(amount);
 
+        Events::SupplySuccess {
+            account_id: env::signer_account_id(),
+            amount,
+            dtoken_amount,
+            state: self.get_action_state(
+                self.get_account_accrued_interest(&env::signer_account_id()),
+                health_factor,
+            ),
+        }
+        .emit();
+
         self.mutex_account_unlock();
         PromiseOrValue::Value(U128(0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Contract, InterestRateModel};
+    use general::events::parse_event;
+    use general::ratio::Ratio;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseOrValue, PromiseResult, RuntimeFeesConfig, VMConfig};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_supply_over_supply_cap() {
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(alice())
+                .signer_account_id(alice())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+
+        let mut contract = Contract::new(Config {
+            initial_exchange_rate: U128::from(Ratio::one()),
+            underlying_token_id: bob(),
+            underlying_token_decimals: 24,
+            owner_id: alice(),
+            controller_account_id: carol(),
+            interest_rate_model: InterestRateModel::default(),
+            disable_transfer_token: true,
+        });
+        contract.set_supply_cap(Some(U128(100)));
+
+        match contract.post_supply(U128(150)) {
+            PromiseOrValue::Value(refund) => assert_eq!(refund, U128(150)),
+            PromiseOrValue::Promise(_) => panic!("Supply over the cap should be refunded"),
+        }
+        assert_eq!(contract.get_account_supplies(alice()), 0);
+    }
+
+    #[test]
+    fn test_supply_success_event() {
+        let health_factor = U128::from(Ratio::from_str("1.5").unwrap());
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(alice())
+                .signer_account_id(alice())
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                serde_json::to_vec(&Some(health_factor)).unwrap()
+            )]
+        );
+
+        let mut contract = Contract::new(Config {
+            initial_exchange_rate: U128::from(Ratio::one()),
+            underlying_token_id: bob(),
+            underlying_token_decimals: 24,
+            owner_id: alice(),
+            controller_account_id: carol(),
+            interest_rate_model: InterestRateModel::default(),
+            disable_transfer_token: true,
+        });
+
+        contract.controller_increase_supplies_callback(U128(100), U128(100));
+
+        let event = parse_event(get_logs().last().unwrap()).unwrap();
+        assert_eq!(event.event, "supply_success");
+        assert_eq!(
+            event.data,
+            json!({
+                "account_id": alice(),
+                "amount": "100",
+                "dtoken_amount": "100",
+                "exchange_rate": U128::from(Ratio::one()),
+                "accrued_interest": "0",
+                "health_factor": health_factor
+            })
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::*;
+use general::timelock::Proposal;
+use near_sdk::serde_json::json;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum RiskParameterChange {
+    SetInterestRateModel(InterestRateModel),
+    GrantRole(Role, AccountId),
+    RevokeRole(Role, AccountId),
+    SetGuardian(AccountId),
+    SetTimelockDelay(BlockHeight),
+    SetSupplyCap(Option<WBalance>),
+    SetFixedRateConfig(Option<FixedRateConfig>),
+    SetFlashLoanReceiver {
+        receiver_id: AccountId,
+        is_allowed: bool,
+    },
+    SetFlashLoanFee(Option<WRatio>),
+}
+
+impl RiskParameterChange {
+    /// Role which is allowed to schedule the change
+    fn get_role(&self) -> Role {
+        match self {
+            RiskParameterChange::GrantRole(..)
+            | RiskParameterChange::RevokeRole(..)
+            | RiskParameterChange::SetGuardian(_)
+            | RiskParameterChange::SetTimelockDelay(_) => Role::Owner,
+            _ => Role::RiskManager,
+        }
+    }
+
+    fn get_event(&self) -> Events {
+        match self {
+            RiskParameterChange::SetInterestRateModel(interest_rate_model) => {
+                Events::parameter_change("interest_rate_model", interest_rate_model)
+            }
+            RiskParameterChange::GrantRole(role, account) => Events::parameter_change(
+                "grant_role",
+                json!({ "role": role, "account_id": account }),
+            ),
+            RiskParameterChange::RevokeRole(role, account) => Events::parameter_change(
+                "revoke_role",
+                json!({ "role": role, "account_id": account }),
+            ),
+            RiskParameterChange::SetGuardian(account) => {
+                Events::parameter_change("guardian", account)
+            }
+            RiskParameterChange::SetTimelockDelay(delay) => {
+                Events::parameter_change("timelock_delay", delay)
+            }
+            RiskParameterChange::SetSupplyCap(supply_cap) => {
+                Events::parameter_change("supply_cap", supply_cap)
+            }
+            RiskParameterChange::SetFixedRateConfig(fixed_rate_config) => {
+                Events::parameter_change("fixed_rate_config", fixed_rate_config)
+            }
+            RiskParameterChange::SetFlashLoanReceiver {
+                receiver_id,
+                is_allowed,
+            } => Events::parameter_change("flash_loan_receiver", (receiver_id, is_allowed)),
+            RiskParameterChange::SetFlashLoanFee(flash_loan_fee) => {
+                Events::parameter_change("flash_loan_fee", flash_loan_fee)
+            }
+        }
+    }
+}
+
+impl Contract {
+    /// Applies the change scheduled through the timelock or made instantly
+    pub(crate) fn apply_risk_parameter_change(&mut self, change: RiskParameterChange) {
+        let event = change.get_event();
+        match change {
+            RiskParameterChange::SetInterestRateModel(interest_rate_model) => {
+                self.apply_interest_rate_model(interest_rate_model)
+            }
+            RiskParameterChange::GrantRole(role, account) => self.roles.grant(role, account),
+            RiskParameterChange::RevokeRole(role, account) => self.roles.revoke(role, &account),
+            RiskParameterChange::SetGuardian(account) => self.timelock.guardian = account,
+            RiskParameterChange::SetTimelockDelay(delay) => self.timelock.delay = delay,
+            RiskParameterChange::SetSupplyCap(supply_cap) => {
+                self.supply_cap = supply_cap.map(Balance::from)
+            }
+            RiskParameterChange::SetFixedRateConfig(fixed_rate_config) => {
+                self.fixed_rate_config = fixed_rate_config
+            }
+            RiskParameterChange::SetFlashLoanReceiver {
+                receiver_id,
+                is_allowed,
+            } => {
+                if is_allowed {
+                    self.flash_loan_receivers.insert(&receiver_id);
+                } else {
+                    self.flash_loan_receivers.remove(&receiver_id);
+                }
+            }
+            RiskParameterChange::SetFlashLoanFee(flash_loan_fee) => {
+                self.flash_loan_fee = flash_loan_fee.map(Ratio::from)
+            }
+        }
+        event.emit();
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Queues the change which could be executed after the ETA, returns the proposal id
+    pub fn schedule_risk_parameter_change(
+        &mut self,
+        change: RiskParameterChange,
+        eta: BlockHeight,
+    ) -> u64 {
+        self.roles.assert_role(change.get_role());
+        if let RiskParameterChange::SetInterestRateModel(interest_rate_model) = &change {
+            interest_rate_model.assert_valid();
+        }
+
+        self.timelock.schedule(change, eta)
+    }
+
+    pub fn execute_risk_parameter_change(&mut self, proposal_id: u64) {
+        self.roles.assert_role(Role::Executor);
+
+        let change = self.timelock.take_executable(proposal_id);
+        self.apply_risk_parameter_change(change);
+    }
+
+    /// Cancels the queued change, could be called by the guardian only
+    pub fn cancel_risk_parameter_change(
+        &mut self,
+        proposal_id: u64,
+    ) -> Proposal<RiskParameterChange> {
+        self.timelock.cancel(proposal_id)
+    }
+
+    /// Changes are applied instantly while the timelock delay is zero
+    pub fn set_timelock_delay(&mut self, delay: BlockHeight) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetTimelockDelay(delay));
+    }
+
+    pub fn set_guardian(&mut self, account: AccountId) {
+        self.roles.assert_role(Role::Owner);
+        self.timelock.assert_instant_change();
+
+        self.apply_risk_parameter_change(RiskParameterChange::SetGuardian(account));
+    }
+
+    pub fn get_timelock_delay(&self) -> BlockHeight {
+        self.timelock.delay
+    }
+
+    pub fn get_guardian(&self) -> AccountId {
+        self.timelock.guardian.clone()
+    }
+
+    pub fn view_risk_parameter_changes(&self) -> Vec<Proposal<RiskParameterChange>> {
+        self.timelock.get_proposals()
+    }
+
+    pub fn view_risk_parameter_change(
+        &self,
+        proposal_id: u64,
+    ) -> Option<Proposal<RiskParameterChange>> {
+        self.timelock.get_proposal(proposal_id)
+    }
+}
@@ -1,5 +1,4 @@
 use crate::*;
-use near_sdk::PanicOnDefault;
 
 pub trait Upgradable {
     /// function to migrate state with or without new field.
@@ -20,24 +19,7 @@ impl Upgradable for Contract {
     #[init(ignore_state)]
     #[private]
     fn migrate() -> Self {
-        #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
-        /// Copy of the scheme before upgrade
-        struct PreviousStorageSchema {
-            // Define previous scheme here
-            /// Contract admin account (dtoken itself by default)
-            pub admin: AccountId,
-            // .. etc
-        }
-
-        // read and parse previos storage
-        let contract: PreviousStorageSchema =
-            env::state_read().expect("Contract is not initialized");
-
-        // initialize new storage from an old schema
-        Contract {
-            admin: contract.admin,
-            ..Contract::default()
-        }
+        Contract::migrate_state(StateVersion::read())
     }
 
     fn get_version(&self) -> String {
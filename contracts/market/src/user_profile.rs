@@ -1,29 +1,32 @@
 use crate::*;
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
-#[serde(crate = "near_sdk::serde")]
-pub struct AccruedInterest {
-    pub last_recalculation_block: BlockHeight,
-    pub accumulated_interest: Balance,
-}
-
-// Cannot derive Default as `last_recalculation_block` by default should be current block
-impl Default for AccruedInterest {
-    fn default() -> Self {
-        AccruedInterest {
-            last_recalculation_block: env::block_height(),
-            accumulated_interest: 0,
-        }
-    }
-}
-
-#[derive(Default, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct UserProfile {
+    /// Borrows principal at the moment of the last borrows update
     pub borrows: Balance,
 
-    pub borrow_interest: AccruedInterest,
-    pub supply_interest: AccruedInterest,
+    /// Market borrow index at the moment of the last borrows update
+    pub borrow_index: Ratio,
+
+    /// Market supply index at the moment of supply, averaged by the supplied dtokens
+    pub supply_index: Ratio,
 
     pub is_consistent: bool,
+
+    /// Fixed rate loan tracked separately from the variable rate borrows
+    pub fixed_rate_loan: Option<FixedRateLoan>,
+}
+
+// Cannot derive Default as indexes by default should be one
+impl Default for UserProfile {
+    fn default() -> Self {
+        UserProfile {
+            borrows: 0,
+            borrow_index: Ratio::one(),
+            supply_index: Ratio::one(),
+            is_consistent: false,
+            fixed_rate_loan: None,
+        }
+    }
 }
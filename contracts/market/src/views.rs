@@ -19,6 +19,14 @@ pub struct MarketData {
     pub exchange_rate_ratio: WRatio,
     pub interest_rate_ratio: WRatio,
     pub borrow_rate_ratio: WRatio,
+
+    /// Max amount of underlying tokens supplied to the market
+    pub supply_cap: Option<WBalance>,
+    /// Amount which still could be supplied before reaching the supply cap
+    pub supply_headroom: Option<WBalance>,
+
+    pub borrow_index: WRatio,
+    pub supply_index: WRatio,
 }
 
 #[near_bindgen]
@@ -40,9 +48,10 @@ impl Contract {
     }
 
     pub fn view_market_data(&self) -> MarketData {
+        let accrual = self.calculate_interest_accrual();
         let total_supplies = self.get_total_supplies();
-        let total_borrows = self.get_total_borrows();
-        let total_reserves = self.get_total_reserves();
+        let total_borrows = accrual.total_borrows + accrual.total_fixed_borrows;
+        let total_reserves = accrual.total_reserves;
 
         let contract_ft_balance = self.view_contract_balance();
 
@@ -75,6 +84,12 @@ impl Contract {
             exchange_rate_ratio: WRatio::from(exchange_rate),
             interest_rate_ratio: WRatio::from(interest_rate),
             borrow_rate_ratio: WRatio::from(borrow_rate),
+            supply_cap: self.get_supply_cap(),
+            supply_headroom: self
+                .get_supply_headroom(contract_ft_balance)
+                .map(WBalance::from),
+            borrow_index: WRatio::from(accrual.borrow_index),
+            supply_index: WRatio::from(accrual.supply_index),
         }
     }
 
@@ -98,7 +113,7 @@ impl Contract {
         self.get_rewards_list(user_id)
     }
 
-    pub fn view_reward_state(&self, user_id: AccountId, campaign_id: String) -> RewardState {
+    pub fn view_reward_state(&self, user_id: AccountId, campaign_id: String) -> Vec<RewardState> {
         self.get_reward_state(user_id, campaign_id)
     }
 }
@@ -193,6 +208,10 @@ mod tests {
             exchange_rate_ratio: U128::from(Ratio::one()),
             interest_rate_ratio: U128(0),
             borrow_rate_ratio: U128::from(Ratio::one()),
+            supply_cap: None,
+            supply_headroom: None,
+            borrow_index: U128::from(Ratio::one()),
+            supply_index: U128::from(Ratio::one()),
         };
 
         assert_eq!(
@@ -221,6 +240,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_view_market_data_supply_headroom() {
+        let mut contract = init_test_env(true);
+
+        contract.set_supply_cap(Some(U128(1000)));
+        contract.increase_contract_balance(U128(500));
+
+        let market_data = contract.view_market_data();
+
+        assert_eq!(market_data.supply_cap, Some(U128(1000)));
+        // 1000 - (500 + 0 - 200)
+        assert_eq!(market_data.supply_headroom, Some(U128(700)));
+    }
+
     #[test]
     fn test_view_withdraw_info() {
         let contract = init_test_env(false);
@@ -10,32 +10,13 @@ impl Contract {
         }
         self.adjust_rewards_by_campaign_type(CampaignType::Supply);
 
-        let balance_of = self.view_contract_balance();
-
-        let exchange_rate: Ratio = self.get_exchange_rate(balance_of);
-
-        let interest_rate_model = self.config.get().unwrap().interest_rate_model;
-
-        let supply_rate: Ratio = self.get_supply_rate(
-            balance_of,
-            U128(self.get_total_borrows()),
-            U128(self.get_total_reserves()),
-            interest_rate_model.get_reserve_factor(),
-        );
-
-        let accrued_supply_interest = interest_rate_model.calculate_accrued_interest(
-            supply_rate,
-            self.get_account_supplies(env::signer_account_id()),
-            self.get_accrued_supply_interest(env::signer_account_id()),
-        );
+        self.accrue_interest();
+        let exchange_rate: Ratio = self.get_exchange_rate(self.view_contract_balance());
 
+        // supply interest is included into the exchange rate
         let token_amount: Balance =
             (Ratio::from(Balance::from(dtoken_amount)) * exchange_rate).round_u128();
 
-        let whole_amount: Balance = token_amount + accrued_supply_interest.accumulated_interest;
-
-        self.set_accrued_supply_interest(env::signer_account_id(), accrued_supply_interest);
-
         controller::withdraw_supplies(
             env::signer_account_id(),
             self.get_contract_address(),
@@ -48,7 +29,6 @@ impl Contract {
             env::signer_account_id(),
             token_amount.into(),
             dtoken_amount,
-            whole_amount.into(),
             env::current_account_id(),
             NO_DEPOSIT,
             self.terra_gas(80),
@@ -86,17 +66,13 @@ impl Contract {
         user_account: AccountId,
         token_amount: WBalance,
         dtoken_amount: WBalance,
-        whole_amount: WBalance,
     ) -> PromiseOrValue<WBalance> {
         if !is_promise_success() {
-            log!(
-                "{}",
-                Events::WithdrawFailedToDecreaseSupplyOnController(
-                    env::signer_account_id(),
-                    Balance::from(dtoken_amount),
-                    self.get_contract_address()
-                )
-            );
+            Events::WithdrawFailedToDecreaseSupplyOnController {
+                account_id: env::signer_account_id(),
+                dtoken_amount,
+            }
+            .emit();
             self.mutex_account_unlock();
             return PromiseOrValue::Value(dtoken_amount);
         }
@@ -104,10 +80,10 @@ impl Contract {
         // Cross-contract call to market token
         underlying_token::ft_transfer(
             user_account,
-            self.to_decimals_token(whole_amount),
+            self.to_decimals_token(token_amount),
             Some(format!(
                 "Withdraw with token_amount {}",
-                Balance::from(whole_amount)
+                Balance::from(token_amount)
             )),
             self.get_underlying_contract_address(),
             ONE_YOCTO,
@@ -116,6 +92,7 @@ impl Contract {
         .then(ext_self::withdraw_ft_transfer_call_callback(
             token_amount,
             dtoken_amount,
+            self.get_health_factor_result(),
             env::current_account_id(),
             NO_DEPOSIT,
             self.terra_gas(30),
@@ -128,17 +105,28 @@ impl Contract {
         &mut self,
         token_amount: WBalance,
         dtoken_amount: WBalance,
+        health_factor: Option<WRatio>,
     ) -> PromiseOrValue<WBalance> {
         if is_promise_success() {
             self.burn(&env::signer_account_id(), dtoken_amount);
-            self.mutex_account_unlock();
-            log!(
-                "{}",
-                Events::WithdrawSuccess(env::signer_account_id(), Balance::from(dtoken_amount))
+            self.update_campaigns_by_account_balance(
+                env::signer_account_id(),
+                CampaignType::Supply,
             );
-            self.set_accrued_supply_interest(env::signer_account_id(), AccruedInterest::default());
-
-            self.decrease_contract_balance(dtoken_amount);
+            self.mutex_account_unlock();
+            self.decrease_contract_balance(token_amount);
+
+            Events::WithdrawSuccess {
+                account_id: env::signer_account_id(),
+                amount: token_amount,
+                dtoken_amount,
+                state: self.get_action_state(
+                    self.get_account_accrued_interest(&env::signer_account_id()),
+                    health_factor,
+                ),
+            }
+            .emit();
+            self.refund_storage_if_empty(&env::signer_account_id());
 
             PromiseOrValue::Value(dtoken_amount)
         } else {
@@ -167,21 +155,20 @@ impl Contract {
     ) -> PromiseOrValue<WBalance> {
         if !is_promise_success() {
             self.add_inconsistent_account(env::signer_account_id());
-            log!(
-                "{}",
-                Events::WithdrawFailedToFallback(
-                    env::signer_account_id(),
-                    Balance::from(token_amount)
-                )
-            );
+            Events::WithdrawFailedToFallback {
+                account_id: env::signer_account_id(),
+                amount: token_amount,
+            }
+            .emit();
             return PromiseOrValue::Value(token_amount);
         }
-        self.update_campaigns_market_total_by_type(CampaignType::Supply);
+        self.update_campaigns_by_account_balance(env::signer_account_id(), CampaignType::Supply);
         self.mutex_account_unlock();
-        log!(
-            "{}",
-            Events::WithdrawFallbackSuccess(env::signer_account_id(), Balance::from(token_amount))
-        );
+        Events::WithdrawFallbackSuccess {
+            account_id: env::signer_account_id(),
+            amount: token_amount,
+        }
+        .emit();
         PromiseOrValue::Value(token_amount)
     }
 }
@@ -1,5 +1,5 @@
 use crate::utils::{
-    add_market, borrow, initialize_controller, initialize_three_dtokens, initialize_three_utokens,
+    add_market, initialize_controller, initialize_three_dtokens, initialize_three_utokens,
     mint_and_reserve, mint_tokens, new_user, set_price, supply, view_balance,
 };
 use controller::ActionType::Borrow;
@@ -10,7 +10,8 @@ use near_sdk_sim::{call, init_simulator, view, ContractAccount, UserAccount};
 
 const WETH_AMOUNT: Balance = 5;
 const WNEAR_AMOUNT: Balance = 5;
-const BORROW_AMOUNT: Balance = 11;
+const BORROW_AMOUNT: Balance = 5;
+const DELEGATION_LIMIT: Balance = 8;
 const START_BALANCE: Balance = 1000;
 const START_PRICE: Balance = 10000;
 const RESERVE_AMOUNT: Balance = 1000;
@@ -20,10 +21,12 @@ fn borrow_fixture() -> (
     ContractAccount<controller::ContractContract>,
     ContractAccount<mock_token::ContractContract>,
     UserAccount,
+    UserAccount,
 ) {
     let root = init_simulator(None);
 
     let user = new_user(&root, "user".parse().unwrap());
+    let delegatee = new_user(&root, "delegatee".parse().unwrap());
     let (weth, wnear, wbtc) = initialize_three_utokens(&root);
     let controller = initialize_controller(&root);
     let (droot, weth_market, wnear_market, dwbtc) = initialize_three_dtokens(
@@ -44,6 +47,7 @@ fn borrow_fixture() -> (
     mint_tokens(&weth, user.account_id(), U128(WETH_AMOUNT));
     mint_tokens(&wnear, user.account_id(), U128(WNEAR_AMOUNT));
     mint_tokens(&wbtc, user.account_id(), U128(0));
+    mint_tokens(&wbtc, delegatee.account_id(), U128(0));
 
     add_market(
         &controller,
@@ -103,71 +107,86 @@ fn borrow_fixture() -> (
 
     supply(&user, &wnear, wnear_market.account_id(), WNEAR_AMOUNT).assert_success();
 
-    (dwbtc, controller, wbtc, user)
+    (dwbtc, controller, wbtc, user, delegatee)
 }
 
 #[test]
-fn scenario_undercollaterilazied_borrow() {
-    let (dwbtc, controller, wbtc, user) = borrow_fixture();
+fn scenario_delegated_borrow() {
+    let (dwbtc, controller, wbtc, user, delegatee) = borrow_fixture();
 
     call!(
-        dwbtc.user_account,
-        dwbtc.set_admin(user.account_id.clone()),
-        deposit = 0
-    );
-    call!(
-        controller.user_account,
-        controller.set_admin(user.account_id.clone()),
-        deposit = 0
-    );
-    call!(
-        dwbtc.user_account,
-        dwbtc.set_eligible_to_borrow_uncollateralized_account(user.account_id.clone()),
+        user,
+        controller.approve_credit_delegation(
+            delegatee.account_id(),
+            dwbtc.account_id(),
+            U128(DELEGATION_LIMIT),
+            1_000_000
+        ),
         deposit = 0
-    );
+    )
+    .assert_success();
+
     call!(
-        controller.user_account,
-        controller.set_eligible_to_borrow_uncollateralized_account(user.account_id.clone()),
+        delegatee,
+        dwbtc.delegated_borrow(user.account_id(), U128(BORROW_AMOUNT)),
         deposit = 0
-    );
-
-    let leverage = 2u128;
-
-    // borrowing 2 times more
-
-    borrow(&user, &dwbtc, BORROW_AMOUNT * leverage).assert_success();
+    )
+    .assert_success();
 
     let user_balance: Balance =
         view_balance(&controller, Borrow, user.account_id(), dwbtc.account_id());
+    assert_eq!(
+        user_balance, BORROW_AMOUNT,
+        "Delegator borrow balance on controller should be {}",
+        BORROW_AMOUNT
+    );
 
+    let delegatee_balance: Balance = view_balance(
+        &controller,
+        Borrow,
+        delegatee.account_id(),
+        dwbtc.account_id(),
+    );
     assert_eq!(
-        user_balance,
-        BORROW_AMOUNT * leverage,
-        "User borrow balance on controller should be {}",
-        BORROW_AMOUNT * leverage
+        delegatee_balance, 0,
+        "Delegatee borrow balance on controller should be 0"
     );
 
     let user_balance: Balance = view!(dwbtc.get_account_borrows(user.account_id())).unwrap_json();
     assert_eq!(
-        user_balance,
-        BORROW_AMOUNT * leverage,
-        "User borrow balance on dtoken should be {}",
-        BORROW_AMOUNT * leverage
+        user_balance, BORROW_AMOUNT,
+        "Delegator borrow balance on dtoken should be {}",
+        BORROW_AMOUNT
+    );
+
+    let delegatee_balance: U128 = view!(wbtc.ft_balance_of(delegatee.account_id())).unwrap_json();
+    assert_eq!(
+        delegatee_balance,
+        U128(BORROW_AMOUNT),
+        "Delegatee utoken balance should be {}",
+        BORROW_AMOUNT
     );
 
-    let user_balance: U128 = view!(wbtc.ft_balance_of(user.account_id())).unwrap_json();
+    // the rest of the delegated limit is not enough for one more borrow
+    call!(
+        delegatee,
+        dwbtc.delegated_borrow(user.account_id(), U128(BORROW_AMOUNT)),
+        deposit = 0
+    );
+
+    let user_balance: Balance =
+        view_balance(&controller, Borrow, user.account_id(), dwbtc.account_id());
     assert_eq!(
-        user_balance,
-        U128(BORROW_AMOUNT * leverage),
-        "User utoken balance should be {}",
-        BORROW_AMOUNT * leverage
+        user_balance, BORROW_AMOUNT,
+        "Delegator borrow balance on controller should be {}",
+        BORROW_AMOUNT
     );
 
     let dtoken_balance: U128 = view!(wbtc.ft_balance_of(dwbtc.account_id())).unwrap_json();
     assert_eq!(
         dtoken_balance,
-        U128(START_BALANCE - BORROW_AMOUNT * leverage),
+        U128(START_BALANCE - BORROW_AMOUNT),
         "Dtoken balance on utoken should be {}",
-        START_BALANCE - BORROW_AMOUNT * leverage
+        START_BALANCE - BORROW_AMOUNT
     );
 }
@@ -1,5 +1,5 @@
+mod borrow_delegated;
 mod borrow_more_than_allowed;
 mod borrow_success;
 mod borrow_success_after_failure;
-mod borrow_undercollateralized;
 mod borrow_zero_tokens;
@@ -0,0 +1,4 @@
+#[path = "upgrade/upgrade_migrate_state.rs"]
+mod upgrade_migrate_state;
+#[allow(dead_code)]
+mod utils;
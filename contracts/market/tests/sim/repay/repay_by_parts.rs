@@ -4,7 +4,7 @@ use crate::utils::{
 };
 use controller::ActionType::Borrow;
 use general::{ratio::Ratio, Price};
-use market::{InterestRateModel, WRatio};
+use market::{InterestRateModel, JumpRateModel, WRatio};
 use near_sdk::{json_types::U128, Balance};
 use near_sdk_sim::{init_simulator, view, ContractAccount, UserAccount};
 
@@ -28,13 +28,13 @@ fn repay_fixture() -> (
     let user = new_user(&root, "user".parse().unwrap());
     let (weth, wnear, wbtc) = initialize_three_utokens(&root);
     let controller = initialize_controller(&root);
-    let interest_rate_model = InterestRateModel {
+    let interest_rate_model = InterestRateModel::JumpRate(JumpRateModel {
         kink: WRatio::from(0),
         base_rate_per_block: WRatio::from(0),
         multiplier_per_block: WRatio::from(0),
         jump_multiplier_per_block: WRatio::from(0),
         reserve_factor: WRatio::from(0),
-    };
+    });
     let (droot, weth_market, wnear_market, dwbtc) = initialize_three_dtokens(
         &root,
         weth.account_id(),
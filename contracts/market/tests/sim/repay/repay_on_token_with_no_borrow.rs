@@ -3,7 +3,7 @@ use crate::utils::{
     mint_tokens, new_user, repay, set_price, supply,
 };
 use general::Price;
-use market::{InterestRateModel, WRatio};
+use market::{InterestRateModel, JumpRateModel, WRatio};
 use near_sdk::{json_types::U128, Balance};
 use near_sdk_sim::{init_simulator, view, ContractAccount, UserAccount};
 
@@ -24,13 +24,13 @@ fn repay_fixture() -> (
     let user = new_user(&root, "user".parse().unwrap());
     let (weth, wnear) = initialize_two_utokens(&root);
     let controller = initialize_controller(&root);
-    let interest_rate_model = InterestRateModel {
+    let interest_rate_model = InterestRateModel::JumpRate(JumpRateModel {
         kink: WRatio::from(0),
         base_rate_per_block: WRatio::from(0),
         multiplier_per_block: WRatio::from(0),
         jump_multiplier_per_block: WRatio::from(0),
         reserve_factor: WRatio::from(0),
-    };
+    });
     let (_, weth_market, wnear_market) = initialize_two_dtokens(
         &root,
         weth.account_id(),
@@ -1,2 +1,3 @@
 mod upgrade_check_state;
+mod upgrade_migrate_state;
 mod upgrade_without_field;
@@ -0,0 +1,245 @@
+// IMPORTANT! The previous wasm should keep the state layout of the oldest supported state version
+near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
+    DTOKEN_CURRENT_WASM_BYTES => "../target/wasm32-unknown-unknown/release/market.wasm",
+    DTOKEN_PREVIOUS_WASM_BYTES => "tests/sim/contracts/dtoken_v1.wasm",
+}
+
+use crate::utils::{
+    add_market, initialize_controller, initialize_utoken, mint_and_reserve, mint_tokens, new_user,
+    register_account, set_price, supply_registered, upgrade_dtoken, view_balance,
+};
+use controller::ActionType::Supply;
+use general::ratio::Ratio;
+use general::{MigrationProgress, Price};
+use market::ContractContract as Dtoken;
+use market::StateVersion;
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::{json, Value};
+use near_sdk::{AccountId, Balance};
+use near_sdk_sim::{call, deploy, init_simulator, to_yocto, view, ContractAccount, UserAccount};
+
+const RESERVE_AMOUNT: Balance = 1000;
+const START_BALANCE: Balance = 100;
+const SUPPLY_AMOUNTS: [Balance; 3] = [10, 20, 30];
+
+fn upgrade_fixture() -> (
+    ContractAccount<market::ContractContract>,
+    ContractAccount<controller::ContractContract>,
+    Vec<UserAccount>,
+) {
+    let root = init_simulator(None);
+
+    let controller = initialize_controller(&root);
+    let utoken = initialize_utoken(&root);
+    let droot = root.create_user("dtoken".parse().unwrap(), to_yocto("1200000"));
+
+    let dtoken = deploy!(
+        contract: Dtoken,
+        contract_id: AccountId::new_unchecked("dtoken_contract".to_string()),
+        bytes: &DTOKEN_PREVIOUS_WASM_BYTES,
+        signer_account: droot
+    );
+
+    // Config of the previous version has the jump rate model fields at the top level
+    dtoken
+        .user_account
+        .call(
+            dtoken.account_id(),
+            "new",
+            json!({
+                "config": {
+                    "initial_exchange_rate": U128::from(Ratio::one()),
+                    "underlying_token_id": utoken.account_id(),
+                    "underlying_token_decimals": 24,
+                    "owner_id": dtoken.account_id(),
+                    "controller_account_id": controller.account_id(),
+                    "interest_rate_model": {
+                        "kink": "0",
+                        "multiplier_per_block": "0",
+                        "base_rate_per_block": "0",
+                        "jump_multiplier_per_block": "0",
+                        "reserve_factor": "0"
+                    },
+                    "disable_transfer_token": true
+                }
+            })
+            .to_string()
+            .as_bytes(),
+            near_sdk_sim::DEFAULT_GAS,
+            0,
+        )
+        .assert_success();
+
+    add_market(
+        &controller,
+        utoken.account_id(),
+        dtoken.account_id(),
+        "weth".to_string(),
+    );
+    set_price(
+        &controller,
+        dtoken.account_id(),
+        &Price {
+            ticker_id: "weth".to_string(),
+            value: U128(10000),
+            volatility: U128(100),
+            fraction_digits: 4,
+        },
+    );
+
+    mint_and_reserve(&droot, &utoken, &dtoken, RESERVE_AMOUNT);
+
+    dtoken
+        .user_account
+        .call(
+            dtoken.account_id(),
+            "add_reward_campaign",
+            json!({
+                "reward_campaign": {
+                    "campaign_type": "Supply",
+                    "start_time": 0,
+                    "end_time": 4_000_000_000u64,
+                    "token": utoken.account_id(),
+                    "ticker_id": "weth",
+                    "reward_amount": "1000",
+                    "last_update_time": 0,
+                    "rewards_per_token": "0",
+                    "last_market_total": "0",
+                    "vesting": {
+                        "start_time": 0,
+                        "end_time": 4_000_000_000u64,
+                        "penalty": "0"
+                    }
+                }
+            })
+            .to_string()
+            .as_bytes(),
+            near_sdk_sim::DEFAULT_GAS,
+            0,
+        )
+        .assert_success();
+
+    let users = SUPPLY_AMOUNTS
+        .iter()
+        .enumerate()
+        .map(|(index, amount)| {
+            let user = new_user(&root, format!("user{}", index).parse().unwrap());
+            mint_tokens(&utoken, user.account_id(), U128(START_BALANCE));
+            // The previous version has no storage management, only the controller requires it
+            register_account(&user, controller.account_id());
+            supply_registered(&user, &utoken, dtoken.account_id(), *amount).assert_success();
+            user
+        })
+        .collect();
+
+    (dtoken, controller, users)
+}
+
+#[test]
+fn test_upgrade_migrate_state() {
+    let (dtoken, controller, users) = upgrade_fixture();
+
+    let old_total_supplies = view!(dtoken.view_total_supplies()).unwrap_json::<U128>();
+    let old_total_reserves = view!(dtoken.view_total_reserves()).unwrap_json::<U128>();
+    let old_contract_balance = view!(dtoken.view_contract_balance()).unwrap_json::<U128>();
+    let old_campaigns = view!(dtoken.view_reward_campaigns()).unwrap_json_value();
+    let old_profiles: Vec<(U128, Balance, Value)> = users
+        .iter()
+        .map(|user| {
+            (
+                view!(dtoken.ft_balance_of(user.account_id())).unwrap_json::<U128>(),
+                view_balance(&controller, Supply, user.account_id(), dtoken.account_id()),
+                view!(dtoken.view_rewards_list(user.account_id())).unwrap_json_value(),
+            )
+        })
+        .collect();
+
+    upgrade_dtoken(&dtoken, &DTOKEN_CURRENT_WASM_BYTES).assert_success();
+
+    assert_eq!(
+        view!(dtoken.get_state_version()).unwrap_json::<StateVersion>(),
+        market::CURRENT_STATE_VERSION
+    );
+
+    // rewards of the accounts are migrated in batches after the upgrade
+    if view!(dtoken.get_rewards_migration())
+        .unwrap_json::<Option<MigrationProgress>>()
+        .is_some()
+    {
+        let remaining_accounts: u64 =
+            call!(dtoken.user_account, dtoken.migrate_rewards(None)).unwrap_json();
+        assert_eq!(remaining_accounts, 0);
+    }
+
+    assert_eq!(
+        old_total_supplies,
+        view!(dtoken.view_total_supplies()).unwrap_json::<U128>()
+    );
+    assert_eq!(
+        old_total_reserves,
+        view!(dtoken.view_total_reserves()).unwrap_json::<U128>()
+    );
+    assert_eq!(
+        old_contract_balance,
+        view!(dtoken.view_contract_balance()).unwrap_json::<U128>()
+    );
+    // single token campaigns of the previous version are migrated to the list of reward tokens
+    let campaigns = view!(dtoken.view_reward_campaigns()).unwrap_json_value();
+    for (old, new) in old_campaigns
+        .as_array()
+        .unwrap()
+        .iter()
+        .zip(campaigns.as_array().unwrap())
+    {
+        let (old_campaign, new_campaign) = (&old["campaign"], &new["campaign"]);
+        assert_eq!(old["campaign_id"], new["campaign_id"]);
+        assert_eq!(old["market_total"], new["market_total"]);
+        for field in ["campaign_type", "start_time", "end_time"] {
+            assert_eq!(old_campaign[field], new_campaign[field]);
+        }
+        for field in ["start_time", "end_time", "penalty"] {
+            assert_eq!(
+                old_campaign["vesting"][field],
+                new_campaign["vesting"][field]
+            );
+        }
+        assert_eq!(new_campaign["vesting"]["penalty_destination"], "Burn");
+        for field in ["token", "ticker_id", "reward_amount", "rewards_per_token"] {
+            assert_eq!(old_campaign[field], new_campaign["tokens"][0][field]);
+        }
+        assert_eq!(
+            old["rewards_per_day"],
+            new["rewards_per_day"][old_campaign["token"].as_str().unwrap()]
+        );
+        assert_eq!(new_campaign["boost"], Value::Null);
+    }
+
+    for (user, (supplies, controller_supplies, rewards)) in users.iter().zip(old_profiles) {
+        assert_eq!(
+            supplies,
+            view!(dtoken.ft_balance_of(user.account_id())).unwrap_json::<U128>()
+        );
+        assert_eq!(
+            controller_supplies,
+            view_balance(&controller, Supply, user.account_id(), dtoken.account_id())
+        );
+        let new_rewards = view!(dtoken.view_rewards_list(user.account_id())).unwrap_json_value();
+        for (campaign_id, reward) in rewards.as_object().unwrap() {
+            let token = old_campaigns
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|campaign| &campaign["campaign_id"] == campaign_id)
+                .map(|campaign| campaign["campaign"]["token"].as_str().unwrap())
+                .unwrap();
+            let new_reward = &new_rewards[campaign_id]["tokens"][token];
+            for field in ["amount", "rewards_per_token_paid", "claimed", "unlocked"] {
+                assert_eq!(reward[field], new_reward[field]);
+            }
+        }
+        assert_eq!(
+            view!(dtoken.get_account_borrows(user.account_id())).unwrap_json::<Balance>(),
+            0
+        );
+    }
+}
@@ -419,11 +419,40 @@ pub fn mint_and_reserve(
     );
 }
 
+/// Registers the storage deposit of the user in the contract, the excess deposit is refunded
+pub fn register_account(user: &UserAccount, contract_id: AccountId) {
+    user.call(
+        contract_id,
+        "storage_deposit",
+        json!({ "registration_only": true }).to_string().as_bytes(),
+        near_sdk_sim::DEFAULT_GAS,
+        to_yocto("0.1"),
+    )
+    .assert_success();
+}
+
+/// Users should be registered in the market and the controller before their state is written
+fn register_market_user(user: &UserAccount, dtoken: AccountId) {
+    register_account(user, dtoken);
+    register_account(user, "controller_contract".parse().unwrap());
+}
+
 pub fn supply(
     user: &UserAccount,
     utoken: &ContractAccount<mock_token::ContractContract>,
     dtoken: AccountId,
     amount: Balance,
+) -> ExecutionResult {
+    register_market_user(user, dtoken.clone());
+    supply_registered(user, utoken, dtoken, amount)
+}
+
+/// Supplies without registering the user, which should be done beforehand
+pub fn supply_registered(
+    user: &UserAccount,
+    utoken: &ContractAccount<mock_token::ContractContract>,
+    dtoken: AccountId,
+    amount: Balance,
 ) -> ExecutionResult {
     let action = "\"Supply\"".to_string();
     call!(
@@ -446,6 +475,7 @@ pub fn borrow(
     dtoken: &ContractAccount<market::ContractContract>,
     amount: Balance,
 ) -> ExecutionResult {
+    register_market_user(user, dtoken.account_id());
     call!(user, dtoken.borrow(U128(amount)), deposit = 0)
 }
 
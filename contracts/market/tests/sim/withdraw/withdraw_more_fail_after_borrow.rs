@@ -4,7 +4,7 @@ use crate::utils::{
 };
 use controller::ActionType::Supply;
 use general::Price;
-use market::InterestRateModel;
+use market::{InterestRateModel, JumpRateModel};
 use near_sdk::{json_types::U128, Balance};
 use near_sdk_sim::{init_simulator, view, ContractAccount, UserAccount};
 
@@ -25,13 +25,13 @@ fn withdraw_fixture() -> (
     let user = new_user(&root, "user".parse().unwrap());
     let (weth, wbtc) = initialize_two_utokens(&root);
     let controller = initialize_controller(&root);
-    let interest_model = InterestRateModel {
+    let interest_model = InterestRateModel::JumpRate(JumpRateModel {
         kink: U128(0),
         multiplier_per_block: U128(0),
         base_rate_per_block: U128(0),
         jump_multiplier_per_block: U128(0),
         reserve_factor: U128(0),
-    };
+    });
     let (droot, weth_market, dwbtc) = initialize_two_dtokens(
         &root,
         weth.account_id(),
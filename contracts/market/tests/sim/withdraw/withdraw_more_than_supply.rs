@@ -5,7 +5,7 @@ use crate::utils::{
 use controller::ActionType::Supply;
 use general::ratio::Ratio;
 use general::Price;
-use market::InterestRateModel;
+use market::{InterestRateModel, JumpRateModel};
 use near_sdk::{json_types::U128, Balance};
 use near_sdk_sim::{init_simulator, view, ContractAccount, UserAccount};
 
@@ -24,13 +24,13 @@ fn withdraw_more_than_supply_fixture() -> (
     let user = new_user(&root, "user".parse().unwrap());
     let wnear = initialize_utoken(&root);
     let controller = initialize_controller(&root);
-    let interest_model = InterestRateModel {
+    let interest_model = InterestRateModel::JumpRate(JumpRateModel {
         kink: U128::from(Ratio::zero()),
         multiplier_per_block: U128::from(Ratio::zero()),
         base_rate_per_block: U128::from(Ratio::zero()),
         jump_multiplier_per_block: U128::from(Ratio::zero()),
         reserve_factor: U128::from(Ratio::zero()),
-    };
+    });
     let (droot, wnear_market) = initialize_dtoken(
         &root,
         wnear.account_id(),
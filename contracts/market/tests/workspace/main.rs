@@ -1,6 +1,7 @@
 pub mod borrow;
 pub mod repay;
 pub mod reserve;
+pub mod rewards;
 pub mod supply;
 pub mod utils;
 pub mod withdraw;
@@ -172,6 +172,9 @@ async fn repay_fixture(
         .transact()
         .await?;
 
+    register_account(owner, &market).await?;
+    register_account(owner, &controller).await?;
+
     let _ = owner
         .call(underlying.id(), "ft_transfer_call")
         .args_json(json!({
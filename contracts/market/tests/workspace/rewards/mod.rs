@@ -0,0 +1 @@
+mod test_claim_rewards_gas;
@@ -0,0 +1,231 @@
+use crate::utils::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::json;
+use workspaces::network::Sandbox;
+use workspaces::{Account, Worker};
+
+const DECIMALS: u8 = 24;
+const SUPPLY_AMOUNT: u128 = 1000 * 10_u128.pow(24);
+
+async fn rewards_fixture(
+    owner: &Account,
+    worker: &Worker<Sandbox>,
+) -> anyhow::Result<
+    (
+        workspaces::Contract,
+        workspaces::Contract,
+        workspaces::Contract,
+        String,
+    ),
+    anyhow::Error,
+> {
+    ////////////////////////////////////////////////////////////////////////////
+    // Stage 1: Deploy contracts such as underlying, controller, and markets
+    ////////////////////////////////////////////////////////////////////////////
+
+    let underlying = deploy_underlying(owner, worker, DECIMALS).await?;
+    let controller = deploy_controller(owner, worker).await?;
+    let market = deploy_market(
+        owner,
+        worker,
+        underlying.as_account(),
+        DECIMALS,
+        controller.as_account(),
+    )
+    .await?;
+
+    ////////////////////////////////////////////////////////////////////////////////////////////
+    // Stage 2: Fund the market with reserve which is used to pay the rewards
+    ////////////////////////////////////////////////////////////////////////////////////////////
+
+    let _ = underlying
+        .call("storage_deposit")
+        .args_json(json!({
+            "account_id": market.id()
+        }))
+        .max_gas()
+        .deposit(25 * 10u128.pow(23))
+        .transact()
+        .await?;
+
+    let _ = underlying
+        .call("mint")
+        .args_json(json!({
+            "account_id": owner.id(),
+            "amount": U128(2000 * 10_u128.pow(24) + 51 * SUPPLY_AMOUNT)
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+
+    let _ = owner
+        .call(underlying.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": market.id(),
+            "amount": U128::from(1000000000000000000000000000),
+            "msg": "\"Reserve\""
+        }))
+        .max_gas()
+        .deposit(1)
+        .transact()
+        .await?;
+
+    let _ = controller
+        .call("add_market")
+        .args_json(json!({
+            "asset_id": underlying.id(),
+            "dtoken": market.id(),
+            "ticker_id": "weth",
+            "ltv": "0.4",
+            "lth": "0.8"
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+
+    ////////////////////////////////////////////////////////////////////////////////////////////
+    // Stage 3: Add and fund supply campaign paid in the underlying token without vesting
+    ////////////////////////////////////////////////////////////////////////////////////////////
+
+    let now = worker.view_block().await?.timestamp() / 10_u64.pow(9);
+    let campaign_id: String = owner
+        .call(market.id(), "add_reward_campaign")
+        .args_json(json!({
+            "reward_campaign": {
+                "campaign_type": "Supply",
+                "start_time": now,
+                "end_time": now + 24 * 60 * 60,
+                "tokens": [{
+                    "token": underlying.id(),
+                    "ticker_id": "weth",
+                    "reward_amount": U128(100 * 10_u128.pow(24)),
+                    "distributed_amount": U128(0),
+                    "rewards_per_token": "0"
+                }],
+                "last_update_time": now,
+                "last_market_total": U128(0),
+                "vesting": {
+                    "start_time": now,
+                    "end_time": now,
+                    "penalty": "0",
+                    "penalty_destination": "Burn"
+                },
+                "boost": null,
+                "boosted_shares": U128(0)
+            }
+        }))
+        .max_gas()
+        .transact()
+        .await?
+        .json()?;
+
+    let _ = owner
+        .call(underlying.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": market.id(),
+            "amount": U128(100 * 10_u128.pow(24)),
+            "msg": json!({ "FundCampaign": { "campaign_id": campaign_id } }).to_string()
+        }))
+        .max_gas()
+        .deposit(1)
+        .transact()
+        .await?;
+
+    Ok((underlying, controller, market, campaign_id))
+}
+
+/// Creates the account which supplies to the market and so takes part in the supply campaign
+async fn add_supplier(
+    owner: &Account,
+    worker: &Worker<Sandbox>,
+    underlying: &workspaces::Contract,
+    controller: &workspaces::Contract,
+    market: &workspaces::Contract,
+) -> anyhow::Result<Account> {
+    let user = worker.dev_create_account().await?;
+
+    let _ = underlying
+        .call("storage_deposit")
+        .args_json(json!({
+            "account_id": user.id()
+        }))
+        .max_gas()
+        .deposit(25 * 10u128.pow(23))
+        .transact()
+        .await?;
+
+    let _ = owner
+        .call(underlying.id(), "ft_transfer")
+        .args_json(json!({
+            "receiver_id": user.id(),
+            "amount": U128(SUPPLY_AMOUNT),
+        }))
+        .max_gas()
+        .deposit(1)
+        .transact()
+        .await?;
+
+    register_account(&user, market).await?;
+    register_account(&user, controller).await?;
+
+    let _ = user
+        .call(underlying.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": market.id(),
+            "amount": U128(SUPPLY_AMOUNT),
+            "msg": "\"Supply\"",
+        }))
+        .max_gas()
+        .deposit(1)
+        .transact()
+        .await?;
+
+    Ok(user)
+}
+
+async fn claim_rewards_gas(
+    user: &Account,
+    underlying: &workspaces::Contract,
+    market: &workspaces::Contract,
+    campaign_id: &str,
+) -> anyhow::Result<u64> {
+    let result = user
+        .call(market.id(), "claim_rewards")
+        .args_json(json!({
+            "campaign_id": campaign_id,
+            "token": underlying.id(),
+            "amount": U128(1),
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+
+    assert!(result.is_success(), "Claim of the rewards failed");
+    Ok(result.total_gas_burnt)
+}
+
+#[tokio::test]
+async fn test_claim_rewards_gas_does_not_grow_with_users() -> anyhow::Result<()> {
+    let worker = workspaces::sandbox().await?;
+    let owner = worker.root_account()?;
+    let (underlying, controller, market, campaign_id) = rewards_fixture(&owner, &worker).await?;
+
+    let user = add_supplier(&owner, &worker, &underlying, &controller, &market).await?;
+    let gas_with_few_users = claim_rewards_gas(&user, &underlying, &market, &campaign_id).await?;
+
+    for _ in 0..50 {
+        add_supplier(&owner, &worker, &underlying, &controller, &market).await?;
+    }
+    let gas_with_many_users = claim_rewards_gas(&user, &underlying, &market, &campaign_id).await?;
+
+    // claim reads and writes the rewards of the caller only, so the cost
+    // should not depend on the number of users taking part in the campaign
+    assert!(
+        gas_with_many_users <= gas_with_few_users + gas_with_few_users / 20,
+        "Claim gas grew from {} to {} with the number of users",
+        gas_with_few_users,
+        gas_with_many_users
+    );
+
+    Ok(())
+}
@@ -167,6 +167,9 @@ async fn supply_fixture(
         .transact()
         .await?;
 
+    register_account(owner, &market).await?;
+    register_account(owner, &controller).await?;
+
     Ok((underlying, controller, market))
 }
 
@@ -50,11 +50,13 @@ pub async fn deploy_market(
             "controller_account_id": controller.id(),
             "initial_exchange_rate":"1000000000000000000000000",
             "interest_rate_model":{
-                "kink":"650000000000000000000000",
-                "multiplier_per_block":"3044140030441400",
-                "base_rate_per_block":"0",
-                "jump_multiplier_per_block":"38051750380517500",
-                "reserve_factor":"10000000000000000000000"
+                "JumpRate":{
+                    "kink":"650000000000000000000000",
+                    "multiplier_per_block":"3044140030441400",
+                    "base_rate_per_block":"0",
+                    "jump_multiplier_per_block":"38051750380517500",
+                    "reserve_factor":"10000000000000000000000"
+                }
             }
         }))
         .max_gas()
@@ -95,3 +97,19 @@ pub async fn deploy_controller(
 
     Ok(controller)
 }
+
+/// Registers the storage deposit of the account in the market or the controller
+pub async fn register_account(
+    account: &Account,
+    contract: &workspaces::Contract,
+) -> Result<(), workspaces::error::Error> {
+    let _ = account
+        .call(contract.id(), "storage_deposit")
+        .args_json(json!({}))
+        .max_gas()
+        .deposit(10u128.pow(23))
+        .transact()
+        .await?;
+
+    Ok(())
+}
@@ -186,6 +186,9 @@ async fn withdraw_fixture(
         .transact()
         .await?;
 
+    register_account(user, &market).await?;
+    register_account(user, &controller).await?;
+
     let _ = controller
         .call("upsert_price")
         .args_json(json!({
@@ -3,16 +3,28 @@ use near_contract_standards::fungible_token::metadata::{
 };
 use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LazyOption;
+use near_sdk::collections::{LazyOption, LookupMap, TreeMap};
 use near_sdk::json_types::U128;
 use near_sdk::require;
-use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, PromiseOrValue};
+use near_sdk::{env, near_bindgen, AccountId, Balance, PanicOnDefault, PromiseOrValue};
+
+mod migration;
+mod voting_escrow;
+
+pub use crate::migration::*;
+pub use crate::voting_escrow::*;
 
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
 pub struct Contract {
     token: FungibleToken,
     metadata: LazyOption<FungibleTokenMetadata>,
+    /// Tokens locked by the accounts in exchange for the voting power
+    locks: LookupMap<AccountId, Lock>,
+    /// Voting power of the locks which aren't expired yet or expired after the last checkpoint
+    total_voting_power: Balance,
+    /// Unlock time -> Voting power of the locks expiring at that time
+    voting_power_expirations: TreeMap<u64, Balance>,
 }
 
 // example from near
@@ -45,11 +57,15 @@ impl Contract {
     #[init]
     pub fn new(owner_id: AccountId, total_supply: U128, metadata: FungibleTokenMetadata) -> Self {
         require!(!env::state_exists(), "Already initialized");
+        CURRENT_STATE_VERSION.write();
 
         metadata.assert_valid();
         let mut this = Self {
             token: FungibleToken::new(b"a".to_vec()),
             metadata: LazyOption::new(b"m".to_vec(), Some(&metadata)),
+            locks: LookupMap::new(b"l".to_vec()),
+            total_voting_power: 0,
+            voting_power_expirations: TreeMap::new(b"e".to_vec()),
         };
         this.token.internal_register_account(&owner_id);
         this.token.internal_deposit(&owner_id, total_supply.into());
@@ -0,0 +1,140 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+const STATE_VERSION_KEY: &[u8] = b"v";
+
+/// Layout of the stored contract state.
+/// Whenever the layout of a released version changes, the previous `Contract` schema
+/// should be copied below, a new version added and `migrate_state` extended with the step
+/// converting the previous version into the next one.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    PartialOrd,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum StateVersion {
+    /// State written before the versioning was introduced, the version isn't stored for it
+    V1,
+    V2,
+}
+
+pub const CURRENT_STATE_VERSION: StateVersion = StateVersion::V2;
+
+impl StateVersion {
+    pub fn read() -> Self {
+        env::storage_read(STATE_VERSION_KEY)
+            .map(|version| {
+                StateVersion::try_from_slice(&version).expect("State version is corrupted")
+            })
+            .unwrap_or(StateVersion::V1)
+    }
+
+    pub fn write(&self) {
+        env::storage_write(STATE_VERSION_KEY, &self.try_to_vec().unwrap());
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ContractV1 {
+    token: FungibleToken,
+    metadata: LazyOption<FungibleTokenMetadata>,
+}
+
+impl ContractV1 {
+    /// Balances and metadata are kept, the voting escrow starts with no locks
+    fn migrate(self) -> Contract {
+        Contract {
+            token: self.token,
+            metadata: self.metadata,
+            locks: LookupMap::new(b"l".to_vec()),
+            total_voting_power: 0,
+            voting_power_expirations: TreeMap::new(b"e".to_vec()),
+        }
+    }
+}
+
+impl Contract {
+    /// Reads the state stored in the given version and migrates it up to the current one
+    pub(crate) fn migrate_state(version: StateVersion) -> Self {
+        require!(
+            version <= CURRENT_STATE_VERSION,
+            "State version is newer than the contract one"
+        );
+
+        let contract = match version {
+            StateVersion::V1 => env::state_read::<ContractV1>()
+                .expect("Contract is not initialized")
+                .migrate(),
+            StateVersion::V2 => env::state_read().expect("Contract is not initialized"),
+        };
+
+        CURRENT_STATE_VERSION.write();
+        contract
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Migrates the state after the new code is deployed to the account
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate() -> Self {
+        Self::migrate_state(StateVersion::read())
+    }
+
+    pub fn get_state_version(&self) -> StateVersion {
+        StateVersion::read()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    #[test]
+    fn test_migrate_from_v1() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(alice())
+            .predecessor_account_id(alice())
+            .build());
+
+        let mut token = FungibleToken::new(b"a".to_vec());
+        token.internal_register_account(&bob());
+        token.internal_deposit(&bob(), 1000);
+        let contract = ContractV1 {
+            token,
+            metadata: LazyOption::new(
+                b"m".to_vec(),
+                Some(&FungibleTokenMetadata {
+                    spec: FT_METADATA_SPEC.to_string(),
+                    name: "Token".to_string(),
+                    symbol: "TKN".to_string(),
+                    icon: None,
+                    reference: None,
+                    reference_hash: None,
+                    decimals: 24,
+                }),
+            ),
+        };
+        env::state_write(&contract);
+
+        let contract = Contract::migrate_state(StateVersion::read());
+        assert_eq!(contract.get_state_version(), CURRENT_STATE_VERSION);
+        assert_eq!(contract.ft_balance_of(bob()), U128(1000));
+        assert_eq!(contract.ft_total_supply(), U128(1000));
+        assert_eq!(contract.ft_metadata().symbol, "TKN");
+        assert!(contract.get_lock(bob()).is_none());
+        assert_eq!(contract.get_voting_power(bob()).total, U128(0));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Balance;
+use std::cmp::max;
+
+/// Longest period tokens could be locked for, it gives the voting power equal to the locked amount
+pub const MAX_LOCK_DURATION: u64 = 4 * 365 * 24 * 60 * 60;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Lock {
+    pub amount: U128,
+    /// Time in seconds when the tokens could be withdrawn
+    pub unlock_time: u64,
+    pub voting_power: U128,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VotingPower {
+    pub account: U128,
+    pub total: U128,
+}
+
+impl Contract {
+    fn get_timestamp_in_seconds() -> u64 {
+        env::block_timestamp() / 1_000_000_000
+    }
+
+    fn get_lock_voting_power(amount: Balance, duration: u64) -> Balance {
+        amount * duration as u128 / MAX_LOCK_DURATION as u128
+    }
+
+    /// Voting power of the locks expired by the given time but not yet removed from the total
+    fn get_expired_voting_power(&self, now: u64) -> Balance {
+        self.voting_power_expirations
+            .iter()
+            .take_while(|(unlock_time, _)| *unlock_time <= now)
+            .map(|(_, voting_power)| voting_power)
+            .sum()
+    }
+
+    /// Removes the voting power of the expired locks from the total
+    fn checkpoint_voting_power(&mut self, now: u64) {
+        while let Some(unlock_time) = self.voting_power_expirations.min() {
+            if unlock_time > now {
+                break;
+            }
+            let voting_power = self.voting_power_expirations.remove(&unlock_time).unwrap();
+            self.total_voting_power -= voting_power;
+        }
+    }
+
+    fn add_voting_power(&mut self, unlock_time: u64, voting_power: Balance) {
+        let expiring = self.voting_power_expirations.get(&unlock_time).unwrap_or(0);
+        self.voting_power_expirations
+            .insert(&unlock_time, &(expiring + voting_power));
+        self.total_voting_power += voting_power;
+    }
+
+    fn remove_voting_power(&mut self, unlock_time: u64, voting_power: Balance) {
+        let expiring = self.voting_power_expirations.get(&unlock_time).unwrap_or(0) - voting_power;
+        if expiring == 0 {
+            self.voting_power_expirations.remove(&unlock_time);
+        } else {
+            self.voting_power_expirations
+                .insert(&unlock_time, &expiring);
+        }
+        self.total_voting_power -= voting_power;
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Locks the tokens of the caller for the duration in seconds, locking on top of the active
+    /// lock adds the tokens and keeps the later unlock time
+    #[payable]
+    pub fn lock(&mut self, amount: U128, duration: u64) -> Lock {
+        assert_one_yocto();
+        require!(amount.0 > 0, "Amount should be positive");
+        require!(
+            duration <= MAX_LOCK_DURATION,
+            "Lock duration exceeds the maximum"
+        );
+
+        let account_id = env::predecessor_account_id();
+        let now = Self::get_timestamp_in_seconds();
+        self.checkpoint_voting_power(now);
+        let (locked_amount, locked_until, voting_power) = match self.locks.get(&account_id) {
+            Some(lock) => {
+                require!(
+                    lock.unlock_time > now,
+                    "Lock is expired, tokens should be unlocked first"
+                );
+                (lock.amount.0, lock.unlock_time, lock.voting_power.0)
+            }
+            None => (0, 0, 0),
+        };
+
+        let contract_id = env::current_account_id();
+        if !self.token.accounts.contains_key(&contract_id) {
+            self.token.internal_register_account(&contract_id);
+        }
+        self.token.internal_transfer(
+            &account_id,
+            &contract_id,
+            amount.0,
+            Some("lock".to_string()),
+        );
+
+        let amount = locked_amount + amount.0;
+        let unlock_time = max(locked_until, now + duration);
+        let lock = Lock {
+            amount: U128(amount),
+            unlock_time,
+            voting_power: U128(Self::get_lock_voting_power(amount, unlock_time - now)),
+        };
+        if voting_power > 0 {
+            self.remove_voting_power(locked_until, voting_power);
+        }
+        self.add_voting_power(lock.unlock_time, lock.voting_power.0);
+        self.locks.insert(&account_id, &lock);
+        lock
+    }
+
+    /// Returns the tokens of the expired lock to the caller
+    #[payable]
+    pub fn unlock(&mut self) -> U128 {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let lock = self.locks.get(&account_id).expect("There is no lock");
+        let now = Self::get_timestamp_in_seconds();
+        require!(lock.unlock_time <= now, "Lock is not expired yet");
+
+        // Voting power of the expired lock leaves the total with the checkpoint
+        self.checkpoint_voting_power(now);
+
+        self.token.internal_transfer(
+            &env::current_account_id(),
+            &account_id,
+            lock.amount.0,
+            Some("unlock".to_string()),
+        );
+        self.locks.remove(&account_id);
+        lock.amount
+    }
+
+    pub fn get_lock(&self, account_id: AccountId) -> Option<Lock> {
+        self.locks.get(&account_id)
+    }
+
+    /// Voting power is fixed for the lock duration, expired locks count neither for the account
+    /// nor in the total
+    pub fn get_voting_power(&self, account_id: AccountId) -> VotingPower {
+        let now = Self::get_timestamp_in_seconds();
+        let account = match self.locks.get(&account_id) {
+            Some(lock) if lock.unlock_time > now => lock.voting_power,
+            _ => U128(0),
+        };
+        VotingPower {
+            account,
+            total: U128(self.total_voting_power - self.get_expired_voting_power(now)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn set_context(account_id: AccountId, timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(carol())
+            .predecessor_account_id(account_id)
+            .block_timestamp(timestamp * 1_000_000_000)
+            .attached_deposit(1)
+            .build());
+    }
+
+    fn init() -> Contract {
+        set_context(alice(), 0);
+        let mut contract = Contract::new_default_meta(
+            alice(),
+            "Token".to_string(),
+            "TKN".to_string(),
+            U128(10000),
+        );
+        contract.token.internal_register_account(&bob());
+        contract
+            .token
+            .internal_transfer(&alice(), &bob(), 5000, None);
+        contract
+    }
+
+    #[test]
+    fn test_lock() {
+        let mut contract = init();
+
+        let lock = contract.lock(U128(4000), MAX_LOCK_DURATION / 4);
+        assert_eq!(lock.amount, U128(4000));
+        assert_eq!(lock.unlock_time, MAX_LOCK_DURATION / 4);
+        assert_eq!(lock.voting_power, U128(1000));
+        assert_eq!(contract.ft_balance_of(alice()), U128(1000));
+        assert_eq!(contract.ft_balance_of(carol()), U128(4000));
+    }
+
+    #[test]
+    fn test_relock_extends_unlock_time() {
+        let mut contract = init();
+        contract.lock(U128(1000), 100 * DAY);
+
+        set_context(alice(), 50 * DAY);
+        let lock = contract.lock(U128(1000), 200 * DAY);
+        assert_eq!(lock.amount, U128(2000));
+        assert_eq!(lock.unlock_time, 250 * DAY);
+        assert_eq!(
+            lock.voting_power.0,
+            Contract::get_lock_voting_power(2000, 200 * DAY)
+        );
+
+        // Shorter lock on top keeps the later unlock time
+        let lock = contract.lock(U128(1000), DAY);
+        assert_eq!(lock.unlock_time, 250 * DAY);
+        assert_eq!(
+            contract.get_voting_power(alice()).total,
+            contract.get_voting_power(alice()).account
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Lock is not expired yet")]
+    fn test_unlock_before_expiry() {
+        let mut contract = init();
+        contract.lock(U128(1000), 100 * DAY);
+
+        set_context(alice(), 100 * DAY - 1);
+        contract.unlock();
+    }
+
+    #[test]
+    fn test_unlock_after_expiry() {
+        let mut contract = init();
+        contract.lock(U128(1000), 100 * DAY);
+
+        set_context(alice(), 100 * DAY);
+        assert_eq!(contract.unlock(), U128(1000));
+        assert_eq!(contract.ft_balance_of(alice()), U128(5000));
+        assert!(contract.get_lock(alice()).is_none());
+        assert_eq!(contract.get_voting_power(alice()).total, U128(0));
+    }
+
+    #[test]
+    fn test_get_voting_power() {
+        let mut contract = init();
+        contract.lock(U128(4000), MAX_LOCK_DURATION);
+        set_context(bob(), 0);
+        contract.lock(U128(4000), MAX_LOCK_DURATION / 2);
+
+        let voting_power = contract.get_voting_power(bob());
+        assert_eq!(voting_power.account, U128(2000));
+        assert_eq!(voting_power.total, U128(6000));
+
+        // Expired lock leaves the total before it is unlocked
+        set_context(bob(), MAX_LOCK_DURATION / 2);
+        let voting_power = contract.get_voting_power(bob());
+        assert_eq!(voting_power.account, U128(0));
+        assert_eq!(voting_power.total, U128(4000));
+        assert_eq!(contract.get_voting_power(alice()).account, U128(4000));
+
+        contract.unlock();
+        assert_eq!(contract.get_voting_power(bob()).total, U128(4000));
+
+        set_context(bob(), MAX_LOCK_DURATION);
+        assert_eq!(contract.get_voting_power(alice()).total, U128(0));
+    }
+}
@@ -106,13 +106,13 @@ deploy_markets(){
             "underlying_token_decimals": '$ETH_TOKEN_DECIMALS',
             "controller_account_id":"'$CONTROLLER_ACCOUNT'.'$1'",
             "initial_exchange_rate":"1000000000000000000000000",
-            "interest_rate_model":{
+            "interest_rate_model":{"JumpRate":{
                 "kink":"650000000000000000000000",
                 "multiplier_per_block":"3044140030441400",
                 "base_rate_per_block":"0",
                 "jump_multiplier_per_block":"38051750380517500",
                 "reserve_factor":"10000000000000000000000"
-            }
+            }}
         }' &
     near deploy wnear_market.$1 \
         --wasmFile  ./contracts/target/wasm32-unknown-unknown/release/market.wasm \
@@ -123,13 +123,13 @@ deploy_markets(){
             "underlying_token_decimals": '$NEAR_TOKEN_DECIMALS',
             "controller_account_id":"'$CONTROLLER_ACCOUNT'.'$1'",
             "initial_exchange_rate":"1000000000000000000000000",
-            "interest_rate_model":{
+            "interest_rate_model":{"JumpRate":{
                 "kink":"650000000000000000000000",
                 "multiplier_per_block":"3044140030441400",
                 "base_rate_per_block":"0",
                 "jump_multiplier_per_block":"38051750380517500",
                 "reserve_factor":"10000000000000000000000"
-            }
+            }}
         }' &
     near deploy usdt_market.$1 \
         --wasmFile  ./contracts/target/wasm32-unknown-unknown/release/market.wasm \
@@ -140,13 +140,13 @@ deploy_markets(){
         "underlying_token_decimals": '$USDT_TOKEN_DECIMALS',
         "controller_account_id":"'$CONTROLLER_ACCOUNT'.'$1'",
             "initial_exchange_rate":"1000000000000000000000000",
-            "interest_rate_model":{
+            "interest_rate_model":{"JumpRate":{
             "kink":"800000000000000000000000",
             "multiplier_per_block":"1522070015220700",
             "base_rate_per_block":"0",
             "jump_multiplier_per_block":"28538812785388100",
             "reserve_factor":"10000000000000000000000"
-            }
+            }}
         }' &
     near deploy usdc_market.$1 \
         --wasmFile  ./contracts/target/wasm32-unknown-unknown/release/market.wasm \
@@ -157,13 +157,13 @@ deploy_markets(){
         "underlying_token_decimals": '$USDC_TOKEN_DECIMALS',
         "controller_account_id":"'$CONTROLLER_ACCOUNT'.'$1'",
             "initial_exchange_rate":"1000000000000000000000000",
-            "interest_rate_model":{
+            "interest_rate_model":{"JumpRate":{
             "kink":"800000000000000000000000",
             "multiplier_per_block":"1522070015220700",
             "base_rate_per_block":"0",
             "jump_multiplier_per_block":"28538812785388100",
             "reserve_factor":"10000000000000000000000"
-            }
+            }}
         }' &
     
     wait
@@ -303,11 +303,12 @@ setup_reserves(){
 }
 
 configure_acl() {
-    # set shared admin as admin for dtokens
-    near call weth_market.$1 set_admin '{"account": "shared_admin.testnet"}' --gas 300000000000000 --accountId $1 &
-    near call wnear_market.$1 set_admin '{"account": "shared_admin.testnet"}' --gas 300000000000000 --accountId $1 &
-    near call usdt_market.$1 set_admin '{"account": "shared_admin.testnet"}' --gas 300000000000000 --accountId $1 &
-    near call usdc_market.$1 set_admin '{"account": "shared_admin.testnet"}' --gas 300000000000000 --accountId $1 &
+    # grant shared admin with the admin roles of dtokens
+    for market in weth_market wnear_market usdt_market usdc_market; do
+        for role in Owner RiskManager PauseGuardian RewardManager Executor; do
+            near call $market.$1 grant_role '{"role": "'$role'", "account": "shared_admin.testnet"}' --gas 300000000000000 --accountId $1 &
+        done
+    done
 
     wait
 }
\ No newline at end of file